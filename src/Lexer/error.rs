@@ -12,6 +12,7 @@ pub enum CustomError {
     FloatOverflow(String, (usize, usize)),
     IdentifierTooLong(String, (usize, usize)),
     ReDeclaredIdentifier(String, (usize, usize)),
+    UndeclaredIdentifier(String, (usize, usize)),
 }
 impl fmt::Display for CustomError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -23,6 +24,7 @@ impl fmt::Display for CustomError {
             CustomError::FloatOverflow(num, (line, column)) => write!(f, "Float overflow: {} at ({}:{})", num, line, column),
             CustomError::IdentifierTooLong(id, (line, column)) => write!(f, "Identifier too long: {} at ({}:{})", id, line, column),
             CustomError::ReDeclaredIdentifier(id, (line, column)) => write!(f, "Identifier Already Declared: {} at ({}:{})", id, line, column),
+            CustomError::UndeclaredIdentifier(id, (line, column)) => write!(f, "Undeclared Identifier: {} at ({}:{})", id, line, column),
         }
     }
 }
@@ -0,0 +1,367 @@
+use std::collections::BTreeSet;
+use crate::Semantic::quadruplets::{jump_target, Operator, Quadruplet};
+
+/// Straight-line run of quadruplets, only entered at its first quadruplet and left after its last one
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    pub id: usize,
+    pub start: usize,               // Index of the first quadruplet (the leader)
+    pub end: usize,                 // Index one past the last quadruplet
+    pub predecessors: Vec<usize>,
+    pub successors: Vec<usize>,
+}
+
+impl BasicBlock {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Index of the last quadruplet of the block
+    pub fn last(&self) -> usize {
+        self.end - 1
+    }
+}
+
+/// Control-flow graph of a list of quadruplets
+/// Block 0 is the entry, blocks without successors leave the program
+#[derive(Debug, Clone)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    block_of: Vec<usize>,   // Block holding each quadruplet
+}
+
+impl ControlFlowGraph {
+    /// Splits the quadruplets into basic blocks
+    /// Leaders are the first quadruplet, every jump target and every quadruplet following a jump
+    pub fn build(quadruplets: &[Quadruplet]) -> Self {
+        let mut leaders = BTreeSet::new();
+        if !quadruplets.is_empty() {
+            leaders.insert(0);
+        }
+        for (index, quadruplet) in quadruplets.iter().enumerate() {
+            if quadruplet.operator.is_jump() {
+                if let Some(target) = jump_target(quadruplet).filter(|t| *t < quadruplets.len()) {
+                    leaders.insert(target);
+                }
+                if index + 1 < quadruplets.len() {
+                    leaders.insert(index + 1);
+                }
+            }
+        }
+
+        let leaders: Vec<usize> = leaders.into_iter().collect();
+        let mut blocks = Vec::with_capacity(leaders.len());
+        let mut block_of = vec![0; quadruplets.len()];
+        for (id, &start) in leaders.iter().enumerate() {
+            let end = leaders.get(id + 1).copied().unwrap_or(quadruplets.len());
+            block_of[start..end].fill(id);
+            blocks.push(BasicBlock { id, start, end, predecessors: Vec::new(), successors: Vec::new() });
+        }
+
+        let mut graph = ControlFlowGraph { blocks, block_of };
+        for id in 0..graph.blocks.len() {
+            let last = &quadruplets[graph.blocks[id].last()];
+            let fall_through = (id + 1 < graph.blocks.len()).then_some(id + 1);
+            let jump = jump_target(last).filter(|t| *t < quadruplets.len()).map(|t| graph.block_of[t]);
+            let successors: Vec<usize> = match last.operator {
+                Operator::Goto => jump.into_iter().collect(),
+                Operator::IfTrue | Operator::IfFalse => fall_through.into_iter().chain(jump).collect(),
+                _ => fall_through.into_iter().collect(),
+            };
+            for successor in successors {
+                graph.add_edge(id, successor);
+            }
+        }
+        graph
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize) {
+        if !self.blocks[from].successors.contains(&to) {
+            self.blocks[from].successors.push(to);
+            self.blocks[to].predecessors.push(from);
+        }
+    }
+
+    pub fn entry(&self) -> Option<usize> {
+        (!self.blocks.is_empty()).then_some(0)
+    }
+
+    /// Block holding the quadruplet at `index`
+    pub fn block_of(&self, index: usize) -> usize {
+        self.block_of[index]
+    }
+
+    /// Blocks reachable from the entry in reverse post-order, every block comes before
+    /// its successors except along back edges
+    pub fn reverse_post_order(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.blocks.len());
+        let mut visited = vec![false; self.blocks.len()];
+        if let Some(entry) = self.entry() {
+            // Iterative depth-first search, each frame remembers the next successor to visit
+            let mut stack = vec![(entry, 0)];
+            visited[entry] = true;
+            while let Some((block, next)) = stack.pop() {
+                match self.blocks[block].successors.get(next) {
+                    Some(&successor) => {
+                        stack.push((block, next + 1));
+                        if !visited[successor] {
+                            visited[successor] = true;
+                            stack.push((successor, 0));
+                        }
+                    },
+                    None => order.push(block),
+                }
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// Dominator tree, computed with the iterative algorithm of Cooper, Harvey and Kennedy
+    pub fn dominators(&self) -> Dominators {
+        let order = self.reverse_post_order();
+        let mut rank = vec![usize::MAX; self.blocks.len()];
+        for (position, &block) in order.iter().enumerate() {
+            rank[block] = position;
+        }
+
+        let mut idom: Vec<Option<usize>> = vec![None; self.blocks.len()];
+        let Some(entry) = self.entry() else {
+            return Dominators { idom };
+        };
+        idom[entry] = Some(entry);
+        let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+            while a != b {
+                while rank[a] > rank[b] {
+                    a = idom[a].unwrap();
+                }
+                while rank[b] > rank[a] {
+                    b = idom[b].unwrap();
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut new_idom = None;
+                for &predecessor in &self.blocks[block].predecessors {
+                    if idom[predecessor].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => predecessor,
+                        Some(current) => intersect(&idom, predecessor, current),
+                    });
+                }
+                if new_idom.is_some() && idom[block] != new_idom {
+                    idom[block] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        // The entry has no immediate dominator
+        idom[entry] = None;
+        Dominators { idom }
+    }
+
+    /// Graphviz description of the graph, each block lists its quadruplets
+    /// Conditional jumps label their edges with the branch they follow
+    pub fn to_dot(&self, quadruplets: &[Quadruplet], title: &str) -> String {
+        let mut dot = format!("digraph \"{}\" {{\n", escape_dot(title));
+        dot += "    node [shape=box, fontname=\"monospace\"];\n";
+        dot += "    entry [shape=oval];\n";
+        dot += "    exit [shape=oval];\n";
+
+        for block in &self.blocks {
+            let mut label = format!("B{}\\l", block.id);
+            for (index, quadruplet) in quadruplets.iter().enumerate().take(block.end).skip(block.start) {
+                label += &format!("{}: {}\\l", index, escape_dot(&quadruplet.to_string()));
+            }
+            dot += &format!("    B{} [label=\"{}\"];\n", block.id, label);
+        }
+
+        if let Some(entry) = self.entry() {
+            dot += &format!("    entry -> B{};\n", entry);
+        } else {
+            dot += "    entry -> exit;\n";
+        }
+        for block in &self.blocks {
+            let last = &quadruplets[block.last()];
+            let jump = jump_target(last).filter(|t| *t < quadruplets.len()).map(|t| self.block_of(t));
+            for &successor in &block.successors {
+                let label = match last.operator {
+                    Operator::IfTrue | Operator::IfFalse if Some(successor) == jump => {
+                        if last.operator == Operator::IfTrue { "true" } else { "false" }
+                    },
+                    Operator::IfTrue => "false",
+                    Operator::IfFalse => "true",
+                    _ => "",
+                };
+                if label.is_empty() {
+                    dot += &format!("    B{} -> B{};\n", block.id, successor);
+                } else {
+                    dot += &format!("    B{} -> B{} [label=\"{}\"];\n", block.id, successor, label);
+                }
+            }
+            if block.successors.is_empty() || jump_target(last) == Some(quadruplets.len())
+                || (block.id + 1 == self.blocks.len() && !matches!(last.operator, Operator::Goto)) {
+                dot += &format!("    B{} -> exit;\n", block.id);
+            }
+        }
+        dot += "}\n";
+        dot
+    }
+}
+
+/// Dominator tree of a control-flow graph, unreachable blocks are left out of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dominators {
+    idom: Vec<Option<usize>>,   // Immediate dominator of each block
+}
+
+impl Dominators {
+    pub fn immediate(&self, block: usize) -> Option<usize> {
+        self.idom[block]
+    }
+
+    /// Blocks immediately dominated by each block, the edges of the dominator tree
+    pub fn children(&self) -> Vec<Vec<usize>> {
+        let mut children = vec![Vec::new(); self.idom.len()];
+        for (block, idom) in self.idom.iter().enumerate() {
+            if let Some(parent) = idom {
+                children[*parent].push(block);
+            }
+        }
+        children
+    }
+
+    /// Dominance frontier of each block: the blocks where its dominance stops, where the values
+    /// it defines meet the values coming from other paths
+    pub fn frontiers(&self, cfg: &ControlFlowGraph) -> Vec<BTreeSet<usize>> {
+        let mut frontiers = vec![BTreeSet::new(); self.idom.len()];
+        for block in &cfg.blocks {
+            if block.predecessors.len() < 2 {
+                continue;
+            }
+            let Some(idom) = self.idom[block.id] else {
+                continue;
+            };
+            for &predecessor in &block.predecessors {
+                // Walk up from each predecessor until the immediate dominator of the join
+                let mut runner = Some(predecessor);
+                while let Some(current) = runner.filter(|r| *r != idom) {
+                    if !self.dominates(cfg.entry().unwrap_or(0), current) {
+                        break;
+                    }
+                    frontiers[current].insert(block.id);
+                    runner = self.idom[current];
+                }
+            }
+        }
+        frontiers
+    }
+
+    /// True when every path from the entry to `block` goes through `dominator`
+    pub fn dominates(&self, dominator: usize, block: usize) -> bool {
+        let mut current = Some(block);
+        while let Some(b) = current {
+            if b == dominator {
+                return true;
+            }
+            current = self.idom[b];
+        }
+        false
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+
+    // FOR(I = 0 : 1 : 10) { IF (I > 5) { WRITE(I); } }
+    const LOOP: &str = r#"
+        INTEGER I, t1, t2, t3;
+        0: (Assign, 0, _, I)
+        1: (LessThan, I, 10, t1)
+        2: (IfFalse, t1, _, 10)
+        3: (GreaterThan, I, 5, t2)
+        4: (IfFalse, t2, _, 7)
+        5: (Write, I, _, _)
+        6: (Write, "\n", _, _)
+        7: (Add, I, 1, t3)
+        8: (Assign, t3, _, I)
+        9: (Goto, _, _, 1)
+    "#;
+
+    #[test]
+    fn test_basic_blocks_and_edges() {
+        let quads = parse_quadruplets(LOOP).unwrap();
+        let cfg = ControlFlowGraph::build(&quads);
+
+        let ranges: Vec<(usize, usize)> = cfg.blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(ranges, vec![(0, 1), (1, 3), (3, 5), (5, 7), (7, 10)]);
+        let successors: Vec<Vec<usize>> = cfg.blocks.iter().map(|b| b.successors.clone()).collect();
+        assert_eq!(successors, vec![vec![1], vec![2], vec![3, 4], vec![4], vec![1]]);
+        assert_eq!(cfg.blocks[1].predecessors, vec![0, 4]);
+        assert_eq!(cfg.blocks[4].predecessors, vec![2, 3]);
+        assert_eq!(cfg.block_of(6), 3);
+    }
+
+    #[test]
+    fn test_reverse_post_order() {
+        let quads = parse_quadruplets(LOOP).unwrap();
+        let cfg = ControlFlowGraph::build(&quads);
+        assert_eq!(cfg.reverse_post_order(), vec![0, 1, 2, 3, 4]);
+
+        // The block after an unconditional jump to the end is unreachable
+        let quads = parse_quadruplets("INTEGER X;\n(Goto, _, _, 2)\n(Assign, 1, _, X)").unwrap();
+        let cfg = ControlFlowGraph::build(&quads);
+        assert_eq!(cfg.blocks.len(), 2);
+        assert_eq!(cfg.reverse_post_order(), vec![0]);
+    }
+
+    #[test]
+    fn test_dominators() {
+        let quads = parse_quadruplets(LOOP).unwrap();
+        let dominators = ControlFlowGraph::build(&quads).dominators();
+        let idom: Vec<Option<usize>> = (0..5).map(|b| dominators.immediate(b)).collect();
+        assert_eq!(idom, vec![None, Some(0), Some(1), Some(2), Some(2)]);
+        assert!(dominators.dominates(1, 4));
+        assert!(dominators.dominates(3, 3));
+        assert!(!dominators.dominates(3, 4));
+        assert_eq!(dominators.children(), vec![vec![1], vec![2], vec![3, 4], vec![], vec![]]);
+
+        let frontiers = dominators.frontiers(&ControlFlowGraph::build(&quads));
+        assert_eq!(frontiers, vec![
+            BTreeSet::new(),
+            BTreeSet::from([1]),
+            BTreeSet::from([1]),
+            BTreeSet::from([4]),
+            BTreeSet::from([1]),
+        ]);
+    }
+
+    #[test]
+    fn test_dot_export() {
+        let quads = parse_quadruplets(LOOP).unwrap();
+        let dot = ControlFlowGraph::build(&quads).to_dot(&quads, "loop");
+        assert!(dot.starts_with("digraph \"loop\" {"));
+        assert!(dot.contains("B3 [label=\"B3\\l5: (Write, I, _, _)\\l6: (Write, \\\"\\\\n\\\", _, _)\\l\"];"));
+        assert!(dot.contains("B1 -> B2 [label=\"true\"];"));
+        assert!(dot.contains("B2 -> B4 [label=\"false\"];"));
+        assert!(dot.contains("B4 -> B1;"));
+        assert!(dot.contains("entry -> B0;"));
+    }
+}
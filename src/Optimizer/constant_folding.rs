@@ -0,0 +1,308 @@
+use std::collections::{HashMap, HashSet};
+use crate::Optimizer::cfg::ControlFlowGraph;
+use crate::Semantic::quadruplets::{remove_quadruplets, Operand, Operator, Quadruplet, Value};
+
+/// Local optimizer: constant propagation, constant folding and algebraic simplification
+/// inside each basic block. Returns true when the quadruplets changed
+///
+/// Folding never hides a run-time error: INTEGER operations that overflow or divide by zero
+/// are left in place so they still fail when the program runs
+pub fn fold_constants(quadruplets: &mut Vec<Quadruplet>) -> bool {
+    let mut changed = false;
+    // Removing a jump on a constant condition can merge blocks and expose more constants
+    while fold_blocks(quadruplets) {
+        changed = true;
+    }
+    changed
+}
+
+fn fold_blocks(quadruplets: &mut Vec<Quadruplet>) -> bool {
+    let cfg = ControlFlowGraph::build(quadruplets);
+    let mut changed = false;
+    let mut removed = vec![false; quadruplets.len()];
+
+    for block in &cfg.blocks {
+        // Variables and temporaries holding a known constant at this point of the block
+        let mut known: HashMap<String, Value> = HashMap::new();
+
+        for index in block.start..block.end {
+            let quadruplet = &mut quadruplets[index];
+
+            // Changes are reported by the rewrites rather than by comparing with the original
+            // quadruplet, a NaN constant is never equal to itself
+            changed |= propagate(quadruplet, &known);
+            changed |= simplify(quadruplet);
+            match quadruplet.operator {
+                Operator::IfTrue | Operator::IfFalse => {
+                    if let Some(Operand::Constant(condition)) = &quadruplet.operand1 {
+                        if condition.is_true() == (quadruplet.operator == Operator::IfTrue) {
+                            quadruplet.operator = Operator::Goto;
+                            quadruplet.operand1 = None;
+                        } else {
+                            removed[index] = true;
+                        }
+                        changed = true;
+                    }
+                },
+                _ => {},
+            }
+
+            if let Some(name) = quadruplet.result.as_ref().and_then(scalar_name) {
+                match (&quadruplet.operator, &quadruplet.operand1) {
+                    (Operator::Assign, Some(Operand::Constant(value))) => known.insert(name, *value),
+                    _ => known.remove(&name),
+                };
+            }
+        }
+    }
+
+    // Temporaries set to a constant and no longer read are dropped, their value was propagated
+    let used = used_temporaries(quadruplets);
+    for (index, quadruplet) in quadruplets.iter().enumerate() {
+        if let (Operator::Assign, Some(Operand::Constant(_)), Some(Operand::Temporary(number, _))) =
+            (&quadruplet.operator, &quadruplet.operand1, &quadruplet.result) {
+            if !used.contains(number) {
+                removed[index] = true;
+                changed = true;
+            }
+        }
+    }
+
+    remove_quadruplets(quadruplets, &removed);
+    changed
+}
+
+/// Name under which a variable or temporary is tracked, array cells are not tracked
+fn scalar_name(operand: &Operand) -> Option<String> {
+    match operand {
+        Operand::Variable(_, _) | Operand::Temporary(_, _) => Some(operand.to_string()),
+        _ => None,
+    }
+}
+
+/// Replaces the operands read by the quadruplet with their known constant value, returns
+/// true when one was replaced
+fn propagate(quadruplet: &mut Quadruplet, known: &HashMap<String, Value>) -> bool {
+    let mut changed = false;
+    let mut replace = |operand: &mut Operand| {
+        if let Some(value) = scalar_name(operand).and_then(|name| known.get(&name)) {
+            *operand = Operand::Constant(*value);
+            changed = true;
+        }
+    };
+    for operand in [&mut quadruplet.operand1, &mut quadruplet.operand2].into_iter().flatten() {
+        match operand {
+            Operand::ArrayElement(_, _, index) => replace(index),
+            _ => replace(operand),
+        }
+    }
+    // The index of a stored array cell is read too
+    if let Some(Operand::ArrayElement(_, _, index)) = &mut quadruplet.result {
+        replace(index);
+    }
+    changed
+}
+
+/// Folds operations on constants and applies the identities x+0, x-0, x*1, x/1 and x*0,
+/// returns true when the quadruplet was rewritten
+fn simplify(quadruplet: &mut Quadruplet) -> bool {
+    if quadruplet.operator == Operator::LogicalNot {
+        if let Some(Operand::Constant(value)) = &quadruplet.operand1 {
+            let value = Value::Integer(!value.is_true() as i16);
+            *quadruplet = Quadruplet { origin: quadruplet.origin.take(), ..Quadruplet::new(Operator::Assign, Some(Operand::Constant(value)), None, quadruplet.result.take()) };
+            return true;
+        }
+        return false;
+    }
+    if !quadruplet.operator.is_binary() {
+        return false;
+    }
+
+    let (Some(left), Some(right)) = (&quadruplet.operand1, &quadruplet.operand2) else {
+        return false;
+    };
+    let replacement = match (left, right) {
+        (Operand::Constant(a), Operand::Constant(b)) => a.binary(&quadruplet.operator, b).ok().map(Operand::Constant),
+        (x, Operand::Constant(c)) => match (&quadruplet.operator, c) {
+            (Operator::Add | Operator::Subtract, Value::Integer(0)) => Some(x.clone()),
+            (Operator::Subtract, Value::Float(f)) if *f == 0.0 => Some(x.clone()),
+            (Operator::Multiply | Operator::Divide, Value::Integer(1)) => Some(x.clone()),
+            (Operator::Multiply | Operator::Divide, Value::Float(f)) if *f == 1.0 => Some(x.clone()),
+            (Operator::Multiply, Value::Integer(0)) => Some(Operand::Constant(Value::Integer(0))),
+            _ => None,
+        },
+        (Operand::Constant(c), x) => match (&quadruplet.operator, c) {
+            (Operator::Add, Value::Integer(0)) => Some(x.clone()),
+            (Operator::Multiply, Value::Integer(1)) => Some(x.clone()),
+            (Operator::Multiply, Value::Float(f)) if *f == 1.0 => Some(x.clone()),
+            (Operator::Multiply, Value::Integer(0)) => Some(Operand::Constant(Value::Integer(0))),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    match replacement {
+        Some(value) => {
+            *quadruplet = Quadruplet { origin: quadruplet.origin.take(), ..Quadruplet::new(Operator::Assign, Some(value), None, quadruplet.result.take()) };
+            true
+        },
+        None => false,
+    }
+}
+
+/// Numbers of the temporaries read anywhere in the quadruplets
+fn used_temporaries(quadruplets: &[Quadruplet]) -> HashSet<usize> {
+    fn visit(operand: &Operand, used: &mut HashSet<usize>) {
+        match operand {
+            Operand::Temporary(number, _) => {
+                used.insert(*number);
+            },
+            Operand::ArrayElement(_, _, index) => visit(index, used),
+            _ => {},
+        }
+    }
+
+    let mut used = HashSet::new();
+    for quadruplet in quadruplets {
+        for operand in [&quadruplet.operand1, &quadruplet.operand2].into_iter().flatten() {
+            visit(operand, &mut used);
+        }
+        if let Some(Operand::ArrayElement(_, _, index)) = &quadruplet.result {
+            visit(index, &mut used);
+        }
+    }
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::{format_quadruplets, parse_quadruplets};
+
+    fn optimize(text: &str) -> Vec<String> {
+        let mut quads = parse_quadruplets(text).unwrap();
+        fold_constants(&mut quads);
+        quads.iter().map(|q| q.to_string()).collect()
+    }
+
+    #[test]
+    fn test_folding_and_propagation() {
+        let quads = optimize(r#"
+            INTEGER X, Y, t1, t2, t3;
+            FLOAT F, t4;
+            (Assign, 6, _, X)
+            (Multiply, X, 7, t1)
+            (Add, t1, 2, t2)
+            (Assign, t2, _, Y)
+            (Divide, 1.0, 4.0, t4)
+            (Assign, t4, _, F)
+            (Read, _, _, X)
+            (Add, X, 1, t3)
+        "#);
+        assert_eq!(quads, vec![
+            "(Assign, 6, _, X)",
+            "(Assign, 44, _, Y)",
+            "(Assign, 0.25, _, F)",
+            "(Read, _, _, X)",
+            "(Add, X, 1, t3)",
+        ]);
+    }
+
+    #[test]
+    fn test_integer_errors_are_not_folded() {
+        let quads = optimize(r#"
+            INTEGER t1, t2;
+            (Divide, 5, 0, t1)
+            (Add, 32767, 1, t2)
+            (Write, t1, _, _)
+            (Write, t2, _, _)
+        "#);
+        assert_eq!(quads, vec!["(Divide, 5, 0, t1)", "(Add, 32767, 1, t2)", "(Write, t1, _, _)", "(Write, t2, _, _)"]);
+    }
+
+    #[test]
+    fn test_nan_is_folded_once() {
+        // NaN is not equal to itself, folding it must still reach a fixed point
+        let quads = optimize(r#"
+            FLOAT F, t1, t2;
+            (Divide, 0.0, 0.0, t1)
+            (Subtract, t1, t1, t2)
+            (Assign, t2, _, F)
+            (Write, F, _, _)
+        "#);
+        assert_eq!(quads, vec!["(Assign, NaN, _, F)", "(Write, NaN, _, _)"]);
+    }
+
+    #[test]
+    fn test_algebraic_identities() {
+        let quads = optimize(r#"
+            INTEGER X, t1, t2, t3, t4;
+            FLOAT F, t5, t6;
+            (Read, _, _, X)
+            (Read, _, _, F)
+            (Multiply, X, 1, t1)
+            (Add, 0, X, t2)
+            (Multiply, X, 0, t3)
+            (Subtract, X, 0, t4)
+            (Multiply, F, 0.0, t5)
+            (Divide, F, 1.0, t6)
+            (Write, t1, _, _)
+            (Write, t2, _, _)
+            (Write, t3, _, _)
+            (Write, t4, _, _)
+            (Write, t5, _, _)
+            (Write, t6, _, _)
+        "#);
+        assert_eq!(quads, vec![
+            "(Read, _, _, X)",
+            "(Read, _, _, F)",
+            "(Assign, X, _, t1)",
+            "(Assign, X, _, t2)",
+            "(Assign, X, _, t4)",
+            // x * 0.0 is not 0.0 for infinities and NaN
+            "(Multiply, F, 0.0, t5)",
+            "(Assign, F, _, t6)",
+            "(Write, t1, _, _)",
+            "(Write, t2, _, _)",
+            "(Write, 0, _, _)",
+            "(Write, t4, _, _)",
+            "(Write, t5, _, _)",
+            "(Write, t6, _, _)",
+        ]);
+    }
+
+    #[test]
+    fn test_constant_conditions() {
+        let mut quads = parse_quadruplets(r#"
+            INTEGER X, t1, t2;
+            0: (Assign, 3, _, X)
+            1: (GreaterThan, X, 5, t1)
+            2: (IfTrue, t1, _, 5)
+            3: (Write, "small", _, _)
+            4: (Assign, 10, _, X)
+            5: (Add, X, 1, t2)
+            6: (Write, t2, _, _)
+        "#).unwrap();
+        assert!(fold_constants(&mut quads));
+        assert_eq!(format_quadruplets(&quads), "%% MinING quadruplets\nINTEGER X;\n\
+            0: (Assign, 3, _, X)\n\
+            1: (Write, \"small\", _, _)\n\
+            2: (Assign, 10, _, X)\n\
+            3: (Write, 11, _, _)\n");
+    }
+
+    #[test]
+    fn test_block_boundaries() {
+        let mut quads = parse_quadruplets(r#"
+            INTEGER X, Y, t1;
+            0: (Assign, 3, _, X)
+            1: (Read, _, _, Y)
+            2: (IfTrue, Y, _, 4)
+            3: (Assign, 10, _, X)
+            4: (Add, X, 1, t1)
+            5: (Write, t1, _, _)
+        "#).unwrap();
+        // X is not known at quadruplet 4 since two paths reach it
+        assert!(!fold_constants(&mut quads));
+    }
+}
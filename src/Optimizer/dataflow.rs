@@ -0,0 +1,447 @@
+use std::collections::{BTreeSet, VecDeque};
+use crate::Optimizer::cfg::{BasicBlock, ControlFlowGraph};
+use crate::Semantic::quadruplets::{Operand, Quadruplet};
+
+/// Direction in which the facts of an analysis flow along the edges of the CFG
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Forward,    // From the entry towards the exits, facts hold before each block
+    Backward,   // From the exits towards the entry, facts hold after each block
+}
+
+/// A dataflow problem solved by `solve`
+///
+/// Facts form a semi-lattice: `meet` combines the facts of several paths and `initial` is its
+/// top element, the value every block starts from before the first iteration
+pub trait DataflowAnalysis {
+    type Fact: Clone + PartialEq;
+
+    fn direction(&self) -> Direction;
+
+    /// Fact at the program entry (forward) or at the program exits (backward)
+    fn boundary(&self) -> Self::Fact;
+
+    /// Starting fact of every block
+    fn initial(&self) -> Self::Fact;
+
+    /// Combines the fact of another path into `fact`
+    fn meet(&self, fact: &mut Self::Fact, other: &Self::Fact);
+
+    /// Effect of a single quadruplet, `fact` holds before it (forward) or after it (backward)
+    fn transfer(&self, index: usize, quadruplet: &Quadruplet, fact: &mut Self::Fact);
+
+    /// Effect of a whole block, applies `transfer` to its quadruplets in flow order
+    fn transfer_block(&self, block: &BasicBlock, quadruplets: &[Quadruplet], fact: &mut Self::Fact) {
+        match self.direction() {
+            Direction::Forward => {
+                for (index, quadruplet) in quadruplets.iter().enumerate().take(block.end).skip(block.start) {
+                    self.transfer(index, quadruplet, fact);
+                }
+            },
+            Direction::Backward => {
+                for (index, quadruplet) in quadruplets.iter().enumerate().take(block.end).skip(block.start).rev() {
+                    self.transfer(index, quadruplet, fact);
+                }
+            },
+        }
+    }
+}
+
+/// Fixpoint of an analysis, facts are indexed by block id
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataflowResult<F> {
+    pub block_in: Vec<F>,       // Fact before the first quadruplet of each block
+    pub block_out: Vec<F>,      // Fact after the last quadruplet of each block
+    pub iterations: usize,      // Number of times a block was processed
+}
+
+/// Iterative worklist solver
+/// Blocks are visited in reverse post-order (forward) or post-order (backward) so that most
+/// problems converge in a couple of passes, unreachable blocks are solved as well
+pub fn solve<A: DataflowAnalysis>(analysis: &A, cfg: &ControlFlowGraph, quadruplets: &[Quadruplet]) -> DataflowResult<A::Fact> {
+    let count = cfg.blocks.len();
+    let mut block_in = vec![analysis.initial(); count];
+    let mut block_out = vec![analysis.initial(); count];
+    let direction = analysis.direction();
+
+    let mut order = cfg.reverse_post_order();
+    let mut reached = vec![false; count];
+    for &block in &order {
+        reached[block] = true;
+    }
+    order.extend((0..count).filter(|block| !reached[*block]));
+    if direction == Direction::Backward {
+        order.reverse();
+    }
+
+    let mut worklist: VecDeque<usize> = order.into_iter().collect();
+    let mut queued = vec![true; count];
+    let mut iterations = 0;
+    while let Some(id) = worklist.pop_front() {
+        queued[id] = false;
+        iterations += 1;
+        let block = &cfg.blocks[id];
+
+        // Meet of the neighbours the facts come from, the boundary joins in at the entry or exits
+        let (sources, facts, is_boundary) = match direction {
+            Direction::Forward => (&block.predecessors, &block_out, cfg.entry() == Some(id)),
+            Direction::Backward => (&block.successors, &block_in, block.successors.is_empty()),
+        };
+        let mut fact = if is_boundary { Some(analysis.boundary()) } else { None };
+        for &source in sources {
+            match &mut fact {
+                Some(fact) => analysis.meet(fact, &facts[source]),
+                None => fact = Some(facts[source].clone()),
+            }
+        }
+        let fact = fact.unwrap_or_else(|| analysis.initial());
+
+        let mut result = fact.clone();
+        analysis.transfer_block(block, quadruplets, &mut result);
+        let (start, end, targets) = match direction {
+            Direction::Forward => (&mut block_in, &mut block_out, &block.successors),
+            Direction::Backward => (&mut block_out, &mut block_in, &block.predecessors),
+        };
+        start[id] = fact;
+        if end[id] != result {
+            end[id] = result;
+            for &target in targets {
+                if !queued[target] {
+                    queued[target] = true;
+                    worklist.push_back(target);
+                }
+            }
+        }
+    }
+
+    DataflowResult { block_in, block_out, iterations }
+}
+
+/// Fact holding at each quadruplet once the analysis is solved: before the quadruplet for a
+/// forward analysis, after it for a backward one (e.g., the variables live after it)
+pub fn quadruplet_facts<A: DataflowAnalysis>(
+    analysis: &A,
+    cfg: &ControlFlowGraph,
+    quadruplets: &[Quadruplet],
+    result: &DataflowResult<A::Fact>,
+) -> Vec<A::Fact> {
+    let mut facts = vec![analysis.initial(); quadruplets.len()];
+    for block in &cfg.blocks {
+        match analysis.direction() {
+            Direction::Forward => {
+                let mut fact = result.block_in[block.id].clone();
+                for index in block.start..block.end {
+                    facts[index] = fact.clone();
+                    analysis.transfer(index, &quadruplets[index], &mut fact);
+                }
+            },
+            Direction::Backward => {
+                let mut fact = result.block_out[block.id].clone();
+                for index in (block.start..block.end).rev() {
+                    facts[index] = fact.clone();
+                    analysis.transfer(index, &quadruplets[index], &mut fact);
+                }
+            },
+        }
+    }
+    facts
+}
+
+/// Name of the variable or temporary the quadruplet assigns, None when it stores into an array
+/// cell or has no result
+pub fn defined_name(quadruplet: &Quadruplet) -> Option<String> {
+    if quadruplet.operator.is_jump() {
+        return None;
+    }
+    match &quadruplet.result {
+        Some(operand @ (Operand::Variable(_, _) | Operand::Temporary(_, _))) => Some(operand.to_string()),
+        _ => None,
+    }
+}
+
+/// Names of the variables, temporaries and arrays read by the quadruplet
+/// Reading an array cell reads the whole array and the index, storing into one reads the index
+pub fn used_names(quadruplet: &Quadruplet) -> Vec<String> {
+    fn visit(operand: &Operand, names: &mut Vec<String>) {
+        match operand {
+            Operand::Variable(_, _) | Operand::Temporary(_, _) => names.push(operand.to_string()),
+            Operand::ArrayElement(name, _, index) => {
+                names.push(name.clone());
+                visit(index, names);
+            },
+            _ => {},
+        }
+    }
+
+    let mut names = Vec::new();
+    for operand in [&quadruplet.operand1, &quadruplet.operand2].into_iter().flatten() {
+        visit(operand, &mut names);
+    }
+    if let Some(Operand::ArrayElement(_, _, index)) = &quadruplet.result {
+        visit(index, &mut names);
+    }
+    names
+}
+
+/// Reaching definitions: the quadruplets whose assignment may still hold at each point
+/// A store into an array cell is a definition of the array which never kills another one
+pub struct ReachingDefinitions {
+    definitions: Vec<Option<String>>,   // Name defined by each quadruplet
+}
+
+impl ReachingDefinitions {
+    pub fn new(quadruplets: &[Quadruplet]) -> Self {
+        let definitions = quadruplets.iter().map(|quadruplet| match &quadruplet.result {
+            Some(Operand::ArrayElement(name, _, _)) => Some(name.clone()),
+            _ => defined_name(quadruplet),
+        }).collect();
+        ReachingDefinitions { definitions }
+    }
+
+    /// Name defined by the quadruplet at `index`
+    pub fn defined(&self, index: usize) -> Option<&str> {
+        self.definitions[index].as_deref()
+    }
+}
+
+impl DataflowAnalysis for ReachingDefinitions {
+    type Fact = BTreeSet<usize>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn boundary(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn initial(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn meet(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.extend(other.iter().copied());
+    }
+
+    fn transfer(&self, index: usize, quadruplet: &Quadruplet, fact: &mut Self::Fact) {
+        let Some(name) = &self.definitions[index] else {
+            return;
+        };
+        if !matches!(quadruplet.result, Some(Operand::ArrayElement(_, _, _))) {
+            fact.retain(|definition| self.definitions[*definition].as_ref() != Some(name));
+        }
+        fact.insert(index);
+    }
+}
+
+/// Live variables: the variables, temporaries and arrays whose current value may still be read
+pub struct LiveVariables;
+
+impl DataflowAnalysis for LiveVariables {
+    type Fact = BTreeSet<String>;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn boundary(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn initial(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn meet(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.extend(other.iter().cloned());
+    }
+
+    fn transfer(&self, _index: usize, quadruplet: &Quadruplet, fact: &mut Self::Fact) {
+        if let Some(name) = defined_name(quadruplet) {
+            fact.remove(&name);
+        }
+        fact.extend(used_names(quadruplet));
+    }
+}
+
+/// Expression computed by a binary quadruplet, operands are kept in their textual form
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Expression {
+    pub operator: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl Expression {
+    /// Expression of a binary quadruplet whose operands are variables, temporaries or constants
+    pub fn of(quadruplet: &Quadruplet) -> Option<Expression> {
+        if !quadruplet.operator.is_binary() {
+            return None;
+        }
+        let scalar = |operand: &Option<Operand>| match operand {
+            Some(operand @ (Operand::Variable(_, _) | Operand::Temporary(_, _) | Operand::Constant(_))) => {
+                Some(operand.to_string())
+            },
+            _ => None,
+        };
+        Some(Expression {
+            operator: format!("{:?}", quadruplet.operator),
+            left: scalar(&quadruplet.operand1)?,
+            right: scalar(&quadruplet.operand2)?,
+        })
+    }
+
+    fn uses(&self, name: &str) -> bool {
+        self.left == name || self.right == name
+    }
+}
+
+/// Available expressions: the expressions computed on every path and whose operands were not
+/// assigned since, the fact of a block starts from every expression of the program
+pub struct AvailableExpressions {
+    universe: BTreeSet<Expression>,
+}
+
+impl AvailableExpressions {
+    pub fn new(quadruplets: &[Quadruplet]) -> Self {
+        AvailableExpressions { universe: quadruplets.iter().filter_map(Expression::of).collect() }
+    }
+}
+
+impl DataflowAnalysis for AvailableExpressions {
+    type Fact = BTreeSet<Expression>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    fn boundary(&self) -> Self::Fact {
+        BTreeSet::new()
+    }
+
+    fn initial(&self) -> Self::Fact {
+        self.universe.clone()
+    }
+
+    fn meet(&self, fact: &mut Self::Fact, other: &Self::Fact) {
+        fact.retain(|expression| other.contains(expression));
+    }
+
+    fn transfer(&self, _index: usize, quadruplet: &Quadruplet, fact: &mut Self::Fact) {
+        if let Some(expression) = Expression::of(quadruplet) {
+            fact.insert(expression);
+        }
+        // An assignment kills every expression reading the assigned name, its own included
+        if let Some(name) = defined_name(quadruplet) {
+            fact.retain(|expression| !expression.uses(&name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+
+    // S = 0; FOR(I = 0 : 1 : N) { S = S + I * 2; } WRITE(S);
+    const LOOP: &str = r#"
+        INTEGER S, I, N, t1, t2, t3, t4;
+        0: (Assign, 0, _, S)
+        1: (Assign, 0, _, I)
+        2: (LessThan, I, N, t1)
+        3: (IfFalse, t1, _, 10)
+        4: (Multiply, I, 2, t2)
+        5: (Add, S, t2, t3)
+        6: (Assign, t3, _, S)
+        7: (Add, I, 1, t4)
+        8: (Assign, t4, _, I)
+        9: (Goto, _, _, 2)
+        10: (Write, S, _, _)
+        11: (Multiply, I, 2, t2)
+    "#;
+
+    fn set<T: Ord + Clone>(items: &[T]) -> BTreeSet<T> {
+        items.iter().cloned().collect()
+    }
+
+    fn names(items: &[&str]) -> BTreeSet<String> {
+        items.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_reaching_definitions() {
+        let quads = parse_quadruplets(LOOP).unwrap();
+        let cfg = ControlFlowGraph::build(&quads);
+        let analysis = ReachingDefinitions::new(&quads);
+        let result = solve(&analysis, &cfg, &quads);
+
+        // Blocks: B0 = 0..2, B1 = 2..4 (loop header), B2 = 4..10 (body), B3 = 10..12
+        assert_eq!(cfg.blocks.len(), 4);
+        assert_eq!(result.block_in[0], set(&[]));
+        // Both the initial values and the values of the previous iteration reach the header
+        assert_eq!(result.block_in[1], set(&[0, 1, 2, 4, 5, 6, 7, 8]));
+        assert_eq!(result.block_out[2], set(&[2, 4, 5, 6, 7, 8]));
+        assert_eq!(analysis.defined(6), Some("S"));
+
+        let facts = quadruplet_facts(&analysis, &cfg, &quads, &result);
+        assert!(facts[10].contains(&0) && facts[10].contains(&6));
+        assert!(!facts[11].contains(&11));
+    }
+
+    #[test]
+    fn test_live_variables() {
+        let quads = parse_quadruplets(LOOP).unwrap();
+        let cfg = ControlFlowGraph::build(&quads);
+        let result = solve(&LiveVariables, &cfg, &quads);
+
+        assert_eq!(result.block_in[0], names(&["N"]));
+        assert_eq!(result.block_in[1], names(&["I", "N", "S"]));
+        assert_eq!(result.block_out[3], names(&[]));
+
+        // Live after each quadruplet of the body
+        let facts = quadruplet_facts(&LiveVariables, &cfg, &quads, &result);
+        assert_eq!(facts[4], names(&["I", "N", "S", "t2"]));
+        assert_eq!(facts[6], names(&["I", "N", "S"]));
+        assert_eq!(facts[10], names(&["I"]));
+    }
+
+    #[test]
+    fn test_available_expressions() {
+        let quads = parse_quadruplets(LOOP).unwrap();
+        let cfg = ControlFlowGraph::build(&quads);
+        let analysis = AvailableExpressions::new(&quads);
+        let result = solve(&analysis, &cfg, &quads);
+
+        let i_times_2 = Expression { operator: "Multiply".to_string(), left: "I".to_string(), right: "2".to_string() };
+        let i_less_n = Expression { operator: "LessThan".to_string(), left: "I".to_string(), right: "N".to_string() };
+        // Nothing is available at the entry, I < N is computed on every path reaching the exit block
+        assert_eq!(result.block_in[0], set(&[]));
+        assert_eq!(result.block_in[3], set(std::slice::from_ref(&i_less_n)));
+        // I changes at the end of the body, so I * 2 does not survive the back edge
+        assert!(!result.block_in[1].contains(&i_times_2));
+        let facts = quadruplet_facts(&analysis, &cfg, &quads, &result);
+        assert!(facts[5].contains(&i_times_2));
+        assert!(!facts[9].contains(&i_times_2));
+    }
+
+    #[test]
+    fn test_array_definitions_do_not_kill() {
+        let quads = parse_quadruplets(r#"
+            INTEGER K;
+            INTEGER V[4];
+            0: (Assign, 1, _, V[0])
+            1: (Read, _, _, K)
+            2: (Assign, 2, _, V[K])
+            3: (Write, V[1], _, _)
+        "#).unwrap();
+        let cfg = ControlFlowGraph::build(&quads);
+        let analysis = ReachingDefinitions::new(&quads);
+        let result = solve(&analysis, &cfg, &quads);
+        let facts = quadruplet_facts(&analysis, &cfg, &quads, &result);
+        assert_eq!(facts[3], set(&[0, 1, 2]));
+
+        let live = quadruplet_facts(&LiveVariables, &cfg, &quads, &solve(&LiveVariables, &cfg, &quads));
+        assert_eq!(live[1], names(&["K", "V"]));
+        assert_eq!(live[3], names(&[]));
+    }
+}
@@ -0,0 +1,96 @@
+use crate::Optimizer::cfg::ControlFlowGraph;
+use crate::Optimizer::dataflow::{defined_name, quadruplet_facts, solve, LiveVariables};
+use crate::Semantic::quadruplets::{jump_target, remove_quadruplets, Operator, Quadruplet};
+
+/// Removes unreachable blocks, assignments whose result is never read and jumps to the next
+/// quadruplet. Returns true when the quadruplets changed
+///
+/// READ consumes input, and quadruplets that may fail at run time (INTEGER arithmetic, array
+/// cells whose index is not a constant in bounds) are always kept
+pub fn eliminate_dead_code(quadruplets: &mut Vec<Quadruplet>) -> bool {
+    let mut changed = false;
+    loop {
+        let cfg = ControlFlowGraph::build(quadruplets);
+        let mut removed = vec![false; quadruplets.len()];
+
+        let mut reachable = vec![false; cfg.blocks.len()];
+        for block in cfg.reverse_post_order() {
+            reachable[block] = true;
+        }
+        for block in cfg.blocks.iter().filter(|b| !reachable[b.id]) {
+            removed[block.start..block.end].fill(true);
+        }
+
+        let live = quadruplet_facts(&LiveVariables, &cfg, quadruplets, &solve(&LiveVariables, &cfg, quadruplets));
+        for (index, quadruplet) in quadruplets.iter().enumerate() {
+            let dead = match defined_name(quadruplet) {
+                Some(name) => !live[index].contains(&name) && quadruplet.operator != Operator::Read && !quadruplet.can_fail(),
+                None => quadruplet.operator == Operator::Goto && jump_target(quadruplet) == Some(index + 1),
+            };
+            removed[index] |= dead;
+        }
+
+        if !removed.contains(&true) {
+            return changed;
+        }
+        remove_quadruplets(quadruplets, &removed);
+        changed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+
+    #[test]
+    fn test_dead_code_elimination() {
+        let mut quads = parse_quadruplets(r#"
+            INTEGER X, Y, Z, t1, t2, t3;
+            FLOAT F, t4;
+            0: (Read, _, _, X)
+            1: (Assign, 5, _, Y)
+            2: (Add, X, 1, t1)
+            3: (Assign, t1, _, Z)
+            4: (Multiply, F, 2.0, t4)
+            5: (GreaterThan, X, 0, t2)
+            6: (Goto, _, _, 9)
+            7: (Assign, 1, _, Z)
+            8: (Write, Z, _, _)
+            9: (Assign, t2, _, Y)
+            10: (Goto, _, _, 11)
+            11: (Write, Y, _, _)
+        "#).unwrap();
+        assert!(eliminate_dead_code(&mut quads));
+        let lines: Vec<String> = quads.iter().map(|q| q.to_string()).collect();
+        // X + 1 may overflow so it stays although t1 is never read
+        assert_eq!(lines, vec![
+            "(Read, _, _, X)",
+            "(Add, X, 1, t1)",
+            "(GreaterThan, X, 0, t2)",
+            "(Assign, t2, _, Y)",
+            "(Write, Y, _, _)",
+        ]);
+        assert!(!eliminate_dead_code(&mut quads));
+    }
+
+    #[test]
+    fn test_dead_array_reads() {
+        let mut quads = parse_quadruplets(r#"
+            INTEGER N, X, Y;
+            INTEGER A[5];
+            0: (Read, _, _, N)
+            1: (Assign, A[N], _, X)
+            2: (Assign, A[2], _, Y)
+            3: (Write, N, _, _)
+        "#).unwrap();
+        assert!(eliminate_dead_code(&mut quads));
+        let lines: Vec<String> = quads.iter().map(|q| q.to_string()).collect();
+        // A[N] is out of bounds when N >= 5, only the constant in-bounds read is removed
+        assert_eq!(lines, vec![
+            "(Read, _, _, N)",
+            "(Assign, A[N], _, X)",
+            "(Write, N, _, _)",
+        ]);
+    }
+}
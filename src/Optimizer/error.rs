@@ -0,0 +1,32 @@
+use thiserror::Error;
+use crate::codegen::error::RuntimeError;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum VerificationError {
+    #[error("Quadruplet {0} jumps to {1}, past the end of the program")]
+    InvalidTarget(usize, usize),
+
+    #[error("Malformed quadruplet {0}: {1}")]
+    MalformedQuadruplet(usize, String),
+
+    #[error("Name '{1}' used with two different types at quadruplet {0}")]
+    InconsistentType(usize, String),
+
+    #[error("Index {1} out of the bounds of array '{2}' at quadruplet {0}")]
+    IndexOutOfBounds(usize, i16, String),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PassError {
+    #[error("Unknown pass '{0}', expected one of: {1}")]
+    UnknownPass(String, String),
+
+    #[error("Invalid quadruplets after pass '{0}': {1}")]
+    Verification(String, VerificationError),
+
+    #[error("Cannot check the passes, the input program does not run: {0}")]
+    Unchecked(RuntimeError),
+
+    #[error("Pass '{0}' changed the behaviour of the program: {1}")]
+    Miscompilation(String, String),
+}
@@ -0,0 +1,532 @@
+use std::collections::{BTreeSet, HashMap};
+use crate::Optimizer::cfg::ControlFlowGraph;
+use crate::Optimizer::dataflow::{defined_name, solve, LiveVariables};
+use crate::Semantic::quadruplets::{insert_quadruplets, jump_target, next_temporary, remove_quadruplets, Operand, Operator, Quadruplet, Value};
+use crate::Semantic::ts::Types;
+
+/// Loop of a back edge: the header and every block reaching a latch without going through the header
+#[derive(Debug, Clone, PartialEq)]
+pub struct NaturalLoop {
+    pub header: usize,
+    pub latches: Vec<usize>,        // Blocks jumping back to the header
+    pub blocks: BTreeSet<usize>,
+}
+
+impl NaturalLoop {
+    pub fn contains(&self, block: usize) -> bool {
+        self.blocks.contains(&block)
+    }
+
+    /// Blocks outside the loop that are reached from inside it
+    pub fn exits(&self, cfg: &ControlFlowGraph) -> BTreeSet<usize> {
+        self.blocks.iter()
+            .flat_map(|block| cfg.blocks[*block].successors.iter().copied())
+            .filter(|successor| !self.contains(*successor))
+            .collect()
+    }
+
+    /// Indices of the quadruplets of the loop, in program order
+    fn quadruplets<'a>(&'a self, cfg: &'a ControlFlowGraph) -> impl Iterator<Item = usize> + 'a {
+        self.blocks.iter().flat_map(|block| cfg.blocks[*block].start..cfg.blocks[*block].end)
+    }
+}
+
+/// Natural loops of the graph, innermost first
+/// Back edges sharing a header make up a single loop
+pub fn find_loops(cfg: &ControlFlowGraph) -> Vec<NaturalLoop> {
+    let dominators = cfg.dominators();
+    let mut loops: Vec<NaturalLoop> = Vec::new();
+    for block in &cfg.blocks {
+        for &header in &block.successors {
+            if !dominators.dominates(header, block.id) {
+                continue;
+            }
+            let mut blocks = BTreeSet::from([header]);
+            let mut stack = vec![block.id];
+            while let Some(current) = stack.pop() {
+                if blocks.insert(current) {
+                    stack.extend(cfg.blocks[current].predecessors.iter().filter(|p| dominators.dominates(header, **p)));
+                }
+            }
+            match loops.iter_mut().find(|l| l.header == header) {
+                Some(existing) => {
+                    existing.latches.push(block.id);
+                    existing.blocks.extend(blocks);
+                },
+                None => loops.push(NaturalLoop { header, latches: vec![block.id], blocks }),
+            }
+        }
+    }
+    loops.sort_by_key(|l| (l.blocks.len(), l.header));
+    loops
+}
+
+/// Basic induction variable: a variable changed once per iteration by a loop invariant step,
+/// either directly (I = I + c) or through a temporary like the FOR loops do (t = I + c; I = t)
+#[derive(Debug, Clone, PartialEq)]
+pub struct InductionVariable {
+    pub variable: Operand,
+    pub operator: Operator,         // Add or Subtract
+    pub step: Operand,
+    pub update: usize,              // Index of the quadruplet assigning the new value
+}
+
+/// What the loop assigns, used to tell loop invariant operands apart
+struct LoopBody {
+    in_loop: Vec<bool>,                         // Whether each quadruplet belongs to the loop
+    definitions: HashMap<String, Vec<usize>>,   // Quadruplets of the loop assigning each name
+}
+
+impl LoopBody {
+    fn new(quadruplets: &[Quadruplet], cfg: &ControlFlowGraph, natural_loop: &NaturalLoop) -> Self {
+        let mut in_loop = vec![false; quadruplets.len()];
+        let mut definitions: HashMap<String, Vec<usize>> = HashMap::new();
+        for index in natural_loop.quadruplets(cfg) {
+            in_loop[index] = true;
+            let name = match &quadruplets[index].result {
+                Some(Operand::ArrayElement(name, _, _)) => Some(name.clone()),
+                _ => defined_name(&quadruplets[index]),
+            };
+            if let Some(name) = name {
+                definitions.entry(name).or_default().push(index);
+            }
+        }
+        LoopBody { in_loop, definitions }
+    }
+
+    /// Constants and the variables and temporaries the loop never assigns
+    fn is_invariant(&self, operand: &Operand) -> bool {
+        match operand {
+            Operand::Constant(_) => true,
+            Operand::Variable(_, _) | Operand::Temporary(_, _) => !self.definitions.contains_key(&operand.to_string()),
+            _ => false,
+        }
+    }
+
+    fn single_definition(&self, name: &str) -> Option<usize> {
+        match self.definitions.get(name).map(Vec::as_slice) {
+            Some([index]) => Some(*index),
+            _ => None,
+        }
+    }
+
+    /// Operator and step when the quadruplet computes `variable + step` or `variable - step`
+    fn step_of(&self, quadruplet: &Quadruplet, variable: &Operand) -> Option<(Operator, Operand)> {
+        match (&quadruplet.operator, &quadruplet.operand1, &quadruplet.operand2) {
+            (Operator::Add | Operator::Subtract, Some(a), Some(step)) if a == variable && self.is_invariant(step) => {
+                Some((quadruplet.operator.clone(), step.clone()))
+            },
+            (Operator::Add, Some(step), Some(a)) if a == variable && self.is_invariant(step) => {
+                Some((Operator::Add, step.clone()))
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Basic INTEGER induction variables of a loop
+pub fn induction_variables(quadruplets: &[Quadruplet], cfg: &ControlFlowGraph, natural_loop: &NaturalLoop) -> Vec<InductionVariable> {
+    find_induction_variables(quadruplets, cfg, natural_loop, &LoopBody::new(quadruplets, cfg, natural_loop))
+}
+
+fn find_induction_variables(
+    quadruplets: &[Quadruplet],
+    cfg: &ControlFlowGraph,
+    natural_loop: &NaturalLoop,
+    body: &LoopBody,
+) -> Vec<InductionVariable> {
+    let mut variables = Vec::new();
+    for update in natural_loop.quadruplets(cfg) {
+        let quadruplet = &quadruplets[update];
+        let Some(variable) = quadruplet.result.clone() else {
+            continue;
+        };
+        let Some(name) = defined_name(quadruplet) else {
+            continue;
+        };
+        if body.single_definition(&name) != Some(update) || variable.value_type() != Some(Types::Integer) {
+            continue;
+        }
+
+        let step = match (&quadruplet.operator, &quadruplet.operand1) {
+            (Operator::Assign, Some(temporary @ Operand::Temporary(_, _))) => {
+                // The new value is computed just before in the same block
+                body.single_definition(&temporary.to_string())
+                    .filter(|index| *index < update && cfg.block_of(*index) == cfg.block_of(update))
+                    .and_then(|index| body.step_of(&quadruplets[index], &variable))
+            },
+            _ => body.step_of(quadruplet, &variable),
+        };
+        if let Some((operator, step)) = step {
+            variables.push(InductionVariable { variable, operator, step, update });
+        }
+    }
+    variables
+}
+
+/// What the loop optimizer did
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LoopStatistics {
+    pub loops: usize,       // Natural loops found
+    pub hoisted: usize,     // Loop invariant quadruplets moved to a preheader
+    pub reduced: usize,     // Multiplications by an induction variable replaced by additions
+}
+
+/// Loop invariant code motion and strength reduction on every natural loop, innermost loops first
+///
+/// Strength reduction is only applied when constant bounds show that the running product stays
+/// in the INTEGER range, since it is also updated after the last iteration
+pub fn optimize_loops(quadruplets: &mut Vec<Quadruplet>) -> LoopStatistics {
+    let mut statistics = LoopStatistics {
+        loops: find_loops(&ControlFlowGraph::build(quadruplets)).len(),
+        ..LoopStatistics::default()
+    };
+    // Each change moves code out of a loop or removes a multiplication, the graph is rebuilt after it
+    loop {
+        let cfg = ControlFlowGraph::build(quadruplets);
+        let mut changed = false;
+        for natural_loop in find_loops(&cfg) {
+            if hoist_invariant(quadruplets, &cfg, &natural_loop) {
+                statistics.hoisted += 1;
+            } else if reduce_strength(quadruplets, &cfg, &natural_loop) {
+                statistics.reduced += 1;
+            } else {
+                continue;
+            }
+            changed = true;
+            break;
+        }
+        if !changed {
+            return statistics;
+        }
+    }
+}
+
+/// Index where the preheader of the loop is inserted: right before the header, jumps entering
+/// the loop from outside go to the preheader. None when the header is reached by falling through
+/// from inside the loop, the preheader would be part of the loop
+fn preheader_position(quadruplets: &[Quadruplet], cfg: &ControlFlowGraph, natural_loop: &NaturalLoop) -> Option<usize> {
+    let start = cfg.blocks[natural_loop.header].start;
+    if start > 0 && natural_loop.contains(cfg.block_of(start - 1)) && quadruplets[start - 1].operator != Operator::Goto {
+        return None;
+    }
+    Some(start)
+}
+
+/// Moves the first loop invariant quadruplet that can safely run once before the loop into its preheader
+///
+/// The quadruplet must be the only assignment of its result in the loop, and the result must not
+/// be read before being assigned. It runs whether or not the loop is entered, so its result must
+/// be dead after the loop unless its block runs on every iteration. Operations that may fail are
+/// only hoisted from the start of the header, where they would have run first anyway
+fn hoist_invariant(quadruplets: &mut Vec<Quadruplet>, cfg: &ControlFlowGraph, natural_loop: &NaturalLoop) -> bool {
+    let Some(at) = preheader_position(quadruplets, cfg, natural_loop) else {
+        return false;
+    };
+    let body = LoopBody::new(quadruplets, cfg, natural_loop);
+    let live = solve(&LiveVariables, cfg, quadruplets);
+    let dominators = cfg.dominators();
+    let exits = natural_loop.exits(cfg);
+    let exiting: Vec<usize> = natural_loop.blocks.iter().copied()
+        .filter(|block| cfg.blocks[*block].successors.iter().any(|s| exits.contains(s)))
+        .collect();
+    let header = &cfg.blocks[natural_loop.header];
+
+    let hoistable = |index: usize| {
+        let quadruplet = &quadruplets[index];
+        let computes = quadruplet.operator == Operator::Assign
+            || quadruplet.operator == Operator::LogicalNot
+            || quadruplet.operator.is_binary();
+        let Some(name) = defined_name(quadruplet).filter(|_| computes) else {
+            return false;
+        };
+        if body.single_definition(&name) != Some(index)
+            || !quadruplet.operand1.iter().chain(&quadruplet.operand2).all(|o| body.is_invariant(o))
+            || live.block_in[natural_loop.header].contains(&name) {
+            return false;
+        }
+
+        let block = cfg.block_of(index);
+        if quadruplet.can_fail() {
+            block == natural_loop.header && (header.start..index).all(|i| {
+                !matches!(quadruplets[i].operator, Operator::Read | Operator::Write) && !quadruplets[i].can_fail()
+            })
+        } else {
+            exiting.iter().all(|e| dominators.dominates(block, *e))
+                || exits.iter().all(|e| !live.block_in[*e].contains(&name))
+        }
+    };
+    let Some(index) = natural_loop.quadruplets(cfg).find(|index| hoistable(*index)) else {
+        return false;
+    };
+
+    let quadruplet = quadruplets[index].clone();
+    insert_quadruplets(quadruplets, at, vec![quadruplet], |jump| !body.in_loop[jump]);
+    let mut removed = vec![false; quadruplets.len()];
+    removed[if index >= at { index + 1 } else { index }] = true;
+    remove_quadruplets(quadruplets, &removed);
+    true
+}
+
+/// Values a basic induction variable goes through, from the one it has on entering the loop to
+/// the one that ends it. Only known for a constant assigned right before the loop, a positive
+/// constant step and a header leaving the loop once the variable reaches a constant bound
+fn induction_range(quadruplets: &[Quadruplet], cfg: &ControlFlowGraph, natural_loop: &NaturalLoop, body: &LoopBody, variable: &InductionVariable) -> Option<(i32, i32)> {
+    let (Operator::Add, Operand::Constant(Value::Integer(step))) = (&variable.operator, &variable.step) else {
+        return None;
+    };
+    let header = &cfg.blocks[natural_loop.header];
+    let [.., compare, branch] = &quadruplets[header.start..header.end] else {
+        return None;
+    };
+    let (Operator::LessThan, Some(counter), Some(Operand::Constant(Value::Integer(bound)))) = (&compare.operator, &compare.operand1, &compare.operand2) else {
+        return None;
+    };
+    let exit = match (&branch.operator, &branch.operand1) {
+        (Operator::IfFalse, Some(condition)) if Some(condition) == compare.result.as_ref() => jump_target(branch)?,
+        _ => return None,
+    };
+    if counter != &variable.variable || *step <= 0 || exit >= quadruplets.len() || natural_loop.contains(cfg.block_of(exit)) {
+        return None;
+    }
+
+    // The loop is only entered by falling through from the assignment of the initial value
+    let entry = header.start.checked_sub(1)?;
+    let (Operator::Assign, Some(Operand::Constant(Value::Integer(initial)))) = (&quadruplets[entry].operator, &quadruplets[entry].operand1) else {
+        return None;
+    };
+    let entered_by_jump = quadruplets.iter().enumerate().any(|(index, q)| !body.in_loop[index] && jump_target(q) == Some(header.start));
+    if quadruplets[entry].result.as_ref() != Some(&variable.variable) || entered_by_jump {
+        return None;
+    }
+
+    let (initial, bound, step) = (*initial as i32, *bound as i32, *step as i32);
+    let last = match initial < bound {
+        true => initial + (bound - initial + step - 1) / step * step,
+        false => initial,
+    };
+    Some((initial, last))
+}
+
+/// Replaces a product `t = I * c` of a basic induction variable and a constant factor by a
+/// running product p: p = I * c before the loop, p = p + c * step after each update of I and t = p.
+/// Every value of p, the one following the last iteration included, must be an INTEGER
+fn reduce_strength(quadruplets: &mut Vec<Quadruplet>, cfg: &ControlFlowGraph, natural_loop: &NaturalLoop) -> bool {
+    let Some(at) = preheader_position(quadruplets, cfg, natural_loop) else {
+        return false;
+    };
+    let body = LoopBody::new(quadruplets, cfg, natural_loop);
+    let variables = find_induction_variables(quadruplets, cfg, natural_loop, &body);
+
+    for index in natural_loop.quadruplets(cfg) {
+        let quadruplet = &quadruplets[index];
+        let (Operator::Multiply, Some(left), Some(right)) = (&quadruplet.operator, &quadruplet.operand1, &quadruplet.operand2) else {
+            continue;
+        };
+        let single = defined_name(quadruplet).and_then(|name| body.single_definition(&name));
+        if single != Some(index) || quadruplet.result.as_ref().and_then(Operand::value_type) != Some(Types::Integer) {
+            continue;
+        }
+        let found = variables.iter().find_map(|variable| {
+            if &variable.variable == left && body.is_invariant(right) {
+                Some((variable, right))
+            } else if &variable.variable == right && body.is_invariant(left) {
+                Some((variable, left))
+            } else {
+                None
+            }
+        });
+        let Some((variable, Operand::Constant(Value::Integer(factor)))) = found else {
+            continue;
+        };
+        let Some((first, last)) = induction_range(quadruplets, cfg, natural_loop, &body, variable) else {
+            continue;
+        };
+        let Operand::Constant(Value::Integer(step)) = variable.step else {
+            continue;
+        };
+        let fits = |value: i32| i16::try_from(value).is_ok();
+        let factor = *factor as i32;
+        if !fits(last) || !fits(factor * first) || !fits(factor * last) || !fits(factor * step as i32) {
+            continue;
+        }
+
+        let product = Operand::Temporary(next_temporary(quadruplets), Types::Integer);
+        let preheader = vec![Quadruplet::new(Operator::Multiply, Some(left.clone()), Some(right.clone()), Some(product.clone()))];
+        let increment = Operand::Constant(Value::Integer((factor * step as i32) as i16));
+        let update = Quadruplet::new(variable.operator.clone(), Some(product.clone()), Some(increment), Some(product.clone()));
+        let after_update = variable.update + 1;
+
+        quadruplets[index] = Quadruplet { origin: quadruplets[index].origin.take(), ..Quadruplet::new(Operator::Assign, Some(product), None, quadruplets[index].result.clone()) };
+        // Jumps to the quadruplet following the update did not go through the update
+        insert_quadruplets(quadruplets, after_update, vec![update], |_| false);
+        let mut in_loop = body.in_loop;
+        in_loop.insert(after_update, true);
+        let at = if at >= after_update { at + 1 } else { at };
+        insert_quadruplets(quadruplets, at, preheader, |jump| !in_loop[jump]);
+        return true;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::executor::Execution;
+    use crate::Semantic::quadruplets::{format_quadruplets, parse_quadruplets};
+
+    // FOR(Var1 = 1 : 1 : 10) { FOR(Var2 = 0 : 2 : 6) { Array1[Var2] = Var2 * 3; } }
+    const NESTED: &str = r#"
+        INTEGER Var1, t1, Var2, t2, t3, t4, t5;
+        INTEGER Array1[10];
+        0: (Assign, 1, _, Var1)
+        1: (LessThan, Var1, 10, t1)
+        2: (IfFalse, t1, _, 14)
+        3: (Assign, 0, _, Var2)
+        4: (LessThan, Var2, 6, t2)
+        5: (IfFalse, t2, _, 11)
+        6: (Multiply, Var2, 3, t3)
+        7: (Assign, t3, _, Array1[Var2])
+        8: (Add, Var2, 2, t4)
+        9: (Assign, t4, _, Var2)
+        10: (Goto, _, _, 4)
+        11: (Add, Var1, 1, t5)
+        12: (Assign, t5, _, Var1)
+        13: (Goto, _, _, 1)
+    "#;
+
+    #[test]
+    fn test_natural_loops() {
+        let quads = parse_quadruplets(NESTED).unwrap();
+        let cfg = ControlFlowGraph::build(&quads);
+        let loops = find_loops(&cfg);
+        assert_eq!(loops.len(), 2);
+
+        let headers: Vec<usize> = loops.iter().map(|l| cfg.blocks[l.header].start).collect();
+        assert_eq!(headers, vec![4, 1]);
+        assert_eq!(loops[0].blocks.len(), 2);
+        assert!(loops[0].blocks.is_subset(&loops[1].blocks));
+        assert_eq!(loops[0].exits(&cfg).into_iter().map(|b| cfg.blocks[b].start).collect::<Vec<_>>(), vec![11]);
+        // Leaving the outer loop ends the program
+        assert!(loops[1].exits(&cfg).is_empty());
+    }
+
+    #[test]
+    fn test_induction_variables() {
+        let quads = parse_quadruplets(NESTED).unwrap();
+        let cfg = ControlFlowGraph::build(&quads);
+        let loops = find_loops(&cfg);
+
+        let inner = induction_variables(&quads, &cfg, &loops[0]);
+        assert_eq!(inner.len(), 1);
+        assert_eq!(inner[0].variable.to_string(), "Var2");
+        assert_eq!(inner[0].operator, Operator::Add);
+        assert_eq!(inner[0].step.to_string(), "2");
+        assert_eq!(inner[0].update, 9);
+
+        // Var2 is assigned twice in the outer loop
+        let outer: Vec<String> = induction_variables(&quads, &cfg, &loops[1]).iter().map(|v| v.variable.to_string()).collect();
+        assert_eq!(outer, vec!["Var1"]);
+    }
+
+    #[test]
+    fn test_strength_reduction() {
+        let mut quads = parse_quadruplets(NESTED).unwrap();
+        let statistics = optimize_loops(&mut quads);
+        // Var2 * 3 in the inner loop, Var2 is not an induction variable of the outer loop
+        assert_eq!(statistics, LoopStatistics { loops: 2, hoisted: 0, reduced: 1 });
+        assert_eq!(format_quadruplets(&quads), "%% MinING quadruplets\n\
+            INTEGER Var1, t1, Var2, t6, t2, t3, t4, t5;\n\
+            INTEGER Array1[10];\n\
+            0: (Assign, 1, _, Var1)\n\
+            1: (LessThan, Var1, 10, t1)\n\
+            2: (IfFalse, t1, _, 16)\n\
+            3: (Assign, 0, _, Var2)\n\
+            4: (Multiply, Var2, 3, t6)\n\
+            5: (LessThan, Var2, 6, t2)\n\
+            6: (IfFalse, t2, _, 13)\n\
+            7: (Assign, t6, _, t3)\n\
+            8: (Assign, t3, _, Array1[Var2])\n\
+            9: (Add, Var2, 2, t4)\n\
+            10: (Assign, t4, _, Var2)\n\
+            11: (Add, t6, 6, t6)\n\
+            12: (Goto, _, _, 5)\n\
+            13: (Add, Var1, 1, t5)\n\
+            14: (Assign, t5, _, Var1)\n\
+            15: (Goto, _, _, 1)\n");
+    }
+
+    #[test]
+    fn test_strength_reduction_overflow() {
+        // FOR (Ia = 0 : 1 : BOUND) { Xa = Ia * 1000; } WRITE(Xa);
+        let program = |bound: i16| parse_quadruplets(&format!(r#"
+            INTEGER Ia, t1, t2, Xa, t3;
+            0: (Assign, 0, _, Ia)
+            1: (LessThan, Ia, {}, t1)
+            2: (IfFalse, t1, _, 8)
+            3: (Multiply, Ia, 1000, t2)
+            4: (Assign, t2, _, Xa)
+            5: (Add, Ia, 1, t3)
+            6: (Assign, t3, _, Ia)
+            7: (Goto, _, _, 1)
+            8: (Write, Xa, _, _)
+        "#, bound)).unwrap();
+
+        // The running product would reach 33 * 1000 after the last iteration
+        let mut quads = program(33);
+        assert_eq!(optimize_loops(&mut quads).reduced, 0);
+        let execution = Execution::capture(&quads, b"", 10_000);
+        assert!(execution.result.is_ok());
+        assert_eq!(execution.output, b"32000");
+
+        let mut quads = program(32);
+        assert_eq!(optimize_loops(&mut quads).reduced, 1);
+        let execution = Execution::capture(&quads, b"", 10_000);
+        assert!(execution.result.is_ok());
+        assert_eq!(execution.output, b"31000");
+    }
+
+    #[test]
+    fn test_invariant_code_motion() {
+        // FOR(I = 0 : 1 : N * 2) { X = N + 1; Y = F * 2.5; WRITE(I, Y); }
+        let mut quads = parse_quadruplets(r#"
+            INTEGER I, N, X, t1, t2, t3, t4;
+            FLOAT F, Y, t5;
+            0: (Read, _, _, N)
+            1: (Assign, 0, _, I)
+            2: (Multiply, N, 2, t1)
+            3: (LessThan, I, t1, t2)
+            4: (IfFalse, t2, _, 13)
+            5: (Add, N, 1, t3)
+            6: (Assign, t3, _, X)
+            7: (Multiply, F, 2.5, t5)
+            8: (Assign, t5, _, Y)
+            9: (Write, Y, _, _)
+            10: (Add, I, 1, t4)
+            11: (Assign, t4, _, I)
+            12: (Goto, _, _, 2)
+            13: (Write, X, _, _)
+        "#).unwrap();
+        let statistics = optimize_loops(&mut quads);
+        // The bound is hoisted since the header runs it first, N + 1 may overflow in a body that might
+        // not run and X is read after the loop, the FLOAT product cannot fail
+        assert_eq!(statistics, LoopStatistics { loops: 1, hoisted: 3, reduced: 0 });
+        let lines: Vec<String> = quads.iter().map(|q| q.to_string()).collect();
+        assert_eq!(lines, vec![
+            "(Read, _, _, N)",
+            "(Assign, 0, _, I)",
+            "(Multiply, N, 2, t1)",
+            "(Multiply, F, 2.5, t5)",
+            "(Assign, t5, _, Y)",
+            "(LessThan, I, t1, t2)",
+            "(IfFalse, t2, _, 13)",
+            "(Add, N, 1, t3)",
+            "(Assign, t3, _, X)",
+            "(Write, Y, _, _)",
+            "(Add, I, 1, t4)",
+            "(Assign, t4, _, I)",
+            "(Goto, _, _, 5)",
+            "(Write, X, _, _)",
+        ]);
+    }
+}
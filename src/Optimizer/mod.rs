@@ -0,0 +1,9 @@
+pub mod cfg;
+pub mod constant_folding;
+pub mod dataflow;
+pub mod dead_code;
+pub mod error;
+pub mod loops;
+pub mod pass_manager;
+pub mod ssa;
+pub mod verifier;
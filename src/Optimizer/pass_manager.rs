@@ -0,0 +1,306 @@
+use std::time::{Duration, Instant};
+use colored::*;
+use crate::codegen::error::RuntimeError;
+use crate::codegen::executor::Execution;
+use crate::Optimizer::constant_folding::fold_constants;
+use crate::Optimizer::dead_code::eliminate_dead_code;
+use crate::Optimizer::error::PassError;
+use crate::Optimizer::loops::optimize_loops;
+use crate::Optimizer::ssa::SsaForm;
+use crate::Optimizer::verifier::verify_quadruplets;
+use crate::Semantic::quadruplets::{print_quadruplets, Quadruplet};
+
+/// Quadruplets executed by each run of the program when checking the passes
+pub const CHECK_STEP_LIMIT: u64 = 10_000_000;
+
+/// Passes that can be named in a pipeline or in --print-after
+pub const PASS_NAMES: [&str; 4] = ["constfold", "loops", "dce", "ssa"];
+
+/// A transformation of the quadruplets, returns true when it changed them
+/// The optional note summarizes what the pass did (e.g., the loops it found)
+pub trait Pass {
+    fn name(&self) -> &'static str;
+    fn run(&mut self, quadruplets: &mut Vec<Quadruplet>) -> (bool, Option<String>);
+}
+
+struct ConstantFolding;
+
+impl Pass for ConstantFolding {
+    fn name(&self) -> &'static str {
+        "constfold"
+    }
+
+    fn run(&mut self, quadruplets: &mut Vec<Quadruplet>) -> (bool, Option<String>) {
+        (fold_constants(quadruplets), None)
+    }
+}
+
+struct LoopOptimization;
+
+impl Pass for LoopOptimization {
+    fn name(&self) -> &'static str {
+        "loops"
+    }
+
+    fn run(&mut self, quadruplets: &mut Vec<Quadruplet>) -> (bool, Option<String>) {
+        let statistics = optimize_loops(quadruplets);
+        let note = format!("{} loop(s), {} hoisted, {} reduced", statistics.loops, statistics.hoisted, statistics.reduced);
+        (statistics.hoisted + statistics.reduced > 0, Some(note))
+    }
+}
+
+struct DeadCodeElimination;
+
+impl Pass for DeadCodeElimination {
+    fn name(&self) -> &'static str {
+        "dce"
+    }
+
+    fn run(&mut self, quadruplets: &mut Vec<Quadruplet>) -> (bool, Option<String>) {
+        (eliminate_dead_code(quadruplets), None)
+    }
+}
+
+/// Goes through SSA form and back, the copies it leaves are cleaned up by the later passes
+struct SsaRoundTrip;
+
+impl Pass for SsaRoundTrip {
+    fn name(&self) -> &'static str {
+        "ssa"
+    }
+
+    fn run(&mut self, quadruplets: &mut Vec<Quadruplet>) -> (bool, Option<String>) {
+        let ssa = SsaForm::build(quadruplets);
+        let phis = ssa.phis.iter().map(Vec::len).sum::<usize>();
+        let translated = ssa.into_quadruplets();
+        let changed = translated != *quadruplets;
+        *quadruplets = translated;
+        (changed, Some(format!("{} phi function(s)", phis)))
+    }
+}
+
+fn create_pass(name: &str) -> Result<Box<dyn Pass>, PassError> {
+    match name {
+        "constfold" => Ok(Box::new(ConstantFolding)),
+        "loops" => Ok(Box::new(LoopOptimization)),
+        "dce" => Ok(Box::new(DeadCodeElimination)),
+        "ssa" => Ok(Box::new(SsaRoundTrip)),
+        _ => Err(PassError::UnknownPass(name.to_string(), PASS_NAMES.join(", "))),
+    }
+}
+
+/// Passes run at each optimization level
+pub fn pipeline_for_level(level: u8) -> Vec<&'static str> {
+    match level {
+        0 => vec![],
+        1 => vec!["constfold", "dce"],
+        _ => vec!["constfold", "loops", "constfold", "dce"],
+    }
+}
+
+/// What a pass did, printed as one line of the pass report
+#[derive(Debug, Clone)]
+pub struct PassReport {
+    pub name: &'static str,
+    pub duration: Duration,
+    pub before: usize,          // Number of quadruplets before the pass
+    pub after: usize,           // Number of quadruplets after the pass
+    pub changed: bool,
+    pub note: Option<String>,
+}
+
+/// Runs a pipeline of passes over the quadruplets
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    print_after: Vec<String>,   // Names of the passes followed by a dump of the quadruplets, or "all"
+    verify: bool,               // Verify the quadruplets before the first pass and after each one
+    check_input: Option<Vec<u8>>,   // Input the program runs on before the first pass and after each one
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        PassManager::default()
+    }
+
+    /// Pipeline built from pass names (e.g., ["constfold", "dce"])
+    pub fn with_passes(names: &[&str]) -> Result<Self, PassError> {
+        let mut manager = PassManager::new();
+        for name in names {
+            manager.add_pass(create_pass(name)?);
+        }
+        Ok(manager)
+    }
+
+    pub fn add_pass(&mut self, pass: Box<dyn Pass>) {
+        self.passes.push(pass);
+    }
+
+    pub fn print_after(&mut self, name: &str) {
+        self.print_after.push(name.to_string());
+    }
+
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    /// Runs the program on `input` before the passes and after each one, a pass changing
+    /// what the program writes or how it ends is reported as a miscompilation
+    pub fn set_check_input(&mut self, input: Option<Vec<u8>>) {
+        self.check_input = input;
+    }
+
+    pub fn run(&mut self, quadruplets: &mut Vec<Quadruplet>) -> Result<Vec<PassReport>, PassError> {
+        if self.verify {
+            verify_quadruplets(quadruplets).map_err(|e| PassError::Verification("input".to_string(), e))?;
+        }
+        let reference = match &self.check_input {
+            Some(input) => {
+                let execution = Execution::capture(quadruplets, input, CHECK_STEP_LIMIT);
+                if let Err(e @ (RuntimeError::StepLimit(_) | RuntimeError::InvalidProgram(_) | RuntimeError::Io(_))) = execution.result {
+                    return Err(PassError::Unchecked(e));
+                }
+                Some(execution)
+            },
+            None => None,
+        };
+
+        let mut reports = Vec::with_capacity(self.passes.len());
+        for pass in &mut self.passes {
+            let before = quadruplets.len();
+            let start = Instant::now();
+            let (changed, note) = pass.run(quadruplets);
+            let duration = start.elapsed();
+
+            if self.verify {
+                verify_quadruplets(quadruplets).map_err(|e| PassError::Verification(pass.name().to_string(), e))?;
+            }
+            if let (Some(reference), Some(input)) = (&reference, &self.check_input) {
+                let execution = Execution::capture(quadruplets, input, CHECK_STEP_LIMIT);
+                if let Some(difference) = reference.difference(&execution) {
+                    return Err(PassError::Miscompilation(pass.name().to_string(), difference));
+                }
+            }
+            if self.print_after.iter().any(|name| name == pass.name() || name == "all") {
+                print_quadruplets(&format!("Quadruplets after {}", pass.name()), quadruplets);
+            }
+            reports.push(PassReport { name: pass.name(), duration, before, after: quadruplets.len(), changed, note });
+        }
+        Ok(reports)
+    }
+}
+
+pub fn print_reports(reports: &[PassReport]) {
+    println!("{}", "Optimization passes:".blue());
+    println!("pass            time (us)   before    after  changes");
+    for report in reports {
+        let changes = match (&report.note, report.changed) {
+            (Some(note), _) => note.clone(),
+            (None, true) => "changed".to_string(),
+            (None, false) => "unchanged".to_string(),
+        };
+        println!("{:<12} {:>12} {:>8} {:>8}  {}", report.name, report.duration.as_micros(), report.before, report.after, changes);
+    }
+    println!("----------------------\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::{parse_quadruplets, Operand, Operator, Value};
+
+    const PROGRAM: &str = r#"
+        INTEGER X, Y, I, t1, t2, t3, t4;
+        0: (Assign, 4, _, X)
+        1: (Multiply, X, 2, t1)
+        2: (Assign, t1, _, Y)
+        3: (Assign, 0, _, I)
+        4: (LessThan, I, Y, t2)
+        5: (IfFalse, t2, _, 10)
+        6: (Write, I, _, _)
+        7: (Add, I, 1, t3)
+        8: (Assign, t3, _, I)
+        9: (Goto, _, _, 4)
+        10: (GreaterThan, Y, 1, t4)
+    "#;
+
+    #[test]
+    fn test_pipelines() {
+        let mut quads = parse_quadruplets(PROGRAM).unwrap();
+        let mut manager = PassManager::with_passes(&pipeline_for_level(0)).unwrap();
+        assert!(manager.run(&mut quads).unwrap().is_empty());
+
+        let mut manager = PassManager::with_passes(&pipeline_for_level(1)).unwrap();
+        manager.set_verify(true);
+        let reports = manager.run(&mut quads).unwrap();
+        let summary: Vec<(&str, usize, usize, bool)> = reports.iter().map(|r| (r.name, r.before, r.after, r.changed)).collect();
+        // X * 2 is folded into Y = 8, X and the unused comparison after the loop are dead
+        assert_eq!(summary, vec![("constfold", 11, 10, true), ("dce", 10, 8, true)]);
+        let lines: Vec<String> = quads.iter().map(|q| q.to_string()).collect();
+        assert_eq!(lines, vec![
+            "(Assign, 8, _, Y)",
+            "(Assign, 0, _, I)",
+            "(LessThan, I, Y, t2)",
+            "(IfFalse, t2, _, 8)",
+            "(Write, I, _, _)",
+            "(Add, I, 1, t3)",
+            "(Assign, t3, _, I)",
+            "(Goto, _, _, 2)",
+        ]);
+    }
+
+    #[test]
+    fn test_unknown_pass_and_verification() {
+        assert!(matches!(PassManager::with_passes(&["inline"]), Err(PassError::UnknownPass(_, _))));
+
+        // A pass breaking the quadruplets is reported by name
+        struct Truncate;
+        impl Pass for Truncate {
+            fn name(&self) -> &'static str {
+                "truncate"
+            }
+            fn run(&mut self, quadruplets: &mut Vec<Quadruplet>) -> (bool, Option<String>) {
+                quadruplets.truncate(6);
+                (true, None)
+            }
+        }
+        let mut quads = parse_quadruplets(PROGRAM).unwrap();
+        let mut manager = PassManager::new();
+        manager.add_pass(Box::new(Truncate));
+        manager.set_verify(true);
+        assert!(matches!(manager.run(&mut quads), Err(PassError::Verification(name, _)) if name == "truncate"));
+    }
+
+    #[test]
+    fn test_check_passes() {
+        let mut quads = parse_quadruplets(PROGRAM).unwrap();
+        let mut manager = PassManager::with_passes(&pipeline_for_level(2)).unwrap();
+        manager.set_check_input(Some(Vec::new()));
+        assert!(manager.run(&mut quads).is_ok());
+
+        // A pass doubling the step of the loop changes what the program writes
+        struct DoubleStep;
+        impl Pass for DoubleStep {
+            fn name(&self) -> &'static str {
+                "double-step"
+            }
+            fn run(&mut self, quadruplets: &mut Vec<Quadruplet>) -> (bool, Option<String>) {
+                for quadruplet in quadruplets.iter_mut().filter(|q| q.operator == Operator::Add) {
+                    quadruplet.operand2 = Some(Operand::Constant(Value::Integer(2)));
+                }
+                (true, None)
+            }
+        }
+        let mut quads = parse_quadruplets(PROGRAM).unwrap();
+        let mut manager = PassManager::with_passes(&["constfold"]).unwrap();
+        manager.add_pass(Box::new(DoubleStep));
+        manager.set_check_input(Some(Vec::new()));
+        assert_eq!(
+            manager.run(&mut quads).unwrap_err(),
+            PassError::Miscompilation("double-step".to_string(), "line 1 of the output is \"0246\" instead of \"01234567\"".to_string()),
+        );
+
+        let mut quads = parse_quadruplets("INTEGER t1;\n0: (Add, 1, 2.0, t1)").unwrap();
+        assert!(matches!(manager.run(&mut quads), Err(PassError::Unchecked(RuntimeError::InvalidProgram(_)))));
+    }
+}
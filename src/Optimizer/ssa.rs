@@ -0,0 +1,451 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use crate::Optimizer::cfg::ControlFlowGraph;
+use crate::Optimizer::dataflow::{defined_name, solve, LiveVariables};
+use crate::Semantic::quadruplets::{jump_target, next_temporary, Operand, Operator, Quadruplet};
+
+/// Phi function at the start of a block: `target` gets the argument of the predecessor the
+/// block was entered from, arguments follow the order of the predecessors in the CFG
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phi {
+    pub target: Operand,
+    pub arguments: Vec<Operand>,
+}
+
+impl fmt::Display for Phi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arguments: Vec<String> = self.arguments.iter().map(|a| a.to_string()).collect();
+        write!(f, "{} = phi({})", self.target, arguments.join(", "))
+    }
+}
+
+/// Quadruplets in static single assignment form: every variable and temporary is assigned once
+///
+/// Quadruplets keep their indices so jumps are unchanged, the phi functions of each block are
+/// kept apart. A name whose value on entry is never read keeps its name for its first assignment,
+/// the other assignments of a variable X are renamed X_1, X_2... and temporaries get new numbers.
+/// Arrays are left as they are, only their index is renamed
+#[derive(Debug, Clone)]
+pub struct SsaForm {
+    pub cfg: ControlFlowGraph,
+    pub quadruplets: Vec<Quadruplet>,
+    pub phis: Vec<Vec<Phi>>,    // Phi functions of each block
+}
+
+impl SsaForm {
+    /// Builds the pruned SSA form: phi functions go on the iterated dominance frontier of the
+    /// assignments of a name, only where the name is live
+    pub fn build(quadruplets: &[Quadruplet]) -> SsaForm {
+        let cfg = ControlFlowGraph::build(quadruplets);
+        let dominators = cfg.dominators();
+        let frontiers = dominators.frontiers(&cfg);
+        let live = solve(&LiveVariables, &cfg, quadruplets);
+
+        // Blocks assigning each name, with one of the operands naming it
+        let mut definitions: HashMap<String, (Operand, BTreeSet<usize>)> = HashMap::new();
+        for (index, quadruplet) in quadruplets.iter().enumerate() {
+            if let (Some(name), Some(operand)) = (defined_name(quadruplet), &quadruplet.result) {
+                definitions.entry(name).or_insert_with(|| (operand.clone(), BTreeSet::new())).1.insert(cfg.block_of(index));
+            }
+        }
+
+        let mut phis: Vec<Vec<Phi>> = vec![Vec::new(); cfg.blocks.len()];
+        let mut names: Vec<&String> = definitions.keys().collect();
+        names.sort();
+        for name in names {
+            let (operand, blocks) = &definitions[name];
+            let mut has_phi = HashSet::new();
+            let mut worklist: Vec<usize> = blocks.iter().copied().collect();
+            while let Some(block) = worklist.pop() {
+                for &frontier in &frontiers[block] {
+                    if live.block_in[frontier].contains(name) && has_phi.insert(frontier) {
+                        let arguments = vec![operand.clone(); cfg.blocks[frontier].predecessors.len()];
+                        phis[frontier].push(Phi { target: operand.clone(), arguments });
+                        worklist.push(frontier);
+                    }
+                }
+            }
+        }
+
+        let entry_live = cfg.entry().map(|entry| live.block_in[entry].clone()).unwrap_or_default();
+        let mut renamer = Renamer {
+            stacks: HashMap::new(),
+            versions: HashMap::new(),
+            entry_live: entry_live.into_iter().collect(),
+            next_temporary: next_temporary(quadruplets),
+        };
+        let mut ssa = SsaForm { cfg, quadruplets: quadruplets.to_vec(), phis };
+        if let Some(entry) = ssa.cfg.entry() {
+            let children = dominators.children();
+            renamer.rename_block(&mut ssa, &children, entry);
+        }
+        ssa
+    }
+
+    /// Translates back to plain quadruplets
+    /// Each phi function becomes copies at the end of the predecessors, critical edges get a block
+    /// of their own so that the copies only run on that edge. The copies of an edge happen at once,
+    /// a new temporary breaks the cycles (e.g., X_1 = Y_1 and Y_1 = X_1)
+    pub fn into_quadruplets(self) -> Vec<Quadruplet> {
+        let SsaForm { cfg, quadruplets, phis } = self;
+        let mut next = next_temporary(&quadruplets).max(next_temporary(&phi_quadruplets(&phis)));
+        let copies = |from: usize, to: usize, next: &mut usize| {
+            let position = cfg.blocks[to].predecessors.iter().position(|p| *p == from).unwrap();
+            let copies = phis[to].iter().map(|phi| (phi.target.clone(), phi.arguments[position].clone())).collect();
+            sequentialize(copies, next)
+        };
+        let critical = |from: usize| cfg.blocks[from].successors.len() > 1;
+        let falls_through = |block: usize| {
+            block > 0 && cfg.blocks[block].predecessors.contains(&(block - 1))
+                && quadruplets[cfg.blocks[block - 1].last()].operator != Operator::Goto
+        };
+
+        // Jump targets are resolved once every block has its final position
+        enum Target {
+            Block(usize),
+            Edge(usize, usize),
+            End,
+        }
+        let mut output: Vec<Quadruplet> = Vec::new();
+        let mut targets: Vec<(usize, Target)> = Vec::new();
+        let mut block_start = vec![0; cfg.blocks.len()];
+        let mut edge_start: HashMap<(usize, usize), usize> = HashMap::new();
+
+        for block in &cfg.blocks {
+            let id = block.id;
+            let has_phis = !phis[id].is_empty();
+            let pads: Vec<usize> = block.predecessors.iter().copied()
+                .filter(|p| has_phis && critical(*p) && !(falls_through(id) && *p == id - 1))
+                .collect();
+
+            // Copies of a critical edge falling into the block, they skip the landing pads below
+            if has_phis && falls_through(id) && critical(id - 1) {
+                output.extend(copies(id - 1, id, &mut next));
+            }
+            if !pads.is_empty() {
+                if falls_through(id) {
+                    targets.push((output.len(), Target::Block(id)));
+                    output.push(Quadruplet::new(Operator::Goto, None, None, None));
+                }
+                for &pad in &pads {
+                    edge_start.insert((pad, id), output.len());
+                    output.extend(copies(pad, id, &mut next));
+                    targets.push((output.len(), Target::Block(id)));
+                    output.push(Quadruplet::new(Operator::Goto, None, None, None));
+                }
+            }
+
+            block_start[id] = output.len();
+            for (index, quadruplet) in quadruplets.iter().enumerate().take(block.end).skip(block.start) {
+                let last = index == block.last();
+                // Copies of the only edge leaving the block go before its jump or after its last quadruplet
+                let leaving = match block.successors.as_slice() {
+                    [successor] if last && !phis[*successor].is_empty() => copies(id, *successor, &mut next),
+                    _ => Vec::new(),
+                };
+                let is_jump = quadruplet.operator.is_jump();
+                if is_jump {
+                    output.extend(leaving.iter().cloned());
+                }
+                if let Some(target) = jump_target(quadruplet) {
+                    let target = if target >= quadruplets.len() {
+                        Target::End
+                    } else {
+                        Target::Edge(id, cfg.block_of(target))
+                    };
+                    targets.push((output.len(), target));
+                }
+                output.push(quadruplet.clone());
+                if !is_jump {
+                    output.extend(leaving);
+                }
+            }
+        }
+
+        let end = output.len();
+        for (index, target) in targets {
+            let label = match target {
+                Target::Block(block) => block_start[block],
+                Target::Edge(from, to) => edge_start.get(&(from, to)).copied().unwrap_or(block_start[to]),
+                Target::End => end,
+            };
+            output[index].result = Some(Operand::Label(label));
+        }
+        output
+    }
+}
+
+/// Phi functions written as assignments, only used to find the temporaries they name
+fn phi_quadruplets(phis: &[Vec<Phi>]) -> Vec<Quadruplet> {
+    phis.iter().flatten()
+        .flat_map(|phi| phi.arguments.iter().map(|a| Quadruplet::new(Operator::Assign, Some(a.clone()), None, Some(phi.target.clone()))))
+        .collect()
+}
+
+/// Orders copies meant to happen at once (destination, source) so that no source is overwritten
+/// before being read
+fn sequentialize(copies: Vec<(Operand, Operand)>, next: &mut usize) -> Vec<Quadruplet> {
+    let mut pending: Vec<(Operand, Operand)> = copies.into_iter().filter(|(target, source)| target != source).collect();
+    let mut output = Vec::new();
+    while !pending.is_empty() {
+        let ready = (0..pending.len()).find(|&i| {
+            pending.iter().enumerate().all(|(j, (_, source))| j == i || *source != pending[i].0)
+        });
+        match ready {
+            Some(i) => {
+                let (target, source) = pending.remove(i);
+                output.push(Quadruplet::new(Operator::Assign, Some(source), None, Some(target)));
+            },
+            None => {
+                // Only cycles are left, the first destination is saved before being overwritten
+                let saved = pending[0].0.clone();
+                let temporary = Operand::Temporary(*next, saved.value_type().unwrap_or(crate::Semantic::ts::Types::Integer));
+                *next += 1;
+                output.push(Quadruplet::new(Operator::Assign, Some(saved.clone()), None, Some(temporary.clone())));
+                for (_, source) in pending.iter_mut().filter(|(_, source)| *source == saved) {
+                    *source = temporary.clone();
+                }
+            },
+        }
+    }
+    output
+}
+
+/// Renaming walk over the dominator tree
+struct Renamer {
+    stacks: HashMap<String, Vec<Operand>>,      // Current version of each name
+    versions: HashMap<String, usize>,           // Number of assignments renamed so far
+    entry_live: HashSet<String>,                // Names whose value on entry is read
+    next_temporary: usize,
+}
+
+impl Renamer {
+    fn new_version(&mut self, operand: &Operand) -> Operand {
+        let name = operand.to_string();
+        let count = self.versions.entry(name.clone()).or_insert(0);
+        *count += 1;
+        // Numbered from 1 after the name itself when it is reused
+        let keeps_name = !self.entry_live.contains(&name);
+        let renamed = match operand {
+            _ if keeps_name && *count == 1 => operand.clone(),
+            Operand::Variable(variable, t) => {
+                Operand::Variable(format!("{}_{}", variable, if keeps_name { *count - 1 } else { *count }), t.clone())
+            },
+            Operand::Temporary(_, t) => {
+                self.next_temporary += 1;
+                Operand::Temporary(self.next_temporary - 1, t.clone())
+            },
+            _ => operand.clone(),
+        };
+        self.stacks.entry(name).or_default().push(renamed.clone());
+        renamed
+    }
+
+    /// Version of the name read at this point, the name itself before any assignment
+    fn current(&self, operand: &Operand) -> Operand {
+        match operand {
+            Operand::Variable(_, _) | Operand::Temporary(_, _) => {
+                self.stacks.get(&operand.to_string()).and_then(|stack| stack.last()).cloned().unwrap_or_else(|| operand.clone())
+            },
+            Operand::ArrayElement(name, t, index) => Operand::ArrayElement(name.clone(), t.clone(), Box::new(self.current(index))),
+            _ => operand.clone(),
+        }
+    }
+
+    fn rename_block(&mut self, ssa: &mut SsaForm, children: &[Vec<usize>], block: usize) {
+        let mut pushed = Vec::new();
+        for phi in &mut ssa.phis[block] {
+            pushed.push(phi.target.to_string());
+            phi.target = self.new_version(&phi.target);
+        }
+
+        let (start, end) = (ssa.cfg.blocks[block].start, ssa.cfg.blocks[block].end);
+        for quadruplet in &mut ssa.quadruplets[start..end] {
+            for operand in [&mut quadruplet.operand1, &mut quadruplet.operand2].into_iter().flatten() {
+                *operand = self.current(operand);
+            }
+            if let Some(Operand::ArrayElement(_, _, index)) = &mut quadruplet.result {
+                **index = self.current(index);
+            }
+            if let (Some(name), Some(result)) = (defined_name(quadruplet), &mut quadruplet.result) {
+                pushed.push(name);
+                *result = self.new_version(result);
+            }
+        }
+
+        for &successor in &ssa.cfg.blocks[block].successors {
+            let position = ssa.cfg.blocks[successor].predecessors.iter().position(|p| *p == block).unwrap();
+            for phi in &mut ssa.phis[successor] {
+                phi.arguments[position] = self.current(&phi.arguments[position]);
+            }
+        }
+
+        for &child in &children[block] {
+            self.rename_block(ssa, children, child);
+        }
+        for name in pushed {
+            self.stacks.get_mut(&name).and_then(|stack| stack.pop());
+        }
+    }
+}
+
+impl fmt::Display for SsaForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for block in &self.cfg.blocks {
+            let predecessors: Vec<String> = block.predecessors.iter().map(|p| format!("B{}", p)).collect();
+            if predecessors.is_empty() {
+                writeln!(f, "B{}:", block.id)?;
+            } else {
+                writeln!(f, "B{}: %% from {}", block.id, predecessors.join(", "))?;
+            }
+            for phi in &self.phis[block.id] {
+                writeln!(f, "    {}", phi)?;
+            }
+            for index in block.start..block.end {
+                writeln!(f, "    {}: {}", index, self.quadruplets[index])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::{format_quadruplets, parse_quadruplets};
+
+    // S = 0; FOR(I = 0 : 1 : N) { IF (I > 2) { S = S + I; } ELSE { S = S - 1; } } WRITE(S);
+    const PROGRAM: &str = r#"
+        INTEGER S, I, N, t1, t2, t3, t4, t5;
+        0: (Read, _, _, N)
+        1: (Assign, 0, _, S)
+        2: (Assign, 0, _, I)
+        3: (LessThan, I, N, t1)
+        4: (IfFalse, t1, _, 15)
+        5: (GreaterThan, I, 2, t2)
+        6: (IfFalse, t2, _, 10)
+        7: (Add, S, I, t3)
+        8: (Assign, t3, _, S)
+        9: (Goto, _, _, 12)
+        10: (Subtract, S, 1, t4)
+        11: (Assign, t4, _, S)
+        12: (Add, I, 1, t5)
+        13: (Assign, t5, _, I)
+        14: (Goto, _, _, 3)
+        15: (Write, S, _, _)
+    "#;
+
+    #[test]
+    fn test_phi_insertion_and_renaming() {
+        let quads = parse_quadruplets(PROGRAM).unwrap();
+        let ssa = SsaForm::build(&quads);
+        assert_eq!(ssa.to_string(), "\
+B0:
+    0: (Read, _, _, N)
+    1: (Assign, 0, _, S)
+    2: (Assign, 0, _, I)
+B1: %% from B0, B5
+    I_1 = phi(I, I_2)
+    S_1 = phi(S, S_4)
+    3: (LessThan, I_1, N, t1)
+    4: (IfFalse, t1, _, 15)
+B2: %% from B1
+    5: (GreaterThan, I_1, 2, t2)
+    6: (IfFalse, t2, _, 10)
+B3: %% from B2
+    7: (Add, S_1, I_1, t3)
+    8: (Assign, t3, _, S_2)
+    9: (Goto, _, _, 12)
+B4: %% from B2
+    10: (Subtract, S_1, 1, t4)
+    11: (Assign, t4, _, S_3)
+B5: %% from B3, B4
+    S_4 = phi(S_2, S_3)
+    12: (Add, I_1, 1, t5)
+    13: (Assign, t5, _, I_2)
+    14: (Goto, _, _, 3)
+B6: %% from B1
+    15: (Write, S_1, _, _)
+");
+    }
+
+    #[test]
+    fn test_translation_out_of_ssa() {
+        let quads = parse_quadruplets(PROGRAM).unwrap();
+        let lines: Vec<String> = SsaForm::build(&quads).into_quadruplets().iter().map(|q| q.to_string()).collect();
+        assert_eq!(lines, vec![
+            "(Read, _, _, N)",
+            "(Assign, 0, _, S)",
+            "(Assign, 0, _, I)",
+            "(Assign, I, _, I_1)",
+            "(Assign, S, _, S_1)",
+            "(LessThan, I_1, N, t1)",
+            "(IfFalse, t1, _, 21)",
+            "(GreaterThan, I_1, 2, t2)",
+            "(IfFalse, t2, _, 13)",
+            "(Add, S_1, I_1, t3)",
+            "(Assign, t3, _, S_2)",
+            "(Assign, S_2, _, S_4)",
+            "(Goto, _, _, 16)",
+            "(Subtract, S_1, 1, t4)",
+            "(Assign, t4, _, S_3)",
+            "(Assign, S_3, _, S_4)",
+            "(Add, I_1, 1, t5)",
+            "(Assign, t5, _, I_2)",
+            "(Assign, I_2, _, I_1)",
+            "(Assign, S_4, _, S_1)",
+            "(Goto, _, _, 5)",
+            "(Write, S_1, _, _)",
+        ]);
+    }
+
+    #[test]
+    fn test_swapped_copies_and_critical_edges() {
+        // X and Y are swapped on every iteration, the loop is left from its header
+        let quads = parse_quadruplets(r#"
+            INTEGER X, Y, K, T, t1;
+            0: (Assign, 1, _, X)
+            1: (Assign, 2, _, Y)
+            2: (Read, _, _, K)
+            3: (Assign, X, _, T)
+            4: (Assign, Y, _, X)
+            5: (Assign, T, _, Y)
+            6: (IfTrue, K, _, 2)
+            7: (Write, X, _, _)
+        "#).unwrap();
+        let ssa = SsaForm::build(&quads);
+        assert_eq!(ssa.phis[1].iter().map(|phi| phi.to_string()).collect::<Vec<_>>(), vec![
+            "X_1 = phi(X, X_2)",
+            "Y_1 = phi(Y, Y_2)",
+        ]);
+
+        // The back edge leaves a block with two successors, its copies get a block of their own
+        let quads = ssa.into_quadruplets();
+        assert_eq!(format_quadruplets(&quads), "%% MinING quadruplets\n\
+            INTEGER X, Y, X_1, Y_1, X_2, Y_2, K, T;\n\
+            0: (Assign, 1, _, X)\n\
+            1: (Assign, 2, _, Y)\n\
+            2: (Assign, X, _, X_1)\n\
+            3: (Assign, Y, _, Y_1)\n\
+            4: (Goto, _, _, 8)\n\
+            5: (Assign, X_2, _, X_1)\n\
+            6: (Assign, Y_2, _, Y_1)\n\
+            7: (Goto, _, _, 8)\n\
+            8: (Read, _, _, K)\n\
+            9: (Assign, X_1, _, T)\n\
+            10: (Assign, Y_1, _, X_2)\n\
+            11: (Assign, T, _, Y_2)\n\
+            12: (IfTrue, K, _, 5)\n\
+            13: (Write, X_2, _, _)\n");
+    }
+
+    #[test]
+    fn test_parallel_copies() {
+        let x = Operand::Variable("X".to_string(), crate::Semantic::ts::Types::Integer);
+        let y = Operand::Variable("Y".to_string(), crate::Semantic::ts::Types::Integer);
+        let mut next = 7;
+        let lines: Vec<String> = sequentialize(vec![(x.clone(), y.clone()), (y, x)], &mut next).iter().map(|q| q.to_string()).collect();
+        assert_eq!(lines, vec!["(Assign, X, _, t7)", "(Assign, Y, _, X)", "(Assign, t7, _, Y)"]);
+        assert_eq!(next, 8);
+    }
+}
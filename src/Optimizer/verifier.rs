@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use crate::Optimizer::error::VerificationError;
+use crate::Semantic::quadruplets::{Operand, Operator, Quadruplet, Value};
+use crate::Semantic::ts::Types;
+
+/// Checks that the quadruplets are well formed: every operator has the operands it needs, jumps
+/// stay inside the program, results can be assigned, array indices are scalar INTEGER values
+/// within the bounds of the array and every name keeps a single type
+pub fn verify_quadruplets(quadruplets: &[Quadruplet]) -> Result<(), VerificationError> {
+    let mut types: HashMap<String, Types> = HashMap::new();
+    for (index, quadruplet) in quadruplets.iter().enumerate() {
+        let malformed = |message: &str| Err(VerificationError::MalformedQuadruplet(index, message.to_string()));
+        let (a, b, r) = (&quadruplet.operand1, &quadruplet.operand2, &quadruplet.result);
+
+        match quadruplet.operator {
+            Operator::Goto | Operator::IfTrue | Operator::IfFalse => {
+                match r {
+                    Some(Operand::Label(target)) if *target > quadruplets.len() => {
+                        return Err(VerificationError::InvalidTarget(index, *target));
+                    },
+                    Some(Operand::Label(_)) => {},
+                    _ => return malformed("a jump needs a target"),
+                }
+                let conditional = quadruplet.operator != Operator::Goto;
+                if conditional != a.as_ref().is_some_and(is_value) || b.is_some() {
+                    return malformed("a conditional jump reads one value, Goto none");
+                }
+            },
+            Operator::Assign | Operator::LogicalNot => {
+                if !a.as_ref().is_some_and(is_value) || b.is_some() || !r.as_ref().is_some_and(is_assignable) {
+                    return malformed("expected a value and a result");
+                }
+            },
+            Operator::Read => {
+                if a.is_some() || b.is_some() || !r.as_ref().is_some_and(is_assignable) {
+                    return malformed("READ only has a result");
+                }
+            },
+            Operator::Write => {
+                let printable = a.as_ref().is_some_and(|o| is_value(o) || matches!(o, Operand::String(_)));
+                if !printable || b.is_some() || r.is_some() {
+                    return malformed("WRITE only reads one value or string");
+                }
+            },
+            Operator::For => return malformed("For is not lowered to jumps"),
+            _ => {
+                if !a.as_ref().is_some_and(is_value) || !b.as_ref().is_some_and(is_value) || !r.as_ref().is_some_and(is_assignable) {
+                    return malformed("expected two values and a result");
+                }
+            },
+        }
+
+        for operand in [a, b, r].into_iter().flatten() {
+            check_operand(index, operand, &mut types)?;
+        }
+    }
+    Ok(())
+}
+
+fn is_value(operand: &Operand) -> bool {
+    matches!(operand, Operand::Variable(_, _) | Operand::Temporary(_, _) | Operand::Constant(_) | Operand::ArrayElement(_, _, _))
+}
+
+fn is_assignable(operand: &Operand) -> bool {
+    matches!(operand, Operand::Variable(_, _) | Operand::Temporary(_, _) | Operand::ArrayElement(_, _, _))
+}
+
+fn check_operand(index: usize, operand: &Operand, types: &mut HashMap<String, Types>) -> Result<(), VerificationError> {
+    let (name, symbol_type) = match operand {
+        Operand::Variable(name, t) => (name.clone(), t),
+        Operand::Temporary(_, t) => (operand.to_string(), t),
+        Operand::ArrayElement(name, t, element) => {
+            match (t, element.as_ref()) {
+                (Types::Array(_, size), Operand::Constant(Value::Integer(i))) if *i < 0 || *i >= *size => {
+                    return Err(VerificationError::IndexOutOfBounds(index, *i, name.clone()));
+                },
+                (Types::Array(_, _), Operand::Variable(_, _) | Operand::Temporary(_, _) | Operand::Constant(_))
+                    if element.value_type() == Some(Types::Integer) => check_operand(index, element, types)?,
+                _ => return Err(VerificationError::MalformedQuadruplet(index, format!("invalid element of '{}'", name))),
+            }
+            (name.clone(), t)
+        },
+        _ => return Ok(()),
+    };
+    match types.get(&name) {
+        Some(known) if known != symbol_type => Err(VerificationError::InconsistentType(index, name)),
+        Some(_) => Ok(()),
+        None => {
+            types.insert(name, symbol_type.clone());
+            Ok(())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+
+    #[test]
+    fn test_verification() {
+        let quads = parse_quadruplets(r#"
+            INTEGER I, t1;
+            FLOAT V[3];
+            0: (Assign, 0, _, I)
+            1: (LessThan, I, 3, t1)
+            2: (IfFalse, t1, _, 6)
+            3: (Assign, 1.5, _, V[I])
+            4: (Add, I, 1, I)
+            5: (Goto, _, _, 1)
+        "#).unwrap();
+        assert_eq!(verify_quadruplets(&quads), Ok(()));
+
+        let mut broken = quads.clone();
+        broken[5].result = Some(Operand::Label(9));
+        assert_eq!(verify_quadruplets(&broken), Err(VerificationError::InvalidTarget(5, 9)));
+
+        let mut broken = quads.clone();
+        broken[4].operand2 = None;
+        assert!(matches!(verify_quadruplets(&broken), Err(VerificationError::MalformedQuadruplet(4, _))));
+
+        let mut broken = quads.clone();
+        broken[3].result = Some(Operand::ArrayElement("V".to_string(), Types::Array(Box::new(Types::Float), 3),
+            Box::new(Operand::Constant(Value::Integer(3)))));
+        assert_eq!(verify_quadruplets(&broken), Err(VerificationError::IndexOutOfBounds(3, 3, "V".to_string())));
+
+        let mut broken = quads;
+        broken[4].result = Some(Operand::Variable("I".to_string(), Types::Float));
+        assert_eq!(verify_quadruplets(&broken), Err(VerificationError::InconsistentType(4, "I".to_string())));
+    }
+}
@@ -75,6 +75,15 @@ pub enum BinOp {
     Div(usize, usize),
 }
 
+impl BinOp {
+    /// Line and column of the operator
+    pub fn position(&self) -> (usize, usize) {
+        match self {
+            BinOp::Add(line, column) | BinOp::Sub(line, column) | BinOp::Mul(line, column) | BinOp::Div(line, column) => (*line, *column),
+        }
+    }
+}
+
 impl std::fmt::Display for BinOp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let symbol = match self {
@@ -106,17 +115,35 @@ pub enum Instruction {
     Write(WriteStmt),
 }
 
+impl Instruction {
+    /// Line and column of the instruction: its target for assignments, its keyword for IF and FOR,
+    /// the variable read by READ and the first element written by WRITE
+    pub fn position(&self) -> (usize, usize) {
+        match self {
+            Instruction::Assign(assignment) => assignment.var.1,
+            Instruction::If(if_stmt) => if_stmt.position,
+            Instruction::For(for_stmt) => for_stmt.position,
+            Instruction::Read(read_stmt) => read_stmt.variable.1,
+            Instruction::Write(write_stmt) => match write_stmt.elements.first() {
+                Some(WriteElement::String((_, position))) | Some(WriteElement::Variable((_, position), _)) => *position,
+                None => (0, 0),
+            },
+        }
+    }
+}
+
 // If statement structure
 #[derive(Debug, Clone)]
 pub struct IfStmt {
     pub condition: Condition,
     pub then_block: Vec<Instruction>,
     pub else_block: Option<Vec<Instruction>>,
+    pub position: (usize, usize),   // Line and column of the IF keyword
 }
 
 impl IfStmt {
-    pub fn new(condition: Condition, then_block: Vec<Instruction>, else_block: Option<Vec<Instruction>>) -> Self {
-        IfStmt { condition, then_block, else_block }
+    pub fn new(condition: Condition, then_block: Vec<Instruction>, else_block: Option<Vec<Instruction>>, position: (usize, usize)) -> Self {
+        IfStmt { condition, then_block, else_block, position }
     }
 }
 
@@ -127,11 +154,12 @@ pub struct ForStmt {
     pub step: Expr,
     pub condition: Expr,
     pub body: Vec<Instruction>,
+    pub position: (usize, usize),   // Line and column of the FOR keyword
 }
 
 impl ForStmt {
-    pub fn new(init: Assignment, step: Expr, condition: Expr, body: Vec<Instruction>) -> Self {
-        ForStmt { init, step, condition, body }
+    pub fn new(init: Assignment, step: Expr, condition: Expr, body: Vec<Instruction>, position: (usize, usize)) -> Self {
+        ForStmt { init, step, condition, body, position }
     }
 }
 
@@ -200,6 +228,30 @@ pub enum RelOp {
     Ne(usize, usize),  // Not equal to
 }
 
+impl RelOp {
+    /// Line and column of the operator
+    pub fn position(&self) -> (usize, usize) {
+        match self {
+            RelOp::Gt(line, column) | RelOp::Lt(line, column) | RelOp::Ge(line, column)
+            | RelOp::Le(line, column) | RelOp::Eq(line, column) | RelOp::Ne(line, column) => (*line, *column),
+        }
+    }
+}
+
+impl std::fmt::Display for RelOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            RelOp::Gt(_, _) => ">",
+            RelOp::Lt(_, _) => "<",
+            RelOp::Ge(_, _) => ">=",
+            RelOp::Le(_, _) => "<=",
+            RelOp::Eq(_, _) => "==",
+            RelOp::Ne(_, _) => "!=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
 // Logical operators for boolean operations
 #[derive(Debug, Clone)]
 pub enum LogOp {
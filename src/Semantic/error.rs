@@ -27,3 +27,30 @@ pub enum SemanticError {
     #[error("Semantic error: {0}")]
     Generic(String),
 }
+
+#[derive(Debug, Error, PartialEq)]
+pub enum QuadrupletParseError {
+    #[error("Invalid quadruplet at line {0}: {1}")]
+    Syntax(usize, String),
+
+    #[error("Undeclared name '{1}' at line {0}")]
+    UndeclaredName(usize, String),
+
+    #[error("Name '{1}' declared twice at line {0}")]
+    DuplicateDeclaration(usize, String),
+
+    #[error("Quadruplet {0} jumps to {1}, past the end of the program")]
+    InvalidTarget(usize, usize),
+}
+
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum ArithmeticError {
+    #[error("Division by zero")]
+    DivisionByZero,
+
+    #[error("INTEGER overflow")]
+    Overflow,
+
+    #[error("Invalid operands for {0}")]
+    InvalidOperands(String),
+}
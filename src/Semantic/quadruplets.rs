@@ -1,13 +1,37 @@
+//! Quadruplet intermediate representation
+//!
+//! Textual format, as written by `format_quadruplets` and read back by `parse_quadruplets`:
+//!
+//! ```text
+//! %% Comments start with %% like in MinING sources
+//! INTEGER Var1, t1, t2;          declarations of the variables and temporaries
+//! FLOAT Array2[5];               arrays carry their size
+//! 0: (Assign, 5, _, Var1)
+//! 1: (Add, Var1, 2, t1)
+//! 2: (Assign, t1, _, Array2[t2])
+//! 3: (IfFalse, t1, _, 0)
+//! 4: (Write, "Result: ", _, _)
+//! ```
+//!
+//! Every quadruplet is `(Operator, operand1, operand2, result)` where the operator is written
+//! like its variant name and `_` stands for an empty operand. Operands are declared names,
+//! temporaries `t{number}`, integers (`-15`), floats (`2.0`), chars (`'a'`), strings (`"text"`)
+//! and array cells `Name[index]`. The result of `Goto`, `IfTrue` and `IfFalse` is the index of
+//! the target quadruplet. The `index:` prefix is optional, when present it must match the position.
+
 use crate::Semantic::ts::{Symbol, Types};
+use crate::Semantic::error::{ArithmeticError, QuadrupletParseError};
+use crate::codegen::error::{Frame, Origin, Position};
 use crate::Parser::ast::*;
 use crate::Lexer::lexer::Token;
 use crate::Lexer::error::CustomError;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::sync::Mutex;
 use once_cell::sync::Lazy;
 
 /// Represents an operation in the intermediate representation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Operator {
     // Arithmetic Operators
     Add,
@@ -67,45 +91,673 @@ impl From<Token> for Operator {
     }
 }
 
+/// Constant value carried by an operand
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Integer(i16),
+    Float(f32),
+    Char(char),
+}
+
+impl Value {
+    pub fn get_type(&self) -> Types {
+        match self {
+            Value::Integer(_) => Types::Integer,
+            Value::Float(_) => Types::Float,
+            Value::Char(_) => Types::Char,
+        }
+    }
+
+    /// Truth value used by conditions and logical operators, anything but zero is true
+    pub fn is_true(&self) -> bool {
+        match self {
+            Value::Integer(i) => *i != 0,
+            Value::Float(f) => *f != 0.0,
+            Value::Char(c) => *c != '\0',
+        }
+    }
+
+    /// Applies a binary arithmetic, comparison or logical operator with the MinING semantics:
+    /// INTEGER is a 16 bits signed integer that fails on overflow and division by zero, FLOAT follows
+    /// f32 arithmetic and CHAR addition and subtraction wrap around 0x7F like in the semantic analyzer.
+    /// Comparisons and logical operators give the INTEGER 1 when true and 0 when false
+    pub fn binary(&self, operator: &Operator, right: &Value) -> Result<Value, ArithmeticError> {
+        let invalid = || ArithmeticError::InvalidOperands(format!("{:?}", operator));
+        let boolean = |b: bool| Ok(Value::Integer(b as i16));
+        match operator {
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => match (self, right) {
+                (Value::Integer(a), Value::Integer(b)) => {
+                    let result = match operator {
+                        Operator::Add => a.checked_add(*b),
+                        Operator::Subtract => a.checked_sub(*b),
+                        Operator::Multiply => a.checked_mul(*b),
+                        _ if *b == 0 => return Err(ArithmeticError::DivisionByZero),
+                        _ => a.checked_div(*b),
+                    };
+                    result.map(Value::Integer).ok_or(ArithmeticError::Overflow)
+                },
+                (Value::Float(a), Value::Float(b)) => Ok(Value::Float(match operator {
+                    Operator::Add => a + b,
+                    Operator::Subtract => a - b,
+                    Operator::Multiply => a * b,
+                    _ => a / b,
+                })),
+                (Value::Char(a), Value::Char(b)) => {
+                    let (a, b) = (*a as i32, *b as i32);
+                    let code = match operator {
+                        Operator::Add => (a + b).rem_euclid(0x7F),
+                        Operator::Subtract => (a - b).rem_euclid(0x7F),
+                        _ => return Err(invalid()),
+                    };
+                    Ok(Value::Char(char::from_u32(code as u32).ok_or_else(invalid)?))
+                },
+                _ => Err(invalid()),
+            },
+            Operator::GreaterThan | Operator::LessThan | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual | Operator::Equal | Operator::NotEqual => {
+                let ordering = match (self, right) {
+                    (Value::Integer(a), Value::Integer(b)) => a.partial_cmp(b),
+                    (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+                    (Value::Char(a), Value::Char(b)) => a.partial_cmp(b),
+                    _ => return Err(invalid()),
+                };
+                // Comparisons involving NaN are only true for NotEqual
+                let Some(ordering) = ordering else {
+                    return boolean(*operator == Operator::NotEqual);
+                };
+                boolean(match operator {
+                    Operator::GreaterThan => ordering.is_gt(),
+                    Operator::LessThan => ordering.is_lt(),
+                    Operator::GreaterThanOrEqual => ordering.is_ge(),
+                    Operator::LessThanOrEqual => ordering.is_le(),
+                    Operator::Equal => ordering.is_eq(),
+                    _ => ordering.is_ne(),
+                })
+            },
+            Operator::LogicalAnd => boolean(self.is_true() && right.is_true()),
+            Operator::LogicalOr => boolean(self.is_true() || right.is_true()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Char(c) => write!(f, "{:?}", c),
+        }
+    }
+}
+
+/// Operand of a quadruplet
+/// Array cells only appear as the source or target of an Assign, the target of a Read
+/// and the operand of a Write, their index is always a variable, a temporary or a constant
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Variable(String, Types),                        // MinING variable or constant (e.g., X)
+    Temporary(usize, Types),                        // Compiler generated temporary (e.g., t1)
+    Constant(Value),                                // Literal value (e.g., 5, 2.5, 'a')
+    String(String),                                 // String literal of a WRITE
+    Label(usize),                                   // Index of the quadruplet a jump goes to
+    ArrayElement(String, Types, Box<Operand>),      // Array cell: base name, array type and index (e.g., A[t1])
+}
+
+impl Operand {
+    /// Type of the value held by the operand, None for strings and labels
+    pub fn value_type(&self) -> Option<Types> {
+        match self {
+            Operand::Variable(_, t) | Operand::Temporary(_, t) => Some(t.clone()),
+            Operand::Constant(value) => Some(value.get_type()),
+            Operand::ArrayElement(_, Types::Array(element, _), _) => Some(*element.clone()),
+            Operand::ArrayElement(_, t, _) => Some(t.clone()),
+            Operand::String(_) | Operand::Label(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Variable(name, _) => write!(f, "{}", name),
+            Operand::Temporary(number, _) => write!(f, "t{}", number),
+            Operand::Constant(value) => write!(f, "{}", value),
+            Operand::String(text) => write!(f, "{:?}", text),
+            Operand::Label(target) => write!(f, "{}", target),
+            Operand::ArrayElement(name, _, index) => write!(f, "{}[{}]", name, index),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Quadruplet {
     pub operator: Operator,
-    pub operand1: Option<String>,
-    pub operand2: Option<String>,
-    pub result: Option<String>,
+    pub operand1: Option<Operand>,
+    pub operand2: Option<Operand>,
+    pub result: Option<Operand>,
+    pub origin: Option<Origin>,     // Source of the instruction, reported by runtime errors. Not part of the textual format
+}
+
+/// Quadruplets are equal when they compute the same thing, wherever they come from
+impl PartialEq for Quadruplet {
+    fn eq(&self, other: &Self) -> bool {
+        self.operator == other.operator && self.operand1 == other.operand1
+            && self.operand2 == other.operand2 && self.result == other.result
+    }
+}
+
+impl Operator {
+    /// Operator written with its variant name, as used by the textual format
+    pub fn from_name(name: &str) -> Option<Operator> {
+        let operator = match name {
+            "Add" => Operator::Add,
+            "Subtract" => Operator::Subtract,
+            "Multiply" => Operator::Multiply,
+            "Divide" => Operator::Divide,
+            "GreaterThan" => Operator::GreaterThan,
+            "LessThan" => Operator::LessThan,
+            "GreaterThanOrEqual" => Operator::GreaterThanOrEqual,
+            "LessThanOrEqual" => Operator::LessThanOrEqual,
+            "Equal" => Operator::Equal,
+            "NotEqual" => Operator::NotEqual,
+            "LogicalAnd" => Operator::LogicalAnd,
+            "LogicalOr" => Operator::LogicalOr,
+            "LogicalNot" => Operator::LogicalNot,
+            "Assign" => Operator::Assign,
+            "Read" => Operator::Read,
+            "Write" => Operator::Write,
+            "Goto" => Operator::Goto,
+            "IfTrue" => Operator::IfTrue,
+            "IfFalse" => Operator::IfFalse,
+            "For" => Operator::For,
+            _ => return None,
+        };
+        Some(operator)
+    }
+
+    pub fn is_jump(&self) -> bool {
+        matches!(self, Operator::Goto | Operator::IfTrue | Operator::IfFalse)
+    }
+
+    /// Operators computing `result = operand1 op operand2`
+    pub fn is_binary(&self) -> bool {
+        matches!(self,
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide
+            | Operator::GreaterThan | Operator::LessThan | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual | Operator::Equal | Operator::NotEqual
+            | Operator::LogicalAnd | Operator::LogicalOr)
+    }
 }
 
 impl Quadruplet {
     pub fn new(
         operator: Operator,
-        operand1: Option<String>,
-        operand2: Option<String>,
-        result: Option<String>
+        operand1: Option<Operand>,
+        operand2: Option<Operand>,
+        result: Option<Operand>
     ) -> Self {
         Quadruplet {
             operator,
             operand1,
             operand2,
             result,
+            origin: None,
         }
     }
 
-    pub fn to_string(&self) -> String {
-        format!(
+    /// INTEGER arithmetic fails on overflow and division by zero and array cells fail when
+    /// their index is out of bounds, other operations never fail
+    pub fn can_fail(&self) -> bool {
+        let out_of_bounds = |operand: &Option<Operand>| match operand {
+            Some(Operand::ArrayElement(_, Types::Array(_, size), index)) => {
+                !matches!(**index, Operand::Constant(Value::Integer(i)) if (0..*size).contains(&i))
+            },
+            Some(Operand::ArrayElement(..)) => true,
+            _ => false,
+        };
+        if [&self.operand1, &self.operand2, &self.result].into_iter().any(out_of_bounds) {
+            return true;
+        }
+        match self.operator {
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => {
+                let types = (
+                    self.operand1.as_ref().and_then(Operand::value_type),
+                    self.operand2.as_ref().and_then(Operand::value_type),
+                );
+                let wraps = matches!(types, (Some(Types::Char), Some(Types::Char)))
+                    && matches!(self.operator, Operator::Add | Operator::Subtract);
+                !wraps && !matches!(types, (Some(Types::Float), Some(Types::Float)))
+            },
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Quadruplet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_operand = |operand: &Option<Operand>| operand.as_ref().map_or("_".to_string(), |o| o.to_string());
+        write!(
+            f,
             "({:?}, {}, {}, {})",
             self.operator,
-            self.operand1.as_ref().unwrap_or(&"_".to_string()),
-            self.operand2.as_ref().unwrap_or(&"_".to_string()),
-            self.result.as_ref().unwrap_or(&"_".to_string())
+            format_operand(&self.operand1),
+            format_operand(&self.operand2),
+            format_operand(&self.result)
         )
     }
 }
 
+fn type_name(symbol_type: &Types) -> &'static str {
+    match symbol_type {
+        Types::Integer => "INTEGER",
+        Types::Float => "FLOAT",
+        Types::Char => "CHAR",
+        Types::Array(element, _) => type_name(element),
+    }
+}
+
+/// Names and types of the variables, arrays and temporaries used by the quadruplets,
+/// in order of first appearance
+pub fn collect_symbols(quadruplets: &[Quadruplet]) -> Vec<(String, Types)> {
+    fn visit(operand: &Operand, seen: &mut HashSet<String>, symbols: &mut Vec<(String, Types)>) {
+        let (name, symbol_type) = match operand {
+            Operand::Variable(name, t) => (name.clone(), t.clone()),
+            Operand::Temporary(number, t) => (format!("t{}", number), t.clone()),
+            Operand::ArrayElement(name, t, index) => {
+                visit(index, seen, symbols);
+                (name.clone(), t.clone())
+            },
+            _ => return,
+        };
+        if seen.insert(name.clone()) {
+            symbols.push((name, symbol_type));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut symbols = Vec::new();
+    for quadruplet in quadruplets {
+        for operand in [&quadruplet.operand1, &quadruplet.operand2, &quadruplet.result].into_iter().flatten() {
+            visit(operand, &mut seen, &mut symbols);
+        }
+    }
+    symbols
+}
+
+/// First temporary number not used by the quadruplets
+pub fn next_temporary(quadruplets: &[Quadruplet]) -> usize {
+    collect_symbols(quadruplets).iter()
+        .filter_map(|(name, _)| name.strip_prefix('t').and_then(|number| number.parse::<usize>().ok()))
+        .max()
+        .map_or(1, |number| number + 1)
+}
+
+/// Writes the quadruplets in the textual format described at the top of this module
+pub fn format_quadruplets(quadruplets: &[Quadruplet]) -> String {
+    let mut text = String::from("%% MinING quadruplets\n");
+    let symbols = collect_symbols(quadruplets);
+    for scalar_type in [Types::Integer, Types::Float, Types::Char] {
+        let names: Vec<&str> = symbols.iter().filter(|(_, t)| *t == scalar_type).map(|(name, _)| name.as_str()).collect();
+        if !names.is_empty() {
+            text += &format!("{} {};\n", type_name(&scalar_type), names.join(", "));
+        }
+    }
+    for (name, symbol_type) in &symbols {
+        if let Types::Array(_, size) = symbol_type {
+            text += &format!("{} {}[{}];\n", type_name(symbol_type), name, size);
+        }
+    }
+    for (index, quadruplet) in quadruplets.iter().enumerate() {
+        text += &format!("{}: {}\n", index, quadruplet);
+    }
+    text
+}
+
+/// Reads quadruplets written in the textual format described at the top of this module
+pub fn parse_quadruplets(text: &str) -> Result<Vec<Quadruplet>, QuadrupletParseError> {
+    let mut symbols: HashMap<String, Types> = HashMap::new();
+    let mut quadruplets = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let mut cursor = Cursor::new(line, number + 1);
+        cursor.skip_spaces();
+        if cursor.at_end() || cursor.rest().starts_with("%%") {
+            continue;
+        }
+
+        let word = cursor.peek_word();
+        if let Some(scalar_type) = match word.as_str() {
+            "INTEGER" => Some(Types::Integer),
+            "FLOAT" => Some(Types::Float),
+            "CHAR" => Some(Types::Char),
+            _ => None,
+        } {
+            if !quadruplets.is_empty() {
+                return Err(cursor.error("declarations must come before the quadruplets"));
+            }
+            cursor.parse_declarations(scalar_type, &mut symbols)?;
+        } else {
+            let quadruplet = cursor.parse_quadruplet(quadruplets.len(), &symbols)?;
+            quadruplets.push(quadruplet);
+        }
+    }
+
+    if let Some((index, target)) = quadruplets.iter().enumerate().find_map(|(i, q)| jump_target(q).filter(|t| *t > quadruplets.len()).map(|t| (i, t))) {
+        return Err(QuadrupletParseError::InvalidTarget(index, target));
+    }
+    Ok(quadruplets)
+}
+
+/// Reads one line of the textual format
+struct Cursor<'a> {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+    text: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str, line: usize) -> Self {
+        Cursor { chars: text.chars().collect(), position: 0, line, text }
+    }
+
+    fn error(&self, message: &str) -> QuadrupletParseError {
+        QuadrupletParseError::Syntax(self.line, format!("{} in '{}'", message, self.text.trim()))
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.chars.len()
+    }
+
+    fn rest(&self) -> String {
+        self.chars[self.position..].iter().collect()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), QuadrupletParseError> {
+        self.skip_spaces();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn peek_word(&self) -> String {
+        self.chars[self.position..].iter().take_while(|c| c.is_ascii_alphanumeric() || **c == '_').collect()
+    }
+
+    fn word(&mut self) -> String {
+        self.skip_spaces();
+        let word = self.peek_word();
+        self.position += word.chars().count();
+        word
+    }
+
+    fn number(&mut self) -> String {
+        self.skip_spaces();
+        let start = self.position;
+        if self.peek() == Some('-') {
+            self.position += 1;
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+') {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    fn parse_declarations(&mut self, scalar_type: Types, symbols: &mut HashMap<String, Types>) -> Result<(), QuadrupletParseError> {
+        self.word();
+        loop {
+            let name = self.word();
+            if name.is_empty() {
+                return Err(self.error("expected a name"));
+            }
+            self.skip_spaces();
+            let symbol_type = if self.peek() == Some('[') {
+                self.position += 1;
+                let size = self.number().parse::<i16>().map_err(|_| self.error("invalid array size"))?;
+                self.expect(']')?;
+                Types::Array(Box::new(scalar_type.clone()), size)
+            } else {
+                scalar_type.clone()
+            };
+            if symbols.insert(name.clone(), symbol_type).is_some() {
+                return Err(QuadrupletParseError::DuplicateDeclaration(self.line, name));
+            }
+            self.skip_spaces();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(';') => break,
+                _ => return Err(self.error("expected ',' or ';'")),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_quadruplet(&mut self, index: usize, symbols: &HashMap<String, Types>) -> Result<Quadruplet, QuadrupletParseError> {
+        if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            let number = self.number();
+            if number.parse::<usize>() != Ok(index) {
+                return Err(self.error(&format!("quadruplet numbered {} found at position {}", number, index)));
+            }
+            self.expect(':')?;
+        }
+        self.expect('(')?;
+        let name = self.word();
+        let operator = Operator::from_name(&name).ok_or_else(|| self.error(&format!("unknown operator '{}'", name)))?;
+        self.expect(',')?;
+        let operand1 = self.parse_operand(symbols)?;
+        self.expect(',')?;
+        let operand2 = self.parse_operand(symbols)?;
+        self.expect(',')?;
+        let result = if operator.is_jump() {
+            self.skip_spaces();
+            let target = self.number();
+            Some(Operand::Label(target.parse().map_err(|_| self.error("expected a quadruplet index"))?))
+        } else {
+            self.parse_operand(symbols)?
+        };
+        self.expect(')')?;
+        self.skip_spaces();
+        if !self.at_end() && !self.rest().starts_with("%%") {
+            return Err(self.error("unexpected text after the quadruplet"));
+        }
+        Ok(Quadruplet::new(operator, operand1, operand2, result))
+    }
+
+    fn parse_operand(&mut self, symbols: &HashMap<String, Types>) -> Result<Option<Operand>, QuadrupletParseError> {
+        self.skip_spaces();
+        let operand = match self.peek() {
+            Some('_') => {
+                self.position += 1;
+                return Ok(None);
+            },
+            Some('"') => Operand::String(self.quoted('"')?),
+            Some('\'') => {
+                let text = self.quoted('\'')?;
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Operand::Constant(Value::Char(c)),
+                    _ => return Err(self.error("a char holds exactly one character")),
+                }
+            },
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let number = self.number();
+                if let Ok(integer) = number.parse::<i16>() {
+                    Operand::Constant(Value::Integer(integer))
+                } else if let Ok(float) = number.parse::<f32>() {
+                    Operand::Constant(Value::Float(float))
+                } else {
+                    return Err(self.error(&format!("invalid number '{}'", number)));
+                }
+            },
+            Some(c) if c.is_ascii_alphabetic() => {
+                let name = self.word();
+                let symbol_type = match symbols.get(&name) {
+                    Some(symbol_type) => symbol_type.clone(),
+                    None => match name.parse::<f32>() {
+                        // inf and NaN are written like words
+                        Ok(float) => return Ok(Some(Operand::Constant(Value::Float(float)))),
+                        Err(_) => return Err(QuadrupletParseError::UndeclaredName(self.line, name)),
+                    },
+                };
+                self.skip_spaces();
+                if self.peek() == Some('[') {
+                    self.position += 1;
+                    let index = self.parse_operand(symbols)?.ok_or_else(|| self.error("missing array index"))?;
+                    self.expect(']')?;
+                    if !matches!(symbol_type, Types::Array(_, _)) {
+                        return Err(self.error(&format!("'{}' is not an array", name)));
+                    }
+                    Operand::ArrayElement(name, symbol_type, Box::new(index))
+                } else if let Some(number) = name.strip_prefix('t').and_then(|n| n.parse::<usize>().ok()) {
+                    Operand::Temporary(number, symbol_type)
+                } else {
+                    Operand::Variable(name, symbol_type)
+                }
+            },
+            _ => return Err(self.error("expected an operand")),
+        };
+        Ok(Some(operand))
+    }
+
+    /// Reads a quoted string or char, undoing the escapes written by `{:?}`
+    fn quoted(&mut self, quote: char) -> Result<String, QuadrupletParseError> {
+        self.position += 1;
+        let mut text = String::new();
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("unterminated literal"))?;
+            self.position += 1;
+            if c == quote {
+                return Ok(text);
+            }
+            if c != '\\' {
+                text.push(c);
+                continue;
+            }
+            let escaped = self.peek().ok_or_else(|| self.error("unterminated literal"))?;
+            self.position += 1;
+            text.push(match escaped {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                '\\' | '\'' | '"' => escaped,
+                'u' => {
+                    self.expect('{')?;
+                    let start = self.position;
+                    while self.peek().is_some_and(|c| c != '}') {
+                        self.position += 1;
+                    }
+                    let code: String = self.chars[start..self.position].iter().collect();
+                    self.expect('}')?;
+                    u32::from_str_radix(&code, 16).ok().and_then(char::from_u32).ok_or_else(|| self.error("invalid unicode escape"))?
+                },
+                _ => return Err(self.error(&format!("unknown escape '\\{}'", escaped))),
+            });
+        }
+    }
+}
+
+/// Prints quadruplets under a title with their index and formatted representation
+pub fn print_quadruplets(title: &str, quadruplets: &[Quadruplet]) {
+    println!("\n{}:", title);
+    println!("----------------------");
+    for (index, quad) in quadruplets.iter().enumerate() {
+        println!("{}: {}", index, quad);
+    }
+    println!("----------------------\n");
+}
+
+/// Target of a jump quadruplet, None for any other operator or an unfilled jump
+pub fn jump_target(quadruplet: &Quadruplet) -> Option<usize> {
+    match quadruplet.operator {
+        Operator::Goto | Operator::IfTrue | Operator::IfFalse => match quadruplet.result {
+            Some(Operand::Label(target)) => Some(target),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Deletes the quadruplets flagged in `removed` and renumbers the jump targets,
+/// a jump to a deleted quadruplet now lands on the next one that is kept
+pub fn remove_quadruplets(quadruplets: &mut Vec<Quadruplet>, removed: &[bool]) {
+    let mut new_index = Vec::with_capacity(quadruplets.len() + 1);
+    let mut kept = 0;
+    for &is_removed in removed.iter().take(quadruplets.len()) {
+        new_index.push(kept);
+        if !is_removed {
+            kept += 1;
+        }
+    }
+    new_index.push(kept);
+
+    let mut index = 0;
+    quadruplets.retain(|_| {
+        index += 1;
+        !removed[index - 1]
+    });
+    for quadruplet in quadruplets.iter_mut() {
+        if let Some(target) = jump_target(quadruplet) {
+            quadruplet.result = Some(Operand::Label(new_index[target]));
+        }
+    }
+}
+
+/// Inserts quadruplets before the one at `at` and renumbers the jump targets
+/// A jump to `at` lands on the first inserted quadruplet when `enters(index)` holds for the index
+/// of the jump, otherwise it keeps going to the quadruplet that was at `at`
+pub fn insert_quadruplets(
+    quadruplets: &mut Vec<Quadruplet>,
+    at: usize,
+    inserted: Vec<Quadruplet>,
+    enters: impl Fn(usize) -> bool,
+) {
+    let count = inserted.len();
+    for (index, quadruplet) in quadruplets.iter_mut().enumerate() {
+        if let Some(target) = jump_target(quadruplet) {
+            if target > at || (target == at && !enters(index)) {
+                quadruplet.result = Some(Operand::Label(target + count));
+            }
+        }
+    }
+    quadruplets.splice(at..at, inserted);
+}
+
+/// Jumps of a condition compiled with backpatching
+#[derive(Debug, Default)]
+struct JumpLists {
+    true_list: Vec<usize>,
+    false_list: Vec<usize>,
+}
+
+fn merge(mut first: Vec<usize>, second: Vec<usize>) -> Vec<usize> {
+    first.extend(second);
+    first
+}
+
 #[derive(Debug)]
 pub struct QuadrupletGenerator {
     quadruplets: Vec<Quadruplet>,
     temp_counter: usize,
     error_handler: Vec<CustomError>,
+    symbols: HashMap<String, Types>,   // Types of the declared variables, arrays hold their element type and size
+    constants: HashMap<String, Value>, // Constant values known at generation time, used for array sizes
+    position: Option<(usize, usize)>,  // Source of the next quadruplets, where the interpreter reports their errors
+    frames: Vec<Frame>,                // IF and FOR statements enclosing the next quadruplets
 }
 
 impl QuadrupletGenerator {
@@ -114,19 +766,30 @@ impl QuadrupletGenerator {
             quadruplets: Vec::new(),
             temp_counter: 0,
             error_handler: Vec::new(),
+            symbols: HashMap::new(),
+            constants: HashMap::new(),
+            position: None,
+            frames: Vec::new(),
         }
     }
 
-    /// Generates a new temporary variable name
-    /// Returns a string in the format "t{number}" where number is incremented for each new temp
-    pub fn generate_temp(&mut self) -> String {
+    /// Generates a new temporary holding a value of type `temp_type`
+    /// Temporaries are printed in the format "t{number}" where number is incremented for each new temp
+    pub fn generate_temp(&mut self, temp_type: Types) -> Operand {
         self.temp_counter += 1;
-        format!("t{}", self.temp_counter)
+        Operand::Temporary(self.temp_counter, temp_type)
     }
 
-    /// Adds a quadruplet to the list of generated quadruplets
+    /// Adds a quadruplet to the list of generated quadruplets, coming from the current position
+    /// unless it has an origin already
     /// Returns a reference to the added quadruplet
-    pub fn add_quadruplet(&mut self, quadruplet: Quadruplet) -> &Quadruplet {
+    pub fn add_quadruplet(&mut self, mut quadruplet: Quadruplet) -> &Quadruplet {
+        if quadruplet.origin.is_none() {
+            quadruplet.origin = self.position.map(|position| Origin {
+                position: Position::from(position),
+                stack: self.frames.iter().rev().cloned().collect(),
+            });
+        }
         self.quadruplets.push(quadruplet);
         self.quadruplets.last().unwrap()
     }
@@ -138,20 +801,388 @@ impl QuadrupletGenerator {
 
     /// Prints all quadruplets with their index and formatted representation
     pub fn print_quadruplets(&self) {
-        println!("\nGenerated Quadruplets:");
-        println!("----------------------");
-        for (index, quad) in self.quadruplets.iter().enumerate() {
-            println!("{}: {}", index, quad.to_string());
+        print_quadruplets("Generated Quadruplets", &self.quadruplets);
+    }
+
+    /// Consumes the generator and returns the generated quadruplets
+    pub fn into_quadruplets(self) -> Vec<Quadruplet> {
+        self.quadruplets
+    }
+
+    /// Generates the quadruplets of a whole program
+    /// Declaration initializers are emitted first, followed by the instructions in order
+    pub fn generate_program(&mut self, program: &Program) -> Result<(), CustomError> {
+        if let Some(globals) = &program.global {
+            self.generate_declarations(globals)?;
+        }
+        if let Some(declarations) = &program.decls {
+            self.generate_declarations(declarations)?;
+        }
+        if let Some(instructions) = &program.inst {
+            self.generate_instructions(instructions)?;
+        }
+        self.simplify_jumps();
+        Ok(())
+    }
+
+    /// Index of the next quadruplet to be generated, used as a jump target
+    fn next_index(&self) -> usize {
+        self.quadruplets.len()
+    }
+
+    /// Emits a jump whose target is not known yet and returns its index so it can be patched later
+    fn emit_jump(&mut self, operator: Operator, condition: Option<Operand>) -> usize {
+        self.add_quadruplet(Quadruplet::new(operator, condition, None, None));
+        self.next_index() - 1
+    }
+
+    /// Sets the target of the jump at `index`
+    fn patch_jump(&mut self, index: usize, target: usize) {
+        self.quadruplets[index].result = Some(Operand::Label(target));
+    }
+
+    fn generate_declarations(&mut self, declarations: &Vec<Declaration>) -> Result<(), CustomError> {
+        for declaration in declarations {
+            match declaration {
+                Declaration::Variable(type_decl, variables) => {
+                    for variable in variables {
+                        match variable {
+                            Variable::Simple((name, _)) => {
+                                self.symbols.insert(name.clone(), to_types(type_decl));
+                            },
+                            Variable::Initialized((name, _), expr) => {
+                                self.symbols.insert(name.clone(), to_types(type_decl));
+                                let value = self.generate_expression(expr)?;
+                                self.emit_assign(value, Operand::Variable(name.clone(), to_types(type_decl)));
+                            },
+                        }
+                    }
+                },
+                Declaration::ADEC(type_decl, arrays) => {
+                    for array in arrays {
+                        match array {
+                            ArrayDecl::Simple((name, _), size) => {
+                                self.declare_array(name, type_decl, size, 0);
+                            },
+                            ArrayDecl::Initialized((name, _), size, values) => {
+                                self.declare_array(name, type_decl, size, values.len());
+                                let mut operands = Vec::new();
+                                for value in values {
+                                    operands.push(self.generate_expression(value)?);
+                                }
+                                self.generate_array_initialization(name, operands);
+                            },
+                            ArrayDecl::InitializedString((name, _), size, (value, _)) => {
+                                // The literal still holds its surrounding double quotes
+                                let content = &value[1..value.len() - 1];
+                                let operands: Vec<Operand> = if content.is_empty() {
+                                    vec![Operand::Constant(Value::Char('\0'))]
+                                } else {
+                                    content.chars().map(|c| Operand::Constant(Value::Char(c))).collect()
+                                };
+                                self.declare_array(name, type_decl, size, operands.len());
+                                self.generate_array_initialization(name, operands);
+                            },
+                        }
+                    }
+                },
+                Declaration::Constant(type_decl, constants) => {
+                    for constant in constants {
+                        self.symbols.insert(constant.var.0.clone(), to_types(type_decl));
+                        if let Some(value) = constant_value(&constant.expr, &self.constants) {
+                            self.constants.insert(constant.var.0.clone(), value);
+                        }
+                        self.generate_assignment(constant)?;
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Records an array and its size, falling back to the number of initial values
+    /// when the size is not a known constant
+    fn declare_array(&mut self, name: &str, type_decl: &Type, size: &Expr, initial_values: usize) {
+        let size = match constant_value(size, &self.constants) {
+            Some(Value::Integer(size)) if size > 0 => size,
+            _ => initial_values as i16,
+        };
+        self.symbols.insert(name.to_string(), Types::Array(Box::new(to_types(type_decl)), size));
+    }
+
+    /// Stores the initial values of an array, repeating them until every cell is filled
+    /// the same way the semantic analyzer fills the symbol table
+    fn generate_array_initialization(&mut self, name: &str, values: Vec<Operand>) {
+        let array_type = self.symbols[name].clone();
+        let size = match array_type {
+            Types::Array(_, size) => size as usize,
+            _ => values.len(),
+        };
+        for index in 0..size {
+            let value = values[index % values.len()].clone();
+            let cell = Operand::ArrayElement(name.to_string(), array_type.clone(), Box::new(Operand::Constant(Value::Integer(index as i16))));
+            self.emit_assign(value, cell);
+        }
+    }
+
+    fn generate_instructions(&mut self, instructions: &Vec<Instruction>) -> Result<(), CustomError> {
+        for instruction in instructions {
+            match instruction {
+                Instruction::Assign(assignment) => self.generate_assignment(assignment)?,
+                Instruction::If(if_stmt) => self.generate_if(if_stmt)?,
+                Instruction::For(for_stmt) => self.generate_for(for_stmt)?,
+                Instruction::Read(read_stmt) => self.generate_read(read_stmt)?,
+                Instruction::Write(write_stmt) => self.generate_write(write_stmt)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_assign(&mut self, value: Operand, target: Operand) {
+        self.add_quadruplet(Quadruplet::new(Operator::Assign, Some(value), None, Some(target)));
+    }
+
+    /// Generates the location written by an assignment or a READ, evaluating the index of array cells
+    /// The next quadruplets come from the name, where the index is checked
+    fn generate_target(&mut self, name: &(String, (usize, usize)), index: &Option<Expr>) -> Result<Operand, CustomError> {
+        let symbol_type = self.symbol_type(name)?;
+        self.position = Some(name.1);
+        match index {
+            None => Ok(Operand::Variable(name.0.clone(), symbol_type)),
+            Some(index) => {
+                let index = self.generate_expression(index)?;
+                self.position = Some(name.1);
+                Ok(Operand::ArrayElement(name.0.clone(), symbol_type, Box::new(index)))
+            }
+        }
+    }
+
+    fn symbol_type(&self, name: &(String, (usize, usize))) -> Result<Types, CustomError> {
+        self.symbols
+            .get(&name.0)
+            .cloned()
+            .ok_or_else(|| CustomError::UndeclaredIdentifier(name.0.clone(), name.1))
+    }
+
+    fn generate_assignment(&mut self, assignment: &Assignment) -> Result<(), CustomError> {
+        let value = self.generate_expression(&assignment.expr)?;
+        let target = self.generate_target(&assignment.var, &assignment.index)?;
+        self.emit_assign(value, target);
+        Ok(())
+    }
+
+    /// IF-ELSE layout, the condition jumps to `then` when it holds and to `else` (or `end`) otherwise:
+    ///     condition jumps
+    /// then:
+    ///     then block
+    ///     (Goto, _, _, end)        only when there is an else block
+    /// else:
+    ///     else block
+    /// end:
+    fn generate_if(&mut self, if_stmt: &IfStmt) -> Result<(), CustomError> {
+        self.frames.push(Frame::If(if_stmt.position.into()));
+        let condition = self.generate_condition(&if_stmt.condition)?;
+        let then_start = self.next_index();
+        self.backpatch(&condition.true_list, then_start);
+        self.generate_instructions(&if_stmt.then_block)?;
+
+        match &if_stmt.else_block {
+            None => {
+                let end = self.next_index();
+                self.backpatch(&condition.false_list, end);
+            },
+            Some(else_block) => {
+                let jump_to_end = self.emit_jump(Operator::Goto, None);
+                let else_start = self.next_index();
+                self.backpatch(&condition.false_list, else_start);
+                self.generate_instructions(else_block)?;
+                let end = self.next_index();
+                self.patch_jump(jump_to_end, end);
+            },
+        }
+        self.frames.pop();
+        Ok(())
+    }
+
+    /// FOR(var = start : step : end) layout, the loop runs while var < end
+    /// like the condition checked by the semantic analyzer:
+    ///     var = start
+    /// test:
+    ///     var < end jumps
+    /// body:
+    ///     body
+    ///     var = var + step
+    ///     (Goto, _, _, test)
+    /// exit:
+    fn generate_for(&mut self, for_stmt: &ForStmt) -> Result<(), CustomError> {
+        self.generate_assignment(&for_stmt.init)?;
+        self.frames.push(Frame::Loop(for_stmt.position.into()));
+
+        let test = self.next_index();
+        let counter = self.generate_target(&for_stmt.init.var, &for_stmt.init.index)?;
+        let bound = self.generate_expression(&for_stmt.condition)?;
+        self.position = Some(for_stmt.init.var.1);
+        let condition = self.generate_relation(Operator::LessThan, counter, bound);
+        let body_start = self.next_index();
+        self.backpatch(&condition.true_list, body_start);
+
+        self.generate_instructions(&for_stmt.body)?;
+
+        let counter = self.generate_target(&for_stmt.init.var, &for_stmt.init.index)?;
+        let step = self.generate_expression(&for_stmt.step)?;
+        self.position = Some(for_stmt.init.var.1);
+        let next = self.generate_temp(counter.value_type().unwrap_or(Types::Integer));
+        self.add_quadruplet(Quadruplet::new(Operator::Add, Some(counter), Some(step), Some(next.clone())));
+        let counter = self.generate_target(&for_stmt.init.var, &for_stmt.init.index)?;
+        self.emit_assign(next, counter);
+
+        let jump_to_test = self.emit_jump(Operator::Goto, None);
+        self.patch_jump(jump_to_test, test);
+        let exit = self.next_index();
+        self.backpatch(&condition.false_list, exit);
+        self.frames.pop();
+        Ok(())
+    }
+
+    fn generate_read(&mut self, read_stmt: &ReadStmt) -> Result<(), CustomError> {
+        let target = self.generate_target(&read_stmt.variable, &read_stmt.index)?;
+        self.add_quadruplet(Quadruplet::new(Operator::Read, None, None, Some(target)));
+        Ok(())
+    }
+
+    /// Every element of a WRITE gets its own quadruplet, the statement ends with a line break
+    fn generate_write(&mut self, write_stmt: &WriteStmt) -> Result<(), CustomError> {
+        for element in &write_stmt.elements {
+            let operand = match element {
+                // The literal still holds its surrounding double quotes
+                WriteElement::String((literal, position)) => {
+                    self.position = Some(*position);
+                    Operand::String(literal[1..literal.len() - 1].to_string())
+                },
+                WriteElement::Variable(name, index) => self.generate_target(name, index)?,
+            };
+            self.add_quadruplet(Quadruplet::new(Operator::Write, Some(operand), None, None));
         }
-        println!("----------------------\n");
+        self.add_quadruplet(Quadruplet::new(Operator::Write, Some(Operand::String("\n".to_string())), None, None));
+        Ok(())
     }
 
-    fn generate_expression(&mut self, expr: &Expr) -> Result<String, CustomError> {
+    /// Compiles a condition into jumps using backpatching
+    /// The returned lists hold the jumps taken when the condition is true and when it is false,
+    /// their targets are filled in by the caller once they are known
+    fn generate_condition(&mut self, condition: &Condition) -> Result<JumpLists, CustomError> {
+        match condition {
+            Condition::Basic(basic) => {
+                let left = self.generate_expression(&basic.left)?;
+                let right = self.generate_expression(&basic.right)?;
+                let operator = match basic.operator {
+                    RelOp::Gt(_, _) => Operator::GreaterThan,
+                    RelOp::Lt(_, _) => Operator::LessThan,
+                    RelOp::Ge(_, _) => Operator::GreaterThanOrEqual,
+                    RelOp::Le(_, _) => Operator::LessThanOrEqual,
+                    RelOp::Eq(_, _) => Operator::Equal,
+                    RelOp::Ne(_, _) => Operator::NotEqual,
+                };
+                self.position = Some(basic.operator.position());
+                Ok(self.generate_relation(operator, left, right))
+            },
+            Condition::Logic(left, LogOp::And(_, _), right) => {
+                // The right side is only evaluated when the left side holds
+                let left = self.generate_condition(left)?;
+                let right_start = self.next_index();
+                self.backpatch(&left.true_list, right_start);
+                let right = self.generate_condition(right)?;
+                Ok(JumpLists {
+                    true_list: right.true_list,
+                    false_list: merge(left.false_list, right.false_list),
+                })
+            },
+            Condition::Logic(left, LogOp::Or(_, _), right) => {
+                // The right side is only evaluated when the left side fails
+                let left = self.generate_condition(left)?;
+                let right_start = self.next_index();
+                self.backpatch(&left.false_list, right_start);
+                let right = self.generate_condition(right)?;
+                Ok(JumpLists {
+                    true_list: merge(left.true_list, right.true_list),
+                    false_list: right.false_list,
+                })
+            },
+            Condition::Not(inner) => {
+                let inner = self.generate_condition(inner)?;
+                Ok(JumpLists {
+                    true_list: inner.false_list,
+                    false_list: inner.true_list,
+                })
+            },
+        }
+    }
+
+    /// Emits a comparison followed by its two unfilled jumps:
+    ///     (operator, left, right, t)
+    ///     (IfTrue, t, _, ?)        true list
+    ///     (Goto, _, _, ?)          false list
+    fn generate_relation(&mut self, operator: Operator, left: Operand, right: Operand) -> JumpLists {
+        let result = self.generate_temp(Types::Integer);
+        self.add_quadruplet(Quadruplet::new(operator, Some(left), Some(right), Some(result.clone())));
+        let jump_if_true = self.emit_jump(Operator::IfTrue, Some(result));
+        let jump_if_false = self.emit_jump(Operator::Goto, None);
+        JumpLists {
+            true_list: vec![jump_if_true],
+            false_list: vec![jump_if_false],
+        }
+    }
+
+    /// Fills the target of every jump in `list`
+    fn backpatch(&mut self, list: &[usize], target: usize) {
+        for &index in list {
+            self.patch_jump(index, target);
+        }
+    }
+
+    /// Removes the redundant jumps left by backpatching:
+    ///     (IfTrue, t, _, i + 2) (Goto, _, _, L)  becomes  (IfFalse, t, _, L)
+    ///     (Goto, _, _, i + 1)                    is dropped
+    fn simplify_jumps(&mut self) {
+        let targets: HashSet<usize> = self.quadruplets.iter().filter_map(jump_target).collect();
+        let mut removed = vec![false; self.quadruplets.len()];
+
+        let mut index = 0;
+        while index < self.quadruplets.len() {
+            let quad = &self.quadruplets[index];
+            let target = jump_target(quad);
+            match quad.operator {
+                Operator::IfTrue | Operator::IfFalse
+                    if target == Some(index + 2)
+                        && !targets.contains(&(index + 1))
+                        && matches!(self.quadruplets.get(index + 1).map(|q| &q.operator), Some(Operator::Goto)) => {
+                    let operator = match quad.operator {
+                        Operator::IfTrue => Operator::IfFalse,
+                        _ => Operator::IfTrue,
+                    };
+                    let goto_target = jump_target(&self.quadruplets[index + 1]).unwrap();
+                    self.quadruplets[index].operator = operator;
+                    self.patch_jump(index, goto_target);
+                    removed[index + 1] = true;
+                    index += 2;
+                    continue;
+                },
+                Operator::Goto if target == Some(index + 1) => removed[index] = true,
+                _ => {},
+            }
+            index += 1;
+        }
+
+        remove_quadruplets(&mut self.quadruplets, &removed);
+    }
+
+    fn generate_expression(&mut self, expr: &Expr) -> Result<Operand, CustomError> {
         match expr {
             Expr::BinaryOp(left, op, right) => {
                 let left_temp = self.generate_expression(left)?;
                 let right_temp = self.generate_expression(right)?;
-                let result_temp = self.generate_temp();
+                // Both sides have the same type once the semantic analysis succeeded
+                let result_temp = self.generate_temp(left_temp.value_type().unwrap_or(Types::Integer));
 
                 let operator = match op {
                     BinOp::Add(_, _) => Operator::Add,
@@ -159,6 +1190,7 @@ impl QuadrupletGenerator {
                     BinOp::Mul(_, _) => Operator::Multiply,
                     BinOp::Div(_, _) => Operator::Divide,
                 };
+                self.position = Some(op.position());
                 self.add_quadruplet(Quadruplet::new(
                     operator,
                     Some(left_temp),
@@ -168,24 +1200,27 @@ impl QuadrupletGenerator {
 
                 Ok(result_temp)
             },
-            Expr::Variable((name, (line, col))) => {
-                // We can use the line and col information for error reporting if needed
-                Ok(name.clone())
+            Expr::Variable(name) => {
+                let symbol_type = self.symbol_type(name)?;
+                Ok(Operand::Variable(name.0.clone(), symbol_type))
             },
             Expr::SUBS(name, index) => {
-                let index_temp = self.generate_expression(index)?;
-                let result_temp = self.generate_temp();
+                let cell = self.generate_target(name, &Some(*index.clone()))?;
+                let result_temp = self.generate_temp(cell.value_type().unwrap_or(Types::Integer));
 
                 self.add_quadruplet(Quadruplet::new(
                     Operator::Assign,
-                    Some(format!("{:?}[{:?}]", name, index_temp)),
+                    Some(cell),
                     None,
                     Some(result_temp.clone())
                 ));
 
                 Ok(result_temp)
             },
-            Expr::Literal(lit) => Ok(type_value_to_string(lit)),
+            Expr::Literal(lit) => match to_value(lit) {
+                Some(value) => Ok(Operand::Constant(value)),
+                None => Err(CustomError::UnknownError),
+            },
         }
     }
 
@@ -202,12 +1237,38 @@ impl QuadrupletGenerator {
     }
 }
 
-fn type_value_to_string(value: &TypeValue) -> String {
+/// Evaluates an expression made of literals and known constants, as used for array sizes
+pub(crate) fn constant_value(expr: &Expr, constants: &HashMap<String, Value>) -> Option<Value> {
+    match expr {
+        Expr::Literal(value) => to_value(value),
+        Expr::Variable((name, _)) => constants.get(name).copied(),
+        Expr::SUBS(_, _) => None,
+        Expr::BinaryOp(left, op, right) => {
+            match (constant_value(left, constants)?, op, constant_value(right, constants)?) {
+                (Value::Integer(a), BinOp::Add(_, _), Value::Integer(b)) => Some(Value::Integer(a.checked_add(b)?)),
+                (Value::Integer(a), BinOp::Sub(_, _), Value::Integer(b)) => Some(Value::Integer(a.checked_sub(b)?)),
+                (Value::Integer(a), BinOp::Mul(_, _), Value::Integer(b)) => Some(Value::Integer(a.checked_mul(b)?)),
+                (Value::Integer(a), BinOp::Div(_, _), Value::Integer(b)) => Some(Value::Integer(a.checked_div(b)?)),
+                _ => None,
+            }
+        },
+    }
+}
+
+pub(crate) fn to_value(value: &TypeValue) -> Option<Value> {
     match value {
-        TypeValue::Integer(i) => format!("{:?}", i),
-        TypeValue::Float(f) => format!("{:?}", f),
-        TypeValue::Char(c) => format!("{:?}", c),
-        TypeValue::Array(_) => "Array".to_string(),
+        TypeValue::Integer(i) => Some(Value::Integer(i.0)),
+        TypeValue::Float(f) => Some(Value::Float(f.0)),
+        TypeValue::Char(c) => Some(Value::Char(c.0)),
+        TypeValue::Array(_) => None,
+    }
+}
+
+pub(crate) fn to_types(type_decl: &Type) -> Types {
+    match type_decl {
+        Type::Integer => Types::Integer,
+        Type::Float => Types::Float,
+        Type::Char => Types::Char,
     }
 }
 
@@ -215,22 +1276,31 @@ fn type_value_to_string(value: &TypeValue) -> String {
 mod tests {
     use super::*;
     use TypeValue;
+    use crate::Test::tests::parse_source;
+
+    fn generate(source: &str) -> Vec<String> {
+        let program = parse_source(source);
+        let mut generator = QuadrupletGenerator::new();
+        generator.generate_program(&program).unwrap();
+        generator.get_quadruplets().iter().map(|q| q.to_string()).collect()
+    }
 
     #[test]
     fn test_quadruplet_generation() {
         let mut generator = QuadrupletGenerator::new();
 
         // Test temporary variable generation
-        let temp1 = generator.generate_temp();
-        assert_eq!(temp1, "t1");
-        let temp2 = generator.generate_temp();
-        assert_eq!(temp2, "t2");
+        let temp1 = generator.generate_temp(Types::Integer);
+        assert_eq!(temp1, Operand::Temporary(1, Types::Integer));
+        assert_eq!(temp1.to_string(), "t1");
+        let temp2 = generator.generate_temp(Types::Integer);
+        assert_eq!(temp2.to_string(), "t2");
 
         // Test quadruplet addition
         generator.add_quadruplet(Quadruplet::new(
             Operator::Add,
-            Some("a".to_string()),
-            Some("b".to_string()),
+            Some(Operand::Variable("A".to_string(), Types::Integer)),
+            Some(Operand::Variable("B".to_string(), Types::Integer)),
             Some(temp1.clone())
         ));
 
@@ -239,7 +1309,7 @@ mod tests {
         generator.add_quadruplet(Quadruplet::new(
             Operator::Multiply,
             Some(temp1),
-            Some("2".to_string()),
+            Some(Operand::Constant(Value::Integer(2))),
             Some(temp2)
         ));
 
@@ -258,4 +1328,171 @@ mod tests {
         assert!(generator.has_errors());
         assert_eq!(generator.get_errors().len(), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_program_generation_if_else() {
+        let quads = generate(r#"
+            VAR_GLOBAL { INTEGER Qa, Qb; }
+            DECLARATION { CONST INTEGER Qmax = 10; }
+            INSTRUCTION {
+                READ(Qa);
+                IF (Qa > Qmax) { Qb = Qa - Qmax; } ELSE { Qb = 0; }
+                WRITE("Qb = ", Qb);
+            }
+        "#);
+        assert_eq!(quads, vec![
+            "(Assign, 10, _, Qmax)",
+            "(Read, _, _, Qa)",
+            "(GreaterThan, Qa, Qmax, t1)",
+            "(IfFalse, t1, _, 7)",
+            "(Subtract, Qa, Qmax, t2)",
+            "(Assign, t2, _, Qb)",
+            "(Goto, _, _, 8)",
+            "(Assign, 0, _, Qb)",
+            "(Write, \"Qb = \", _, _)",
+            "(Write, Qb, _, _)",
+            "(Write, \"\\n\", _, _)",
+        ]);
+    }
+
+    #[test]
+    fn test_program_generation_for_and_arrays() {
+        let quads = generate(r#"
+            VAR_GLOBAL { INTEGER Fi; INTEGER Farr[3] = [7, 8]; }
+            DECLARATION { }
+            INSTRUCTION {
+                FOR (Fi = 0 : 1 : 3) { Farr[Fi] = Fi * 2; }
+            }
+        "#);
+        assert_eq!(quads, vec![
+            "(Assign, 7, _, Farr[0])",
+            "(Assign, 8, _, Farr[1])",
+            "(Assign, 7, _, Farr[2])",
+            "(Assign, 0, _, Fi)",
+            "(LessThan, Fi, 3, t1)",
+            "(IfFalse, t1, _, 11)",
+            "(Multiply, Fi, 2, t2)",
+            "(Assign, t2, _, Farr[Fi])",
+            "(Add, Fi, 1, t3)",
+            "(Assign, t3, _, Fi)",
+            "(Goto, _, _, 4)",
+        ]);
+    }
+
+    #[test]
+    fn test_short_circuit_conditions() {
+        let quads = generate(r#"
+            VAR_GLOBAL { INTEGER Sa, Sb; }
+            DECLARATION { }
+            INSTRUCTION {
+                IF (Sa > 0 && Sb < 10) { Sa = 1; }
+                IF (Sa > 0 || Sb < 10) { Sa = 2; }
+                IF (!(Sa == Sb)) { Sa = 3; }
+            }
+        "#);
+        assert_eq!(quads, vec![
+            // The right side is skipped as soon as the left side fails
+            "(GreaterThan, Sa, 0, t1)",
+            "(IfFalse, t1, _, 5)",
+            "(LessThan, Sb, 10, t2)",
+            "(IfFalse, t2, _, 5)",
+            "(Assign, 1, _, Sa)",
+            // The right side is skipped as soon as the left side holds
+            "(GreaterThan, Sa, 0, t3)",
+            "(IfTrue, t3, _, 9)",
+            "(LessThan, Sb, 10, t4)",
+            "(IfFalse, t4, _, 10)",
+            "(Assign, 2, _, Sa)",
+            // Negation swaps the jumps instead of computing a value
+            "(Equal, Sa, Sb, t5)",
+            "(IfTrue, t5, _, 13)",
+            "(Assign, 3, _, Sa)",
+        ]);
+    }
+
+    #[test]
+    fn test_typed_operands() {
+        let program = parse_source(r#"
+            VAR_GLOBAL { FLOAT Tf; INTEGER Ti; FLOAT Tarr[4]; }
+            DECLARATION { }
+            INSTRUCTION { Tf = Tarr[Ti + 1] * 2.5; }
+        "#);
+        let mut generator = QuadrupletGenerator::new();
+        generator.generate_program(&program).unwrap();
+        let array_type = Types::Array(Box::new(Types::Float), 4);
+        assert_eq!(generator.get_quadruplets(), &vec![
+            Quadruplet::new(
+                Operator::Add,
+                Some(Operand::Variable("Ti".to_string(), Types::Integer)),
+                Some(Operand::Constant(Value::Integer(1))),
+                Some(Operand::Temporary(1, Types::Integer)),
+            ),
+            Quadruplet::new(
+                Operator::Assign,
+                Some(Operand::ArrayElement("Tarr".to_string(), array_type, Box::new(Operand::Temporary(1, Types::Integer)))),
+                None,
+                Some(Operand::Temporary(2, Types::Float)),
+            ),
+            Quadruplet::new(
+                Operator::Multiply,
+                Some(Operand::Temporary(2, Types::Float)),
+                Some(Operand::Constant(Value::Float(2.5))),
+                Some(Operand::Temporary(3, Types::Float)),
+            ),
+            Quadruplet::new(
+                Operator::Assign,
+                Some(Operand::Temporary(3, Types::Float)),
+                None,
+                Some(Operand::Variable("Tf".to_string(), Types::Float)),
+            ),
+        ]);
+        assert_eq!(generator.get_quadruplets()[1].to_string(), "(Assign, Tarr[t1], _, t2)");
+    }
+
+    #[test]
+    fn test_textual_format_round_trip() {
+        let program = parse_source(r#"
+            VAR_GLOBAL { INTEGER Ra; FLOAT Rf; CHAR Rs[3] = "a'b"; }
+            DECLARATION { }
+            INSTRUCTION {
+                READ(Ra);
+                IF (Ra > 0 || Rf <= 1.5) { Rs[Ra] = ','; } ELSE { Rf = Rf / 2.0; }
+                WRITE("a \"quoted\", text", Rs[0]);
+            }
+        "#);
+        let mut generator = QuadrupletGenerator::new();
+        generator.generate_program(&program).unwrap();
+
+        let text = format_quadruplets(generator.get_quadruplets());
+        let parsed = parse_quadruplets(&text).unwrap();
+        assert_eq!(&parsed, generator.get_quadruplets());
+        assert_eq!(format_quadruplets(&parsed), text);
+    }
+
+    #[test]
+    fn test_textual_format_parsing() {
+        let quads = parse_quadruplets(r#"
+            %% Hand written program
+            INTEGER X, t1;
+            FLOAT Arr[2];
+            0: (Add, X, -3, t1)        %% trailing comment
+            (IfFalse, t1, _, 3)
+            2: (Assign, 2.5, _, Arr[t1])
+            3: (Write, "done\n", _, _)
+        "#).unwrap();
+        assert_eq!(quads.len(), 4);
+        assert_eq!(quads[0].result, Some(Operand::Temporary(1, Types::Integer)));
+        assert_eq!(quads[1].result, Some(Operand::Label(3)));
+        assert_eq!(quads[2].result, Some(Operand::ArrayElement(
+            "Arr".to_string(),
+            Types::Array(Box::new(Types::Float), 2),
+            Box::new(Operand::Temporary(1, Types::Integer)),
+        )));
+        assert_eq!(quads[3].operand1, Some(Operand::String("done\n".to_string())));
+
+        assert_eq!(parse_quadruplets("0: (Assign, 1, _, Y)"), Err(QuadrupletParseError::UndeclaredName(1, "Y".to_string())));
+        assert!(matches!(parse_quadruplets("INTEGER X;\n1: (Assign, 1, _, X)"), Err(QuadrupletParseError::Syntax(2, _))));
+        assert!(matches!(parse_quadruplets("(Jump, _, _, 0)"), Err(QuadrupletParseError::Syntax(1, _))));
+        assert_eq!(parse_quadruplets("(Goto, _, _, 9)"), Err(QuadrupletParseError::InvalidTarget(0, 9)));
+    }
+}
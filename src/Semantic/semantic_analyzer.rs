@@ -540,7 +540,8 @@ impl SemanticAnalyzer {
             }
         }
 
-        // Need to implement the index into the program later, just need to figure out the problem with nabil
+        // The value read is only known when the program runs (see codegen/interpreter.rs),
+        // the analysis records a zero of the right type so the variable counts as initialized
         match SymbolTable.lock().unwrap().get_mut(Identifier.0.as_str()) {
             None => return Err(format!("Undeclared variable '{}' inside READ instruction at ({}:{}).", Identifier.0, Identifier.1.0, Identifier.1.1)),
             Some(symbol) => {
@@ -1,10 +1,180 @@
 #![cfg(test)]
 
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
 use logos::Logos;
-use crate::{grammar, Lexer};
+use crate::{grammar, Lexer, SymbolTable};
+use crate::Parser::ast::Program;
+use crate::Semantic::ts::Symbol;
 use crate::Semantic::semantic_analyzer::SemanticAnalyzer;
+use crate::Semantic::quadruplets::{Quadruplet, QuadrupletGenerator};
 use super::*;
 
+// The grammar inserts every declaration into the global symbol table, so programs parsed
+// by the unit tests of the later phases are parsed one at a time on a cleared table
+static PARSE_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn parse_source(program: &str) -> Program {
+    let _guard = PARSE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    SymbolTable.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    let lexer = Lexer::lexer::Token::lexer(program);
+    let parser = grammar::ProgramParser::new();
+    parser.parse(program, lexer.enumerate().map(|(i, t)| t.map(|token| (i, token, i+1))))
+        .expect("Parsing should succeed")
+}
+
+/// Parses and analyzes a program, with a copy of the symbol table the analysis filled
+pub fn analyze_source(program: &str) -> (Program, HashMap<String, Symbol>) {
+    let _guard = PARSE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    SymbolTable.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    let lexer = Lexer::lexer::Token::lexer(program);
+    let parser = grammar::ProgramParser::new();
+    let parsed = parser.parse(program, lexer.enumerate().map(|(i, t)| t.map(|token| (i, token, i+1))))
+        .expect("Parsing should succeed");
+    SemanticAnalyzer::new().analyze(&parsed).expect("Semantic analysis should succeed");
+    let symbols = SymbolTable.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    (parsed, symbols)
+}
+
+/// Quadruplets generated for a program once it is analyzed
+pub fn generate_source(program: &str) -> Vec<Quadruplet> {
+    let (parsed, _) = analyze_source(program);
+    let mut generator = QuadrupletGenerator::new();
+    generator.generate_program(&parsed).expect("Generation should succeed");
+    generator.into_quadruplets()
+}
+
+/// Directory of the files a test hands to external tools, removed when the test ends even
+/// if an assertion failed
+pub struct TemporaryDirectory(PathBuf);
+
+impl TemporaryDirectory {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&path).expect("Creating the temporary directory should succeed");
+        TemporaryDirectory(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TemporaryDirectory {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+/// Runs a tool the tests need and checks that it succeeds, a missing tool fails the test
+pub fn run_tool(command: &mut Command) {
+    let name = command.get_program().to_string_lossy().to_string();
+    let status = command.status().unwrap_or_else(|e| panic!("{} is needed by this test: {}", name, e));
+    assert!(status.success(), "{} failed", name);
+}
+
+/// Output, errors and exit code of a program given some input
+pub fn run_program(command: &mut Command, input: &str) -> (String, String, i32) {
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()
+        .unwrap_or_else(|e| panic!("{} could not start: {}", command.get_program().to_string_lossy(), e));
+    child.stdin.take().unwrap().write_all(input.as_bytes()).ok();
+    let output = child.wait_with_output().expect("The program should end");
+    (String::from_utf8_lossy(&output.stdout).to_string(), String::from_utf8_lossy(&output.stderr).to_string(), output.status.code().unwrap_or(-1))
+}
+
+/// A MinING program using every kind of declaration, expression and statement, every backend
+/// compiling the syntax tree must give the same output
+pub const PROGRAM: &str = r#"
+    VAR_GLOBAL {
+        INTEGER N, I, S = 0;
+        FLOAT F, G = 1.5;
+        CHAR C;
+    }
+    DECLARATION {
+        CONST INTEGER Size = 2 + 1;
+        INTEGER A[Size] = [0];
+        INTEGER B[4] = [1, 2];
+        CHAR Word[5] = "ab?";
+    }
+    INSTRUCTION {
+        READ(N);
+        READ(F);
+        READ(C);
+        FOR (I = 0 : 1 : Size) {
+            A[I] = I * N;
+            S = S + A[I];
+        }
+        WRITE("S = ", S, " 100%");
+        WRITE(B[3], Word[3], Word[4]);
+        F = F / G;
+        C = C + 'b';
+        WRITE(F, C);
+        IF (F >= G && !(C == 'a')) {
+            WRITE("yes");
+        } ELSE {
+            WRITE("no");
+        }
+        S = S / (N + 1);
+        WRITE(S);
+    }
+"#;
+
+/// Quadruplets using every kind of operand and operator the backends lower, every backend
+/// running them must give the same output
+pub const QUADRUPLETS: &str = r#"
+    INTEGER N, I, S, t1, t2, t3;
+    FLOAT F, G, t4;
+    CHAR C, t5;
+    INTEGER A[3];
+    0: (Assign, 0, _, S)
+    1: (Assign, 1.5, _, G)
+    2: (Read, _, _, N)
+    3: (Read, _, _, F)
+    4: (Read, _, _, C)
+    5: (Assign, 0, _, I)
+    6: (LessThan, I, 3, t1)
+    7: (IfFalse, t1, _, 13)
+    8: (Multiply, I, N, A[I])
+    9: (Add, S, A[I], S)
+    10: (Add, I, 1, t2)
+    11: (Assign, t2, _, I)
+    12: (Goto, _, _, 6)
+    13: (Write, "S = ", _, _)
+    14: (Write, S, _, _)
+    15: (Write, "\n", _, _)
+    16: (Divide, F, G, t4)
+    17: (Write, t4, _, _)
+    18: (Add, C, '\u{1}', t5)
+    19: (Write, t5, _, _)
+    20: (GreaterThanOrEqual, F, G, t3)
+    21: (Write, t3, _, _)
+    22: (Divide, S, N, S)
+    23: (Write, S, _, _)
+"#;
+
+/// Fails at run time depending on its input: "1" divides by zero and "32767" overflows inside
+/// nested IF and FOR statements, "5" is an index out of bounds and "2 x" an invalid input
+pub const FAILING_PROGRAM: &str = r#"VAR_GLOBAL {
+    INTEGER I, J, N, S = 0;
+    INTEGER A[3] = [0];
+}
+DECLARATION { }
+INSTRUCTION {
+    READ(N);
+    FOR (I = 0 : 1 : 3) {
+        IF (I > 0) {
+            FOR (J = 0 : 1 : 2) {
+                S = S + 10 / (N + I - 2);
+            }
+        }
+    }
+    A[N] = S;
+    READ(S);
+}"#;
+
 #[test]
 fn test_lexical_error() {
     let program = r#"
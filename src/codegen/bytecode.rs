@@ -0,0 +1,709 @@
+//! MinING bytecode: a stack machine compiled from the quadruplets and its `.mbc` file format
+//!
+//! Every variable, array and temporary gets a typed slot, instructions push their operands on
+//! the stack and pop them, jumps go to the index of an instruction. A `.mbc` file holds, with
+//! every number in little endian:
+//!
+//! ```text
+//! "MBC\0" u16 version
+//! u16 count, constants:  u8 tag (0 INTEGER i16, 1 FLOAT f32, 2 CHAR u8, 3 STRING u32 length + bytes)
+//! u16 count, slots:      u8 type (0 INTEGER, 1 FLOAT, 2 CHAR, +0x10 for arrays followed by u16 size),
+//!                        u8 name length + name
+//! u32 count, code:       u8 opcode + u16 operand (constant or slot) or u32 operand (jump target)
+//! u32 count, origins:    u32 first instruction, u32 line, u32 column,
+//!                        u8 count + frames: u8 kind (0 IF, 1 FOR), u32 line, u32 column
+//! ```
+//!
+//! Version 1 files end with the code and have no origins.
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::codegen::error::{BytecodeError, CodegenError, Frame, Origin, Position};
+use crate::Semantic::quadruplets::{collect_symbols, jump_target, Operand, Operator, Quadruplet, Value};
+use crate::Semantic::ts::Types;
+
+const MAGIC: &[u8; 4] = b"MBC\0";
+const VERSION: u16 = 2;
+
+/// Entry of the constant pool
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Value(Value),
+    String(String),
+}
+
+/// Storage of a variable, an array or a temporary
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slot {
+    pub name: String,
+    pub slot_type: Types,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Push(u16),          // Pushes a constant
+    Pop,
+    Load(u16),          // Pushes the value of a slot
+    Store(u16),         // Pops a value into a slot
+    LoadElement(u16),   // Pops an index and pushes the array cell
+    StoreElement(u16),  // Pops a value then an index and stores the value in the array cell
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    GreaterThan,
+    LessThan,
+    GreaterThanOrEqual,
+    LessThanOrEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+    Not,
+    Jump(u32),
+    JumpIfTrue(u32),    // Pops a value and jumps when it is not zero
+    JumpIfFalse(u32),
+    ReadInteger,        // Pushes the value read
+    ReadFloat,
+    ReadChar,
+    Write,              // Pops a value and writes it
+    WriteString(u16),   // Writes a string of the constant pool
+    Halt,
+}
+
+impl Instruction {
+    fn opcode(&self) -> u8 {
+        match self {
+            Instruction::Push(_) => 0x01,
+            Instruction::Pop => 0x02,
+            Instruction::Load(_) => 0x03,
+            Instruction::Store(_) => 0x04,
+            Instruction::LoadElement(_) => 0x05,
+            Instruction::StoreElement(_) => 0x06,
+            Instruction::Add => 0x10,
+            Instruction::Subtract => 0x11,
+            Instruction::Multiply => 0x12,
+            Instruction::Divide => 0x13,
+            Instruction::GreaterThan => 0x18,
+            Instruction::LessThan => 0x19,
+            Instruction::GreaterThanOrEqual => 0x1A,
+            Instruction::LessThanOrEqual => 0x1B,
+            Instruction::Equal => 0x1C,
+            Instruction::NotEqual => 0x1D,
+            Instruction::And => 0x20,
+            Instruction::Or => 0x21,
+            Instruction::Not => 0x22,
+            Instruction::Jump(_) => 0x28,
+            Instruction::JumpIfTrue(_) => 0x29,
+            Instruction::JumpIfFalse(_) => 0x2A,
+            Instruction::ReadInteger => 0x30,
+            Instruction::ReadFloat => 0x31,
+            Instruction::ReadChar => 0x32,
+            Instruction::Write => 0x38,
+            Instruction::WriteString(_) => 0x39,
+            Instruction::Halt => 0x3F,
+        }
+    }
+
+    /// Operator applied to the two values on top of the stack
+    pub fn binary_operator(&self) -> Option<Operator> {
+        let operator = match self {
+            Instruction::Add => Operator::Add,
+            Instruction::Subtract => Operator::Subtract,
+            Instruction::Multiply => Operator::Multiply,
+            Instruction::Divide => Operator::Divide,
+            Instruction::GreaterThan => Operator::GreaterThan,
+            Instruction::LessThan => Operator::LessThan,
+            Instruction::GreaterThanOrEqual => Operator::GreaterThanOrEqual,
+            Instruction::LessThanOrEqual => Operator::LessThanOrEqual,
+            Instruction::Equal => Operator::Equal,
+            Instruction::NotEqual => Operator::NotEqual,
+            Instruction::And => Operator::LogicalAnd,
+            Instruction::Or => Operator::LogicalOr,
+            _ => return None,
+        };
+        Some(operator)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = format!("{:?}", self);
+        let name = name.split('(').next().unwrap_or_default();
+        match self {
+            Instruction::Push(operand) | Instruction::Load(operand) | Instruction::Store(operand)
+            | Instruction::LoadElement(operand) | Instruction::StoreElement(operand)
+            | Instruction::WriteString(operand) => write!(f, "{} {}", name, operand),
+            Instruction::Jump(target) | Instruction::JumpIfTrue(target) | Instruction::JumpIfFalse(target) => {
+                write!(f, "{} {}", name, target)
+            },
+            _ => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A compiled program: constant pool, slots and instructions
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    pub constants: Vec<Constant>,
+    pub slots: Vec<Slot>,
+    pub code: Vec<Instruction>,
+    pub origins: Vec<(u32, Origin)>,    // By increasing instruction, each one covers the instructions up to the next
+}
+
+/// Compiles the quadruplets, the program stops on the Halt that follows the last one
+pub fn compile_bytecode(quadruplets: &[Quadruplet]) -> Result<Module, CodegenError> {
+    let mut compiler = Compiler {
+        module: Module::default(),
+        slots: HashMap::new(),
+        current: 0,
+        quadruplet: String::new(),
+    };
+    for (name, slot_type) in collect_symbols(quadruplets) {
+        compiler.slots.insert(name.clone(), compiler.module.slots.len() as u16);
+        compiler.module.slots.push(Slot { name, slot_type });
+    }
+
+    // Jumps are first emitted with the index of their quadruplet, then mapped to instructions
+    let mut starts = Vec::with_capacity(quadruplets.len() + 1);
+    for (index, quadruplet) in quadruplets.iter().enumerate() {
+        compiler.current = index;
+        compiler.quadruplet = quadruplet.to_string();
+        starts.push(compiler.module.code.len() as u32);
+        if let Some(origin) = &quadruplet.origin {
+            if compiler.module.origins.last().map(|(_, last)| last) != Some(origin) {
+                compiler.module.origins.push((compiler.module.code.len() as u32, origin.clone()));
+            }
+        }
+        compiler.quadruplet(quadruplet)?;
+    }
+    starts.push(compiler.module.code.len() as u32);
+    compiler.module.code.push(Instruction::Halt);
+
+    for instruction in &mut compiler.module.code {
+        if let Instruction::Jump(target) | Instruction::JumpIfTrue(target) | Instruction::JumpIfFalse(target) = instruction {
+            *target = starts[(*target as usize).min(quadruplets.len())];
+        }
+    }
+    Ok(compiler.module)
+}
+
+struct Compiler {
+    module: Module,
+    slots: HashMap<String, u16>,
+    current: usize,
+    quadruplet: String,
+}
+
+impl Compiler {
+    fn unsupported(&self, reason: &str) -> CodegenError {
+        CodegenError::Unsupported(self.current, self.quadruplet.clone(), reason.to_string())
+    }
+
+    fn constant(&mut self, constant: Constant) -> u16 {
+        // FLOAT constants are compared by their bits so that 0.0 and -0.0 stay apart
+        let same = |known: &Constant| match (known, &constant) {
+            (Constant::Value(Value::Float(a)), Constant::Value(Value::Float(b))) => a.to_bits() == b.to_bits(),
+            (known, constant) => known == constant,
+        };
+        match self.module.constants.iter().position(same) {
+            Some(index) => index as u16,
+            None => {
+                self.module.constants.push(constant);
+                self.module.constants.len() as u16 - 1
+            },
+        }
+    }
+
+    fn slot(&self, operand: &Operand) -> Result<u16, CodegenError> {
+        let name = match operand {
+            Operand::Variable(name, _) | Operand::ArrayElement(name, _, _) => name.clone(),
+            Operand::Temporary(number, _) => format!("t{}", number),
+            _ => return Err(self.unsupported("expected a variable, a temporary or an array element")),
+        };
+        Ok(self.slots[&name])
+    }
+
+    // Pushes the value of an operand and returns its type
+    fn push(&mut self, operand: &Operand) -> Result<Types, CodegenError> {
+        let value_type = match operand.value_type() {
+            Some(t @ (Types::Integer | Types::Float | Types::Char)) => t,
+            _ => return Err(self.unsupported("expected an INTEGER, FLOAT or CHAR value")),
+        };
+        let instruction = match operand {
+            Operand::Constant(value) => Instruction::Push(self.constant(Constant::Value(*value))),
+            Operand::ArrayElement(_, _, index) => {
+                self.index(index)?;
+                Instruction::LoadElement(self.slot(operand)?)
+            },
+            _ => Instruction::Load(self.slot(operand)?),
+        };
+        self.module.code.push(instruction);
+        Ok(value_type)
+    }
+
+    fn index(&mut self, index: &Operand) -> Result<(), CodegenError> {
+        if self.push(index)? != Types::Integer {
+            return Err(self.unsupported("array indices are INTEGER values"));
+        }
+        Ok(())
+    }
+
+    // Stores the value computed by `value` into the result. The index of an array cell is pushed
+    // first but only checked by the store, once the value has been computed
+    fn store(&mut self, result: Option<&Operand>, value: impl FnOnce(&mut Self) -> Result<Types, CodegenError>) -> Result<(), CodegenError> {
+        let result = result.ok_or_else(|| self.unsupported("missing result"))?;
+        if let Operand::ArrayElement(_, _, index) = result {
+            self.index(index)?;
+        }
+        if value(self)? != result.value_type().ok_or_else(|| self.unsupported("missing result"))? {
+            return Err(self.unsupported("the result does not have the type of the value"));
+        }
+        let instruction = match result {
+            Operand::ArrayElement(_, _, _) => Instruction::StoreElement(self.slot(result)?),
+            _ => Instruction::Store(self.slot(result)?),
+        };
+        self.module.code.push(instruction);
+        Ok(())
+    }
+
+    fn quadruplet(&mut self, quadruplet: &Quadruplet) -> Result<(), CodegenError> {
+        let result = quadruplet.result.as_ref();
+        let operand1 = quadruplet.operand1.as_ref();
+        let operand2 = quadruplet.operand2.as_ref();
+        match quadruplet.operator {
+            Operator::Assign => self.store(result, |compiler| {
+                compiler.push(operand1.ok_or_else(|| compiler.unsupported("missing value"))?)
+            }),
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide
+            | Operator::GreaterThan | Operator::LessThan | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual | Operator::Equal | Operator::NotEqual
+            | Operator::LogicalAnd | Operator::LogicalOr => self.store(result, |compiler| {
+                let (Some(a), Some(b)) = (operand1, operand2) else {
+                    return Err(compiler.unsupported("expected two operands"));
+                };
+                let value_type = compiler.push(a)?;
+                if compiler.push(b)? != value_type && !matches!(quadruplet.operator, Operator::LogicalAnd | Operator::LogicalOr) {
+                    return Err(compiler.unsupported("operands of different types"));
+                }
+                if value_type == Types::Char && matches!(quadruplet.operator, Operator::Multiply | Operator::Divide) {
+                    return Err(compiler.unsupported("CHAR values only support addition and subtraction"));
+                }
+                let (instruction, result_type) = match quadruplet.operator {
+                    Operator::Add => (Instruction::Add, value_type),
+                    Operator::Subtract => (Instruction::Subtract, value_type),
+                    Operator::Multiply => (Instruction::Multiply, value_type),
+                    Operator::Divide => (Instruction::Divide, value_type),
+                    Operator::GreaterThan => (Instruction::GreaterThan, Types::Integer),
+                    Operator::LessThan => (Instruction::LessThan, Types::Integer),
+                    Operator::GreaterThanOrEqual => (Instruction::GreaterThanOrEqual, Types::Integer),
+                    Operator::LessThanOrEqual => (Instruction::LessThanOrEqual, Types::Integer),
+                    Operator::Equal => (Instruction::Equal, Types::Integer),
+                    Operator::NotEqual => (Instruction::NotEqual, Types::Integer),
+                    Operator::LogicalAnd => (Instruction::And, Types::Integer),
+                    _ => (Instruction::Or, Types::Integer),
+                };
+                compiler.module.code.push(instruction);
+                Ok(result_type)
+            }),
+            Operator::LogicalNot => self.store(result, |compiler| {
+                compiler.push(operand1.ok_or_else(|| compiler.unsupported("missing value"))?)?;
+                compiler.module.code.push(Instruction::Not);
+                Ok(Types::Integer)
+            }),
+            Operator::Read => self.store(result, |compiler| {
+                let value_type = result.and_then(Operand::value_type).ok_or_else(|| compiler.unsupported("missing result"))?;
+                let instruction = match value_type {
+                    Types::Integer => Instruction::ReadInteger,
+                    Types::Float => Instruction::ReadFloat,
+                    Types::Char => Instruction::ReadChar,
+                    Types::Array(_, _) => return Err(compiler.unsupported("READ of a whole array")),
+                };
+                compiler.module.code.push(instruction);
+                Ok(value_type)
+            }),
+            Operator::Write => {
+                match operand1 {
+                    Some(Operand::String(text)) => {
+                        let constant = self.constant(Constant::String(text.clone()));
+                        self.module.code.push(Instruction::WriteString(constant));
+                    },
+                    Some(value) => {
+                        self.push(value)?;
+                        self.module.code.push(Instruction::Write);
+                    },
+                    None => return Err(self.unsupported("missing value")),
+                }
+                Ok(())
+            },
+            Operator::Goto | Operator::IfTrue | Operator::IfFalse => {
+                let target = jump_target(quadruplet).ok_or_else(|| self.unsupported("missing target"))? as u32;
+                let instruction = match (&quadruplet.operator, operand1) {
+                    (Operator::Goto, _) => Instruction::Jump(target),
+                    (_, None) => return Err(self.unsupported("missing condition")),
+                    (operator, Some(condition)) => {
+                        self.push(condition)?;
+                        if *operator == Operator::IfTrue { Instruction::JumpIfTrue(target) } else { Instruction::JumpIfFalse(target) }
+                    },
+                };
+                self.module.code.push(instruction);
+                Ok(())
+            },
+            Operator::For => Err(self.unsupported("For must be lowered to jumps")),
+        }
+    }
+}
+
+fn type_tag(value_type: &Types) -> u8 {
+    match value_type {
+        Types::Integer => 0,
+        Types::Float => 1,
+        Types::Char => 2,
+        Types::Array(element, _) => 0x10 | type_tag(element),
+    }
+}
+
+impl Module {
+    /// Source of the instruction at `index`, when the quadruplets it was compiled from had one
+    pub fn origin(&self, index: usize) -> Option<&Origin> {
+        let covering = self.origins.partition_point(|(first, _)| *first as usize <= index);
+        covering.checked_sub(1).map(|entry| &self.origins[entry].1)
+    }
+
+    /// Contents of the `.mbc` file
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+
+        bytes.extend((self.constants.len() as u16).to_le_bytes());
+        for constant in &self.constants {
+            match constant {
+                Constant::Value(Value::Integer(i)) => {
+                    bytes.push(0);
+                    bytes.extend(i.to_le_bytes());
+                },
+                Constant::Value(Value::Float(f)) => {
+                    bytes.push(1);
+                    bytes.extend(f.to_bits().to_le_bytes());
+                },
+                Constant::Value(Value::Char(c)) => bytes.extend([2, *c as u32 as u8]),
+                Constant::String(text) => {
+                    bytes.push(3);
+                    bytes.extend((text.len() as u32).to_le_bytes());
+                    bytes.extend(text.as_bytes());
+                },
+            }
+        }
+
+        bytes.extend((self.slots.len() as u16).to_le_bytes());
+        for slot in &self.slots {
+            bytes.push(type_tag(&slot.slot_type));
+            if let Types::Array(_, size) = slot.slot_type {
+                bytes.extend((size.max(0) as u16).to_le_bytes());
+            }
+            let name = &slot.name.as_bytes()[..slot.name.len().min(255)];
+            bytes.push(name.len() as u8);
+            bytes.extend(name);
+        }
+
+        bytes.extend((self.code.len() as u32).to_le_bytes());
+        for instruction in &self.code {
+            bytes.push(instruction.opcode());
+            match instruction {
+                Instruction::Push(operand) | Instruction::Load(operand) | Instruction::Store(operand)
+                | Instruction::LoadElement(operand) | Instruction::StoreElement(operand)
+                | Instruction::WriteString(operand) => bytes.extend(operand.to_le_bytes()),
+                Instruction::Jump(target) | Instruction::JumpIfTrue(target) | Instruction::JumpIfFalse(target) => {
+                    bytes.extend(target.to_le_bytes())
+                },
+                _ => {},
+            }
+        }
+
+        bytes.extend((self.origins.len() as u32).to_le_bytes());
+        for (first, origin) in &self.origins {
+            bytes.extend(first.to_le_bytes());
+            bytes.extend(encode_position(&origin.position));
+            let frames = &origin.stack[..origin.stack.len().min(255)];
+            bytes.push(frames.len() as u8);
+            for frame in frames {
+                let (kind, position) = match frame {
+                    Frame::If(position) => (0, position),
+                    Frame::For { position, .. } | Frame::Loop(position) => (1, position),
+                };
+                bytes.push(kind);
+                bytes.extend(encode_position(position));
+            }
+        }
+        bytes
+    }
+
+    /// Reads a `.mbc` file, checking that every operand refers to an entry of the right kind
+    pub fn decode(bytes: &[u8]) -> Result<Module, BytecodeError> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(BytecodeError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != VERSION && version != 1 {
+            return Err(BytecodeError::UnsupportedVersion(version));
+        }
+
+        let mut module = Module::default();
+        for _ in 0..reader.u16()? {
+            let constant = match reader.u8()? {
+                0 => Constant::Value(Value::Integer(reader.u16()? as i16)),
+                1 => Constant::Value(Value::Float(f32::from_bits(reader.u32()?))),
+                2 => Constant::Value(Value::Char(reader.u8()? as char)),
+                3 => {
+                    let length = reader.u32()? as usize;
+                    Constant::String(String::from_utf8_lossy(reader.take(length)?).to_string())
+                },
+                tag => return Err(reader.invalid(format!("unknown constant tag {}", tag))),
+            };
+            module.constants.push(constant);
+        }
+
+        for _ in 0..reader.u16()? {
+            let tag = reader.u8()?;
+            let element = match tag & 0x0F {
+                0 => Types::Integer,
+                1 => Types::Float,
+                2 => Types::Char,
+                _ => return Err(reader.invalid(format!("unknown slot type {}", tag))),
+            };
+            let slot_type = match tag & 0xF0 {
+                0 => element,
+                0x10 => Types::Array(Box::new(element), i16::try_from(reader.u16()?).map_err(|_| reader.invalid("array too large".to_string()))?),
+                _ => return Err(reader.invalid(format!("unknown slot type {}", tag))),
+            };
+            let length = reader.u8()? as usize;
+            let name = String::from_utf8_lossy(reader.take(length)?).to_string();
+            module.slots.push(Slot { name, slot_type });
+        }
+
+        let count = reader.u32()?;
+        for _ in 0..count {
+            let offset = reader.position;
+            let instruction = match reader.u8()? {
+                0x01 => Instruction::Push(reader.u16()?),
+                0x02 => Instruction::Pop,
+                0x03 => Instruction::Load(reader.u16()?),
+                0x04 => Instruction::Store(reader.u16()?),
+                0x05 => Instruction::LoadElement(reader.u16()?),
+                0x06 => Instruction::StoreElement(reader.u16()?),
+                0x10 => Instruction::Add,
+                0x11 => Instruction::Subtract,
+                0x12 => Instruction::Multiply,
+                0x13 => Instruction::Divide,
+                0x18 => Instruction::GreaterThan,
+                0x19 => Instruction::LessThan,
+                0x1A => Instruction::GreaterThanOrEqual,
+                0x1B => Instruction::LessThanOrEqual,
+                0x1C => Instruction::Equal,
+                0x1D => Instruction::NotEqual,
+                0x20 => Instruction::And,
+                0x21 => Instruction::Or,
+                0x22 => Instruction::Not,
+                0x28 => Instruction::Jump(reader.u32()?),
+                0x29 => Instruction::JumpIfTrue(reader.u32()?),
+                0x2A => Instruction::JumpIfFalse(reader.u32()?),
+                0x30 => Instruction::ReadInteger,
+                0x31 => Instruction::ReadFloat,
+                0x32 => Instruction::ReadChar,
+                0x38 => Instruction::Write,
+                0x39 => Instruction::WriteString(reader.u16()?),
+                0x3F => Instruction::Halt,
+                opcode => return Err(BytecodeError::Invalid(offset, format!("unknown opcode 0x{:02X}", opcode))),
+            };
+            module.check(&instruction, count).map_err(|reason| BytecodeError::Invalid(offset, reason))?;
+            module.code.push(instruction);
+        }
+
+        if version > 1 {
+            for _ in 0..reader.u32()? {
+                let first = reader.u32()?;
+                if first >= count || module.origins.last().is_some_and(|(last, _)| *last >= first) {
+                    return Err(reader.invalid(format!("origin of instruction {} out of order", first)));
+                }
+                let position = reader.position()?;
+                let mut stack = Vec::new();
+                for _ in 0..reader.u8()? {
+                    let frame = match reader.u8()? {
+                        0 => Frame::If(reader.position()?),
+                        1 => Frame::Loop(reader.position()?),
+                        kind => return Err(reader.invalid(format!("unknown frame kind {}", kind))),
+                    };
+                    stack.push(frame);
+                }
+                module.origins.push((first, Origin { position, stack }));
+            }
+        }
+        if reader.position != bytes.len() {
+            return Err(reader.invalid("trailing bytes".to_string()));
+        }
+        Ok(module)
+    }
+
+    fn check(&self, instruction: &Instruction, count: u32) -> Result<(), String> {
+        let slot = |index: &u16| self.slots.get(*index as usize).map(|slot| &slot.slot_type).ok_or(format!("unknown slot {}", index));
+        let constant = |index: &u16| self.constants.get(*index as usize).ok_or(format!("unknown constant {}", index));
+        match instruction {
+            Instruction::Push(index) if !matches!(constant(index)?, Constant::Value(_)) => Err(format!("constant {} is not a value", index)),
+            Instruction::WriteString(index) if !matches!(constant(index)?, Constant::String(_)) => Err(format!("constant {} is not a string", index)),
+            Instruction::Load(index) | Instruction::Store(index) if matches!(slot(index)?, Types::Array(_, _)) => {
+                Err(format!("slot {} is an array", index))
+            },
+            Instruction::LoadElement(index) | Instruction::StoreElement(index) if !matches!(slot(index)?, Types::Array(_, _)) => {
+                Err(format!("slot {} is not an array", index))
+            },
+            Instruction::Jump(target) | Instruction::JumpIfTrue(target) | Instruction::JumpIfFalse(target) if *target >= count => {
+                Err(format!("jump to {} outside of the code", target))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Module {
+    /// Listing of the constants, slots and instructions
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, constant) in self.constants.iter().enumerate() {
+            match constant {
+                Constant::Value(value) => writeln!(f, "constant {}: {}", index, value)?,
+                Constant::String(text) => writeln!(f, "constant {}: {:?}", index, text)?,
+            }
+        }
+        for (index, slot) in self.slots.iter().enumerate() {
+            writeln!(f, "slot {}: {} {:?}", index, slot.name, slot.slot_type)?;
+        }
+        for (index, instruction) in self.code.iter().enumerate() {
+            writeln!(f, "{}: {}", index, instruction)?;
+        }
+        Ok(())
+    }
+}
+
+fn encode_position(position: &Position) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&(position.line as u32).to_le_bytes());
+    bytes[4..].copy_from_slice(&(position.column as u32).to_le_bytes());
+    bytes
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn invalid(&self, reason: String) -> BytecodeError {
+        BytecodeError::Invalid(self.position, reason)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], BytecodeError> {
+        let bytes = self.bytes.get(self.position..self.position + length).ok_or(BytecodeError::Truncated)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, BytecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, BytecodeError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, BytecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn position(&mut self) -> Result<Position, BytecodeError> {
+        Ok(Position { line: self.u32()? as usize, column: self.u32()? as usize })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+
+    #[test]
+    fn test_compiled_bytecode() {
+        let quads = parse_quadruplets(r#"
+            INTEGER I, t1;
+            INTEGER A[3];
+            0: (Assign, 0, _, I)
+            1: (LessThan, I, 3, t1)
+            2: (IfFalse, t1, _, 6)
+            3: (Read, _, _, A[I])
+            4: (Add, I, 1, I)
+            5: (Goto, _, _, 1)
+            6: (Write, "done", _, _)
+        "#).unwrap();
+        let module = compile_bytecode(&quads).unwrap();
+        let code: Vec<String> = module.code.iter().map(Instruction::to_string).collect();
+        assert_eq!(code, [
+            "Push 0", "Store 0",
+            "Load 0", "Push 1", "LessThan", "Store 1",
+            "Load 1", "JumpIfFalse 16",
+            // The index is pushed first and checked once the value is read
+            "Load 0", "ReadInteger", "StoreElement 2",
+            "Load 0", "Push 2", "Add", "Store 0",
+            "Jump 2",
+            "WriteString 3", "Halt",
+        ]);
+        assert_eq!(module.constants[3], Constant::String("done".to_string()));
+        assert_eq!(module.slots[2], Slot { name: "A".to_string(), slot_type: Types::Array(Box::new(Types::Integer), 3) });
+    }
+
+    #[test]
+    fn test_file_format() {
+        let module = Module {
+            constants: vec![
+                Constant::Value(Value::Integer(-2)),
+                Constant::Value(Value::Float(-0.0)),
+                Constant::Value(Value::Char('\u{e9}')),
+                Constant::String("Result: ".to_string()),
+            ],
+            slots: vec![
+                Slot { name: "X".to_string(), slot_type: Types::Float },
+                Slot { name: "Word".to_string(), slot_type: Types::Array(Box::new(Types::Char), 4) },
+            ],
+            code: vec![Instruction::Push(1), Instruction::Store(0), Instruction::WriteString(3), Instruction::Jump(4), Instruction::Halt],
+            origins: vec![
+                (0, Origin { position: Position { line: 3, column: 7 }, stack: Vec::new() }),
+                (2, Origin {
+                    position: Position { line: 5, column: 9 },
+                    stack: vec![Frame::Loop(Position { line: 4, column: 5 }), Frame::If(Position { line: 1, column: 1 })],
+                }),
+            ],
+        };
+        let bytes = module.encode();
+        assert_eq!(&bytes[..6], b"MBC\0\x02\x00");
+        let decoded = Module::decode(&bytes).unwrap();
+        assert_eq!(decoded, module);
+        assert!(matches!(decoded.constants[1], Constant::Value(Value::Float(f)) if f.is_sign_negative()));
+
+        assert_eq!(Module::decode(b"MBA\0\x01\x00"), Err(BytecodeError::BadMagic));
+        assert_eq!(Module::decode(b"MBC\0\x03\x00"), Err(BytecodeError::UnsupportedVersion(3)));
+        assert_eq!(Module::decode(&bytes[..bytes.len() - 1]), Err(BytecodeError::Truncated));
+
+        // A jump outside of the code and a Load of an array are rejected
+        let mut invalid = module.clone();
+        invalid.code[3] = Instruction::Jump(9);
+        assert!(matches!(Module::decode(&invalid.encode()), Err(BytecodeError::Invalid(_, reason)) if reason.contains("jump to 9")));
+        invalid.code[3] = Instruction::Load(1);
+        assert!(matches!(Module::decode(&invalid.encode()), Err(BytecodeError::Invalid(_, reason)) if reason.contains("is an array")));
+        let mut invalid = module.clone();
+        invalid.origins[1].0 = 0;
+        assert!(matches!(Module::decode(&invalid.encode()), Err(BytecodeError::Invalid(_, reason)) if reason.contains("out of order")));
+
+        // Version 1 files end with the code
+        let mut old = Module { origins: Vec::new(), ..module.clone() }.encode();
+        old[4] = 1;
+        old.truncate(old.len() - 4);
+        assert_eq!(Module::decode(&old), Ok(Module { origins: Vec::new(), ..module }));
+    }
+}
@@ -0,0 +1,732 @@
+//! C99 backend, translating the abstract syntax tree to a single C file
+//!
+//! Variables and arrays become `static` objects of file scope named `m_<name>` like in the
+//! assembly backends, INTEGER is `int16_t`, FLOAT `float` and CHAR `char`. Constants with a value
+//! known at compile time are `static const`, the others are `const` locals of `main` initialized
+//! at the point of their declaration. Expressions keep the evaluation order of the quadruplets:
+//! when both operands of an operator can fail, the left one is stored in a temporary first
+//! with the comma operator so that the first run-time error is the one reported. Helpers that
+//! may fail are given a `mining_at<n>` string with the position and the IF and FOR statements
+//! printed after the message, as the quadruplet origins are by the other backends
+
+use std::collections::{HashMap, HashSet};
+use crate::codegen::error::{CodegenError, Frame, Origin};
+use crate::codegen::symbol;
+use crate::Parser::ast::*;
+use crate::Semantic::quadruplets::{constant_value, to_types, to_value, Value};
+use crate::Semantic::ts::Types;
+
+// Headers of the generated programs
+const INCLUDES: &str = r#"#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+"#;
+
+// Helpers following the run-time conventions shared with the other backends: name, helpers
+// it calls and definition. A program only gets the helpers it calls
+const HELPERS: [(&str, &[&str], &str); 17] = [
+    ("mining_fail", &[], r#"
+static void mining_fail(const char *message, const char *where) {
+    fflush(stdout);
+    fprintf(stderr, "Runtime error: %s%s\n", message, where);
+    exit(1);
+}
+"#),
+    ("mining_check", &["mining_fail"], r#"
+static int16_t mining_check(int32_t value, const char *where) {
+    if (value < INT16_MIN || value > INT16_MAX) mining_fail("INTEGER overflow", where);
+    return (int16_t)value;
+}
+"#),
+    ("mining_add", &["mining_check"], r#"
+static int16_t mining_add(int16_t a, int16_t b, const char *where) { return mining_check((int32_t)a + b, where); }
+"#),
+    ("mining_sub", &["mining_check"], r#"
+static int16_t mining_sub(int16_t a, int16_t b, const char *where) { return mining_check((int32_t)a - b, where); }
+"#),
+    ("mining_mul", &["mining_check"], r#"
+static int16_t mining_mul(int16_t a, int16_t b, const char *where) { return mining_check((int32_t)a * b, where); }
+"#),
+    ("mining_div", &["mining_fail", "mining_check"], r#"
+static int16_t mining_div(int16_t a, int16_t b, const char *where) {
+    if (b == 0) mining_fail("Division by zero", where);
+    return mining_check((int32_t)a / b, where);
+}
+"#),
+    ("mining_char_add", &[], r#"
+/* CHAR arithmetic wraps around 0x7F */
+static char mining_char_add(char a, char b) { return (char)(((unsigned char)a + (unsigned char)b) % 127); }
+"#),
+    ("mining_char_sub", &[], r#"
+static char mining_char_sub(char a, char b) {
+    int code = ((unsigned char)a - (unsigned char)b) % 127;
+    return (char)(code < 0 ? code + 127 : code);
+}
+"#),
+    ("mining_index", &["mining_fail"], r#"
+static int16_t mining_index(int16_t index, int16_t size, const char *where) {
+    if (index < 0 || index >= size) mining_fail("Array index out of bounds", where);
+    return index;
+}
+"#),
+    ("mining_write_int", &[], r#"
+static void mining_write_int(int16_t value) { printf("%d", value); }
+"#),
+    ("mining_write_char", &[], r#"
+static void mining_write_char(char value) { printf("%c", value); }
+"#),
+    ("mining_write_float", &[], r#"
+/* Six decimals without the trailing zeros, values of 1e9 and above carry an exponent */
+static void mining_write_float(float value) {
+    const char *sign = signbit(value) ? "-" : "";
+    double magnitude = fabs((double)value), half;
+    unsigned long long scaled, fraction;
+    int exponent = 0, digits = 6;
+    if (isnan(value)) {
+        printf("nan");
+        return;
+    }
+    if (isinf(value)) {
+        printf("%sinf", sign);
+        return;
+    }
+    while (magnitude >= 1e9) {
+        magnitude /= 10.0;
+        exponent++;
+    }
+    /* Round half to even */
+    scaled = (unsigned long long)(magnitude * 1e6);
+    half = magnitude * 1e6 - (double)scaled;
+    if (half > 0.5 || (half == 0.5 && scaled % 2 == 1)) scaled++;
+    fraction = scaled % 1000000;
+    while (digits > 1 && fraction % 10 == 0) {
+        fraction /= 10;
+        digits--;
+    }
+    printf("%s%llu.%0*llu", sign, scaled / 1000000, digits, fraction);
+    if (exponent > 0) printf("e%d", exponent);
+}
+"#),
+    ("mining_token_start", &["mining_fail"], r#"
+/* First character of the next token, the tokens are separated by white space */
+static int mining_token_start(const char *where) {
+    char c;
+    if (scanf(" %c", &c) != 1) mining_fail("Unexpected end of input", where);
+    return (unsigned char)c;
+}
+"#),
+    ("mining_token_end", &[], r#"
+static int mining_token_end(int c) {
+    return c == EOF || c == ' ' || (c >= '\t' && c <= '\r');
+}
+"#),
+    ("mining_read_int", &["mining_token_start", "mining_token_end", "mining_fail"], r#"
+static void mining_read_int(int16_t *target, const char *where) {
+    int c = mining_token_start(where), negative = c == '-', digits = 0;
+    int32_t value = 0;
+    if (c == '+' || c == '-') c = getchar();
+    for (; c >= '0' && c <= '9'; c = getchar(), digits++) {
+        value = value * 10 + (c - '0');
+        if (value > 32768) mining_fail("Invalid INTEGER input", where);
+    }
+    if (!mining_token_end(c) || digits == 0 || (!negative && value > INT16_MAX)) mining_fail("Invalid INTEGER input", where);
+    *target = (int16_t)(negative ? -value : value);
+}
+"#),
+    ("mining_read_float", &["mining_token_start", "mining_token_end", "mining_fail"], r#"
+static void mining_read_float(float *target, const char *where) {
+    static const double powers[19] = {
+        1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18,
+    };
+    int c = mining_token_start(where), negative = c == '-', digits = 0, decimals = 0, point = 0;
+    unsigned long long mantissa = 0;
+    double value;
+    if (c == '+' || c == '-') c = getchar();
+    for (;; c = getchar()) {
+        if (c == '.' && !point) {
+            point = 1;
+        } else if (c >= '0' && c <= '9' && digits < 18) {
+            mantissa = mantissa * 10 + (unsigned)(c - '0');
+            digits++;
+            decimals += point;
+        } else {
+            break;
+        }
+    }
+    if (!mining_token_end(c) || digits == 0) mining_fail("Invalid FLOAT input", where);
+    value = (double)mantissa / powers[decimals];
+    *target = (float)(negative ? -value : value);
+}
+"#),
+    ("mining_read_char", &["mining_token_start"], r#"
+static void mining_read_char(char *target, const char *where) { *target = (char)mining_token_start(where); }
+"#),
+];
+
+/// Translates a program that passed the semantic analysis to a complete C99 program,
+/// built with any C compiler (`cc -std=c99 prog.c -o prog -lm`)
+pub fn generate_c(program: &Program) -> Result<String, CodegenError> {
+    let mut generator = CGenerator::default();
+    for declarations in [&program.global, &program.decls].into_iter().flatten() {
+        generator.declarations(declarations)?;
+    }
+    if let Some(instructions) = &program.inst {
+        generator.instructions(instructions)?;
+    }
+    Ok(generator.finish())
+}
+
+// Expression translated to C with what is needed to keep the order of evaluation
+struct CExpr {
+    text: String,
+    value_type: Types,
+    can_fail: bool, // Whether evaluating it may stop the program with a run-time error
+}
+
+#[derive(Default)]
+struct CGenerator {
+    symbols: HashMap<String, Types>,
+    constants: HashMap<String, Value>, // Constant values known at generation time, used for array sizes
+    globals: Vec<String>,
+    temporaries: Vec<Types>,           // Temporary n + 1 holds a value of the n-th type
+    body: Vec<String>,
+    depth: usize,
+    helpers: HashSet<&'static str>,    // Helpers called by the program
+    frames: Vec<Frame>,                // IF and FOR statements around the code being translated
+    origins: Vec<String>,              // Where the run-time error of each mining_at<n> string happens
+}
+
+fn c_type(value_type: &Types) -> &'static str {
+    match value_type {
+        Types::Integer => "int16_t",
+        Types::Float => "float",
+        Types::Char => "char",
+        Types::Array(element, _) => c_type(element),
+    }
+}
+
+fn c_value(value: &Value) -> String {
+    match value {
+        Value::Integer(i) if *i < 0 => format!("({})", i),
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) if f.is_sign_negative() => format!("({:?}f)", f),
+        Value::Float(f) => format!("{:?}f", f),
+        Value::Char(c) => match *c as u32 as u8 {
+            b'\'' | b'\\' => format!("'\\{}'", c),
+            code @ 0x20..=0x7E => format!("'{}'", code as char),
+            code => format!("(char){}", code),
+        },
+    }
+}
+
+// String literal, bytes outside of printable ASCII become octal escapes
+// and question marks are escaped so that they cannot start a trigraph
+fn c_string(text: &str) -> String {
+    let mut escaped = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => escaped.push_str(&format!("\\{}", byte as char)),
+            b'%' => escaped.push_str("%%"),
+            0x20..=0x7E => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
+
+impl CGenerator {
+    fn line(&mut self, text: String) {
+        self.body.push(format!("{}{}", "    ".repeat(self.depth + 1), text));
+    }
+
+    fn temporary(&mut self, value_type: Types) -> String {
+        self.temporaries.push(value_type);
+        format!("t{}", self.temporaries.len())
+    }
+
+    // Name of a helper, which the program then includes
+    fn helper(&mut self, name: &'static str) -> &'static str {
+        self.helpers.insert(name);
+        name
+    }
+
+    // Name of the string given to a failing helper, the position and the IF and FOR statements
+    // printed after the message as by the other backends
+    fn origin(&mut self, position: (usize, usize)) -> String {
+        let origin = Origin { position: position.into(), stack: self.frames.iter().rev().cloned().collect() };
+        let location = origin.location();
+        let index = match self.origins.iter().position(|known| *known == location) {
+            Some(index) => index,
+            None => {
+                self.origins.push(location);
+                self.origins.len() - 1
+            },
+        };
+        format!("mining_at{}", index + 1)
+    }
+
+    fn finish(self) -> String {
+        let mut code = String::from(INCLUDES);
+        // Helpers are listed after the ones they call, so these are found in one pass from the end
+        let mut included = self.helpers.clone();
+        for (name, calls, _) in HELPERS.iter().rev() {
+            if included.contains(name) {
+                included.extend(calls.iter());
+            }
+        }
+        for (_, _, definition) in HELPERS.iter().filter(|(name, _, _)| included.contains(name)) {
+            code.push_str(definition);
+        }
+        code.push('\n');
+        for (index, location) in self.origins.iter().enumerate() {
+            code.push_str(&format!("static const char mining_at{}[] = \"{}\";\n", index + 1, location.replace('\n', "\\n")));
+        }
+        for global in &self.globals {
+            code.push_str(global);
+            code.push('\n');
+        }
+        code.push_str("\nint main(void) {\n");
+        for scalar in [Types::Integer, Types::Float, Types::Char] {
+            let names: Vec<String> = (0..self.temporaries.len())
+                .filter(|&n| self.temporaries[n] == scalar)
+                .map(|n| format!("t{}", n + 1))
+                .collect();
+            if !names.is_empty() {
+                code.push_str(&format!("    {} {};\n", c_type(&scalar), names.join(", ")));
+            }
+        }
+        for line in &self.body {
+            code.push_str(line);
+            code.push('\n');
+        }
+        code.push_str("    return 0;\n}\n");
+        code
+    }
+
+    fn symbol_type(&self, name: &str) -> Result<Types, CodegenError> {
+        self.symbols.get(name).cloned().ok_or_else(|| CodegenError::UndeclaredName(name.to_string()))
+    }
+
+    // Same declaration order and array sizes as the quadruplet generator
+    fn declarations(&mut self, declarations: &[Declaration]) -> Result<(), CodegenError> {
+        for declaration in declarations {
+            match declaration {
+                Declaration::Variable(type_decl, variables) => {
+                    let variable_type = to_types(type_decl);
+                    for variable in variables {
+                        let (variable, expr) = match variable {
+                            Variable::Simple(variable) => (variable, None),
+                            Variable::Initialized(variable, expr) => (variable, Some(expr)),
+                        };
+                        let name = &variable.0;
+                        self.symbols.insert(name.clone(), variable_type.clone());
+                        match expr.map(|expr| (expr, constant_value(expr, &self.constants))) {
+                            None => self.globals.push(format!("static {} {};", c_type(&variable_type), symbol(name))),
+                            Some((_, Some(value))) if value.get_type() == variable_type => {
+                                self.globals.push(format!("static {} {} = {};", c_type(&variable_type), symbol(name), c_value(&value)));
+                            },
+                            Some((expr, _)) => {
+                                self.globals.push(format!("static {} {};", c_type(&variable_type), symbol(name)));
+                                self.assignment(variable, None, expr)?;
+                            },
+                        }
+                    }
+                },
+                Declaration::ADEC(type_decl, arrays) => {
+                    for array in arrays {
+                        match array {
+                            ArrayDecl::Simple((name, _), size) => {
+                                self.array(name, type_decl, size, Vec::new())?;
+                            },
+                            ArrayDecl::Initialized((name, _), size, values) => {
+                                self.array(name, type_decl, size, values.clone())?;
+                            },
+                            ArrayDecl::InitializedString((name, _), size, (value, _)) => {
+                                // The literal still holds its surrounding double quotes
+                                let content = &value[1..value.len() - 1];
+                                let values: Vec<Expr> = if content.is_empty() {
+                                    vec![char_literal('\0')]
+                                } else {
+                                    content.chars().map(char_literal).collect()
+                                };
+                                self.array(name, type_decl, size, values)?;
+                            },
+                        }
+                    }
+                },
+                Declaration::Constant(type_decl, constants) => {
+                    let constant_type = to_types(type_decl);
+                    for constant in constants {
+                        let name = &constant.var.0;
+                        self.symbols.insert(name.clone(), constant_type.clone());
+                        match constant_value(&constant.expr, &self.constants) {
+                            Some(value) if value.get_type() == constant_type => {
+                                self.constants.insert(name.clone(), value);
+                                self.globals.push(format!("static const {} {} = {};", c_type(&constant_type), symbol(name), c_value(&value)));
+                            },
+                            value => {
+                                if let Some(value) = value {
+                                    self.constants.insert(name.clone(), value);
+                                }
+                                let expr = self.expression(&constant.expr)?;
+                                self.line(format!("const {} {} = {};", c_type(&constant_type), symbol(name), expr.text));
+                            },
+                        }
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // Arrays of values known at compile time get an initializer list, the others are filled
+    // at the start of main. The values are repeated until every cell is filled
+    fn array(&mut self, name: &str, type_decl: &Type, size: &Expr, values: Vec<Expr>) -> Result<(), CodegenError> {
+        let element = to_types(type_decl);
+        let size = match constant_value(size, &self.constants) {
+            Some(Value::Integer(size)) if size > 0 => size,
+            _ => values.len() as i16,
+        };
+        self.symbols.insert(name.to_string(), Types::Array(Box::new(element.clone()), size));
+        let declaration = format!("static {} {}[{}]", c_type(&element), symbol(name), size.max(1));
+        if values.is_empty() {
+            self.globals.push(format!("{};", declaration));
+            return Ok(());
+        }
+
+        let constants: Option<Vec<Value>> = values.iter().map(|value| constant_value(value, &self.constants)).collect();
+        match constants.filter(|constants| constants.iter().all(|value| value.get_type() == element)) {
+            Some(constants) => {
+                let cells: Vec<String> = (0..size as usize).map(|index| c_value(&constants[index % constants.len()])).collect();
+                self.globals.push(format!("{} = {{{}}};", declaration, cells.join(", ")));
+            },
+            None => {
+                self.globals.push(format!("{};", declaration));
+                let mut texts = Vec::new();
+                for value in &values {
+                    let value = self.expression(value)?;
+                    texts.push(if value.can_fail {
+                        let temporary = self.temporary(value.value_type);
+                        self.line(format!("{} = {};", temporary, value.text));
+                        temporary
+                    } else {
+                        value.text
+                    });
+                }
+                for index in 0..size as usize {
+                    self.line(format!("{}[{}] = {};", symbol(name), index, texts[index % texts.len()]));
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn instructions(&mut self, instructions: &[Instruction]) -> Result<(), CodegenError> {
+        for instruction in instructions {
+            match instruction {
+                Instruction::Assign(assignment) => {
+                    self.assignment(&assignment.var, assignment.index.as_ref(), &assignment.expr)?;
+                },
+                Instruction::If(if_stmt) => {
+                    self.frames.push(Frame::If(if_stmt.position.into()));
+                    let condition = self.condition(&if_stmt.condition)?;
+                    self.line(format!("if ({}) {{", condition));
+                    self.block(&if_stmt.then_block)?;
+                    if let Some(else_block) = &if_stmt.else_block {
+                        self.line("} else {".to_string());
+                        self.block(else_block)?;
+                    }
+                    self.line("}".to_string());
+                    self.frames.pop();
+                },
+                Instruction::For(for_stmt) => self.for_loop(for_stmt)?,
+                Instruction::Read(read_stmt) => {
+                    let target = self.location(&read_stmt.variable, read_stmt.index.as_ref())?;
+                    let function = self.helper(match target.value_type {
+                        Types::Float => "mining_read_float",
+                        Types::Char => "mining_read_char",
+                        _ => "mining_read_int",
+                    });
+                    // The address is computed before the call, so the index is checked before reading
+                    let origin = self.origin(read_stmt.variable.1);
+                    self.line(format!("{}(&{}, {});", function, target.text, origin));
+                },
+                Instruction::Write(write_stmt) => {
+                    for element in &write_stmt.elements {
+                        match element {
+                            // The literal still holds its surrounding double quotes
+                            WriteElement::String((literal, _)) => {
+                                self.line(format!("printf(\"{}\");", c_string(&literal[1..literal.len() - 1])));
+                            },
+                            WriteElement::Variable(name, index) => {
+                                let value = self.location(name, index.as_ref())?;
+                                let function = self.helper(match value.value_type {
+                                    Types::Float => "mining_write_float",
+                                    Types::Char => "mining_write_char",
+                                    _ => "mining_write_int",
+                                });
+                                self.line(format!("{}({});", function, value.text));
+                            },
+                        }
+                    }
+                    self.line("printf(\"\\n\");".to_string());
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, instructions: &[Instruction]) -> Result<(), CodegenError> {
+        self.depth += 1;
+        let result = self.instructions(instructions);
+        self.depth -= 1;
+        result
+    }
+
+    // FOR(var = start : step : end) runs while var < end, the end and the step
+    // are evaluated again at every iteration like in the quadruplets
+    fn for_loop(&mut self, for_stmt: &ForStmt) -> Result<(), CodegenError> {
+        let init = &for_stmt.init;
+        let counter = match &init.index {
+            Some(index) => Expr::SUBS(init.var.clone(), Box::new(index.clone())),
+            None => Expr::Variable(init.var.clone()),
+        };
+        let start = self.assignment_parts(&init.var, init.index.as_ref(), &init.expr)?;
+        self.frames.push(Frame::Loop(for_stmt.position.into()));
+        // The test and the step are errors of the counter, like in the quadruplets
+        let condition = self.binary(&counter, "<", &for_stmt.condition, init.var.1)?;
+        let (line, column) = init.var.1;
+        let next = Expr::BinaryOp(Box::new(counter), BinOp::Add(line, column), Box::new(for_stmt.step.clone()));
+        let step = self.assignment_parts(&init.var, init.index.as_ref(), &next)?;
+
+        self.line(format!("for ({}; {}; {}) {{", start.join(", "), condition.text, step.join(", ")));
+        self.block(&for_stmt.body)?;
+        self.line("}".to_string());
+        self.frames.pop();
+        Ok(())
+    }
+
+    fn assignment(&mut self, name: &(String, (usize, usize)), index: Option<&Expr>, expr: &Expr) -> Result<(), CodegenError> {
+        for part in self.assignment_parts(name, index, expr)? {
+            self.line(format!("{};", part));
+        }
+        Ok(())
+    }
+
+    // Expressions performing an assignment in order: the value is computed before the index
+    // of the target, through a temporary when both of them can fail
+    fn assignment_parts(&mut self, name: &(String, (usize, usize)), index: Option<&Expr>, expr: &Expr) -> Result<Vec<String>, CodegenError> {
+        let value = self.expression(expr)?;
+        let target = self.location(name, index)?;
+        if value.can_fail && target.can_fail {
+            let temporary = self.temporary(value.value_type);
+            Ok(vec![format!("{} = {}", temporary, value.text), format!("{} = {}", target.text, temporary)])
+        } else {
+            Ok(vec![format!("{} = {}", target.text, value.text)])
+        }
+    }
+
+    // Variable or array cell, indexes are checked unless they are constants within the bounds
+    fn location(&mut self, (name, position): &(String, (usize, usize)), index: Option<&Expr>) -> Result<CExpr, CodegenError> {
+        let symbol_type = self.symbol_type(name)?;
+        match (index, symbol_type) {
+            (Some(index), Types::Array(element, size)) => {
+                let index = self.expression(index)?;
+                let in_bounds = index.text.parse::<i16>().is_ok_and(|i| (0..size).contains(&i));
+                let text = if in_bounds {
+                    format!("{}[{}]", symbol(name), index.text)
+                } else {
+                    let origin = self.origin(*position);
+                    format!("{}[{}({}, {}, {})]", symbol(name), self.helper("mining_index"), index.text, size, origin)
+                };
+                Ok(CExpr { text, value_type: *element, can_fail: !in_bounds })
+            },
+            (None, symbol_type) => Ok(CExpr { text: symbol(name), value_type: symbol_type, can_fail: false }),
+            (Some(_), _) => Err(CodegenError::InvalidExpression(format!("'{}' is not an array", name))),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<CExpr, CodegenError> {
+        match expr {
+            Expr::Literal(literal) => match to_value(literal) {
+                Some(value) => Ok(CExpr { text: c_value(&value), value_type: value.get_type(), can_fail: false }),
+                None => Err(CodegenError::InvalidExpression("array literal used as a value".to_string())),
+            },
+            Expr::Variable(name) => self.location(name, None),
+            Expr::SUBS(name, index) => self.location(name, Some(index)),
+            Expr::BinaryOp(left, op, right) => {
+                let operator = match op {
+                    BinOp::Add(_, _) => "+",
+                    BinOp::Sub(_, _) => "-",
+                    BinOp::Mul(_, _) => "*",
+                    BinOp::Div(_, _) => "/",
+                };
+                self.binary(left, operator, right, op.position())
+            },
+        }
+    }
+
+    // Arithmetic operators and comparisons at a position in the source, the left operand is evaluated first
+    fn binary(&mut self, left: &Expr, operator: &str, right: &Expr, position: (usize, usize)) -> Result<CExpr, CodegenError> {
+        let mut left = self.expression(left)?;
+        let right = self.expression(right)?;
+        if left.value_type != right.value_type {
+            return Err(CodegenError::InvalidExpression(format!(
+                "operands of '{}' have different types {:?} and {:?}", operator, left.value_type, right.value_type
+            )));
+        }
+
+        let mut hoisted = None;
+        if left.can_fail && right.can_fail {
+            let temporary = self.temporary(left.value_type.clone());
+            hoisted = Some(format!("{} = {}", temporary, left.text));
+            left.text = temporary;
+        }
+
+        let (text, value_type, can_fail) = match (operator, &left.value_type) {
+            ("<" | "<=" | ">" | ">=" | "==" | "!=", Types::Char) => {
+                (format!("(unsigned char){} {} (unsigned char){}", left.text, operator, right.text), Types::Integer, false)
+            },
+            ("<" | "<=" | ">" | ">=" | "==" | "!=", _) => (format!("{} {} {}", left.text, operator, right.text), Types::Integer, false),
+            (_, Types::Integer) => {
+                let function = self.helper(match operator {
+                    "+" => "mining_add",
+                    "-" => "mining_sub",
+                    "*" => "mining_mul",
+                    _ => "mining_div",
+                });
+                (format!("{}({}, {}, {})", function, left.text, right.text, self.origin(position)), Types::Integer, true)
+            },
+            (_, Types::Float) => (format!("({} {} {})", left.text, operator, right.text), Types::Float, false),
+            ("+", Types::Char) => (format!("{}({}, {})", self.helper("mining_char_add"), left.text, right.text), Types::Char, false),
+            ("-", Types::Char) => (format!("{}({}, {})", self.helper("mining_char_sub"), left.text, right.text), Types::Char, false),
+            _ => {
+                return Err(CodegenError::InvalidExpression(format!(
+                    "'{}' is not defined for {:?} values", operator, left.value_type
+                )));
+            },
+        };
+        let can_fail = can_fail || left.can_fail || right.can_fail;
+        Ok(match hoisted {
+            Some(hoisted) => CExpr { text: format!("({}, {})", hoisted, text), value_type, can_fail },
+            None => CExpr { text, value_type, can_fail },
+        })
+    }
+
+    // C's && and || evaluate their right side only when needed, like the jumps of the quadruplets
+    fn condition(&mut self, condition: &Condition) -> Result<String, CodegenError> {
+        match condition {
+            Condition::Basic(basic) => {
+                let operator = match basic.operator {
+                    RelOp::Gt(_, _) => ">",
+                    RelOp::Lt(_, _) => "<",
+                    RelOp::Ge(_, _) => ">=",
+                    RelOp::Le(_, _) => "<=",
+                    RelOp::Eq(_, _) => "==",
+                    RelOp::Ne(_, _) => "!=",
+                };
+                Ok(self.binary(&basic.left, operator, &basic.right, basic.operator.position())?.text)
+            },
+            Condition::Logic(left, operator, right) => {
+                let operator = match operator {
+                    LogOp::And(_, _) => "&&",
+                    LogOp::Or(_, _) => "||",
+                };
+                Ok(format!("({}) {} ({})", self.condition(left)?, operator, self.condition(right)?))
+            },
+            Condition::Not(inner) => Ok(format!("!({})", self.condition(inner)?)),
+        }
+    }
+}
+
+fn char_literal(c: char) -> Expr {
+    Expr::Literal(TypeValue::Char((c, (0, 0))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use crate::Test::tests::{parse_source, run_program, run_tool, TemporaryDirectory, FAILING_PROGRAM, PROGRAM};
+
+    // Compiles with the system C compiler, which the test needs. Warnings are errors, such as
+    // a helper defined but not called
+    fn run(code: &str, input: &str) -> (String, String, i32) {
+        let directory = TemporaryDirectory::new(&format!("mining_c_{}", code.len()));
+        let (source, program) = (directory.path().join("p.c"), directory.path().join("p"));
+        std::fs::write(&source, code).unwrap();
+        run_tool(Command::new("cc").args(["-std=c99", "-Wall", "-Werror"]).arg(&source).arg("-o").arg(&program).arg("-lm"));
+        run_program(&mut Command::new(&program), input)
+    }
+
+    #[test]
+    fn test_generated_c() {
+        let code = generate_c(&parse_source(PROGRAM)).unwrap();
+        assert!(code.contains("static const int16_t m_Size = 3;"));
+        assert!(code.contains("static int16_t m_A[3] = {0, 0, 0};"));
+        assert!(code.contains("static int16_t m_B[4] = {1, 2, 1, 2};"));
+        assert!(code.contains("static char m_Word[5] = {'a', 'b', '?', 'a', 'b'};"));
+        assert!(code.contains("static float m_G = 1.5f;"));
+        assert!(code.contains("for (m_I = 0; m_I < m_Size; m_I = mining_add(m_I, 1, mining_at4)) {"));
+        assert!(code.contains("printf(\"S = \");"));
+        assert!(code.contains("printf(\" 100%%\");"));
+        // Failing helpers are given where the error happens
+        assert!(code.contains("static const char mining_at4[] = \" at line 17, column 14\\n    in FOR at line 17, column 9\";"));
+        assert!(code.contains("mining_read_int(&m_N, mining_at1);"));
+
+        let (output, _, code_status) = run(&code, "-7 2.25\n a");
+        assert_eq!(code_status, 0);
+        assert_eq!(output, "S = -21 100%\n2ab\n1.5D\nyes\n3\n");
+
+        let (output, errors, status) = run(&code, "-1 1.0 x");
+        assert_eq!(output, "S = -3 100%\n2ab\n0.666667[\nno\n");
+        assert_eq!((errors.as_str(), status), ("Runtime error: Division by zero at line 31, column 15\n", 1));
+
+        let (_, errors, status) = run(&code, "40000");
+        assert_eq!((errors.as_str(), status), ("Runtime error: Invalid INTEGER input at line 14, column 14\n", 1));
+        let (_, errors, status) = run(&code, "5 1e3");
+        assert_eq!((errors.as_str(), status), ("Runtime error: Invalid FLOAT input at line 15, column 14\n", 1));
+        let (_, errors, status) = run(&code, "5 1.0");
+        assert_eq!((errors.as_str(), status), ("Runtime error: Unexpected end of input at line 16, column 14\n", 1));
+    }
+
+    #[test]
+    fn test_evaluation_order() {
+        let program = parse_source(r#"
+            VAR_GLOBAL {
+                INTEGER I = 5, X;
+                INTEGER A[2] = [1];
+            }
+            DECLARATION { }
+            INSTRUCTION {
+                A[I] = 32767 + A[0];
+                X = A[I] + 32767 * 2;
+            }
+        "#);
+        let code = generate_c(&program).unwrap();
+        // The value is computed before the index is checked, the left operand before the right one
+        assert!(code.contains("t1 = mining_add(32767, m_A[0], mining_at1);"));
+        assert!(code.contains("m_A[mining_index(m_I, 2, mining_at2)] = t1;"));
+        assert!(code.contains("m_X = (t2 = m_A[mining_index(m_I, 2, mining_at3)], mining_add(t2, mining_mul(32767, 2, mining_at4), mining_at5));"));
+        // Only the helpers the program calls are defined
+        assert!(code.contains("static int16_t mining_check(") && !code.contains("mining_div") && !code.contains("mining_read_int"));
+
+        let (_, errors, status) = run(&code, "");
+        assert_eq!((errors.as_str(), status), ("Runtime error: INTEGER overflow at line 8, column 30\n", 1));
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let code = generate_c(&parse_source(FAILING_PROGRAM)).unwrap();
+        let error = |input: &str| run(&code, input).1;
+        let stack = "\n    in FOR at line 10, column 13\
+                     \n    in IF at line 9, column 9\
+                     \n    in FOR at line 8, column 5\n";
+        assert_eq!(error("1"), format!("Runtime error: Division by zero at line 11, column 28{}", stack));
+        assert_eq!(error("32767"), format!("Runtime error: INTEGER overflow at line 11, column 33{}", stack));
+        assert_eq!(error("5"), "Runtime error: Array index out of bounds at line 15, column 5\n");
+        assert_eq!(error("2 x"), "Runtime error: Invalid INTEGER input at line 16, column 10\n");
+        assert_eq!(error("2"), "Runtime error: Unexpected end of input at line 16, column 10\n");
+    }
+}
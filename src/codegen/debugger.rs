@@ -0,0 +1,421 @@
+//! Interactive debugger over the tree-walking interpreter, used by `debug`.
+//! Commands are read one per line from the same input as READ, so the values read by the
+//! program are typed when it asks for them, and the messages go to the same output as WRITE
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufRead, Write};
+use logos::Logos;
+use crate::codegen::error::{Frame, LocatedError, Position, RuntimeError};
+use crate::codegen::interpreter::{Interpreter, Observer};
+use crate::grammar::{AssignmentParser, ConditionParser, ExprParser};
+use crate::Lexer::lexer::Token;
+use crate::Parser::ast::*;
+use crate::Semantic::quadruplets::Value;
+use crate::Semantic::ts::{Symbol, Types};
+
+const HELP: &str = "\
+Commands:
+  step, s                 run the next instruction, entering IF and FOR blocks
+  next, n                 run the next instruction, over IF and FOR blocks
+  continue, c             run until a breakpoint or the end of the program
+  break, b <line>         stop before the instructions of a line
+  delete, d <line>        remove a breakpoint
+  breakpoints             list the breakpoints
+  print, p <expression>   print a value, a whole array or a condition
+  set <target> = <value>  change a variable or an array cell (e.g., set A[I] = 2)
+  watch, w <expression>   print an expression at every stop
+  unwatch <number>        remove a watched expression
+  loops                   show the iteration and the counter of the enclosing FOR loops
+  quit, q                 stop the program";
+
+/// Runs a program under the debugger, stopping before its first instruction. `symbols` is the
+/// symbol table filled by the semantic analysis and `source` the text the program was parsed from
+pub fn debug_program<R: BufRead, W: Write>(
+    program: &Program,
+    source: &str,
+    symbols: HashMap<String, Symbol>,
+    input: R,
+    output: &mut W,
+) -> Result<(), LocatedError> {
+    let mut interpreter = Interpreter::new(input, output);
+    interpreter.add_observer(Box::new(Debugger::new(program, source, symbols)));
+    let result = interpreter.run(program);
+    let message = match &result {
+        Ok(()) => "Program finished".to_string(),
+        Err(e) if e.error == RuntimeError::Stopped => e.error.to_string(),
+        Err(e) => format!("Runtime error: {}", e),
+    };
+    writeln!(interpreter.output(), "{}", message).map_err(|e| interpreter.locate(e, None))?;
+    interpreter.output().flush().map_err(|e| interpreter.locate(e, None))?;
+    result
+}
+
+// When the program stops next
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Step,           // Before the next instruction
+    Next(usize),    // Before the next instruction enclosed in at most this many IF and FOR
+    Continue,       // At a breakpoint
+}
+
+enum Watch {
+    Expression(Expr),
+    Condition(Condition),
+}
+
+struct Debugger {
+    source: Vec<String>,
+    symbols: HashMap<String, Symbol>,
+    lines: BTreeSet<usize>,         // Lines holding an instruction, counted from 1
+    breakpoints: BTreeSet<usize>,
+    watches: Vec<(String, Watch)>,
+    mode: Mode,
+}
+
+// Lines of every instruction, blocks included
+fn instruction_lines(instructions: &[Instruction], lines: &mut BTreeSet<usize>) {
+    for instruction in instructions {
+        lines.insert(Position::from(instruction.position()).line);
+        match instruction {
+            Instruction::If(if_stmt) => {
+                instruction_lines(&if_stmt.then_block, lines);
+                instruction_lines(if_stmt.else_block.as_deref().unwrap_or_default(), lines);
+            },
+            Instruction::For(for_stmt) => instruction_lines(&for_stmt.body, lines),
+            _ => {},
+        }
+    }
+}
+
+fn tokens(text: &str) -> impl Iterator<Item = Result<(usize, Token, usize), crate::Lexer::error::CustomError>> + '_ {
+    Token::lexer(text).enumerate().map(|(i, t)| t.map(|token| (i, token, i + 1)))
+}
+
+fn parse_watch(text: &str) -> Result<Watch, String> {
+    if let Ok(expr) = ExprParser::new().parse(text, tokens(text)) {
+        return Ok(Watch::Expression(expr));
+    }
+    ConditionParser::new().parse(text, tokens(text))
+        .map(Watch::Condition)
+        .map_err(|_| format!("Cannot parse '{}' as a MinING expression or condition", text))
+}
+
+fn expression_names<'a>(expr: &'a Expr, names: &mut Vec<&'a str>) {
+    match expr {
+        Expr::BinaryOp(left, _, right) => {
+            expression_names(left, names);
+            expression_names(right, names);
+        },
+        Expr::Variable((name, _)) => names.push(name),
+        Expr::SUBS((name, _), index) => {
+            names.push(name);
+            expression_names(index, names);
+        },
+        Expr::Literal(_) => {},
+    }
+}
+
+fn condition_names<'a>(condition: &'a Condition, names: &mut Vec<&'a str>) {
+    match condition {
+        Condition::Not(inner) => condition_names(inner, names),
+        Condition::Logic(left, _, right) => {
+            condition_names(left, names);
+            condition_names(right, names);
+        },
+        Condition::Basic(basic) => {
+            expression_names(&basic.left, names);
+            expression_names(&basic.right, names);
+        },
+    }
+}
+
+fn type_name(value_type: &Types) -> &'static str {
+    match value_type {
+        Types::Integer => "INTEGER",
+        Types::Float => "FLOAT",
+        Types::Char => "CHAR",
+        Types::Array(_, _) => "array",
+    }
+}
+
+impl Debugger {
+    fn new(program: &Program, source: &str, symbols: HashMap<String, Symbol>) -> Self {
+        let mut lines = BTreeSet::new();
+        instruction_lines(program.inst.as_deref().unwrap_or_default(), &mut lines);
+        Debugger {
+            source: source.lines().map(str::to_string).collect(),
+            symbols,
+            lines,
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+            mode: Mode::Step,
+        }
+    }
+
+    // Names missing from the symbol table
+    fn check_names(&self, names: &[&str]) -> Result<(), String> {
+        match names.iter().find(|name| !self.symbols.contains_key(**name)) {
+            Some(name) => Err(format!("Unknown variable '{}'", name)),
+            None => Ok(()),
+        }
+    }
+
+    fn evaluate<R: BufRead, W: Write>(&self, interpreter: &mut Interpreter<R, W>, watch: &Watch) -> Result<String, String> {
+        let mut names = Vec::new();
+        match watch {
+            Watch::Expression(expr) => expression_names(expr, &mut names),
+            Watch::Condition(condition) => condition_names(condition, &mut names),
+        }
+        self.check_names(&names)?;
+        match watch {
+            // A whole array is printed cell by cell
+            Watch::Expression(Expr::Variable((name, _))) if matches!(interpreter.environment[name].storage_type, Types::Array(_, _)) => {
+                let cells: Vec<String> = interpreter.environment[name].cells.iter().map(Value::to_string).collect();
+                Ok(format!("[{}]", cells.join(", ")))
+            },
+            Watch::Expression(expr) => interpreter.evaluate(expr).map(|value| value.to_string()).map_err(|e| e.error.to_string()),
+            Watch::Condition(condition) => interpreter.condition(condition).map(|holds| holds.to_string()).map_err(|e| e.error.to_string()),
+        }
+    }
+
+    fn set<R: BufRead, W: Write>(&self, interpreter: &mut Interpreter<R, W>, text: &str) -> Result<String, String> {
+        let assignment = AssignmentParser::new().parse(text, tokens(text))
+            .map_err(|_| format!("Cannot parse '{}' as a MinING assignment", text))?;
+        let mut names = vec![assignment.var.0.as_str()];
+        if let Some(index) = &assignment.index {
+            expression_names(index, &mut names);
+        }
+        expression_names(&assignment.expr, &mut names);
+        self.check_names(&names)?;
+
+        if self.symbols[&assignment.var.0].Is_Constant == Some(true) {
+            return Err(format!("'{}' is a constant", assignment.var.0));
+        }
+        let target_type = match (&interpreter.environment[&assignment.var.0].storage_type, &assignment.index) {
+            (Types::Array(element, _), Some(_)) => *element.clone(),
+            (Types::Array(_, _), None) => return Err(format!("'{}' is an array, give the index of a cell", assignment.var.0)),
+            (_, Some(_)) => return Err(format!("'{}' is not an array", assignment.var.0)),
+            (scalar, None) => scalar.clone(),
+        };
+        let value = interpreter.evaluate(&assignment.expr).map_err(|e| e.error.to_string())?;
+        if value.get_type() != target_type {
+            return Err(format!("Cannot store a {} value in {}, which holds {} values",
+                type_name(&value.get_type()), assignment.var.0, type_name(&target_type)));
+        }
+        interpreter.assign(&assignment).map_err(|e| e.error.to_string())?;
+        Ok(format!("{} = {}", text.split('=').next().unwrap_or_default().trim(), value))
+    }
+
+    fn loops<R: BufRead, W: Write>(&self, interpreter: &Interpreter<R, W>) -> String {
+        let loops: Vec<String> = interpreter.frames.iter().rev().filter_map(|frame| match frame {
+            // The counter is read again since it may have been changed with set
+            Frame::For { position, counter, iteration, value } => {
+                let value = match interpreter.environment.get(counter).map(|storage| storage.cells.as_slice()) {
+                    Some([current]) => *current,
+                    _ => *value,
+                };
+                Some(format!("FOR at {}: iteration {} with {} = {}", position, iteration, counter, value))
+            },
+            Frame::If(_) | Frame::Loop(_) => None,
+        }).collect();
+        match loops.is_empty() {
+            true => "Not inside a FOR loop".to_string(),
+            false => loops.join("\n"),
+        }
+    }
+
+    /// Runs the commands until one resumes the program, returns what the command printed
+    /// and whether the program goes on
+    fn command<R: BufRead, W: Write>(&mut self, interpreter: &mut Interpreter<R, W>, line: &str) -> Result<(Option<String>, bool), LocatedError> {
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+        let line_number = || argument.parse::<usize>().map_err(|_| format!("Expected a line number, got '{}'", argument));
+        let message = match command {
+            "" => return Ok((None, false)),
+            "help" | "h" => HELP.to_string(),
+            "step" | "s" => {
+                self.mode = Mode::Step;
+                return Ok((None, true));
+            },
+            "next" | "n" => {
+                self.mode = Mode::Next(interpreter.frames.len());
+                return Ok((None, true));
+            },
+            "continue" | "c" => {
+                self.mode = Mode::Continue;
+                return Ok((None, true));
+            },
+            "break" | "b" => match line_number() {
+                Ok(line) if self.lines.contains(&line) => {
+                    self.breakpoints.insert(line);
+                    format!("Breakpoint at line {}", line)
+                },
+                Ok(line) => format!("No instruction at line {}", line),
+                Err(e) => e,
+            },
+            "delete" | "d" => match line_number() {
+                Ok(line) if self.breakpoints.remove(&line) => format!("Deleted the breakpoint at line {}", line),
+                Ok(line) => format!("No breakpoint at line {}", line),
+                Err(e) => e,
+            },
+            "breakpoints" => match self.breakpoints.is_empty() {
+                true => "No breakpoints".to_string(),
+                false => self.breakpoints.iter().map(|line| format!("line {}", line)).collect::<Vec<_>>().join("\n"),
+            },
+            "print" | "p" => match parse_watch(argument).and_then(|watch| self.evaluate(interpreter, &watch)) {
+                Ok(value) => format!("{} = {}", argument, value),
+                Err(e) => e,
+            },
+            "set" => self.set(interpreter, argument).unwrap_or_else(|e| e),
+            "watch" | "w" => match parse_watch(argument) {
+                Ok(watch) => {
+                    let value = self.evaluate(interpreter, &watch).unwrap_or_else(|e| e);
+                    self.watches.push((argument.to_string(), watch));
+                    format!("Watch {}: {} = {}", self.watches.len(), argument, value)
+                },
+                Err(e) => e,
+            },
+            "unwatch" => match argument.parse::<usize>() {
+                Ok(number) if (1..=self.watches.len()).contains(&number) => {
+                    let (text, _) = self.watches.remove(number - 1);
+                    format!("Removed watch {}: {}", number, text)
+                },
+                _ => format!("No watch '{}'", argument),
+            },
+            "loops" => self.loops(interpreter),
+            "quit" | "q" => return Err(interpreter.locate(RuntimeError::Stopped, None)),
+            _ => format!("Unknown command '{}', type help for the commands", command),
+        };
+        Ok((Some(message), false))
+    }
+
+    fn say<R: BufRead, W: Write>(interpreter: &mut Interpreter<R, W>, text: &str) -> Result<(), LocatedError> {
+        writeln!(interpreter.output(), "{}", text).map_err(|e| interpreter.locate(e, None))
+    }
+}
+
+impl<R: BufRead, W: Write> Observer<R, W> for Debugger {
+    fn before(&mut self, interpreter: &mut Interpreter<R, W>, instruction: &Instruction) -> Result<(), LocatedError> {
+        let position = Position::from(instruction.position());
+        let at_breakpoint = self.breakpoints.contains(&position.line);
+        let stop = match self.mode {
+            Mode::Step => true,
+            Mode::Next(depth) => interpreter.frames.len() <= depth,
+            Mode::Continue => false,
+        };
+        if !stop && !at_breakpoint {
+            return Ok(());
+        }
+
+        let source = self.source.get(position.line - 1).map_or("", |line| line.trim());
+        let kind = if at_breakpoint { "Breakpoint" } else { "Stopped" };
+        Self::say(interpreter, &format!("{} at line {}: {}", kind, position.line, source))?;
+        for (number, (text, watch)) in self.watches.iter().enumerate() {
+            let value = self.evaluate(interpreter, watch).unwrap_or_else(|e| e);
+            Self::say(interpreter, &format!("  watch {}: {} = {}", number + 1, text, value))?;
+        }
+
+        loop {
+            write!(interpreter.output(), "(mining) ").map_err(|e| interpreter.locate(e, None))?;
+            interpreter.output().flush().map_err(|e| interpreter.locate(e, None))?;
+            let line = match interpreter.input().read_line() {
+                Ok(Some(line)) => line,
+                // The end of the commands stops the program
+                Ok(None) => return Err(interpreter.locate(RuntimeError::Stopped, None)),
+                Err(e) => return Err(interpreter.locate(e, None)),
+            };
+            let (message, resume) = self.command(interpreter, line.trim())?;
+            if let Some(message) = message {
+                Self::say(interpreter, &message)?;
+            }
+            if resume {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Test::tests::analyze_source;
+
+    const PROGRAM: &str = r#"
+        VAR_GLOBAL {
+            INTEGER N, I, J, S = 0;
+        }
+        DECLARATION {
+            INTEGER A[3] = [0];
+            CONST INTEGER K = 2;
+        }
+        INSTRUCTION {
+            READ(N);
+            FOR (I = 0 : 1 : K) {
+                FOR (J = 0 : 1 : K) {
+                    S = S + N;
+                }
+                A[I] = S;
+            }
+            WRITE(S);
+        }
+    "#;
+
+    fn debug(script: &str) -> (String, Result<(), RuntimeError>) {
+        let (program, symbols) = analyze_source(PROGRAM);
+        let mut output = Vec::new();
+        let result = debug_program(&program, PROGRAM, symbols, script.as_bytes(), &mut output);
+        (String::from_utf8(output).unwrap(), result.map_err(|e| e.error))
+    }
+
+    #[test]
+    fn test_debug_session() {
+        let (output, result) = debug("b 13\nb 4\nc\n5\nloops\np S + N\nwatch S\nset J = 1\nn\n\
+            set K = 1\nset A = 1\nset A[I] = 'c'\nset A[I] = S * 2\np A\np S > N\np X\nd 13\ns\ns\nunwatch 1\nc\n");
+        assert_eq!(result, Ok(()));
+        assert_eq!(output, "\
+Stopped at line 10: READ(N);
+(mining) Breakpoint at line 13
+(mining) No instruction at line 4
+(mining) Breakpoint at line 13: S = S + N;
+(mining) FOR at line 12, column 17: iteration 1 with J = 0
+FOR at line 11, column 13: iteration 1 with I = 0
+(mining) S + N = 5
+(mining) Watch 1: S = 0
+(mining) J = 1
+(mining) Stopped at line 15: A[I] = S;
+  watch 1: S = 5
+(mining) 'K' is a constant
+(mining) 'A' is an array, give the index of a cell
+(mining) Cannot store a CHAR value in A, which holds INTEGER values
+(mining) A[I] = 10
+(mining) A = [10, 0, 0]
+(mining) S > N = false
+(mining) Unknown variable 'X'
+(mining) Deleted the breakpoint at line 13
+(mining) Stopped at line 12: FOR (J = 0 : 1 : K) {
+  watch 1: S = 5
+(mining) Stopped at line 13: S = S + N;
+  watch 1: S = 5
+(mining) Removed watch 1: S
+(mining) 15
+Program finished
+");
+    }
+
+    #[test]
+    fn test_debug_quit() {
+        let (output, result) = debug("step\n\n7\nloops\nfoo\nquit\nc\n");
+        assert_eq!(result, Err(RuntimeError::Stopped));
+        assert_eq!(output, "\
+Stopped at line 10: READ(N);
+(mining) Stopped at line 11: FOR (I = 0 : 1 : K) {
+(mining) Not inside a FOR loop
+(mining) Unknown command 'foo', type help for the commands
+(mining) Program stopped by the debugger
+");
+        // The end of the commands stops the program too
+        assert_eq!(debug("").1, Err(RuntimeError::Stopped));
+    }
+}
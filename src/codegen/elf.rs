@@ -0,0 +1,1022 @@
+//! Static x86-64 ELF executables without `as` or `ld`
+//!
+//! `build_executable` lowers the quadruplets with the x86-64 backend and assembles its output
+//! here: the assembler understands the Intel syntax subset the backend and its runtime use
+//! (general purpose and scalar SSE instructions, `[base + index*scale + displacement]` and
+//! RIP-relative memory operands, numeric local labels and the data directives). Every jump and
+//! call takes a 32 bits displacement, so instruction sizes never depend on label addresses and a
+//! single pass followed by patching the displacements is enough.
+//!
+//! The image has two `PT_LOAD` segments and no section headers: the ELF header, `.text` and
+//! `.rodata` are mapped read-execute at 0x400000, `.data` and `.bss` read-write on the next page.
+
+use std::collections::HashMap;
+use crate::codegen::error::CodegenError;
+use crate::codegen::regalloc::Allocation;
+use crate::codegen::x86_64::generate_x86_64_with;
+use crate::Semantic::quadruplets::Quadruplet;
+
+// Virtual address of the first byte of the file
+const BASE_ADDRESS: u64 = 0x400000;
+const PAGE_SIZE: usize = 0x1000;
+const ELF_HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+const REGISTERS_64: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+];
+const REGISTERS_32: [&str; 16] = [
+    "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d", "r13d", "r14d", "r15d",
+];
+const REGISTERS_16: [&str; 16] = [
+    "ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "r8w", "r9w", "r10w", "r11w", "r12w", "r13w", "r14w", "r15w",
+];
+const REGISTERS_8: [&str; 16] = [
+    "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b", "r13b", "r14b", "r15b",
+];
+
+// Condition codes of jcc and setcc
+const CONDITIONS: [(&str, u8); 30] = [
+    ("o", 0), ("no", 1), ("b", 2), ("c", 2), ("nae", 2), ("ae", 3), ("nb", 3), ("nc", 3), ("e", 4), ("z", 4),
+    ("ne", 5), ("nz", 5), ("be", 6), ("na", 6), ("a", 7), ("nbe", 7), ("s", 8), ("ns", 9), ("p", 10), ("pe", 10),
+    ("np", 11), ("po", 11), ("l", 12), ("nge", 12), ("ge", 13), ("nl", 13), ("le", 14), ("ng", 14), ("g", 15), ("nle", 15),
+];
+
+// Arithmetic group: the /digit of the immediate forms, the register forms are 8 times it
+const ARITHMETIC: [(&str, u8); 6] = [("add", 0), ("or", 1), ("and", 4), ("sub", 5), ("xor", 6), ("cmp", 7)];
+
+// Unary group of F6/F7 (FE/FF for inc and dec): opcode and /digit
+const UNARY: [(&str, u8, u8); 7] = [
+    ("inc", 0xFE, 0), ("dec", 0xFE, 1), ("not", 0xF6, 2), ("neg", 0xF6, 3), ("mul", 0xF6, 4), ("div", 0xF6, 6), ("idiv", 0xF6, 7),
+];
+
+// Scalar SSE instructions taking an xmm register and an xmm register or memory: mandatory prefix
+// (0 for none) and opcode after 0F
+const SSE: [(&str, u8, u8); 21] = [
+    ("movss", 0xF3, 0x10), ("movsd", 0xF2, 0x10), ("movaps", 0, 0x28),
+    ("addss", 0xF3, 0x58), ("mulss", 0xF3, 0x59), ("subss", 0xF3, 0x5C), ("divss", 0xF3, 0x5E), ("sqrtss", 0xF3, 0x51),
+    ("addsd", 0xF2, 0x58), ("mulsd", 0xF2, 0x59), ("subsd", 0xF2, 0x5C), ("divsd", 0xF2, 0x5E), ("sqrtsd", 0xF2, 0x51),
+    ("ucomiss", 0, 0x2E), ("ucomisd", 0x66, 0x2E), ("comiss", 0, 0x2F), ("comisd", 0x66, 0x2F),
+    ("cvtss2sd", 0xF3, 0x5A), ("cvtsd2ss", 0xF2, 0x5A), ("xorps", 0, 0x57), ("andps", 0, 0x54),
+];
+
+/// Builds the executable of the quadruplets, temporaries placed by the allocation
+pub fn build_executable(quadruplets: &[Quadruplet], allocation: &Allocation) -> Result<Vec<u8>, CodegenError> {
+    assemble(&generate_x86_64_with(quadruplets, allocation)?)
+}
+
+/// Assembles a program in the syntax of the x86-64 backend into an ELF executable, `_start`
+/// being the entry point
+pub fn assemble(source: &str) -> Result<Vec<u8>, CodegenError> {
+    let mut assembler = Assembler::new();
+    for (index, line) in source.lines().enumerate() {
+        assembler.line = index + 1;
+        assembler.statement(line)
+            .map_err(|message| CodegenError::Assembly(format!("line {} `{}`: {}", index + 1, line.trim(), message)))?;
+    }
+    assembler.link()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Text,
+    Rodata,
+    Data,
+    Bss,
+}
+
+impl Section {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Register {
+    number: u8,
+    size: u8,       // In bytes
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Memory {
+    size: Option<u8>,               // From `byte ptr` and the like
+    base: Option<u8>,
+    index: Option<(u8, u8)>,        // Register and scale
+    displacement: i64,
+    symbol: Option<String>,         // Label of a RIP-relative operand
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Argument {
+    Register(Register),
+    Xmm(u8),
+    Immediate(i64),
+    Memory(Memory),
+    Label(String),
+}
+
+impl Argument {
+    fn size(&self) -> Option<u8> {
+        match self {
+            Argument::Register(register) => Some(register.size),
+            Argument::Memory(memory) => memory.size,
+            _ => None,
+        }
+    }
+
+    fn is_register_or_memory(&self) -> bool {
+        matches!(self, Argument::Register(_) | Argument::Memory(_))
+    }
+}
+
+// Machine code of one instruction. At most one field is relative to the end of the instruction,
+// a RIP-relative operand or the target of a jump
+#[derive(Debug, Default)]
+struct Code {
+    bytes: Vec<u8>,
+    relative: Option<(usize, String, i64)>,     // Position of the 32 bits field, label and addend
+    force_rex: bool,                            // spl, bpl, sil and dil only exist with a REX prefix
+}
+
+impl Code {
+    fn push(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn immediate(&mut self, value: i64, size: u8) -> Result<(), String> {
+        let bits = 8 * size as u32;
+        let fits = match size {
+            8 => true,
+            _ => value >= -(1i64 << (bits - 1)) && value < (1i64 << bits),
+        };
+        if !fits {
+            return Err(format!("immediate {} does not fit in {} bits", value, bits));
+        }
+        self.push(&value.to_le_bytes()[..size as usize]);
+        Ok(())
+    }
+
+    // Immediate of an operation of the given size, 64 bits operations sign extend 32 bits
+    fn sign_extended(&mut self, value: i64, size: u8) -> Result<(), String> {
+        if size == 8 && i32::try_from(value).is_err() {
+            return Err(format!("immediate {} does not fit in 32 bits", value));
+        }
+        self.immediate(value, size.min(4))
+    }
+
+    // 32 bits displacement to a label from the end of the instruction
+    fn relative(&mut self, label: &str, addend: i64) {
+        self.relative = Some((self.bytes.len(), label.to_string(), addend));
+        self.push(&[0; 4]);
+    }
+
+    fn rex(&mut self, wide: bool, reg: u8, index: u8, base: u8) {
+        let rex = 0x40 | (wide as u8) << 3 | (reg >> 3 & 1) << 2 | (index >> 3 & 1) << 1 | (base >> 3 & 1);
+        if rex != 0x40 || self.force_rex {
+            self.bytes.push(rex);
+        }
+    }
+
+    // Opcode with the register in its low 3 bits (push, pop, mov with an immediate)
+    fn opcode_register(&mut self, prefixes: &[u8], wide: bool, opcode: u8, register: u8) {
+        self.push(prefixes);
+        self.rex(wide, 0, 0, register);
+        self.bytes.push(opcode + (register & 7));
+    }
+
+    // Prefixes, REX, opcode, ModRM, SIB and displacement of an instruction whose ModRM.reg
+    // is `reg` (a register or an opcode extension) and ModRM.rm the operand
+    fn modrm(&mut self, prefixes: &[u8], wide: bool, opcode: &[u8], reg: u8, operand: &Argument) -> Result<(), String> {
+        let memory = match operand {
+            Argument::Register(Register { number, .. }) | Argument::Xmm(number) => {
+                self.push(prefixes);
+                self.rex(wide, reg, 0, *number);
+                self.push(opcode);
+                self.bytes.push(0xC0 | (reg & 7) << 3 | (number & 7));
+                return Ok(());
+            },
+            Argument::Memory(memory) => memory,
+            _ => return Err("expected a register or a memory operand".to_string()),
+        };
+
+        if let Some(symbol) = &memory.symbol {
+            self.push(prefixes);
+            self.rex(wide, reg, 0, 0);
+            self.push(opcode);
+            self.bytes.push((reg & 7) << 3 | 5);
+            self.relative(symbol, memory.displacement);
+            return Ok(());
+        }
+        let base = memory.base.ok_or("memory operands need a base register")?;
+        let displacement = memory.displacement;
+        // rbp and r13 as a base always take a displacement
+        let mode = if displacement == 0 && base & 7 != 5 {
+            0
+        } else if i8::try_from(displacement).is_ok() {
+            1
+        } else if i32::try_from(displacement).is_ok() {
+            2
+        } else {
+            return Err(format!("displacement {} does not fit in 32 bits", displacement));
+        };
+        let sib = match memory.index {
+            Some((4, _)) => return Err("rsp cannot be an index".to_string()),
+            Some((index, scale)) => {
+                let scale = match scale {
+                    1 => 0,
+                    2 => 1,
+                    4 => 2,
+                    8 => 3,
+                    _ => return Err(format!("invalid scale {}", scale)),
+                };
+                Some((index, scale << 6 | (index & 7) << 3 | (base & 7)))
+            },
+            // rsp and r12 as a base need a SIB byte without index
+            None if base & 7 == 4 => Some((4, 0x20 | (base & 7))),
+            None => None,
+        };
+
+        self.push(prefixes);
+        self.rex(wide, reg, sib.map_or(0, |(index, _)| index), base);
+        self.push(opcode);
+        match sib {
+            Some((_, sib)) => self.push(&[mode << 6 | (reg & 7) << 3 | 4, sib]),
+            None => self.bytes.push(mode << 6 | (reg & 7) << 3 | (base & 7)),
+        }
+        match mode {
+            1 => self.push(&(displacement as i8).to_le_bytes()),
+            2 => self.push(&(displacement as i32).to_le_bytes()),
+            _ => {},
+        }
+        Ok(())
+    }
+}
+
+// Operand size prefix and REX.W of a general purpose operation
+fn size_prefix(size: u8) -> (&'static [u8], bool) {
+    match size {
+        2 => (&[0x66], false),
+        8 => (&[], true),
+        _ => (&[], false),
+    }
+}
+
+fn condition(suffix: &str) -> Option<u8> {
+    CONDITIONS.iter().find(|(name, _)| *name == suffix).map(|(_, code)| *code)
+}
+
+fn parse_integer(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()? as i64,
+        None => digits.parse::<u64>().ok()? as i64,
+    };
+    Some(if negative { value.wrapping_neg() } else { value })
+}
+
+fn parse_register(name: &str) -> Option<Argument> {
+    for (names, size) in [(&REGISTERS_64, 8), (&REGISTERS_32, 4), (&REGISTERS_16, 2), (&REGISTERS_8, 1)] {
+        if let Some(number) = names.iter().position(|known| *known == name) {
+            return Some(Argument::Register(Register { number: number as u8, size }));
+        }
+    }
+    let number: u8 = name.strip_prefix("xmm")?.parse().ok()?;
+    (number < 16).then_some(Argument::Xmm(number))
+}
+
+fn is_symbol(text: &str) -> bool {
+    let mut characters = text.chars();
+    characters.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && characters.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$')
+}
+
+// Text of the line before its comment, `#` may appear in strings
+fn strip_comment(line: &str) -> &str {
+    let (mut in_string, mut escaped) = (false, false);
+    for (position, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..position],
+            _ => {},
+        }
+    }
+    line
+}
+
+// Bytes of a quoted string with the escapes of GAS
+fn parse_string(text: &str) -> Result<Vec<u8>, String> {
+    let inner = text.trim().strip_prefix('"').and_then(|t| t.strip_suffix('"')).ok_or("expected a quoted string")?;
+    let mut bytes = Vec::new();
+    let mut characters = inner.chars().peekable();
+    while let Some(c) = characters.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        match characters.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('r') => bytes.push(b'\r'),
+            Some(digit @ '0'..='7') => {
+                let mut value = digit.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match characters.peek().and_then(|c| c.to_digit(8)) {
+                        Some(next) => {
+                            value = value * 8 + next;
+                            characters.next();
+                        },
+                        None => break,
+                    }
+                }
+                bytes.push(value as u8);
+            },
+            Some(other) => bytes.push(other as u8),
+            None => return Err("unfinished escape".to_string()),
+        }
+    }
+    Ok(bytes)
+}
+
+struct Fixup {
+    section: Section,
+    position: usize,        // Offset of the 32 bits field in the section
+    end: usize,             // Offset of the end of the instruction
+    label: String,
+    addend: i64,
+    line: usize,
+}
+
+struct Assembler {
+    sections: [Vec<u8>; 4],             // The bytes of .bss stay zero and are not written
+    alignments: [usize; 4],
+    current: Section,
+    labels: HashMap<String, (Section, usize)>,
+    fixups: Vec<Fixup>,
+    numeric: HashMap<String, usize>,    // Definitions of each numeric local label so far
+    line: usize,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Assembler {
+            sections: Default::default(),
+            alignments: [16, 1, 1, 1],
+            current: Section::Text,
+            labels: HashMap::new(),
+            fixups: Vec::new(),
+            numeric: HashMap::new(),
+            line: 0,
+        }
+    }
+
+    fn statement(&mut self, line: &str) -> Result<(), String> {
+        let mut rest = strip_comment(line).trim();
+        // Labels, several may precede a statement
+        while let Some((label, after)) = rest.split_once(':') {
+            let label = label.trim();
+            let numeric = !label.is_empty() && label.bytes().all(|b| b.is_ascii_digit());
+            if !numeric && !is_symbol(label) {
+                break;
+            }
+            let name = if numeric {
+                let count = self.numeric.entry(label.to_string()).or_default();
+                *count += 1;
+                format!("{}@{}", label, *count - 1)
+            } else {
+                label.to_string()
+            };
+            let offset = self.sections[self.current.index()].len();
+            if self.labels.insert(name, (self.current, offset)).is_some() {
+                return Err(format!("label '{}' is defined twice", label));
+            }
+            rest = after.trim();
+        }
+        if rest.is_empty() {
+            return Ok(());
+        }
+
+        let (mnemonic, operands) = match rest.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (mnemonic, operands.trim()),
+            None => (rest, ""),
+        };
+        if mnemonic.starts_with('.') {
+            return self.directive(mnemonic, operands);
+        }
+        if self.current != Section::Text {
+            return Err("instructions belong in .text".to_string());
+        }
+        let arguments = match operands {
+            "" => Vec::new(),
+            _ => operands.split(',').map(|operand| self.argument(operand)).collect::<Result<Vec<_>, _>>()?,
+        };
+        let code = encode(mnemonic, &arguments)?;
+
+        let section = &mut self.sections[Section::Text.index()];
+        let start = section.len();
+        section.extend_from_slice(&code.bytes);
+        if let Some((position, label, addend)) = code.relative {
+            self.fixups.push(Fixup { section: Section::Text, position: start + position, end: section.len(), label, addend, line: self.line });
+        }
+        Ok(())
+    }
+
+    fn directive(&mut self, directive: &str, operands: &str) -> Result<(), String> {
+        let section = match (directive, operands) {
+            (".text", _) | (".section", ".text") => Some(Section::Text),
+            (".section", ".rodata") => Some(Section::Rodata),
+            (".data", _) | (".section", ".data") => Some(Section::Data),
+            (".bss", _) | (".section", ".bss") => Some(Section::Bss),
+            (".section", _) => return Err(format!("unknown section '{}'", operands)),
+            _ => None,
+        };
+        if let Some(section) = section {
+            self.current = section;
+            return Ok(());
+        }
+
+        let values = || operands.split(',').map(str::trim).filter(|value| !value.is_empty());
+        let mut bytes = Vec::new();
+        match directive {
+            ".intel_syntax" | ".globl" | ".global" | ".type" | ".size" | ".file" => return Ok(()),
+            ".balign" | ".p2align" => {
+                let alignment = parse_integer(operands).filter(|a| *a > 0).ok_or("invalid alignment")? as usize;
+                let alignment = if directive == ".p2align" { 1 << alignment } else { alignment };
+                let index = self.current.index();
+                self.alignments[index] = self.alignments[index].max(alignment);
+                let fill = if self.current == Section::Text { 0x90 } else { 0 };
+                while !self.sections[index].len().is_multiple_of(alignment) {
+                    self.sections[index].push(fill);
+                }
+                return Ok(());
+            },
+            ".zero" | ".space" => {
+                let count = parse_integer(operands).filter(|c| *c >= 0).ok_or("invalid size")?;
+                bytes.resize(count as usize, 0);
+            },
+            ".byte" | ".word" | ".short" | ".long" | ".int" | ".quad" => {
+                let size = match directive {
+                    ".byte" => 1,
+                    ".word" | ".short" => 2,
+                    ".long" | ".int" => 4,
+                    _ => 8,
+                };
+                for value in values() {
+                    let mut code = Code::default();
+                    code.immediate(parse_integer(value).ok_or_else(|| format!("invalid number '{}'", value))?, size)?;
+                    bytes.extend(code.bytes);
+                }
+            },
+            ".double" | ".float" => {
+                for value in values() {
+                    let number: f64 = value.parse().map_err(|_| format!("invalid number '{}'", value))?;
+                    match directive {
+                        ".double" => bytes.extend(number.to_le_bytes()),
+                        _ => bytes.extend((number as f32).to_le_bytes()),
+                    }
+                }
+            },
+            ".ascii" | ".asciz" | ".string" => {
+                bytes = parse_string(operands)?;
+                if directive != ".ascii" {
+                    bytes.push(0);
+                }
+            },
+            _ => return Err(format!("unknown directive '{}'", directive)),
+        }
+        if self.current == Section::Bss && bytes.iter().any(|b| *b != 0) {
+            return Err(".bss only holds zeros".to_string());
+        }
+        self.sections[self.current.index()].extend(bytes);
+        Ok(())
+    }
+
+    fn argument(&self, text: &str) -> Result<Argument, String> {
+        let text = text.trim();
+        let mut size = None;
+        let mut rest = text;
+        for (prefix, bytes) in [("byte ptr", 1), ("word ptr", 2), ("dword ptr", 4), ("qword ptr", 8)] {
+            if let Some(after) = text.strip_prefix(prefix) {
+                size = Some(bytes);
+                rest = after.trim();
+            }
+        }
+        if let Some(inner) = rest.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+            return self.memory(inner, size).map(Argument::Memory);
+        }
+        if size.is_some() {
+            return Err(format!("expected a memory operand after the size in '{}'", text));
+        }
+        if let Some(register) = parse_register(rest) {
+            return Ok(register);
+        }
+        if let Some(value) = parse_integer(rest) {
+            return Ok(Argument::Immediate(value));
+        }
+        // 1f and 1b: the next and the previous definition of the numeric label 1
+        if let Some((digits, direction)) = rest.split_at_checked(rest.len().saturating_sub(1)) {
+            if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) && (direction == "f" || direction == "b") {
+                let defined = self.numeric.get(digits).copied().unwrap_or(0);
+                return match direction {
+                    "f" => Ok(Argument::Label(format!("{}@{}", digits, defined))),
+                    _ if defined > 0 => Ok(Argument::Label(format!("{}@{}", digits, defined - 1))),
+                    _ => Err(format!("no label {} before", digits)),
+                };
+            }
+        }
+        if is_symbol(rest) {
+            return Ok(Argument::Label(rest.to_string()));
+        }
+        Err(format!("invalid operand '{}'", text))
+    }
+
+    fn memory(&self, inner: &str, size: Option<u8>) -> Result<Memory, String> {
+        let mut memory = Memory { size, base: None, index: None, displacement: 0, symbol: None };
+        let mut rip = false;
+        // Terms with their sign
+        let mut terms = Vec::new();
+        let mut start = 0;
+        let mut negative = false;
+        for (position, c) in inner.char_indices().chain([(inner.len(), '+')]) {
+            if c == '+' || c == '-' {
+                terms.push((negative, inner[start..position].trim()));
+                negative = c == '-';
+                start = position + 1;
+            }
+        }
+        for (negative, term) in terms {
+            if term.is_empty() {
+                continue;
+            }
+            if let Some(value) = parse_integer(term) {
+                memory.displacement += if negative { -value } else { value };
+                continue;
+            }
+            if negative {
+                return Err(format!("'{}' cannot be subtracted", term));
+            }
+            let (name, scale) = match term.split_once('*') {
+                Some((name, scale)) => (name.trim(), Some(parse_integer(scale).ok_or("invalid scale")? as u8)),
+                None => (term, None),
+            };
+            match (name, parse_register(name)) {
+                ("rip", _) => rip = true,
+                (_, Some(Argument::Register(Register { number, size: 8 }))) => match (memory.base, scale) {
+                    (None, None) => memory.base = Some(number),
+                    (_, _) if memory.index.is_none() => memory.index = Some((number, scale.unwrap_or(1))),
+                    _ => return Err("too many registers in the address".to_string()),
+                },
+                (_, Some(_)) => return Err(format!("'{}' cannot be used in an address", name)),
+                _ if is_symbol(name) && memory.symbol.is_none() => memory.symbol = Some(name.to_string()),
+                _ => return Err(format!("invalid address term '{}'", term)),
+            }
+        }
+        match (rip, &memory.symbol) {
+            (true, Some(_)) if memory.base.is_none() && memory.index.is_none() => Ok(memory),
+            (false, None) if memory.base.is_some() => Ok(memory),
+            (false, Some(symbol)) => Err(format!("'{}' must be addressed relative to rip", symbol)),
+            _ => Err("unsupported address".to_string()),
+        }
+    }
+
+    // Lays out the sections, patches the displacements and writes the ELF image
+    fn link(self) -> Result<Vec<u8>, CodegenError> {
+        let align = |value: usize, alignment: usize| value.div_ceil(alignment) * alignment;
+        let [text, rodata, data, bss] = &self.sections;
+        let mut offsets = [0usize; 4];
+        offsets[0] = align(ELF_HEADER_SIZE + 2 * PROGRAM_HEADER_SIZE, self.alignments[0]);
+        offsets[1] = align(offsets[0] + text.len(), self.alignments[1]);
+        offsets[2] = align(offsets[1] + rodata.len(), PAGE_SIZE);
+        offsets[3] = align(offsets[2] + data.len(), self.alignments[3]);
+        let address = |section: Section, offset: usize| BASE_ADDRESS + (offsets[section.index()] + offset) as u64;
+
+        let mut sections = self.sections.clone();
+        for fixup in &self.fixups {
+            let (section, offset) = *self.labels.get(&fixup.label)
+                .ok_or_else(|| CodegenError::Assembly(format!("line {}: unknown label '{}'", fixup.line, fixup.label)))?;
+            let target = address(section, offset) as i64 + fixup.addend;
+            let displacement = i32::try_from(target - address(fixup.section, fixup.end) as i64)
+                .map_err(|_| CodegenError::Assembly(format!("line {}: '{}' is out of reach", fixup.line, fixup.label)))?;
+            sections[fixup.section.index()][fixup.position..fixup.position + 4].copy_from_slice(&displacement.to_le_bytes());
+        }
+        let entry = match self.labels.get("_start") {
+            Some((section, offset)) => address(*section, *offset),
+            None => return Err(CodegenError::Assembly("no _start label".to_string())),
+        };
+
+        let mut image = Vec::new();
+        // ELF header: 64 bits, little endian, executable for x86-64 with two program headers
+        image.extend_from_slice(&[0x7F, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        image.extend_from_slice(&2u16.to_le_bytes());
+        image.extend_from_slice(&0x3Eu16.to_le_bytes());
+        image.extend_from_slice(&1u32.to_le_bytes());
+        image.extend_from_slice(&entry.to_le_bytes());
+        image.extend_from_slice(&(ELF_HEADER_SIZE as u64).to_le_bytes());
+        image.extend_from_slice(&0u64.to_le_bytes());
+        image.extend_from_slice(&0u32.to_le_bytes());
+        for field in [ELF_HEADER_SIZE, PROGRAM_HEADER_SIZE, 2, 64, 0, 0] {
+            image.extend_from_slice(&(field as u16).to_le_bytes());
+        }
+
+        // The code segment maps the headers as well, the data segment ends with .bss
+        let text_end = offsets[1] + rodata.len();
+        let data_end = offsets[3] + bss.len();
+        for (flags, offset, file_size, memory_size) in [(5u32, 0, text_end, text_end), (6, offsets[2], data.len(), data_end - offsets[2])] {
+            image.extend_from_slice(&1u32.to_le_bytes());
+            image.extend_from_slice(&flags.to_le_bytes());
+            image.extend_from_slice(&(offset as u64).to_le_bytes());
+            image.extend_from_slice(&(BASE_ADDRESS + offset as u64).to_le_bytes());
+            image.extend_from_slice(&(BASE_ADDRESS + offset as u64).to_le_bytes());
+            image.extend_from_slice(&(file_size as u64).to_le_bytes());
+            image.extend_from_slice(&(memory_size as u64).to_le_bytes());
+            image.extend_from_slice(&(PAGE_SIZE as u64).to_le_bytes());
+        }
+
+        for section in [Section::Text, Section::Rodata, Section::Data] {
+            image.resize(offsets[section.index()], 0);
+            image.extend_from_slice(&sections[section.index()]);
+        }
+        Ok(image)
+    }
+}
+
+// Machine code of one instruction
+fn encode(mnemonic: &str, arguments: &[Argument]) -> Result<Code, String> {
+    use Argument::*;
+
+    let mut code = Code {
+        force_rex: arguments.iter().any(|a| matches!(a, Register(self::Register { size: 1, number: 4..=7 }))),
+        ..Code::default()
+    };
+    let unsupported = || format!("unsupported operands for {}", mnemonic);
+    let sized = |argument: &Argument| argument.size().ok_or_else(|| format!("the operand size of {} is unknown", mnemonic));
+
+    match (mnemonic, arguments) {
+        ("ret", []) => code.push(&[0xC3]),
+        ("syscall", []) => code.push(&[0x0F, 0x05]),
+        ("nop", []) => code.push(&[0x90]),
+        ("cwd", []) => code.push(&[0x66, 0x99]),
+        ("cdq", []) => code.push(&[0x99]),
+        ("cqo", []) => code.push(&[0x48, 0x99]),
+        ("call", [Label(label)]) => {
+            code.push(&[0xE8]);
+            code.relative(label, 0);
+        },
+        ("jmp", [Label(label)]) => {
+            code.push(&[0xE9]);
+            code.relative(label, 0);
+        },
+        (_, [Label(label)]) if mnemonic.starts_with('j') && condition(&mnemonic[1..]).is_some() => {
+            code.push(&[0x0F, 0x80 + condition(&mnemonic[1..]).unwrap()]);
+            code.relative(label, 0);
+        },
+        (_, [operand]) if mnemonic.starts_with("set") && condition(&mnemonic[3..]).is_some() => {
+            if operand.size() != Some(1) {
+                return Err(unsupported());
+            }
+            code.modrm(&[], false, &[0x0F, 0x90 + condition(&mnemonic[3..]).unwrap()], 0, operand)?;
+        },
+        ("push" | "pop", [Register(register)]) if register.size == 8 => {
+            code.opcode_register(&[], false, if mnemonic == "push" { 0x50 } else { 0x58 }, register.number);
+        },
+
+        ("mov", [destination, Register(source)]) if destination.is_register_or_memory() => {
+            let (prefixes, wide) = size_prefix(source.size);
+            code.modrm(prefixes, wide, &[if source.size == 1 { 0x88 } else { 0x89 }], source.number, destination)?;
+        },
+        ("mov", [Register(destination), source @ Memory(_)]) => {
+            let (prefixes, wide) = size_prefix(destination.size);
+            code.modrm(prefixes, wide, &[if destination.size == 1 { 0x8A } else { 0x8B }], destination.number, source)?;
+        },
+        ("mov", [Register(destination), Immediate(value)]) => {
+            let (prefixes, wide) = size_prefix(destination.size);
+            match destination.size {
+                8 if i32::try_from(*value).is_ok() => {
+                    code.modrm(prefixes, true, &[0xC7], 0, &arguments[0])?;
+                    code.immediate(*value, 4)?;
+                },
+                1 => {
+                    code.opcode_register(prefixes, false, 0xB0, destination.number);
+                    code.immediate(*value, 1)?;
+                },
+                size => {
+                    code.opcode_register(prefixes, wide, 0xB8, destination.number);
+                    code.immediate(*value, size)?;
+                },
+            }
+        },
+        ("mov", [destination @ Memory(_), Immediate(value)]) => {
+            let size = sized(destination)?;
+            let (prefixes, wide) = size_prefix(size);
+            code.modrm(prefixes, wide, &[if size == 1 { 0xC6 } else { 0xC7 }], 0, destination)?;
+            code.sign_extended(*value, size)?;
+        },
+        ("movzx" | "movsx", [Register(destination), source]) if source.is_register_or_memory() => {
+            let size = sized(source)?;
+            if size > 2 || destination.size <= size {
+                return Err(unsupported());
+            }
+            let opcode = if mnemonic == "movzx" { 0xB6 } else { 0xBE } + (size == 2) as u8;
+            let (prefixes, wide) = size_prefix(destination.size);
+            code.modrm(prefixes, wide, &[0x0F, opcode], destination.number, source)?;
+        },
+        ("movsxd", [Register(destination), source]) if destination.size == 8 && source.is_register_or_memory() => {
+            code.modrm(&[], true, &[0x63], destination.number, source)?;
+        },
+        ("lea", [Register(destination), source @ Memory(_)]) if destination.size >= 4 => {
+            code.modrm(&[], destination.size == 8, &[0x8D], destination.number, source)?;
+        },
+
+        (_, [destination, source]) if ARITHMETIC.iter().any(|(name, _)| *name == mnemonic) && destination.is_register_or_memory() => {
+            let group = ARITHMETIC.iter().find(|(name, _)| *name == mnemonic).unwrap().1;
+            match source {
+                Register(source) => {
+                    let (prefixes, wide) = size_prefix(source.size);
+                    code.modrm(prefixes, wide, &[group * 8 + (source.size != 1) as u8], source.number, destination)?;
+                },
+                Memory(_) => {
+                    let Register(destination) = destination else {
+                        return Err(unsupported());
+                    };
+                    let (prefixes, wide) = size_prefix(destination.size);
+                    code.modrm(prefixes, wide, &[group * 8 + 2 + (destination.size != 1) as u8], destination.number, source)?;
+                },
+                Immediate(value) => {
+                    let size = sized(destination)?;
+                    let (prefixes, wide) = size_prefix(size);
+                    if size == 1 {
+                        code.modrm(prefixes, wide, &[0x80], group, destination)?;
+                        code.immediate(*value, 1)?;
+                    } else if i8::try_from(*value).is_ok() {
+                        code.modrm(prefixes, wide, &[0x83], group, destination)?;
+                        code.immediate(*value, 1)?;
+                    } else {
+                        code.modrm(prefixes, wide, &[0x81], group, destination)?;
+                        code.sign_extended(*value, size)?;
+                    }
+                },
+                _ => return Err(unsupported()),
+            }
+        },
+        ("test" | "xchg", [destination, Register(source)]) if destination.is_register_or_memory() => {
+            let (prefixes, wide) = size_prefix(source.size);
+            let opcode = if mnemonic == "test" { 0x84 } else { 0x86 } + (source.size != 1) as u8;
+            code.modrm(prefixes, wide, &[opcode], source.number, destination)?;
+        },
+        ("test", [destination, Immediate(value)]) if destination.is_register_or_memory() => {
+            let size = sized(destination)?;
+            let (prefixes, wide) = size_prefix(size);
+            code.modrm(prefixes, wide, &[if size == 1 { 0xF6 } else { 0xF7 }], 0, destination)?;
+            code.sign_extended(*value, size)?;
+        },
+        (_, [operand]) if UNARY.iter().any(|(name, _, _)| *name == mnemonic) && operand.is_register_or_memory() => {
+            let (_, opcode, extension) = *UNARY.iter().find(|(name, _, _)| *name == mnemonic).unwrap();
+            let size = sized(operand)?;
+            let (prefixes, wide) = size_prefix(size);
+            code.modrm(prefixes, wide, &[opcode + (size != 1) as u8], extension, operand)?;
+        },
+        ("imul", [Register(destination), source]) if destination.size > 1 && source.is_register_or_memory() => {
+            let (prefixes, wide) = size_prefix(destination.size);
+            code.modrm(prefixes, wide, &[0x0F, 0xAF], destination.number, source)?;
+        },
+        ("imul", [Register(destination), source, Immediate(value)]) if destination.size > 1 && source.is_register_or_memory() => {
+            let (prefixes, wide) = size_prefix(destination.size);
+            if i8::try_from(*value).is_ok() {
+                code.modrm(prefixes, wide, &[0x6B], destination.number, source)?;
+                code.immediate(*value, 1)?;
+            } else {
+                code.modrm(prefixes, wide, &[0x69], destination.number, source)?;
+                code.sign_extended(*value, destination.size)?;
+            }
+        },
+        ("bt" | "bts" | "btr" | "btc", [operand, Immediate(bit)]) if operand.is_register_or_memory() => {
+            let extension = match mnemonic {
+                "bt" => 4,
+                "bts" => 5,
+                "btr" => 6,
+                _ => 7,
+            };
+            let (prefixes, wide) = size_prefix(sized(operand)?);
+            code.modrm(prefixes, wide, &[0x0F, 0xBA], extension, operand)?;
+            code.immediate(*bit, 1)?;
+        },
+
+        ("movss" | "movsd" | "movaps", [destination @ Memory(_), Xmm(source)]) => {
+            let (_, prefix, opcode) = *SSE.iter().find(|(name, _, _)| *name == mnemonic).unwrap();
+            let prefixes: &[u8] = if prefix == 0 { &[] } else { &[prefix] };
+            code.modrm(prefixes, false, &[0x0F, opcode + 1], *source, destination)?;
+        },
+        (_, [Xmm(destination), source @ (Xmm(_) | Memory(_))]) if SSE.iter().any(|(name, _, _)| *name == mnemonic) => {
+            let (_, prefix, opcode) = *SSE.iter().find(|(name, _, _)| *name == mnemonic).unwrap();
+            let prefixes: &[u8] = if prefix == 0 { &[] } else { &[prefix] };
+            code.modrm(prefixes, false, &[0x0F, opcode], *destination, source)?;
+        },
+        ("cvtsi2ss" | "cvtsi2sd", [Xmm(destination), source]) if source.is_register_or_memory() => {
+            let prefix = if mnemonic == "cvtsi2ss" { 0xF3 } else { 0xF2 };
+            code.modrm(&[prefix], sized(source)? == 8, &[0x0F, 0x2A], *destination, source)?;
+        },
+        ("cvtss2si" | "cvtsd2si" | "cvttss2si" | "cvttsd2si", [Register(destination), source @ (Xmm(_) | Memory(_))]) if destination.size >= 4 => {
+            let prefix = if mnemonic.contains("ss2") { 0xF3 } else { 0xF2 };
+            let opcode = if mnemonic.starts_with("cvtt") { 0x2C } else { 0x2D };
+            code.modrm(&[prefix], destination.size == 8, &[0x0F, opcode], destination.number, source)?;
+        },
+        ("movq" | "movd", [Xmm(destination), source @ Register(_)]) => {
+            code.modrm(&[0x66], mnemonic == "movq", &[0x0F, 0x6E], *destination, source)?;
+        },
+        ("movq" | "movd", [destination @ Register(_), Xmm(source)]) => {
+            code.modrm(&[0x66], mnemonic == "movq", &[0x0F, 0x7E], *source, destination)?;
+        },
+        _ if arguments.is_empty() => return Err(format!("unknown instruction '{}'", mnemonic)),
+        _ => return Err(unsupported()),
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use crate::codegen::regalloc::{allocate, Strategy};
+    use crate::codegen::x86_64::ALLOCATABLE_REGISTERS;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+    use crate::Test::tests::{generate_source, FAILING_PROGRAM, QUADRUPLETS};
+
+    fn hex(line: &str) -> String {
+        let assembler = Assembler::new();
+        let (mnemonic, operands) = line.split_once(' ').unwrap_or((line, ""));
+        let arguments: Vec<Argument> = operands.split(',').filter(|o| !o.trim().is_empty()).map(|o| assembler.argument(o).unwrap()).collect();
+        let code = encode(mnemonic, &arguments).unwrap_or_else(|e| panic!("{}: {}", line, e));
+        code.bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    // Runs an image on the host, None when it cannot execute x86-64 Linux programs
+    fn run(image: &[u8], input: &str) -> Option<(String, String, i32)> {
+        if !cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+            return None;
+        }
+        let directory = std::env::temp_dir().join(format!("mining_elf_{}_{}", std::process::id(), image.len()));
+        std::fs::create_dir_all(&directory).ok()?;
+        let program = directory.join("p");
+        std::fs::write(&program, image).ok()?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).ok()?;
+        }
+
+        let mut child = Command::new(&program).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().ok()?;
+        child.stdin.take()?.write_all(input.as_bytes()).ok()?;
+        let output = child.wait_with_output().ok()?;
+        std::fs::remove_dir_all(&directory).ok();
+        Some((String::from_utf8_lossy(&output.stdout).to_string(), String::from_utf8_lossy(&output.stderr).to_string(), output.status.code()?))
+    }
+
+    #[test]
+    fn test_encoding() {
+        // Bytes produced by GNU as for the same lines
+        for (line, expected) in [
+            ("btr rax, 63", "480fbaf03f"),
+            ("cmp cx, -1", "6683f9ff"),
+            ("cmp r9d, 32768", "4181f900800000"),
+            ("cvtsd2si r10, xmm0", "f24c0f2dd0"),
+            ("cvtsi2sd xmm0, r9", "f2490f2ac1"),
+            ("cwd", "6699"),
+            ("divsd xmm0, qword ptr [rcx + r12*8]", "f2420f5e04e1"),
+            ("idiv cx", "66f7f9"),
+            ("imul r12d, dword ptr [rbx + 4], 1000", "44696304e8030000"),
+            ("imul r9, r9, 10", "4d6bc90a"),
+            ("inc qword ptr [rsp + 8]", "48ff442408"),
+            ("lea ecx, [rax - 9]", "8d48f7"),
+            ("lea r10, [rbp + r13*4 - 8]", "4e8d54adf8"),
+            ("mov ax, 300", "66b82c01"),
+            ("mov byte ptr [rbp + 0], dil", "40887d00"),
+            ("mov byte ptr [rsi], dl", "8816"),
+            ("mov eax, -1", "b8ffffffff"),
+            ("mov qword ptr [rsp + 8], 0", "48c744240800000000"),
+            ("mov rcx, 0x7FF0000000000000", "48b9000000000000f07f"),
+            ("mov sil, 3", "40b603"),
+            ("movaps xmm5, xmm0", "0f28e8"),
+            ("movq rax, xmm9", "664c0f7ec8"),
+            ("movq xmm12, r11", "664d0f6ee3"),
+            ("movss dword ptr [rsp + 200], xmm0", "f30f118424c8000000"),
+            ("movsx r11, word ptr [r12]", "4d0fbf1c24"),
+            ("movsxd r11, r13d", "4d63dd"),
+            ("movzx edx, byte ptr [rcx + rax]", "0fb61401"),
+            ("pop r13", "415d"),
+            ("push r12", "4154"),
+            ("sete r9b", "410f94c1"),
+            ("setnp cl", "0f9bc1"),
+            ("sub rsp, 2048", "4881ec00080000"),
+            ("ucomiss xmm1, xmm0", "0f2ec8"),
+        ] {
+            assert_eq!(hex(line), expected, "{}", line);
+        }
+        // Jumps always take 32 bits displacements
+        assert_eq!(hex("jmp .Lq0"), "e900000000");
+        assert_eq!(hex("jae mining_index_error"), "0f8300000000");
+    }
+
+    #[test]
+    fn test_layout() {
+        let image = assemble(r#"
+            .intel_syntax noprefix
+            .text
+            .globl _start
+        _start:
+            lea rsi, [rip + message]
+        1:  jmp 1f
+            jmp 1b
+        1:  call done
+        done:
+            ret
+            .section .rodata
+        message: .ascii "a#b\n"    # comment
+            .data
+            .balign 4
+        value: .word -2
+            .bss
+        buffer: .zero 100
+        "#).unwrap();
+        assert_eq!(&image[..4], b"\x7fELF");
+        let u64_at = |offset: usize| u64::from_le_bytes(image[offset..offset + 8].try_into().unwrap());
+        let text = ELF_HEADER_SIZE + 2 * PROGRAM_HEADER_SIZE;
+        let text = text.div_ceil(16) * 16;
+        assert_eq!(u64_at(24), BASE_ADDRESS + text as u64);
+        // lea (7 bytes), jmp 1f (5), jmp 1b (5), call (5), ret: message follows at 23
+        let code = &image[text..text + 23];
+        assert_eq!(&code[3..7], &16i32.to_le_bytes());
+        assert_eq!(&code[8..12], &5i32.to_le_bytes());
+        assert_eq!(&code[13..17], &(-10i32).to_le_bytes());
+        assert_eq!(&code[18..22], &0i32.to_le_bytes());
+        assert_eq!(&image[text + 23..text + 27], b"a#b\n");
+
+        // The data segment starts on a new page, .bss only takes memory
+        let data = PAGE_SIZE;
+        assert_eq!(image.len(), data + 2);
+        assert_eq!(&image[data..], &(-2i16).to_le_bytes());
+        let data_header = ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE;
+        assert_eq!(u64_at(data_header + 16), BASE_ADDRESS + data as u64);
+        assert_eq!((u64_at(data_header + 32), u64_at(data_header + 40)), (2, 102));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| match assemble(source) {
+            Err(CodegenError::Assembly(message)) => message,
+            other => panic!("{:?}", other),
+        };
+        assert!(error("_start:\n    frobnicate").contains("line 2 `frobnicate`: unknown instruction"));
+        assert!(error("_start:\n    jmp nowhere").contains("unknown label 'nowhere'"));
+        assert!(error("    ret").contains("no _start label"));
+        assert!(error("_start:\n    mov rax, qword ptr [data]").contains("relative to rip"));
+        assert!(error("_start:\n    add rax, 0x80000000").contains("does not fit in 32 bits"));
+        assert!(error("_start:\n    mov qword ptr [rsp], rax, 1").contains("unsupported operands for mov"));
+    }
+
+    #[test]
+    fn test_build_executable() {
+        let quads = parse_quadruplets(QUADRUPLETS).unwrap();
+        for allocation in [Allocation::default(), allocate(&quads, ALLOCATABLE_REGISTERS, Strategy::GraphColoring)] {
+            let image = build_executable(&quads, &allocation).unwrap();
+            let Some((output, _, code)) = run(&image, "-7 2.25\n a") else {
+                return;
+            };
+            assert_eq!((output.as_str(), code), ("S = -21\n1.5b13", 0));
+
+            let (output, errors, code) = run(&image, "0 1e9 x").unwrap();
+            assert_eq!(output, "");
+            assert_eq!((errors.as_str(), code), ("Runtime error: Invalid FLOAT input\n", 1));
+
+            let (output, errors, code) = run(&image, "0 1.0 x").unwrap();
+            assert_eq!(output, "S = 0\n0.666667y0");
+            assert_eq!((errors.as_str(), code), ("Runtime error: Division by zero\n", 1));
+        }
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let quads = generate_source(FAILING_PROGRAM);
+        for allocation in [Allocation::default(), allocate(&quads, ALLOCATABLE_REGISTERS, Strategy::GraphColoring)] {
+            let image = build_executable(&quads, &allocation).unwrap();
+            let error = |input: &str| run(&image, input).map(|(_, errors, code)| (errors, code));
+            let Some((errors, code)) = error("1") else {
+                return;
+            };
+            let stack = "\n    in FOR at line 10, column 13\
+                         \n    in IF at line 9, column 9\
+                         \n    in FOR at line 8, column 5\n";
+            assert_eq!((errors, code), (format!("Runtime error: Division by zero at line 11, column 28{}", stack), 1));
+            assert_eq!(error("32767").unwrap().0, format!("Runtime error: INTEGER overflow at line 11, column 33{}", stack));
+            assert_eq!(error("5").unwrap().0, "Runtime error: Array index out of bounds at line 15, column 5\n");
+            assert_eq!(error("2 x").unwrap().0, "Runtime error: Invalid INTEGER input at line 16, column 10\n");
+            assert_eq!(error("2").unwrap().0, "Runtime error: Unexpected end of input at line 16, column 10\n");
+        }
+    }
+}
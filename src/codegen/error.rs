@@ -0,0 +1,199 @@
+use std::fmt;
+use crate::Semantic::error::ArithmeticError;
+use crate::Semantic::quadruplets::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CodegenError {
+    #[error("Cannot generate code for quadruplet {0} {1}: {2}")]
+    Unsupported(usize, String, String),
+
+    #[error("Cannot generate code for undeclared name '{0}'")]
+    UndeclaredName(String),
+
+    #[error("Cannot generate code for an invalid expression: {0}")]
+    InvalidExpression(String),
+
+    #[error("Cannot assemble the program: {0}")]
+    Assembly(String),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SimulationError {
+    #[error("Invalid assembly at line {0}: {1}")]
+    Syntax(usize, String),
+
+    #[error("Unknown label '{1}' at line {0}")]
+    UnknownLabel(usize, String),
+
+    #[error("Fault at line {0}: {1}")]
+    Fault(usize, String),
+
+    #[error("The program did not stop after {0} instructions")]
+    StepLimit(u64),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum BytecodeError {
+    #[error("Not a MinING bytecode file")]
+    BadMagic,
+
+    #[error("Unsupported bytecode version {0}")]
+    UnsupportedVersion(u16),
+
+    #[error("Truncated bytecode file")]
+    Truncated,
+
+    #[error("Invalid bytecode at offset {0}: {1}")]
+    Invalid(usize, String),
+}
+
+/// Errors stopping a running program, printed as `Runtime error: <message>`
+#[derive(Debug, Error, PartialEq)]
+pub enum RuntimeError {
+    #[error("Division by zero")]
+    DivisionByZero,
+
+    #[error("INTEGER overflow")]
+    Overflow,
+
+    #[error("Array index out of bounds")]
+    IndexOutOfBounds,
+
+    #[error("Invalid INTEGER input")]
+    InvalidInteger,
+
+    #[error("Invalid FLOAT input")]
+    InvalidFloat,
+
+    #[error("Unexpected end of input")]
+    EndOfInput,
+
+    #[error("Invalid program: {0}")]
+    InvalidProgram(String),
+
+    #[error("I/O error: {0}")]
+    Io(String),
+
+    #[error("The program did not stop after {0} instructions")]
+    StepLimit(u64),
+
+    #[error("Program stopped by the debugger")]
+    Stopped,
+}
+
+impl From<ArithmeticError> for RuntimeError {
+    fn from(error: ArithmeticError) -> Self {
+        match error {
+            ArithmeticError::DivisionByZero => RuntimeError::DivisionByZero,
+            ArithmeticError::Overflow => RuntimeError::Overflow,
+            ArithmeticError::InvalidOperands(operator) => RuntimeError::InvalidProgram(format!("invalid operands for {}", operator)),
+        }
+    }
+}
+
+impl From<std::io::Error> for RuntimeError {
+    fn from(error: std::io::Error) -> Self {
+        RuntimeError::Io(error.to_string())
+    }
+}
+
+/// Line and column in the source, counted from 1 unlike the positions recorded by the lexer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<(usize, usize)> for Position {
+    fn from((line, column): (usize, usize)) -> Self {
+        Position { line: line + 1, column: column + 1 }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// An IF or FOR statement enclosing the instruction being executed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    If(Position),
+    For { position: Position, counter: String, iteration: usize, value: Value },
+    /// A FOR statement of compiled code, which does not keep track of the iterations
+    Loop(Position),
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Frame::If(position) => write!(f, "in IF at {}", position),
+            Frame::For { position, counter, iteration, value } => {
+                write!(f, "in FOR at {}, iteration {} with {} = {}", position, iteration, counter, value)
+            },
+            Frame::Loop(position) => write!(f, "in FOR at {}", position),
+        }
+    }
+}
+
+/// Place in the source an intermediate instruction was generated from, with the IF and FOR
+/// statements enclosing it, innermost first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub position: Position,
+    pub stack: Vec<Frame>,
+}
+
+impl Origin {
+    pub fn locate(&self, error: impl Into<RuntimeError>) -> LocatedError {
+        LocatedError { error: error.into(), position: Some(self.position), stack: self.stack.clone() }
+    }
+
+    /// Text following the message of a runtime error coming from here, as printed by compiled programs
+    pub fn location(&self) -> String {
+        location(Some(self.position), &self.stack)
+    }
+}
+
+/// Frames shown under a located runtime error, the innermost ones
+const SHOWN_FRAMES: usize = 4;
+
+/// A runtime error with the place in the source where it happened
+/// and the IF and FOR statements enclosing it, innermost first
+#[derive(Debug, PartialEq)]
+pub struct LocatedError {
+    pub error: RuntimeError,
+    pub position: Option<Position>,
+    pub stack: Vec<Frame>,
+}
+
+// The position, then a line per frame
+fn location(position: Option<Position>, stack: &[Frame]) -> String {
+    let mut text = String::new();
+    if let Some(position) = position {
+        text.push_str(&format!(" at {}", position));
+    }
+    for frame in stack.iter().take(SHOWN_FRAMES) {
+        text.push_str(&format!("\n    {}", frame));
+    }
+    if stack.len() > SHOWN_FRAMES {
+        text.push_str(&format!("\n    ... {} more", stack.len() - SHOWN_FRAMES));
+    }
+    text
+}
+
+impl fmt::Display for LocatedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.error, location(self.position, &self.stack))
+    }
+}
+
+impl std::error::Error for LocatedError {}
+
+impl From<RuntimeError> for LocatedError {
+    fn from(error: RuntimeError) -> Self {
+        LocatedError { error, position: None, stack: Vec::new() }
+    }
+}
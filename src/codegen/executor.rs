@@ -0,0 +1,277 @@
+//! Executor running the quadruplets directly, without going through a backend.
+//! It gives the reference behaviour of the intermediate code, so a program can be run
+//! before and after an optimization pass on the same input to catch miscompilations
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, Write};
+use crate::codegen::error::RuntimeError;
+use crate::codegen::runtime::{write_value, zero, Input};
+use crate::Semantic::quadruplets::{collect_symbols, jump_target, Operand, Operator, Quadruplet, Value};
+use crate::Semantic::ts::Types;
+
+/// Runs the quadruplets until the end of the program, READ takes its tokens from `input` and
+/// WRITE goes to `output`. Stops after `limit` quadruplets, returns the number executed
+pub fn run_quadruplets<R: BufRead, W: Write>(quadruplets: &[Quadruplet], input: R, output: &mut W, limit: u64) -> Result<u64, RuntimeError> {
+    let mut executor = Executor::new(quadruplets, input);
+    let result = executor.run(output, limit);
+    output.flush()?;
+    result
+}
+
+/// Everything a run of the quadruplets did, as compared between two versions of a program
+#[derive(Debug, PartialEq)]
+pub struct Execution {
+    pub output: Vec<u8>,
+    pub result: Result<u64, RuntimeError>,
+}
+
+impl Execution {
+    /// Runs the quadruplets on an input given in memory
+    pub fn capture(quadruplets: &[Quadruplet], input: &[u8], limit: u64) -> Execution {
+        let mut output = Vec::new();
+        let result = run_quadruplets(quadruplets, input, &mut output, limit);
+        Execution { output, result }
+    }
+
+    /// How `other` behaves differently, the number of quadruplets executed does not count
+    pub fn difference(&self, other: &Execution) -> Option<String> {
+        if self.output != other.output {
+            let expected = String::from_utf8_lossy(&self.output);
+            let found = String::from_utf8_lossy(&other.output);
+            let mut lines = expected.split_inclusive('\n').zip(found.split_inclusive('\n'));
+            let line = lines.position(|(a, b)| a != b).unwrap_or_else(|| expected.lines().count().min(found.lines().count()));
+            return Some(format!(
+                "line {} of the output is {:?} instead of {:?}",
+                line + 1,
+                found.split_inclusive('\n').nth(line).unwrap_or(""),
+                expected.split_inclusive('\n').nth(line).unwrap_or(""),
+            ));
+        }
+        match (&self.result, &other.result) {
+            (Ok(_), Ok(_)) => None,
+            (a, b) if a.as_ref().err() == b.as_ref().err() => None,
+            _ => Some(format!("it ends with {} instead of {}", Outcome(&other.result), Outcome(&self.result))),
+        }
+    }
+}
+
+struct Outcome<'a>(&'a Result<u64, RuntimeError>);
+
+impl fmt::Display for Outcome<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Ok(_) => write!(f, "success"),
+            Err(e) => write!(f, "\"Runtime error: {}\"", e),
+        }
+    }
+}
+
+// Where a value is stored: a cell of a variable, array or temporary
+struct Location {
+    name: String,
+    cell: usize,
+    value_type: Types,
+}
+
+struct Executor<'a, R> {
+    quadruplets: &'a [Quadruplet],
+    memory: HashMap<String, Vec<Value>>,   // Cells of every name, a single one for scalars
+    input: Input<R>,
+}
+
+fn name(operand: &Operand) -> Option<String> {
+    match operand {
+        Operand::Variable(name, _) | Operand::ArrayElement(name, _, _) => Some(name.clone()),
+        Operand::Temporary(number, _) => Some(format!("t{}", number)),
+        _ => None,
+    }
+}
+
+impl<'a, R: BufRead> Executor<'a, R> {
+    fn new(quadruplets: &'a [Quadruplet], input: R) -> Self {
+        let memory = collect_symbols(quadruplets).into_iter().map(|(name, symbol_type)| {
+            let cells = match &symbol_type {
+                Types::Array(element, size) => vec![zero(element); (*size).max(0) as usize],
+                scalar => vec![zero(scalar)],
+            };
+            (name, cells)
+        }).collect();
+        Executor { quadruplets, memory, input: Input::new(input) }
+    }
+
+    fn location(&self, operand: &Operand) -> Result<Location, RuntimeError> {
+        let value_type = operand.value_type()
+            .ok_or_else(|| RuntimeError::InvalidProgram(format!("{} is not a location", operand)))?;
+        let name = name(operand).ok_or_else(|| RuntimeError::InvalidProgram(format!("{} is not a location", operand)))?;
+        let cells = &self.memory[&name];
+        let cell = match operand {
+            Operand::ArrayElement(_, _, index) => match self.value(index)? {
+                Value::Integer(index) if index >= 0 && (index as usize) < cells.len() => index as usize,
+                Value::Integer(_) => return Err(RuntimeError::IndexOutOfBounds),
+                _ => return Err(RuntimeError::InvalidProgram("array indices are INTEGER values".to_string())),
+            },
+            _ if matches!(value_type, Types::Array(_, _)) => {
+                return Err(RuntimeError::InvalidProgram(format!("whole array {} used as a value", name)));
+            },
+            _ => 0,
+        };
+        Ok(Location { name, cell, value_type })
+    }
+
+    fn value(&self, operand: &Operand) -> Result<Value, RuntimeError> {
+        match operand {
+            Operand::Constant(value) => Ok(*value),
+            Operand::String(_) | Operand::Label(_) => Err(RuntimeError::InvalidProgram(format!("{} is not a value", operand))),
+            _ => {
+                let location = self.location(operand)?;
+                Ok(self.memory[&location.name][location.cell])
+            },
+        }
+    }
+
+    // Values keep the type of their location
+    fn store(&mut self, location: Location, value: Value) -> Result<(), RuntimeError> {
+        if value.get_type() != location.value_type {
+            return Err(RuntimeError::InvalidProgram(format!("{:?} value stored in {}", value.get_type(), location.name)));
+        }
+        self.memory.get_mut(&location.name).unwrap()[location.cell] = value;
+        Ok(())
+    }
+
+    fn run<W: Write>(&mut self, output: &mut W, limit: u64) -> Result<u64, RuntimeError> {
+        let missing = |what: &str, index: usize| RuntimeError::InvalidProgram(format!("missing {} in quadruplet {}", what, index));
+        let mut pc = 0;
+        let mut steps = 0;
+        while pc < self.quadruplets.len() {
+            if steps == limit {
+                return Err(RuntimeError::StepLimit(limit));
+            }
+            steps += 1;
+            let index = pc;
+            let quadruplet = &self.quadruplets[index];
+            let operand1 = || quadruplet.operand1.as_ref().ok_or_else(|| missing("operand", index));
+            let result = || quadruplet.result.as_ref().ok_or_else(|| missing("result", index));
+            pc += 1;
+            match &quadruplet.operator {
+                operator if operator.is_binary() => {
+                    let left = self.value(operand1()?)?;
+                    let right = self.value(quadruplet.operand2.as_ref().ok_or_else(|| missing("operand", index))?)?;
+                    let value = left.binary(operator, &right)?;
+                    let location = self.location(result()?)?;
+                    self.store(location, value)?;
+                },
+                Operator::LogicalNot => {
+                    let value = Value::Integer(!self.value(operand1()?)?.is_true() as i16);
+                    let location = self.location(result()?)?;
+                    self.store(location, value)?;
+                },
+                // The value is computed before the index of the target is checked
+                Operator::Assign => {
+                    let value = self.value(operand1()?)?;
+                    let location = self.location(result()?)?;
+                    self.store(location, value)?;
+                },
+                // The index is checked before reading
+                Operator::Read => {
+                    let location = self.location(result()?)?;
+                    let value = self.input.read(&location.value_type)?;
+                    self.store(location, value)?;
+                },
+                Operator::Write => match operand1()? {
+                    Operand::String(text) => output.write_all(text.as_bytes())?,
+                    operand => write_value(output, &self.value(operand)?)?,
+                },
+                Operator::Goto | Operator::IfTrue | Operator::IfFalse => {
+                    let target = jump_target(quadruplet).ok_or_else(|| missing("target", index))?;
+                    if target > self.quadruplets.len() {
+                        return Err(RuntimeError::InvalidProgram(format!("jump to {} past the end of the program", target)));
+                    }
+                    let taken = match quadruplet.operator {
+                        Operator::IfTrue => self.value(operand1()?)?.is_true(),
+                        Operator::IfFalse => !self.value(operand1()?)?.is_true(),
+                        _ => true,
+                    };
+                    if taken {
+                        pc = target;
+                    }
+                },
+                operator => return Err(RuntimeError::InvalidProgram(format!("{:?} quadruplet", operator))),
+            }
+        }
+        Ok(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::{parse_quadruplets, QuadrupletGenerator};
+    use crate::Test::tests::{analyze_source, PROGRAM};
+
+    fn run(quadruplets: &[Quadruplet], input: &str) -> (String, Result<u64, RuntimeError>) {
+        let execution = Execution::capture(quadruplets, input.as_bytes(), 10_000);
+        (String::from_utf8(execution.output).unwrap(), execution.result)
+    }
+
+    #[test]
+    fn test_run_quadruplets() {
+        let quads = parse_quadruplets(r#"
+            INTEGER N, I, t1, t2, t3;
+            FLOAT F, t4;
+            CHAR C, t5;
+            INTEGER A[3];
+            0: (Read, _, _, N)
+            1: (Read, _, _, F)
+            2: (Assign, 0, _, I)
+            3: (LessThan, I, 3, t1)
+            4: (IfFalse, t1, _, 11)
+            5: (Multiply, I, N, t2)
+            6: (Assign, t2, _, A[I])
+            7: (Write, A[I], _, _)
+            8: (Add, I, 1, t3)
+            9: (Assign, t3, _, I)
+            10: (Goto, _, _, 3)
+            11: (Divide, F, 4.0, t4)
+            12: (Add, 'a', 'b', t5)
+            13: (Write, " ", _, _)
+            14: (Write, t4, _, _)
+            15: (Write, t5, _, _)
+            16: (Assign, 5, _, A[N])
+        "#).unwrap();
+        assert_eq!(run(&quads, "-7 3"), ("0-7-14 0.75D".to_string(), Err(RuntimeError::IndexOutOfBounds)));
+        assert_eq!(run(&quads, "1 1.0"), ("012 0.25D".to_string(), Ok(35)));
+        assert_eq!(run(&quads, "20000 0"), ("020000".to_string(), Err(RuntimeError::Overflow)));
+        assert_eq!(run(&quads, "1 x"), (String::new(), Err(RuntimeError::InvalidFloat)));
+        assert_eq!(run(&quads[..11], "1 1.0").1, Ok(29));
+
+        // A loop that never ends stops at the limit
+        let quads = parse_quadruplets("0: (Goto, _, _, 0)").unwrap();
+        assert_eq!(run(&quads, "").1, Err(RuntimeError::StepLimit(10_000)));
+    }
+
+    #[test]
+    fn test_generated_quadruplets() {
+        // The intermediate code of the shared program behaves like its syntax tree
+        let (program, _) = analyze_source(PROGRAM);
+        let mut generator = QuadrupletGenerator::new();
+        generator.generate_program(&program).unwrap();
+        let quads = generator.into_quadruplets();
+        let (output, result) = run(&quads, "-7 2.25\n a");
+        assert_eq!((output.as_str(), result.is_ok()), ("S = -21 100%\n2ab\n1.5D\nyes\n3\n", true));
+        assert_eq!(run(&quads, "-1 1.0 x"), ("S = -3 100%\n2ab\n0.666667[\nno\n".to_string(), Err(RuntimeError::DivisionByZero)));
+    }
+
+    #[test]
+    fn test_difference() {
+        let execution = |output: &str, result| Execution { output: output.as_bytes().to_vec(), result };
+        let reference = execution("a\nb\n", Ok(10));
+        assert_eq!(reference.difference(&execution("a\nb\n", Ok(7))), None);
+        assert_eq!(reference.difference(&execution("a\nc\n", Ok(10))), Some("line 2 of the output is \"c\\n\" instead of \"b\\n\"".to_string()));
+        assert_eq!(reference.difference(&execution("a\n", Ok(10))), Some("line 2 of the output is \"\" instead of \"b\\n\"".to_string()));
+        assert_eq!(
+            reference.difference(&execution("a\nb\n", Err(RuntimeError::Overflow))),
+            Some("it ends with \"Runtime error: INTEGER overflow\" instead of success".to_string()),
+        );
+    }
+}
@@ -0,0 +1,589 @@
+//! 8086 backend: MASM 6 assembly for a DOS .EXE in the small memory model
+//!
+//! INTEGER values fit the 16 bits registers and CHAR values are bytes. The 8086 has no floating
+//! point instructions, so programs using FLOAT are rejected. Each variable, array and temporary
+//! is a `DW` or `DB` in the data segment, READ and WRITE call a small runtime appended to the
+//! program which goes through the INT 21h services of DOS:
+//!
+//! - 3Fh reads the standard input (handle 0) in a buffer
+//! - 40h writes to the standard output (handle 1) or the standard error (handle 2)
+//! - 4Ch ends the program with an exit code
+//!
+//! Conditional jumps may go further than 128 bytes, they rely on MASM lengthening them. The
+//! output runs in `codegen::sim8086` as well as under DOS
+
+use std::collections::HashSet;
+use crate::codegen::error::CodegenError;
+use crate::codegen::{is_signed, static_initializers, symbol};
+use crate::codegen::x86_64::RUNTIME_ERRORS;
+use crate::Semantic::quadruplets::{collect_symbols, jump_target, Operand, Operator, Quadruplet, Value};
+use crate::Semantic::ts::Types;
+
+/// READ and WRITE procedures, they may clobber AX, BX, CX and DX
+const RUNTIME: &str = r#"
+; MinING runtime, DOS INT 21h services only
+mining_write_string PROC                ; DX = address, CX = length
+    mov ah, 40h
+    mov bx, 1
+    int 21h
+    ret
+mining_write_string ENDP
+
+mining_write_char PROC                  ; AL = CHAR
+    mov mining_output, al
+    mov dx, OFFSET mining_output
+    mov cx, 1
+    jmp mining_write_string
+mining_write_char ENDP
+
+mining_write_int PROC                   ; AX = INTEGER
+    push di
+    mov di, OFFSET mining_output + 8    ; digits are written backwards from the end of the buffer
+    mov bx, ax                          ; keeps the sign
+    test ax, ax
+    jns mining_write_int_digits
+    neg ax                              ; -32768 gives 8000h, read as unsigned below
+mining_write_int_digits:
+    mov cx, 10
+mining_write_int_loop:
+    xor dx, dx
+    div cx
+    add dl, '0'
+    dec di
+    mov [di], dl
+    test ax, ax
+    jnz mining_write_int_loop
+    test bx, bx
+    jns mining_write_int_print
+    dec di
+    mov BYTE PTR [di], '-'
+mining_write_int_print:
+    mov dx, di
+    mov cx, OFFSET mining_output + 8
+    sub cx, di
+    pop di
+    jmp mining_write_string
+mining_write_int ENDP
+
+mining_getc PROC                        ; AX = next input byte, -1 at the end of the input
+    mov bx, mining_input_position
+    cmp bx, mining_input_length
+    jb mining_getc_byte
+    mov ah, 3Fh
+    xor bx, bx
+    mov cx, 128
+    mov dx, OFFSET mining_input
+    int 21h
+    mov mining_input_position, 0
+    mov mining_input_length, 0
+    jc mining_getc_end
+    test ax, ax
+    jz mining_getc_end
+    mov mining_input_length, ax
+    xor bx, bx
+mining_getc_byte:
+    mov al, mining_input[bx]
+    xor ah, ah
+    inc bx
+    mov mining_input_position, bx
+    ret
+mining_getc_end:
+    mov ax, -1
+    ret
+mining_getc ENDP
+
+mining_skip_spaces PROC                 ; AX = first input byte that is not a space
+mining_skip_spaces_loop:
+    call mining_getc
+    cmp ax, ' '
+    je mining_skip_spaces_loop
+    mov cx, ax
+    sub cx, 9
+    cmp cx, 4                           ; tab, line feed, vertical tab, form feed, carriage return
+    jbe mining_skip_spaces_loop
+    cmp ax, -1
+    je mining_end_of_input_error
+    ret
+mining_skip_spaces ENDP
+
+mining_end_of_token PROC                ; ZF set when AX is a space or the end of the input
+    cmp ax, -1
+    je mining_end_of_token_done
+    cmp ax, ' '
+    je mining_end_of_token_done
+    mov cx, ax
+    sub cx, 9
+    cmp cx, 4
+    ja mining_end_of_token_done
+    cmp ax, ax
+mining_end_of_token_done:
+    ret
+mining_end_of_token ENDP
+
+mining_read_int PROC                    ; AX = INTEGER
+    push si
+    push di
+    push bp
+    call mining_skip_spaces
+    xor si, si                          ; 1 for a negative value
+    cmp ax, '+'
+    je mining_read_int_sign
+    cmp ax, '-'
+    jne mining_read_int_start
+    inc si
+mining_read_int_sign:
+    call mining_getc
+mining_read_int_start:
+    xor di, di                          ; value
+    xor bp, bp                          ; number of digits
+mining_read_int_loop:
+    sub ax, '0'
+    cmp ax, 9
+    ja mining_read_int_end
+    mov cx, ax
+    mov ax, 10
+    mul di
+    jc mining_integer_input_error
+    add ax, cx
+    jc mining_integer_input_error
+    cmp ax, 32768
+    ja mining_integer_input_error
+    mov di, ax
+    inc bp
+    call mining_getc
+    jmp mining_read_int_loop
+mining_read_int_end:
+    add ax, '0'
+    call mining_end_of_token
+    jne mining_integer_input_error
+    test bp, bp
+    jz mining_integer_input_error
+    mov ax, di
+    test si, si
+    jz mining_read_int_positive
+    neg ax
+    jmp mining_read_int_done
+mining_read_int_positive:
+    cmp ax, 32767
+    ja mining_integer_input_error
+mining_read_int_done:
+    pop bp
+    pop di
+    pop si
+    ret
+mining_read_int ENDP
+
+mining_read_char PROC                   ; AL = CHAR
+    jmp mining_skip_spaces
+mining_read_char ENDP
+
+mining_fail PROC                        ; DX = message, CX = length
+    mov ah, 40h
+    mov bx, 2
+    int 21h
+    mov ax, 4C01h
+    int 21h
+mining_fail ENDP
+"#;
+
+/// Lowers the quadruplets to a MASM program, `main` being the first quadruplet
+pub fn generate_8086(quadruplets: &[Quadruplet]) -> Result<String, CodegenError> {
+    let mut generator = I8086Generator::new(quadruplets);
+    generator.generate()?;
+    Ok(generator.finish())
+}
+
+// Operands of DB for a text, printable runs are quoted and line feeds become CR LF for DOS
+fn bytes_directive(text: &str) -> String {
+    let mut items = Vec::new();
+    let mut run = String::new();
+    for byte in text.bytes() {
+        if (0x20..=0x7E).contains(&byte) && byte != b'\'' {
+            run.push(byte as char);
+            continue;
+        }
+        if !run.is_empty() {
+            items.push(format!("'{}'", std::mem::take(&mut run)));
+        }
+        match byte {
+            b'\n' => items.push("13, 10".to_string()),
+            _ => items.push(byte.to_string()),
+        }
+    }
+    if !run.is_empty() {
+        items.push(format!("'{}'", run));
+    }
+    if items.is_empty() {
+        items.push("0".to_string());
+    }
+    items.join(", ")
+}
+
+// Bytes written for a text, line feeds count twice
+fn dos_length(text: &str) -> usize {
+    text.len() + text.bytes().filter(|b| *b == b'\n').count()
+}
+
+struct I8086Generator<'a> {
+    quadruplets: &'a [Quadruplet],
+    current: usize,                 // Index of the quadruplet being lowered
+    text: String,
+    labels: usize,                  // Local labels generated so far
+    strings: Vec<String>,           // WRITE strings, named mining_string{index}
+}
+
+impl<'a> I8086Generator<'a> {
+    fn new(quadruplets: &'a [Quadruplet]) -> Self {
+        I8086Generator { quadruplets, current: 0, text: String::new(), labels: 0, strings: Vec::new() }
+    }
+
+    fn line(&mut self, instruction: impl AsRef<str>) {
+        self.text.push_str("    ");
+        self.text.push_str(instruction.as_ref());
+        self.text.push('\n');
+    }
+
+    fn label(&mut self) -> String {
+        self.labels += 1;
+        format!("L{}", self.labels)
+    }
+
+    fn place(&mut self, label: &str) {
+        self.text.push_str(&format!("{}:\n", label));
+    }
+
+    fn unsupported(&self, reason: &str) -> CodegenError {
+        CodegenError::Unsupported(self.current, self.quadruplets[self.current].to_string(), reason.to_string())
+    }
+
+    fn generate(&mut self) -> Result<(), CodegenError> {
+        if collect_symbols(self.quadruplets).iter().any(|(_, t)| matches!(t, Types::Float) || matches!(t, Types::Array(e, _) if **e == Types::Float)) {
+            self.current = self.quadruplets.iter().position(|q| {
+                [&q.operand1, &q.operand2, &q.result].into_iter().flatten().any(|o| o.value_type() == Some(Types::Float))
+            }).unwrap_or(0);
+            return Err(self.unsupported("the 8086 has no FLOAT instructions"));
+        }
+
+        let (initialized, _) = static_initializers(self.quadruplets);
+        let targets: HashSet<usize> = self.quadruplets.iter().filter_map(jump_target).collect();
+        let quadruplets = self.quadruplets;
+        for (index, quadruplet) in quadruplets.iter().enumerate().skip(initialized) {
+            self.current = index;
+            if targets.contains(&index) {
+                self.place(&format!("Lq{}", index));
+            }
+            self.text.push_str(&format!("    ; {}: {}\n", index, quadruplet));
+            self.quadruplet(quadruplet)?;
+        }
+        self.place(&format!("Lq{}", self.quadruplets.len()));
+        self.line("mov ax, 4C00h");
+        self.line("int 21h");
+        Ok(())
+    }
+
+    // Memory operand of a variable, temporary or array element. The index of an array element
+    // is checked against the bounds of the array and kept in BX, doubled for INTEGER arrays
+    fn address(&mut self, operand: &Operand) -> Result<String, CodegenError> {
+        match operand {
+            Operand::Variable(name, _) => Ok(symbol(name)),
+            Operand::Temporary(_, _) => Ok(symbol(&operand.to_string())),
+            Operand::ArrayElement(name, Types::Array(element, size), index) => {
+                let scale = if **element == Types::Integer { 2 } else { 1 };
+                if let Operand::Constant(Value::Integer(i)) = index.as_ref() {
+                    if (0..*size).contains(i) {
+                        return Ok(format!("{}[{}]", symbol(name), *i as usize * scale));
+                    }
+                }
+                match index.as_ref() {
+                    Operand::Constant(Value::Integer(i)) => self.line(format!("mov bx, {}", i)),
+                    Operand::Variable(_, Types::Integer) | Operand::Temporary(_, Types::Integer) => {
+                        let index = self.address(index)?;
+                        self.line(format!("mov bx, {}", index));
+                    },
+                    _ => return Err(self.unsupported("array indices are INTEGER values")),
+                }
+                self.line(format!("cmp bx, {}", size));
+                self.line("jae mining_index_error");
+                if scale == 2 {
+                    self.line("shl bx, 1");
+                }
+                Ok(format!("{}[bx]", symbol(name)))
+            },
+            _ => Err(self.unsupported("expected a variable, a temporary or an array element")),
+        }
+    }
+
+    // Loads an operand in AX or CX, CHAR codes are zero extended, and returns its type
+    fn load(&mut self, operand: &Operand, register: &str) -> Result<Types, CodegenError> {
+        let value_type = match operand.value_type() {
+            Some(t @ (Types::Integer | Types::Char)) => t,
+            _ => return Err(self.unsupported("expected an INTEGER or CHAR value")),
+        };
+        match operand {
+            Operand::Constant(Value::Integer(i)) => self.line(format!("mov {}, {}", register, i)),
+            Operand::Constant(Value::Char(c)) => self.line(format!("mov {}, {}", register, *c as u32 as u8)),
+            _ => {
+                let address = self.address(operand)?;
+                if value_type == Types::Integer {
+                    self.line(format!("mov {}, {}", register, address));
+                } else {
+                    let (low, high) = (register.replace('x', "l"), register.replace('x', "h"));
+                    self.line(format!("mov {}, {}", low, address));
+                    self.line(format!("xor {}, {}", high, high));
+                }
+            },
+        }
+        Ok(value_type)
+    }
+
+    // Stores AX or AL depending on the type of the value
+    fn store(&mut self, operand: Option<&Operand>, value_type: &Types) -> Result<(), CodegenError> {
+        let operand = operand.ok_or_else(|| self.unsupported("missing result"))?;
+        if operand.value_type().as_ref() != Some(value_type) {
+            return Err(self.unsupported("the result does not have the type of the value"));
+        }
+        let address = self.address(operand)?;
+        let register = if *value_type == Types::Integer { "ax" } else { "al" };
+        self.line(format!("mov {}, {}", address, register));
+        Ok(())
+    }
+
+    // Truth value of an operand in AX: 1 unless it is zero
+    fn truth(&mut self, operand: &Operand) -> Result<(), CodegenError> {
+        self.load(operand, "ax")?;
+        // NEG sets the carry for anything but zero
+        self.line("neg ax");
+        self.line("sbb ax, ax");
+        self.line("neg ax");
+        Ok(())
+    }
+
+    fn operands(&self, quadruplet: &Quadruplet) -> Result<(Operand, Operand), CodegenError> {
+        match (&quadruplet.operand1, &quadruplet.operand2) {
+            (Some(a), Some(b)) => Ok((a.clone(), b.clone())),
+            _ => Err(self.unsupported("expected two operands")),
+        }
+    }
+
+    fn quadruplet(&mut self, quadruplet: &Quadruplet) -> Result<(), CodegenError> {
+        let result = quadruplet.result.as_ref();
+        match quadruplet.operator {
+            Operator::Assign => {
+                let value = quadruplet.operand1.as_ref().ok_or_else(|| self.unsupported("missing value"))?;
+                let value_type = self.load(value, "ax")?;
+                self.store(result, &value_type)
+            },
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => {
+                let (a, b) = self.operands(quadruplet)?;
+                let value_type = self.arithmetic(&quadruplet.operator, &a, &b)?;
+                self.store(result, &value_type)
+            },
+            Operator::GreaterThan | Operator::LessThan | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual | Operator::Equal | Operator::NotEqual => {
+                let (a, b) = self.operands(quadruplet)?;
+                self.comparison(&quadruplet.operator, &a, &b)?;
+                self.store(result, &Types::Integer)
+            },
+            Operator::LogicalAnd | Operator::LogicalOr => {
+                let (a, b) = self.operands(quadruplet)?;
+                self.truth(&a)?;
+                self.line("mov dx, ax");
+                self.truth(&b)?;
+                self.line(if quadruplet.operator == Operator::LogicalAnd { "and ax, dx" } else { "or ax, dx" });
+                self.store(result, &Types::Integer)
+            },
+            Operator::LogicalNot => {
+                let value = quadruplet.operand1.as_ref().ok_or_else(|| self.unsupported("missing value"))?;
+                self.truth(value)?;
+                self.line("xor ax, 1");
+                self.store(result, &Types::Integer)
+            },
+            Operator::Read => {
+                let value_type = result.and_then(Operand::value_type).ok_or_else(|| self.unsupported("missing result"))?;
+                match value_type {
+                    Types::Integer => self.line("call mining_read_int"),
+                    Types::Char => self.line("call mining_read_char"),
+                    _ => return Err(self.unsupported("READ of an INTEGER or a CHAR only")),
+                }
+                self.store(result, &value_type)
+            },
+            Operator::Write => match &quadruplet.operand1 {
+                Some(Operand::String(text)) => {
+                    self.strings.push(text.clone());
+                    self.line(format!("mov dx, OFFSET mining_string{}", self.strings.len() - 1));
+                    self.line(format!("mov cx, {}", dos_length(text)));
+                    self.line("call mining_write_string");
+                    Ok(())
+                },
+                Some(value) => {
+                    let call = match self.load(value, "ax")? {
+                        Types::Integer => "call mining_write_int",
+                        _ => "call mining_write_char",
+                    };
+                    self.line(call);
+                    Ok(())
+                },
+                None => Err(self.unsupported("missing value")),
+            },
+            Operator::Goto | Operator::IfTrue | Operator::IfFalse => {
+                let target = jump_target(quadruplet).ok_or_else(|| self.unsupported("missing target"))?;
+                let jump = match (&quadruplet.operator, &quadruplet.operand1) {
+                    (Operator::Goto, _) => "jmp",
+                    (operator, Some(condition)) => {
+                        self.load(condition, "ax")?;
+                        self.line("test ax, ax");
+                        if *operator == Operator::IfTrue { "jnz" } else { "jz" }
+                    },
+                    _ => return Err(self.unsupported("missing condition")),
+                };
+                self.line(format!("{} Lq{}", jump, target));
+                Ok(())
+            },
+            Operator::For => Err(self.unsupported("For must be lowered to jumps")),
+        }
+    }
+
+    // Computes a op b in AX and returns the type of the result
+    fn arithmetic(&mut self, operator: &Operator, a: &Operand, b: &Operand) -> Result<Types, CodegenError> {
+        let value_type = self.load(a, "ax")?;
+        if self.load(b, "cx")? != value_type {
+            return Err(self.unsupported("operands of different types"));
+        }
+        match (value_type.clone(), operator) {
+            (Types::Integer, Operator::Add) => self.line("add ax, cx"),
+            (Types::Integer, Operator::Subtract) => self.line("sub ax, cx"),
+            // IMUL sets the overflow flag when DX:AX does not fit in AX
+            (Types::Integer, Operator::Multiply) => self.line("imul cx"),
+            (Types::Integer, _) => {
+                // The only quotient that does not fit in 16 bits is -32768 / -1
+                let divide = self.label();
+                self.line("test cx, cx");
+                self.line("jz mining_division_error");
+                self.line("cmp cx, -1");
+                self.line(format!("jne {}", divide));
+                self.line("cmp ax, -32768");
+                self.line("je mining_overflow_error");
+                self.place(&divide);
+                self.line("cwd");
+                self.line("idiv cx");
+            },
+            (_, Operator::Add | Operator::Subtract) => {
+                // CHAR codes wrap around 0x7F
+                let positive = self.label();
+                self.line(if *operator == Operator::Add { "add ax, cx" } else { "sub ax, cx" });
+                self.line("cwd");
+                self.line("mov cx, 127");
+                self.line("idiv cx");
+                self.line("test dx, dx");
+                self.line(format!("jns {}", positive));
+                self.line("add dx, 127");
+                self.place(&positive);
+                self.line("mov ax, dx");
+            },
+            _ => return Err(self.unsupported("CHAR values only support addition and subtraction")),
+        }
+        if value_type == Types::Integer && *operator != Operator::Divide {
+            self.line("jo mining_overflow_error");
+        }
+        Ok(value_type)
+    }
+
+    // Sets AX to 1 when the comparison of a with b holds and to 0 otherwise
+    fn comparison(&mut self, operator: &Operator, a: &Operand, b: &Operand) -> Result<(), CodegenError> {
+        let value_type = self.load(a, "ax")?;
+        if self.load(b, "cx")? != value_type {
+            return Err(self.unsupported("operands of different types"));
+        }
+        let signed = is_signed(&value_type);
+        let jump = match operator {
+            Operator::GreaterThan => if signed { "jg" } else { "ja" },
+            Operator::LessThan => if signed { "jl" } else { "jb" },
+            Operator::GreaterThanOrEqual => if signed { "jge" } else { "jae" },
+            Operator::LessThanOrEqual => if signed { "jle" } else { "jbe" },
+            Operator::Equal => "je",
+            _ => "jne",
+        };
+        let done = self.label();
+        self.line("cmp ax, cx");
+        self.line("mov ax, 1");
+        self.line(format!("{} {}", jump, done));
+        self.line("mov ax, 0");
+        self.place(&done);
+        Ok(())
+    }
+
+    // Wraps the code with the data segment, the runtime and the error handlers
+    fn finish(mut self) -> String {
+        let mut output = String::from("; MinING program for the 8086, MASM 6 syntax\n    .MODEL SMALL\n    .STACK 100h\n\n    .DATA\n");
+
+        let (_, initial) = static_initializers(self.quadruplets);
+        for (name, symbol_type) in collect_symbols(self.quadruplets) {
+            let value = initial.iter().find(|(known, _)| *known == name).map(|(_, value)| value);
+            let definition = match (&symbol_type, value) {
+                (Types::Integer, Some(Value::Integer(i))) => format!("DW {}", i),
+                (Types::Char, Some(Value::Char(c))) => format!("DB {}", *c as u32 as u8),
+                (Types::Integer, _) => "DW 0".to_string(),
+                (Types::Array(element, size), _) if **element == Types::Integer => format!("DW {} DUP(0)", size),
+                (Types::Array(_, size), _) => format!("DB {} DUP(0)", size),
+                _ => "DB 0".to_string(),
+            };
+            output.push_str(&format!("{} {}\n", symbol(&name), definition));
+        }
+        for (index, text) in self.strings.iter().enumerate() {
+            output.push_str(&format!("mining_string{} DB {}\n", index, bytes_directive(text)));
+        }
+        for (index, (_, message)) in RUNTIME_ERRORS.iter().enumerate() {
+            output.push_str(&format!("mining_error{} DB {}\n", index, bytes_directive(&format!("Runtime error: {}\n", message))));
+        }
+        output.push_str("mining_output DB 8 DUP(0)\nmining_input_position DW 0\nmining_input_length DW 0\nmining_input DB 128 DUP(0)\n");
+
+        output.push_str("\n    .CODE\nmain PROC\n    mov ax, @data\n    mov ds, ax\n");
+        output.push_str(&std::mem::take(&mut self.text));
+        output.push_str("main ENDP\n");
+        output.push_str(RUNTIME);
+        for (index, (label, message)) in RUNTIME_ERRORS.iter().enumerate() {
+            output.push_str(&format!("\n{}:\n", label));
+            output.push_str(&format!("    mov dx, OFFSET mining_error{}\n", index));
+            output.push_str(&format!("    mov cx, {}\n", dos_length(&format!("Runtime error: {}\n", message))));
+            output.push_str("    jmp mining_fail\n");
+        }
+        output.push_str("\n    END main\n");
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::sim8086::run_8086;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+    use crate::Test::tests::QUADRUPLETS;
+
+    #[test]
+    fn test_8086_program() {
+        // There is no FLOAT on the 8086, the FLOAT variables of the program are INTEGER ones
+        let quads = parse_quadruplets(&QUADRUPLETS.replace("FLOAT", "INTEGER").replace("1.5", "2")).unwrap();
+        let assembly = generate_8086(&quads).unwrap();
+        assert!(assembly.contains("m_S DW 0"));
+        assert!(assembly.contains("m_A DW 3 DUP(0)"));
+        assert!(assembly.contains("mining_string1 DB 13, 10"));
+        assert!(assembly.contains("imul cx"));
+
+        let execution = run_8086(&assembly, b"-7 9 a", 100_000).unwrap();
+        assert_eq!(execution.exit_code, 0);
+        assert_eq!(execution.stdout(), "S = -21\r\n4b13");
+
+        let execution = run_8086(&assembly, b"0 1 z", 100_000).unwrap();
+        assert_eq!(execution.stdout(), "S = 0\r\n0{0");
+        assert_eq!((execution.stderr().as_str(), execution.exit_code), ("Runtime error: Division by zero\r\n", 1));
+
+        let execution = run_8086(&assembly, b"32768", 100_000).unwrap();
+        assert_eq!((execution.stderr().as_str(), execution.exit_code), ("Runtime error: Invalid INTEGER input\r\n", 1));
+    }
+
+    #[test]
+    fn test_float_is_rejected() {
+        let quads = parse_quadruplets("INTEGER X;\nFLOAT F;\n0: (Assign, 1, _, X)\n1: (Assign, 2.5, _, F)").unwrap();
+        assert!(matches!(generate_8086(&quads), Err(CodegenError::Unsupported(1, _, _))));
+    }
+}
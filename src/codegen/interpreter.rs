@@ -0,0 +1,478 @@
+//! Tree-walking interpreter running the syntax tree of a MinING program, used by `run`.
+//! It follows the semantics of the quadruplets: INTEGER arithmetic fails on overflow and
+//! division by zero, FOR(var = start : step : end) runs while var < end with the end and
+//! the step evaluated again on every iteration, and WRITE ends with a line break.
+//! Runtime errors carry the position of the operator, array or READ that failed and the
+//! IF and FOR statements enclosing it
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use crate::codegen::error::{Frame, LocatedError, Position, RuntimeError};
+use crate::codegen::runtime::{write_value, zero, Input};
+use crate::Parser::ast::*;
+use crate::Semantic::quadruplets::{to_types, to_value, Operator, Value};
+use crate::Semantic::ts::Types;
+
+/// Runs the instructions of a program, READ takes its tokens from `input` and WRITE goes to `output`.
+/// The observers follow the execution (e.g., the tracer of `--trace`)
+pub fn run_program<'a, R: BufRead, W: Write>(
+    program: &Program,
+    input: R,
+    output: &'a mut W,
+    observers: Vec<Box<dyn Observer<R, &'a mut W>>>,
+) -> Result<(), LocatedError> {
+    let mut interpreter = Interpreter::new(input, output);
+    for observer in observers {
+        interpreter.add_observer(observer);
+    }
+    let result = interpreter.run(program);
+    if let Err(e) = interpreter.output.flush() {
+        return Err(interpreter.locate(e, None));
+    }
+    result
+}
+
+/// A declared name with its type and its cells, a single one for scalars
+#[derive(Debug, Clone, PartialEq)]
+pub struct Storage {
+    pub storage_type: Types,
+    pub cells: Vec<Value>,
+}
+
+impl Storage {
+    fn new(storage_type: Types) -> Self {
+        let cells = match &storage_type {
+            Types::Array(element, size) => vec![zero(element); (*size).max(0) as usize],
+            scalar => vec![zero(scalar)],
+        };
+        Storage { storage_type, cells }
+    }
+
+    fn is_array(&self) -> bool {
+        matches!(self.storage_type, Types::Array(_, _))
+    }
+}
+
+/// What the instructions do, given to the observers as it happens. The names carry the
+/// position of their occurrence in the source
+#[derive(Debug, Clone, Copy)]
+pub enum Event<'a> {
+    Load(&'a (String, (usize, usize)), Option<usize>, Value),    // A variable or an array cell is read
+    Store(&'a (String, (usize, usize)), Option<usize>, Value),   // A variable or an array cell is written
+    Operation(&'a BinOp, Value),                                // Result of an arithmetic operation
+    Comparison(&'a RelOp, bool),                                // Result of a comparison
+    Iteration(&'a ForStmt, usize, Value),                       // Start of an iteration (counted from 1) with the counter value
+    Step(&'a ForStmt),                                          // End of an iteration, the counter is incremented
+}
+
+/// Follows the execution of a program: the debugger stops it before instructions, the tracer
+/// and the profiler record what it does
+pub trait Observer<R, W> {
+    fn before(&mut self, interpreter: &mut Interpreter<R, W>, instruction: &Instruction) -> Result<(), LocatedError>;
+
+    fn event(&mut self, _event: &Event) {}
+
+    /// Called once the program stopped, even on a runtime error
+    fn finish(&mut self, _interpreter: &mut Interpreter<R, W>) -> Result<(), LocatedError> {
+        Ok(())
+    }
+}
+
+pub struct Interpreter<R, W> {
+    pub environment: HashMap<String, Storage>,
+    pub frames: Vec<Frame>,     // IF and FOR statements enclosing the instruction being executed, outermost first
+    input: Input<R>,
+    output: W,
+    observers: Vec<Box<dyn Observer<R, W>>>,
+}
+
+impl<R: BufRead, W: Write> Interpreter<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Interpreter { environment: HashMap::new(), frames: Vec::new(), input: Input::new(input), output, observers: Vec::new() }
+    }
+
+    pub fn add_observer(&mut self, observer: Box<dyn Observer<R, W>>) {
+        self.observers.push(observer);
+    }
+
+    pub fn input(&mut self) -> &mut Input<R> {
+        &mut self.input
+    }
+
+    pub fn output(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /// Error at a position of the source, with the statements enclosing it
+    pub fn locate(&self, error: impl Into<RuntimeError>, position: Option<(usize, usize)>) -> LocatedError {
+        LocatedError {
+            error: error.into(),
+            position: position.map(Position::from),
+            stack: self.frames.iter().rev().cloned().collect(),
+        }
+    }
+
+    /// Seeds the environment from the declarations, then executes the instructions
+    pub fn run(&mut self, program: &Program) -> Result<(), LocatedError> {
+        let result = self.run_instructions(program);
+        let mut observers = std::mem::take(&mut self.observers);
+        let finished = observers.iter_mut().try_for_each(|observer| observer.finish(self));
+        self.observers = observers;
+        result.and(finished)
+    }
+
+    fn run_instructions(&mut self, program: &Program) -> Result<(), LocatedError> {
+        for declarations in [&program.global, &program.decls].into_iter().flatten() {
+            self.declare(declarations)?;
+        }
+        if let Some(instructions) = &program.inst {
+            self.execute_block(instructions)?;
+        }
+        Ok(())
+    }
+
+    fn notify(&mut self, event: Event) {
+        for observer in &mut self.observers {
+            observer.event(&event);
+        }
+    }
+
+    fn declare(&mut self, declarations: &[Declaration]) -> Result<(), LocatedError> {
+        for declaration in declarations {
+            match declaration {
+                Declaration::Variable(type_decl, variables) => {
+                    for variable in variables {
+                        match variable {
+                            Variable::Simple((name, _)) => {
+                                self.environment.insert(name.clone(), Storage::new(to_types(type_decl)));
+                            },
+                            Variable::Initialized(name, expr) => {
+                                self.environment.insert(name.0.clone(), Storage::new(to_types(type_decl)));
+                                let value = self.evaluate(expr)?;
+                                self.store(name, 0, value)?;
+                            },
+                        }
+                    }
+                },
+                Declaration::ADEC(type_decl, arrays) => {
+                    for array in arrays {
+                        match array {
+                            ArrayDecl::Simple((name, _), size) => {
+                                self.declare_array(name, type_decl, size, Vec::new())?;
+                            },
+                            ArrayDecl::Initialized((name, _), size, values) => {
+                                let values = values.iter().map(|value| self.evaluate(value)).collect::<Result<_, _>>()?;
+                                self.declare_array(name, type_decl, size, values)?;
+                            },
+                            ArrayDecl::InitializedString((name, _), size, (value, _)) => {
+                                // The literal still holds its surrounding double quotes
+                                let content = &value[1..value.len() - 1];
+                                let values = match content.is_empty() {
+                                    true => vec![Value::Char('\0')],
+                                    false => content.chars().map(Value::Char).collect(),
+                                };
+                                self.declare_array(name, type_decl, size, values)?;
+                            },
+                        }
+                    }
+                },
+                Declaration::Constant(type_decl, constants) => {
+                    for constant in constants {
+                        self.environment.insert(constant.var.0.clone(), Storage::new(to_types(type_decl)));
+                        self.assign(constant)?;
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Declares an array, its initial values are repeated until every cell is filled like in the
+    /// quadruplets, and its size falls back to the number of values when it is not positive
+    fn declare_array(&mut self, name: &str, type_decl: &Type, size: &Expr, values: Vec<Value>) -> Result<(), LocatedError> {
+        let size = match self.evaluate(size)? {
+            Value::Integer(size) if size > 0 => size,
+            _ => values.len() as i16,
+        };
+        let mut storage = Storage::new(Types::Array(Box::new(to_types(type_decl)), size));
+        if !values.is_empty() {
+            for (index, cell) in storage.cells.iter_mut().enumerate() {
+                *cell = values[index % values.len()];
+            }
+        }
+        self.environment.insert(name.to_string(), storage);
+        Ok(())
+    }
+
+    pub fn execute_block(&mut self, instructions: &[Instruction]) -> Result<(), LocatedError> {
+        for instruction in instructions {
+            self.execute(instruction)?;
+        }
+        Ok(())
+    }
+
+    pub fn execute(&mut self, instruction: &Instruction) -> Result<(), LocatedError> {
+        // The observers are set aside while they run, so what they evaluate is not observed
+        let mut observers = std::mem::take(&mut self.observers);
+        let result = observers.iter_mut().try_for_each(|observer| observer.before(self, instruction));
+        self.observers = observers;
+        result?;
+        match instruction {
+            Instruction::Assign(assignment) => self.assign(assignment),
+            Instruction::If(if_stmt) => {
+                self.frames.push(Frame::If(if_stmt.position.into()));
+                let result = self.execute_if(if_stmt);
+                self.frames.pop();
+                result
+            },
+            Instruction::For(for_stmt) => {
+                self.assign(&for_stmt.init)?;
+                let value = self.peek(&for_stmt.init.var, for_stmt.init.index.as_ref())?;
+                self.frames.push(Frame::For {
+                    position: for_stmt.position.into(),
+                    counter: for_stmt.init.var.0.clone(),
+                    iteration: 0,
+                    value,
+                });
+                let result = self.execute_for(for_stmt);
+                self.frames.pop();
+                result
+            },
+            Instruction::Read(read_stmt) => {
+                // The index is checked before reading
+                let cell = self.cell(&read_stmt.variable, read_stmt.index.as_ref())?;
+                let value_type = self.environment[&read_stmt.variable.0].cells[cell].get_type();
+                let value = self.input.read(&value_type).map_err(|e| self.locate(e, Some(read_stmt.variable.1)))?;
+                self.store(&read_stmt.variable, cell, value)
+            },
+            Instruction::Write(write_stmt) => {
+                for element in &write_stmt.elements {
+                    match element {
+                        // The literal still holds its surrounding double quotes
+                        WriteElement::String((literal, position)) => {
+                            self.output.write_all(&literal.as_bytes()[1..literal.len() - 1]).map_err(|e| self.locate(e, Some(*position)))?;
+                        },
+                        WriteElement::Variable(name, index) => {
+                            let value = self.load(name, index.as_ref())?;
+                            write_value(&mut self.output, &value).map_err(|e| self.locate(e, Some(name.1)))?;
+                        },
+                    }
+                }
+                self.output.write_all(b"\n").map_err(|e| self.locate(e, None))?;
+                Ok(())
+            },
+        }
+    }
+
+    fn execute_if(&mut self, if_stmt: &IfStmt) -> Result<(), LocatedError> {
+        if self.condition(&if_stmt.condition)? {
+            self.execute_block(&if_stmt.then_block)
+        } else if let Some(else_block) = &if_stmt.else_block {
+            self.execute_block(else_block)
+        } else {
+            Ok(())
+        }
+    }
+
+    // The iterations of a FOR whose counter was initialized, its frame follows the counter
+    fn execute_for(&mut self, for_stmt: &ForStmt) -> Result<(), LocatedError> {
+        while self.for_condition(for_stmt)? {
+            let counter = self.peek(&for_stmt.init.var, for_stmt.init.index.as_ref())?;
+            if let Some(Frame::For { iteration, value, .. }) = self.frames.last_mut() {
+                *iteration += 1;
+                *value = counter;
+                let iteration = *iteration;
+                self.notify(Event::Iteration(for_stmt, iteration, counter));
+            }
+            self.execute_block(&for_stmt.body)?;
+            self.notify(Event::Step(for_stmt));
+            self.for_step(for_stmt)?;
+        }
+        Ok(())
+    }
+
+    /// var < end, checked before every iteration of a FOR
+    pub fn for_condition(&mut self, for_stmt: &ForStmt) -> Result<bool, LocatedError> {
+        let counter = self.load(&for_stmt.init.var, for_stmt.init.index.as_ref())?;
+        let bound = self.evaluate(&for_stmt.condition)?;
+        let holds = counter.binary(&Operator::LessThan, &bound).map_err(|e| self.locate(e, Some(for_stmt.init.var.1)))?;
+        Ok(holds.is_true())
+    }
+
+    /// var = var + step, executed after every iteration of a FOR. An overflow of the counter
+    /// is reported at the counter
+    pub fn for_step(&mut self, for_stmt: &ForStmt) -> Result<(), LocatedError> {
+        let counter = self.load(&for_stmt.init.var, for_stmt.init.index.as_ref())?;
+        let step = self.evaluate(&for_stmt.step)?;
+        let next = counter.binary(&Operator::Add, &step).map_err(|e| self.locate(e, Some(for_stmt.init.var.1)))?;
+        let cell = self.cell(&for_stmt.init.var, for_stmt.init.index.as_ref())?;
+        self.store(&for_stmt.init.var, cell, next)
+    }
+
+    /// The value is computed before the index of the target is checked
+    pub fn assign(&mut self, assignment: &Assignment) -> Result<(), LocatedError> {
+        let value = self.evaluate(&assignment.expr)?;
+        let cell = self.cell(&assignment.var, assignment.index.as_ref())?;
+        self.store(&assignment.var, cell, value)
+    }
+
+    pub fn condition(&mut self, condition: &Condition) -> Result<bool, LocatedError> {
+        match condition {
+            Condition::Basic(basic) => {
+                let left = self.evaluate(&basic.left)?;
+                let right = self.evaluate(&basic.right)?;
+                let operator = match basic.operator {
+                    RelOp::Gt(_, _) => Operator::GreaterThan,
+                    RelOp::Lt(_, _) => Operator::LessThan,
+                    RelOp::Ge(_, _) => Operator::GreaterThanOrEqual,
+                    RelOp::Le(_, _) => Operator::LessThanOrEqual,
+                    RelOp::Eq(_, _) => Operator::Equal,
+                    RelOp::Ne(_, _) => Operator::NotEqual,
+                };
+                let holds = left.binary(&operator, &right).map_err(|e| self.locate(e, Some(basic.operator.position())))?.is_true();
+                self.notify(Event::Comparison(&basic.operator, holds));
+                Ok(holds)
+            },
+            // The right side is only evaluated when the left side does not decide the result
+            Condition::Logic(left, LogOp::And(_, _), right) => Ok(self.condition(left)? && self.condition(right)?),
+            Condition::Logic(left, LogOp::Or(_, _), right) => Ok(self.condition(left)? || self.condition(right)?),
+            Condition::Not(inner) => Ok(!self.condition(inner)?),
+        }
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, LocatedError> {
+        match expr {
+            Expr::BinaryOp(left, op, right) => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                let operator = match op {
+                    BinOp::Add(_, _) => Operator::Add,
+                    BinOp::Sub(_, _) => Operator::Subtract,
+                    BinOp::Mul(_, _) => Operator::Multiply,
+                    BinOp::Div(_, _) => Operator::Divide,
+                };
+                let value = left.binary(&operator, &right).map_err(|e| self.locate(e, Some(op.position())))?;
+                self.notify(Event::Operation(op, value));
+                Ok(value)
+            },
+            Expr::Variable(name) => self.load(name, None),
+            Expr::SUBS(name, index) => self.load(name, Some(index)),
+            Expr::Literal(literal) => to_value(literal)
+                .ok_or_else(|| self.locate(RuntimeError::InvalidProgram("array literal in an expression".to_string()), None)),
+        }
+    }
+
+    /// Cell of a variable or of an array element, an index out of bounds is reported at the array
+    fn cell(&mut self, name: &(String, (usize, usize)), index: Option<&Expr>) -> Result<usize, LocatedError> {
+        let index = match index {
+            Some(index) => Some(self.evaluate(index)?),
+            None => None,
+        };
+        let fail = |error| Err(self.locate(error, Some(name.1)));
+        let Some(storage) = self.environment.get(&name.0) else {
+            return fail(RuntimeError::InvalidProgram(format!("undeclared variable {}", name.0)));
+        };
+        match (index, storage.is_array()) {
+            (None, false) => Ok(0),
+            (Some(Value::Integer(index)), true) if index >= 0 && (index as usize) < storage.cells.len() => Ok(index as usize),
+            (Some(Value::Integer(_)), true) => fail(RuntimeError::IndexOutOfBounds),
+            (Some(_), true) => fail(RuntimeError::InvalidProgram("array indices are INTEGER values".to_string())),
+            (None, true) => fail(RuntimeError::InvalidProgram(format!("whole array {} used as a value", name.0))),
+            (Some(_), false) => fail(RuntimeError::InvalidProgram(format!("{} is not an array", name.0))),
+        }
+    }
+
+    fn load(&mut self, name: &(String, (usize, usize)), index: Option<&Expr>) -> Result<Value, LocatedError> {
+        let cell = self.cell(name, index)?;
+        let storage = &self.environment[&name.0];
+        let (value, index) = (storage.cells[cell], storage.is_array().then_some(cell));
+        self.notify(Event::Load(name, index, value));
+        Ok(value)
+    }
+
+    // Value kept in the FOR frames, which is not a read of the program
+    fn peek(&mut self, name: &(String, (usize, usize)), index: Option<&Expr>) -> Result<Value, LocatedError> {
+        let cell = self.cell(name, index)?;
+        Ok(self.environment[&name.0].cells[cell])
+    }
+
+    // Values keep the type of their variable
+    fn store(&mut self, name: &(String, (usize, usize)), cell: usize, value: Value) -> Result<(), LocatedError> {
+        let target = &mut self.environment.get_mut(&name.0).expect("cells are checked before storing").cells[cell];
+        if target.get_type() != value.get_type() {
+            let error = RuntimeError::InvalidProgram(format!("{:?} value stored in {}", value.get_type(), name.0));
+            return Err(self.locate(error, Some(name.1)));
+        }
+        *target = value;
+        let index = self.environment[&name.0].is_array().then_some(cell);
+        self.notify(Event::Store(name, index, value));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Test::tests::{analyze_source, FAILING_PROGRAM, PROGRAM};
+
+    fn run(program: &Program, input: &str) -> (String, Result<(), RuntimeError>) {
+        let mut output = Vec::new();
+        let result = run_program(program, input.as_bytes(), &mut output, Vec::new());
+        (String::from_utf8(output).unwrap(), result.map_err(|e| e.error))
+    }
+
+    fn error(program: &Program, input: &str) -> String {
+        run_program(program, input.as_bytes(), &mut Vec::new(), Vec::new()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_run_program() {
+        let (program, _) = analyze_source(PROGRAM);
+        assert_eq!(run(&program, "-7 2.25\n a"), ("S = -21 100%\n2ab\n1.5D\nyes\n3\n".to_string(), Ok(())));
+        assert_eq!(run(&program, "-1 1.0 x"), ("S = -3 100%\n2ab\n0.666667[\nno\n".to_string(), Err(RuntimeError::DivisionByZero)));
+        assert_eq!(run(&program, "40000").1, Err(RuntimeError::InvalidInteger));
+        assert_eq!(run(&program, "5 1e3").1, Err(RuntimeError::InvalidFloat));
+        assert_eq!(run(&program, "5 1.0").1, Err(RuntimeError::EndOfInput));
+    }
+
+    #[test]
+    fn test_indexed_read_and_loops() {
+        let (program, _) = analyze_source(r#"
+            VAR_GLOBAL {
+                INTEGER I, J, N;
+                FLOAT V[3];
+            }
+            DECLARATION { }
+            INSTRUCTION {
+                READ(N);
+                READ(V[N + 1]);
+                WRITE(V[0], " ", V[1], " ", V[2]);
+                FOR (I = 10 : (-3) : 0) {
+                    WRITE("never");
+                }
+                FOR (I = 0 : 2 : 5) {
+                    FOR (J = I : 1 : 3) {
+                        WRITE(I, J);
+                    }
+                }
+                WRITE(I, " ", J);
+            }
+        "#);
+        assert_eq!(run(&program, "0 2.5"), ("0.0 2.5 0.0\n00\n01\n02\n22\n6 4\n".to_string(), Ok(())));
+        // The index is checked before reading
+        assert_eq!(run(&program, "2"), (String::new(), Err(RuntimeError::IndexOutOfBounds)));
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let (program, _) = analyze_source(FAILING_PROGRAM);
+        let stack = "\n    in FOR at line 10, column 13, iteration 1 with J = 0\
+                     \n    in IF at line 9, column 9\
+                     \n    in FOR at line 8, column 5, iteration 2 with I = 1";
+        assert_eq!(error(&program, "1"), format!("Division by zero at line 11, column 28{}", stack));
+        assert_eq!(error(&program, "32767"), format!("INTEGER overflow at line 11, column 33{}", stack));
+        assert_eq!(error(&program, "5"), "Array index out of bounds at line 15, column 5");
+        assert_eq!(error(&program, "2 x"), "Invalid INTEGER input at line 16, column 10");
+        assert_eq!(error(&program, "2"), "Unexpected end of input at line 16, column 10");
+    }
+}
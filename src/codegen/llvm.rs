@@ -0,0 +1,849 @@
+//! LLVM IR backend, writing textual IR that `lli`, `llc` or `clang` take directly
+//!
+//! Variables and arrays are globals named `@m_<name>` (`i16`, `float`, `i8` and `[N x T]`),
+//! temporaries are `alloca`s of `main` so that `opt -mem2reg` turns them into registers. Every
+//! quadruplet that is a jump target or follows a jump starts a basic block `%q<index>`. READ and
+//! WRITE call helpers built on the C library's `scanf`, `getchar` and `printf`. Quadruplets that
+//! may fail first store their origin in `@mining_location`, printed after the error message.
+//! Pointers are written with their pointee type (`i16*`), which LLVM 14 requires and later
+//! versions read as `ptr`
+
+use std::collections::HashSet;
+use crate::codegen::error::CodegenError;
+use crate::codegen::static_initializers;
+use crate::codegen::x86_64::RUNTIME_ERRORS;
+use crate::Semantic::quadruplets::{collect_symbols, jump_target, Operand, Operator, Quadruplet, Value};
+use crate::Semantic::ts::Types;
+
+/// Helpers following the run-time conventions shared with the other backends
+const RUNTIME: &str = r#"
+declare i32 @printf(i8*, ...)
+declare i32 @scanf(i8*, ...)
+declare i32 @getchar()
+declare i32 @fflush(i8*)
+declare i64 @write(i32, i8*, i64)
+declare void @exit(i32) noreturn
+declare { i16, i1 } @llvm.sadd.with.overflow.i16(i16, i16)
+declare { i16, i1 } @llvm.ssub.with.overflow.i16(i16, i16)
+declare { i16, i1 } @llvm.smul.with.overflow.i16(i16, i16)
+declare double @llvm.fabs.f64(double)
+
+@mining_format_int = private unnamed_addr constant [3 x i8] c"%d\00"
+@mining_format_char = private unnamed_addr constant [3 x i8] c"%c\00"
+@mining_format_string = private unnamed_addr constant [3 x i8] c"%s\00"
+@mining_format_float = private unnamed_addr constant [14 x i8] c"%s%llu.%0*llu\00"
+@mining_format_exponent = private unnamed_addr constant [4 x i8] c"e%d\00"
+@mining_format_infinity = private unnamed_addr constant [6 x i8] c"%sinf\00"
+@mining_format_token = private unnamed_addr constant [4 x i8] c" %c\00"
+@mining_nan = private unnamed_addr constant [4 x i8] c"nan\00"
+@mining_minus = private unnamed_addr constant [2 x i8] c"-\00"
+@mining_empty = private unnamed_addr constant [1 x i8] c"\00"
+@mining_newline = private unnamed_addr constant [1 x i8] c"\0A"
+; Where the failing quadruplet comes from, an empty text when it is not known
+@mining_location = internal global i8* null
+@mining_location_length = internal global i64 0
+@mining_powers = private unnamed_addr constant [19 x double] [
+  double 1.0, double 1.0e1, double 1.0e2, double 1.0e3, double 1.0e4, double 1.0e5, double 1.0e6,
+  double 1.0e7, double 1.0e8, double 1.0e9, double 1.0e10, double 1.0e11, double 1.0e12,
+  double 1.0e13, double 1.0e14, double 1.0e15, double 1.0e16, double 1.0e17, double 1.0e18
+]
+
+define internal void @mining_fail(i8* %message, i64 %length) noreturn {
+  %flushed = call i32 @fflush(i8* null)
+  %written = call i64 @write(i32 2, i8* %message, i64 %length)
+  %location = load i8*, i8** @mining_location
+  %location_length = load i64, i64* @mining_location_length
+  %located = call i64 @write(i32 2, i8* %location, i64 %location_length)
+  %ended = call i64 @write(i32 2, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @mining_newline, i64 0, i64 0), i64 1)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define internal i16 @mining_div(i16 %a, i16 %b) {
+entry:
+  %zero = icmp eq i16 %b, 0
+  br i1 %zero, label %division, label %nonzero
+division:
+  call void @mining_division_error()
+  unreachable
+nonzero:
+  ; The only quotient that does not fit in 16 bits is -32768 / -1
+  %minimum = icmp eq i16 %a, -32768
+  %minus_one = icmp eq i16 %b, -1
+  %overflows = and i1 %minimum, %minus_one
+  br i1 %overflows, label %overflow, label %divide
+overflow:
+  call void @mining_overflow_error()
+  unreachable
+divide:
+  %quotient = sdiv i16 %a, %b
+  ret i16 %quotient
+}
+
+; CHAR arithmetic wraps around 0x7F
+define internal i8 @mining_char_add(i8 %a, i8 %b) {
+  %x = zext i8 %a to i32
+  %y = zext i8 %b to i32
+  %sum = add i32 %x, %y
+  %code = urem i32 %sum, 127
+  %result = trunc i32 %code to i8
+  ret i8 %result
+}
+
+define internal i8 @mining_char_sub(i8 %a, i8 %b) {
+  %x = zext i8 %a to i32
+  %y = zext i8 %b to i32
+  %difference = sub i32 %x, %y
+  %remainder = srem i32 %difference, 127
+  %negative = icmp slt i32 %remainder, 0
+  %positive = add i32 %remainder, 127
+  %code = select i1 %negative, i32 %positive, i32 %remainder
+  %result = trunc i32 %code to i8
+  ret i8 %result
+}
+
+define internal i64 @mining_index(i16 %index, i16 %size) {
+entry:
+  ; Negative indices are above the size once compared as unsigned values
+  %outside = icmp uge i16 %index, %size
+  br i1 %outside, label %error, label %inside
+error:
+  call void @mining_index_error()
+  unreachable
+inside:
+  %result = sext i16 %index to i64
+  ret i64 %result
+}
+
+define internal void @mining_write_int(i16 %value) {
+  %wide = sext i16 %value to i32
+  %printed = call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @mining_format_int, i64 0, i64 0), i32 %wide)
+  ret void
+}
+
+define internal void @mining_write_char(i8 %value) {
+  %wide = zext i8 %value to i32
+  %printed = call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @mining_format_char, i64 0, i64 0), i32 %wide)
+  ret void
+}
+
+define internal void @mining_write_string(i8* %text) {
+  %printed = call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @mining_format_string, i64 0, i64 0), i8* %text)
+  ret void
+}
+
+; Six decimals without the trailing zeros, values of 1e9 and above carry an exponent
+define internal void @mining_write_float(float %value) {
+entry:
+  %is_nan = fcmp uno float %value, 0.0
+  br i1 %is_nan, label %nan, label %number
+nan:
+  %printed_nan = call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([4 x i8], [4 x i8]* @mining_nan, i64 0, i64 0))
+  ret void
+number:
+  %bits = bitcast float %value to i32
+  %negative = icmp slt i32 %bits, 0
+  %sign = select i1 %negative, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @mining_minus, i64 0, i64 0), i8* getelementptr inbounds ([1 x i8], [1 x i8]* @mining_empty, i64 0, i64 0)
+  %wide = fpext float %value to double
+  %absolute = call double @llvm.fabs.f64(double %wide)
+  %is_infinity = fcmp oeq double %absolute, 0x7FF0000000000000
+  br i1 %is_infinity, label %infinity, label %scale
+infinity:
+  %printed_infinity = call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([6 x i8], [6 x i8]* @mining_format_infinity, i64 0, i64 0), i8* %sign)
+  ret void
+scale:
+  %magnitude = phi double [ %absolute, %number ], [ %divided, %divide ]
+  %exponent = phi i32 [ 0, %number ], [ %next_exponent, %divide ]
+  %large = fcmp oge double %magnitude, 1.0e9
+  br i1 %large, label %divide, label %round
+divide:
+  %divided = fdiv double %magnitude, 10.0
+  %next_exponent = add i32 %exponent, 1
+  br label %scale
+round:
+  ; Round half to even
+  %product = fmul double %magnitude, 1.0e6
+  %truncated = fptoui double %product to i64
+  %back = uitofp i64 %truncated to double
+  %rest = fsub double %product, %back
+  %above = fcmp ogt double %rest, 0.5
+  %tie = fcmp oeq double %rest, 0.5
+  %odd = trunc i64 %truncated to i1
+  %odd_tie = and i1 %tie, %odd
+  %up = or i1 %above, %odd_tie
+  %increment = zext i1 %up to i64
+  %scaled = add i64 %truncated, %increment
+  %integer = udiv i64 %scaled, 1000000
+  %all_decimals = urem i64 %scaled, 1000000
+  br label %strip
+strip:
+  %fraction = phi i64 [ %all_decimals, %round ], [ %shorter, %shorten ]
+  %digits = phi i32 [ 6, %round ], [ %fewer, %shorten ]
+  %several = icmp sgt i32 %digits, 1
+  %last = urem i64 %fraction, 10
+  %last_zero = icmp eq i64 %last, 0
+  %removable = and i1 %several, %last_zero
+  br i1 %removable, label %shorten, label %print
+shorten:
+  %shorter = udiv i64 %fraction, 10
+  %fewer = sub i32 %digits, 1
+  br label %strip
+print:
+  %printed = call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([14 x i8], [14 x i8]* @mining_format_float, i64 0, i64 0), i8* %sign, i64 %integer, i32 %digits, i64 %fraction)
+  %has_exponent = icmp sgt i32 %exponent, 0
+  br i1 %has_exponent, label %print_exponent, label %done
+print_exponent:
+  %printed_exponent = call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([4 x i8], [4 x i8]* @mining_format_exponent, i64 0, i64 0), i32 %exponent)
+  br label %done
+done:
+  ret void
+}
+
+; First character of the next token, the tokens are separated by white space
+define internal i32 @mining_token_start() {
+entry:
+  %character = alloca i8
+  %count = call i32 (i8*, ...) @scanf(i8* getelementptr inbounds ([4 x i8], [4 x i8]* @mining_format_token, i64 0, i64 0), i8* %character)
+  %found = icmp eq i32 %count, 1
+  br i1 %found, label %token, label %end
+end:
+  call void @mining_end_of_input_error()
+  unreachable
+token:
+  %byte = load i8, i8* %character
+  %result = zext i8 %byte to i32
+  ret i32 %result
+}
+
+define internal i1 @mining_token_end(i32 %c) {
+  %end = icmp eq i32 %c, -1
+  %space = icmp eq i32 %c, 32
+  %offset = sub i32 %c, 9
+  %control = icmp ule i32 %offset, 4
+  %blank = or i1 %space, %control
+  %result = or i1 %end, %blank
+  ret i1 %result
+}
+
+define internal i16 @mining_read_int() {
+entry:
+  %first = call i32 @mining_token_start()
+  %negative = icmp eq i32 %first, 45
+  %plus = icmp eq i32 %first, 43
+  %signed = or i1 %negative, %plus
+  br i1 %signed, label %sign, label %digits
+sign:
+  %after_sign = call i32 @getchar()
+  br label %digits
+digits:
+  %start = phi i32 [ %first, %entry ], [ %after_sign, %sign ]
+  br label %loop
+loop:
+  %c = phi i32 [ %start, %digits ], [ %next, %more ]
+  %value = phi i32 [ 0, %digits ], [ %accumulated, %more ]
+  %count = phi i32 [ 0, %digits ], [ %counted, %more ]
+  %digit = sub i32 %c, 48
+  %is_digit = icmp ule i32 %digit, 9
+  br i1 %is_digit, label %accumulate, label %end
+accumulate:
+  %times_ten = mul i32 %value, 10
+  %accumulated = add i32 %times_ten, %digit
+  %counted = add i32 %count, 1
+  %too_large = icmp ugt i32 %accumulated, 32768
+  br i1 %too_large, label %invalid, label %more
+more:
+  %next = call i32 @getchar()
+  br label %loop
+end:
+  %ended = call i1 @mining_token_end(i32 %c)
+  %not_ended = xor i1 %ended, true
+  %empty = icmp eq i32 %count, 0
+  %above_maximum = icmp ugt i32 %value, 32767
+  %positive = xor i1 %negative, true
+  %overflows = and i1 %positive, %above_maximum
+  %malformed = or i1 %not_ended, %empty
+  %bad = or i1 %malformed, %overflows
+  br i1 %bad, label %invalid, label %valid
+invalid:
+  call void @mining_integer_input_error()
+  unreachable
+valid:
+  %opposite = sub i32 0, %value
+  %signed_value = select i1 %negative, i32 %opposite, i32 %value
+  %result = trunc i32 %signed_value to i16
+  ret i16 %result
+}
+
+define internal float @mining_read_float() {
+entry:
+  %first = call i32 @mining_token_start()
+  %negative = icmp eq i32 %first, 45
+  %plus = icmp eq i32 %first, 43
+  %signed = or i1 %negative, %plus
+  br i1 %signed, label %sign, label %digits
+sign:
+  %after_sign = call i32 @getchar()
+  br label %digits
+digits:
+  %start = phi i32 [ %first, %entry ], [ %after_sign, %sign ]
+  br label %loop
+loop:
+  %c = phi i32 [ %start, %digits ], [ %next, %continue ]
+  %mantissa = phi i64 [ 0, %digits ], [ %next_mantissa, %continue ]
+  %count = phi i32 [ 0, %digits ], [ %next_count, %continue ]
+  %decimals = phi i32 [ 0, %digits ], [ %next_decimals, %continue ]
+  %point = phi i32 [ 0, %digits ], [ %next_point, %continue ]
+  %is_point = icmp eq i32 %c, 46
+  %before_point = icmp eq i32 %point, 0
+  %first_point = and i1 %is_point, %before_point
+  br i1 %first_point, label %dot, label %check
+dot:
+  br label %continue
+check:
+  %digit = sub i32 %c, 48
+  %is_digit = icmp ule i32 %digit, 9
+  %room = icmp slt i32 %count, 18
+  %accepted = and i1 %is_digit, %room
+  br i1 %accepted, label %accumulate, label %end
+accumulate:
+  %times_ten = mul i64 %mantissa, 10
+  %wide_digit = zext i32 %digit to i64
+  %accumulated = add i64 %times_ten, %wide_digit
+  %counted = add i32 %count, 1
+  %decimal = add i32 %decimals, %point
+  br label %continue
+continue:
+  %next_mantissa = phi i64 [ %mantissa, %dot ], [ %accumulated, %accumulate ]
+  %next_count = phi i32 [ %count, %dot ], [ %counted, %accumulate ]
+  %next_decimals = phi i32 [ %decimals, %dot ], [ %decimal, %accumulate ]
+  %next_point = phi i32 [ 1, %dot ], [ %point, %accumulate ]
+  %next = call i32 @getchar()
+  br label %loop
+end:
+  %ended = call i1 @mining_token_end(i32 %c)
+  %not_ended = xor i1 %ended, true
+  %empty = icmp eq i32 %count, 0
+  %bad = or i1 %not_ended, %empty
+  br i1 %bad, label %invalid, label %valid
+invalid:
+  call void @mining_float_input_error()
+  unreachable
+valid:
+  %power_address = getelementptr inbounds [19 x double], [19 x double]* @mining_powers, i64 0, i32 %decimals
+  %power = load double, double* %power_address
+  %digits_value = uitofp i64 %mantissa to double
+  %value = fdiv double %digits_value, %power
+  %opposite = fneg double %value
+  %signed_value = select i1 %negative, double %opposite, double %value
+  %result = fptrunc double %signed_value to float
+  ret float %result
+}
+
+define internal i8 @mining_read_char() {
+  %c = call i32 @mining_token_start()
+  %result = trunc i32 %c to i8
+  ret i8 %result
+}
+"#;
+
+/// Lowers the quadruplets to an LLVM module whose `main` runs the program
+pub fn generate_llvm(quadruplets: &[Quadruplet]) -> Result<String, CodegenError> {
+    let mut generator = LlvmGenerator {
+        quadruplets,
+        current: 0,
+        text: String::new(),
+        values: 0,
+        strings: Vec::new(),
+        locations: Vec::new(),
+        located: quadruplets.iter().any(|q| q.origin.is_some()),
+        terminated: false,
+    };
+    generator.generate()?;
+    Ok(generator.finish())
+}
+
+// Global holding a variable, array or temporary
+fn symbol(name: &str) -> String {
+    format!("@{}", crate::codegen::symbol(name))
+}
+
+fn llvm_type(value_type: &Types) -> String {
+    match value_type {
+        Types::Integer => "i16".to_string(),
+        Types::Float => "float".to_string(),
+        Types::Char => "i8".to_string(),
+        Types::Array(element, size) => format!("[{} x {}]", (*size).max(0), llvm_type(element)),
+    }
+}
+
+// Constants of the IR, FLOAT values are written as the hexadecimal bits of the equal double
+fn constant(value: &Value) -> String {
+    match value {
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) => format!("0x{:016X}", (*f as f64).to_bits()),
+        Value::Char(c) => (*c as u32 as u8).to_string(),
+    }
+}
+
+// String constant, bytes outside of printable ASCII are escaped
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'"' | b'\\' => escaped.push_str(&format!("\\{:02X}", byte)),
+            0x20..=0x7E => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:02X}", byte)),
+        }
+    }
+    escaped
+}
+
+struct LlvmGenerator<'a> {
+    quadruplets: &'a [Quadruplet],
+    current: usize,
+    text: String,
+    values: usize,          // Number of the last %v value
+    strings: Vec<String>,
+    locations: Vec<String>, // Text printed after the message of a runtime error, by origin
+    located: bool,          // Whether the quadruplets carry origins
+    terminated: bool,       // Whether the current block already ends with a branch
+}
+
+impl LlvmGenerator<'_> {
+    fn line(&mut self, instruction: impl AsRef<str>) {
+        self.text.push_str("  ");
+        self.text.push_str(instruction.as_ref());
+        self.text.push('\n');
+    }
+
+    fn value(&mut self) -> String {
+        self.values += 1;
+        format!("%v{}", self.values)
+    }
+
+    fn unsupported(&self, reason: &str) -> CodegenError {
+        let quadruplet = self.quadruplets.get(self.current).map(|q| q.to_string()).unwrap_or_default();
+        CodegenError::Unsupported(self.current, quadruplet, reason.to_string())
+    }
+
+    fn label(&mut self, index: usize) {
+        if !self.terminated {
+            self.line(format!("br label %q{}", index));
+        }
+        self.text.push_str(&format!("q{}:\n", index));
+        self.terminated = false;
+    }
+
+    fn branch(&mut self, instruction: String) {
+        self.line(instruction);
+        self.terminated = true;
+    }
+
+    fn generate(&mut self) -> Result<(), CodegenError> {
+        let (initialized, _) = static_initializers(self.quadruplets);
+        let targets: HashSet<usize> = self.quadruplets.iter().filter_map(jump_target).collect();
+
+        self.text.push_str("define i32 @main() {\nentry:\n");
+        for (name, symbol_type) in collect_symbols(self.quadruplets) {
+            if name.starts_with('t') && name[1..].parse::<usize>().is_ok() {
+                self.line(format!("%{} = alloca {}", name, llvm_type(&symbol_type)));
+            }
+        }
+        self.terminated = false;
+        let quadruplets = self.quadruplets;
+        for (index, quadruplet) in quadruplets.iter().enumerate().skip(initialized) {
+            self.current = index;
+            let follows_jump = index > 0 && quadruplets[index - 1].operator.is_jump();
+            if index == initialized || targets.contains(&index) || follows_jump {
+                self.label(index);
+            }
+            self.text.push_str(&format!("  ; {}: {}\n", index, quadruplet));
+            self.locate(quadruplet);
+            self.quadruplet(quadruplet)?;
+        }
+        self.label(quadruplets.len());
+        self.line("ret i32 0");
+        self.text.push_str("}\n");
+        Ok(())
+    }
+
+    // Points @mining_location to where the quadruplet comes from when it may fail, like the
+    // x86-64 backend. Quadruplets without an origin clear the location of the previous ones
+    fn locate(&mut self, quadruplet: &Quadruplet) {
+        if !self.located || !(quadruplet.can_fail() || quadruplet.operator == Operator::Read) {
+            return;
+        }
+        let location = quadruplet.origin.as_ref().map(|origin| origin.location()).unwrap_or_default();
+        if location.is_empty() {
+            self.line("store i8* null, i8** @mining_location");
+            self.line("store i64 0, i64* @mining_location_length");
+            return;
+        }
+        let index = match self.locations.iter().position(|known| *known == location) {
+            Some(index) => index,
+            None => {
+                self.locations.push(location.clone());
+                self.locations.len() - 1
+            },
+        };
+        let array = format!("[{} x i8]", location.len());
+        self.line(format!("store i8* getelementptr inbounds ({}, {}* @mining_origin{}, i64 0, i64 0), i8** @mining_location", array, array, index));
+        self.line(format!("store i64 {}, i64* @mining_location_length", location.len()));
+    }
+
+    // Pointer to a variable, a temporary or an array element whose index is checked
+    fn pointer(&mut self, operand: &Operand) -> Result<String, CodegenError> {
+        match operand {
+            Operand::Variable(name, _) => Ok(symbol(name)),
+            Operand::Temporary(number, _) => Ok(format!("%t{}", number)),
+            Operand::ArrayElement(name, array_type @ Types::Array(_, size), index) => {
+                let offset = match index.as_ref() {
+                    Operand::Constant(Value::Integer(i)) if (0..*size).contains(i) => i.to_string(),
+                    index => {
+                        let (value, index_type) = self.load(index)?;
+                        if index_type != Types::Integer {
+                            return Err(self.unsupported("array indices are INTEGER values"));
+                        }
+                        let offset = self.value();
+                        self.line(format!("{} = call i64 @mining_index(i16 {}, i16 {})", offset, value, size));
+                        offset
+                    },
+                };
+                let pointer = self.value();
+                let array = llvm_type(array_type);
+                self.line(format!("{} = getelementptr inbounds {}, {}* {}, i64 0, i64 {}", pointer, array, array, symbol(name), offset));
+                Ok(pointer)
+            },
+            _ => Err(self.unsupported("expected a variable, a temporary or an array element")),
+        }
+    }
+
+    // Value of an operand and its type
+    fn load(&mut self, operand: &Operand) -> Result<(String, Types), CodegenError> {
+        let value_type = match operand.value_type() {
+            Some(t @ (Types::Integer | Types::Float | Types::Char)) => t,
+            _ => return Err(self.unsupported("expected an INTEGER, FLOAT or CHAR value")),
+        };
+        if let Operand::Constant(value) = operand {
+            return Ok((constant(value), value_type));
+        }
+        let pointer = self.pointer(operand)?;
+        let value = self.value();
+        let ir_type = llvm_type(&value_type);
+        self.line(format!("{} = load {}, {}* {}", value, ir_type, ir_type, pointer));
+        Ok((value, value_type))
+    }
+
+    fn store(&mut self, result: Option<&Operand>, value: &str, value_type: &Types) -> Result<(), CodegenError> {
+        let result = result.ok_or_else(|| self.unsupported("missing result"))?;
+        if result.value_type().as_ref() != Some(value_type) {
+            return Err(self.unsupported("the result does not have the type of the value"));
+        }
+        let pointer = self.pointer(result)?;
+        let ir_type = llvm_type(value_type);
+        self.line(format!("store {} {}, {}* {}", ir_type, value, ir_type, pointer));
+        Ok(())
+    }
+
+    // i1 truth value of an operand: anything but zero, NaN included
+    fn truth(&mut self, operand: &Operand) -> Result<String, CodegenError> {
+        let (value, value_type) = self.load(operand)?;
+        let truth = self.value();
+        match value_type {
+            Types::Float => self.line(format!("{} = fcmp une float {}, 0.0", truth, value)),
+            _ => self.line(format!("{} = icmp ne {} {}, 0", truth, llvm_type(&value_type), value)),
+        }
+        Ok(truth)
+    }
+
+    fn boolean(&mut self, truth: &str) -> String {
+        let value = self.value();
+        self.line(format!("{} = zext i1 {} to i16", value, truth));
+        value
+    }
+
+    fn operands(&self, quadruplet: &Quadruplet) -> Result<(Operand, Operand), CodegenError> {
+        match (&quadruplet.operand1, &quadruplet.operand2) {
+            (Some(a), Some(b)) => Ok((a.clone(), b.clone())),
+            _ => Err(self.unsupported("expected two operands")),
+        }
+    }
+
+    fn quadruplet(&mut self, quadruplet: &Quadruplet) -> Result<(), CodegenError> {
+        let result = quadruplet.result.as_ref();
+        match quadruplet.operator {
+            Operator::Assign => {
+                let value = quadruplet.operand1.as_ref().ok_or_else(|| self.unsupported("missing value"))?;
+                let (value, value_type) = self.load(value)?;
+                self.store(result, &value, &value_type)
+            },
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => {
+                let (a, b) = self.operands(quadruplet)?;
+                let (value, value_type) = self.arithmetic(&quadruplet.operator, &a, &b)?;
+                self.store(result, &value, &value_type)
+            },
+            Operator::GreaterThan | Operator::LessThan | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual | Operator::Equal | Operator::NotEqual => {
+                let (a, b) = self.operands(quadruplet)?;
+                let truth = self.comparison(&quadruplet.operator, &a, &b)?;
+                let value = self.boolean(&truth);
+                self.store(result, &value, &Types::Integer)
+            },
+            Operator::LogicalAnd | Operator::LogicalOr => {
+                let (a, b) = self.operands(quadruplet)?;
+                let (a, b) = (self.truth(&a)?, self.truth(&b)?);
+                let truth = self.value();
+                let operation = if quadruplet.operator == Operator::LogicalAnd { "and" } else { "or" };
+                self.line(format!("{} = {} i1 {}, {}", truth, operation, a, b));
+                let value = self.boolean(&truth);
+                self.store(result, &value, &Types::Integer)
+            },
+            Operator::LogicalNot => {
+                let operand = quadruplet.operand1.as_ref().ok_or_else(|| self.unsupported("missing value"))?;
+                let truth = self.truth(operand)?;
+                let negated = self.value();
+                self.line(format!("{} = xor i1 {}, true", negated, truth));
+                let value = self.boolean(&negated);
+                self.store(result, &value, &Types::Integer)
+            },
+            Operator::Read => {
+                let value_type = result.and_then(Operand::value_type).ok_or_else(|| self.unsupported("missing result"))?;
+                let function = match value_type {
+                    Types::Integer => "mining_read_int",
+                    Types::Float => "mining_read_float",
+                    Types::Char => "mining_read_char",
+                    Types::Array(_, _) => return Err(self.unsupported("READ of a whole array")),
+                };
+                let value = self.value();
+                self.line(format!("{} = call {} @{}()", value, llvm_type(&value_type), function));
+                self.store(result, &value, &value_type)
+            },
+            Operator::Write => match &quadruplet.operand1 {
+                Some(Operand::String(text)) => {
+                    self.strings.push(text.clone());
+                    let array = format!("[{} x i8]", text.len() + 1);
+                    self.line(format!(
+                        "call void @mining_write_string(i8* getelementptr inbounds ({}, {}* @mining_string{}, i64 0, i64 0))",
+                        array, array, self.strings.len() - 1
+                    ));
+                    Ok(())
+                },
+                Some(value) => {
+                    let (value, value_type) = self.load(value)?;
+                    let function = match value_type {
+                        Types::Float => "mining_write_float",
+                        Types::Char => "mining_write_char",
+                        _ => "mining_write_int",
+                    };
+                    self.line(format!("call void @{}({} {})", function, llvm_type(&value_type), value));
+                    Ok(())
+                },
+                None => Err(self.unsupported("missing value")),
+            },
+            Operator::Goto | Operator::IfTrue | Operator::IfFalse => {
+                let target = jump_target(quadruplet).ok_or_else(|| self.unsupported("missing target"))?.min(self.quadruplets.len());
+                let next = self.current + 1;
+                match (&quadruplet.operator, &quadruplet.operand1) {
+                    (Operator::Goto, _) => self.branch(format!("br label %q{}", target)),
+                    (operator, Some(condition)) => {
+                        let truth = self.truth(condition)?;
+                        let (taken, not_taken) = if *operator == Operator::IfTrue { (target, next) } else { (next, target) };
+                        self.branch(format!("br i1 {}, label %q{}, label %q{}", truth, taken, not_taken));
+                    },
+                    _ => return Err(self.unsupported("missing condition")),
+                }
+                Ok(())
+            },
+            Operator::For => Err(self.unsupported("For must be lowered to jumps")),
+        }
+    }
+
+    fn arithmetic(&mut self, operator: &Operator, a: &Operand, b: &Operand) -> Result<(String, Types), CodegenError> {
+        let (a, value_type) = self.load(a)?;
+        let (b, b_type) = self.load(b)?;
+        if b_type != value_type {
+            return Err(self.unsupported("operands of different types"));
+        }
+        let value = self.value();
+        match (&value_type, operator) {
+            (Types::Integer, Operator::Divide) => self.line(format!("{} = call i16 @mining_div(i16 {}, i16 {})", value, a, b)),
+            (Types::Integer, _) => {
+                let function = match operator {
+                    Operator::Add => "mining_add",
+                    Operator::Subtract => "mining_sub",
+                    _ => "mining_mul",
+                };
+                self.line(format!("{} = call i16 @{}(i16 {}, i16 {})", value, function, a, b));
+            },
+            (Types::Float, _) => {
+                let instruction = match operator {
+                    Operator::Add => "fadd",
+                    Operator::Subtract => "fsub",
+                    Operator::Multiply => "fmul",
+                    _ => "fdiv",
+                };
+                self.line(format!("{} = {} float {}, {}", value, instruction, a, b));
+            },
+            (_, Operator::Add) => self.line(format!("{} = call i8 @mining_char_add(i8 {}, i8 {})", value, a, b)),
+            (_, Operator::Subtract) => self.line(format!("{} = call i8 @mining_char_sub(i8 {}, i8 {})", value, a, b)),
+            _ => return Err(self.unsupported("CHAR values only support addition and subtraction")),
+        }
+        Ok((value, value_type))
+    }
+
+    // i1 result of comparing a with b: INTEGER values are signed, CHAR codes unsigned
+    // and FLOAT comparisons involving NaN are only true for !=
+    fn comparison(&mut self, operator: &Operator, a: &Operand, b: &Operand) -> Result<String, CodegenError> {
+        let (a, value_type) = self.load(a)?;
+        let (b, b_type) = self.load(b)?;
+        if b_type != value_type {
+            return Err(self.unsupported("operands of different types"));
+        }
+        let condition = match (&value_type, operator) {
+            (Types::Float, Operator::GreaterThan) => "fcmp ogt",
+            (Types::Float, Operator::LessThan) => "fcmp olt",
+            (Types::Float, Operator::GreaterThanOrEqual) => "fcmp oge",
+            (Types::Float, Operator::LessThanOrEqual) => "fcmp ole",
+            (Types::Float, Operator::Equal) => "fcmp oeq",
+            (Types::Float, _) => "fcmp une",
+            (Types::Integer, Operator::GreaterThan) => "icmp sgt",
+            (Types::Integer, Operator::LessThan) => "icmp slt",
+            (Types::Integer, Operator::GreaterThanOrEqual) => "icmp sge",
+            (Types::Integer, Operator::LessThanOrEqual) => "icmp sle",
+            (_, Operator::GreaterThan) => "icmp ugt",
+            (_, Operator::LessThan) => "icmp ult",
+            (_, Operator::GreaterThanOrEqual) => "icmp uge",
+            (_, Operator::LessThanOrEqual) => "icmp ule",
+            (_, Operator::Equal) => "icmp eq",
+            _ => "icmp ne",
+        };
+        let truth = self.value();
+        self.line(format!("{} = {} {} {}, {}", truth, condition, llvm_type(&value_type), a, b));
+        Ok(truth)
+    }
+
+    // Module header, globals, strings, main and the runtime
+    fn finish(self) -> String {
+        let mut output = String::from("; MinING program\n\n");
+
+        // Variables given a constant at the start of the program are initialized globals
+        let (_, initial) = static_initializers(self.quadruplets);
+        for (name, symbol_type) in collect_symbols(self.quadruplets) {
+            if name.starts_with('t') && name[1..].parse::<usize>().is_ok() {
+                continue;
+            }
+            let value = match initial.iter().find(|(known, _)| *known == name) {
+                Some((_, value)) => constant(value),
+                None if matches!(symbol_type, Types::Array(_, _)) => "zeroinitializer".to_string(),
+                None if symbol_type == Types::Float => "0.0".to_string(),
+                None => "0".to_string(),
+            };
+            output.push_str(&format!("{} = internal global {} {}\n", symbol(&name), llvm_type(&symbol_type), value));
+        }
+        output.push('\n');
+        for (index, text) in self.strings.iter().enumerate() {
+            output.push_str(&format!(
+                "@mining_string{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n", index, text.len() + 1, escape(text)
+            ));
+        }
+        for (index, location) in self.locations.iter().enumerate() {
+            output.push_str(&format!("@mining_origin{} = private unnamed_addr constant [{} x i8] c\"{}\"\n", index, location.len(), escape(location)));
+        }
+        // The location and the line break follow the message
+        for (index, (_, message)) in RUNTIME_ERRORS.iter().enumerate() {
+            let message = format!("Runtime error: {}", message);
+            output.push_str(&format!("@mining_error{} = private unnamed_addr constant [{} x i8] c\"{}\"\n", index, message.len(), escape(&message)));
+        }
+
+        output.push('\n');
+        output.push_str(&self.text);
+        output.push_str(RUNTIME);
+
+        for (index, (function, message)) in RUNTIME_ERRORS.iter().enumerate() {
+            let length = format!("Runtime error: {}", message).len();
+            output.push_str(&format!(
+                "\ndefine internal void @{}() noreturn {{\n  call void @mining_fail(i8* getelementptr inbounds ([{} x i8], [{} x i8]* @mining_error{}, i64 0, i64 0), i64 {})\n  unreachable\n}}\n",
+                function, length, length, index, length
+            ));
+        }
+        for (function, intrinsic) in [("mining_add", "sadd"), ("mining_sub", "ssub"), ("mining_mul", "smul")] {
+            output.push_str(&format!(
+                "\ndefine internal i16 @{}(i16 %a, i16 %b) {{\nentry:\n  %result = call {{ i16, i1 }} @llvm.{}.with.overflow.i16(i16 %a, i16 %b)\n  %overflows = extractvalue {{ i16, i1 }} %result, 1\n  br i1 %overflows, label %overflow, label %done\noverflow:\n  call void @mining_overflow_error()\n  unreachable\ndone:\n  %value = extractvalue {{ i16, i1 }} %result, 0\n  ret i16 %value\n}}\n",
+                function, intrinsic
+            ));
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+    use crate::Test::tests::{generate_source, run_program, TemporaryDirectory, FAILING_PROGRAM, QUADRUPLETS};
+
+    // Runs the module with the LLVM interpreter, which the test needs
+    fn run(module: &str, input: &str) -> (String, String, i32) {
+        let directory = TemporaryDirectory::new(&format!("mining_llvm_{}", module.len()));
+        let path = directory.path().join("p.ll");
+        std::fs::write(&path, module).unwrap();
+        run_program(Command::new("lli").arg(&path), input)
+    }
+
+    #[test]
+    fn test_generated_llvm() {
+        let module = generate_llvm(&parse_quadruplets(QUADRUPLETS).unwrap()).unwrap();
+        // The leading constants are initial values of the globals
+        assert!(module.contains("@m_S = internal global i16 0\n"));
+        assert!(module.contains("@m_G = internal global float 0x3FF8000000000000\n"));
+        assert!(module.contains("@m_A = internal global [3 x i16] zeroinitializer\n"));
+        assert!(module.contains("%t4 = alloca float\n"));
+        assert!(module.contains("q6:\n"));
+
+        let (output, _, status) = run(&module, "-7 2.25\n a");
+        assert_eq!((output.as_str(), status), ("S = -21\n1.5b13", 0));
+
+        let (output, errors, status) = run(&module, "0 1.0 x");
+        assert_eq!((output.as_str(), errors.as_str(), status), ("S = 0\n0.666667y0", "Runtime error: Division by zero\n", 1));
+
+        let (_, errors, status) = run(&module, "40000");
+        assert_eq!((errors.as_str(), status), ("Runtime error: Invalid INTEGER input\n", 1));
+        let (_, errors, _) = run(&module, "1 2.0");
+        assert_eq!(errors, "Runtime error: Unexpected end of input\n");
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let module = generate_llvm(&parse_quadruplets(r#"
+            INTEGER I, X;
+            INTEGER A[2];
+            FLOAT F;
+            0: (Read, _, _, I)
+            1: (Assign, 32767, _, X)
+            2: (Add, X, I, A[I])
+            3: (Read, _, _, F)
+            4: (Write, F, _, _)
+        "#).unwrap()).unwrap();
+        let (output, _, status) = run(&module, "0 -2.5");
+        assert_eq!((output.as_str(), status), ("-2.5", 0));
+        // The overflow is found before the index is checked
+        assert_eq!(run(&module, "5").1, "Runtime error: INTEGER overflow\n");
+        assert_eq!(run(&module, "-1").1, "Runtime error: Array index out of bounds\n");
+        assert_eq!(run(&module, "0 1e3").1, "Runtime error: Invalid FLOAT input\n");
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let module = generate_llvm(&generate_source(FAILING_PROGRAM)).unwrap();
+        let error = |input: &str| run(&module, input).1;
+        let stack = "\n    in FOR at line 10, column 13\
+                     \n    in IF at line 9, column 9\
+                     \n    in FOR at line 8, column 5\n";
+        assert_eq!(error("1"), format!("Runtime error: Division by zero at line 11, column 28{}", stack));
+        assert_eq!(error("32767"), format!("Runtime error: INTEGER overflow at line 11, column 33{}", stack));
+        assert_eq!(error("5"), "Runtime error: Array index out of bounds at line 15, column 5\n");
+        assert_eq!(error("2 x"), "Runtime error: Invalid INTEGER input at line 16, column 10\n");
+        assert_eq!(error("2"), "Runtime error: Unexpected end of input at line 16, column 10\n");
+    }
+}
@@ -0,0 +1,515 @@
+//! MIPS backend: MIPS32 assembly for the SPIM and MARS simulators
+//!
+//! The layout follows the RISC-V backend: every variable, array and temporary has a static slot in
+//! `.data`, each quadruplet loads its operands in $t0/$t1 or $f0/$f2, computes and stores its
+//! result. INTEGER values are sign extended from 16 bits, FLOAT values use coprocessor 1. READ and
+//! WRITE are the SPIM system calls, so that the programs can be studied without a runtime:
+//!
+//! | Statement      | INTEGER        | FLOAT           | CHAR            | String          |
+//! |----------------|----------------|-----------------|-----------------|-----------------|
+//! | WRITE          | 1 `print_int`  | 2 `print_float` | 11 `print_char` | 4 `print_string`|
+//! | READ           | 5 `read_int`   | 6 `read_float`  | 12 `read_char`  |                 |
+//!
+//! The simulator formats FLOAT values and parses the input, so unlike the other backends WRITE
+//! prints floats with its own digits and READ takes one INTEGER or FLOAT per line. Run-time
+//! errors print their message with `print_string`, SPIM has no standard error, and exit with
+//! status 1 through the system call 17 (`exit2`). Branch delay slots are not used, which is the
+//! default of both simulators:
+//!
+//! ```text
+//! spim -file prog.s
+//! java -jar Mars.jar nc prog.s
+//! ```
+
+use std::collections::HashSet;
+use crate::codegen::error::CodegenError;
+use crate::codegen::x86_64::RUNTIME_ERRORS;
+use crate::codegen::{is_signed, static_initializers, storage_size, symbol};
+use crate::Semantic::quadruplets::{collect_symbols, jump_target, Operand, Operator, Quadruplet, Value};
+use crate::Semantic::ts::Types;
+
+// Registers holding the first and second operand of a quadruplet
+const INTEGER_REGISTERS: [&str; 2] = ["$t0", "$t1"];
+const FLOAT_REGISTERS: [&str; 2] = ["$f0", "$f2"];
+
+/// Lowers the quadruplets to a complete SPIM program, `main` being the first quadruplet
+pub fn generate_mips(quadruplets: &[Quadruplet]) -> Result<String, CodegenError> {
+    let mut generator = MipsGenerator::new(quadruplets);
+    generator.generate()?;
+    Ok(generator.finish())
+}
+
+// Data directives for a string ended by a zero byte: .asciiz when it only has printable
+// characters, new lines and tabs, bytes otherwise
+fn string_data(text: &str) -> String {
+    if text.bytes().all(|byte| (0x20..=0x7E).contains(&byte) || byte == b'\n' || byte == b'\t') {
+        let escaped = text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+        return format!(".asciiz \"{}\"", escaped);
+    }
+    let bytes: Vec<String> = text.bytes().chain([0]).map(|byte| byte.to_string()).collect();
+    format!(".byte {}", bytes.join(", "))
+}
+
+fn char_code(c: char) -> u8 {
+    c as u32 as u8
+}
+
+// log2 of the size of the elements of a symbol, for .align and the index scaling
+fn alignment(symbol_type: &Types) -> usize {
+    let size = match symbol_type {
+        Types::Array(element, _) => storage_size(element),
+        scalar => storage_size(scalar),
+    };
+    size.trailing_zeros() as usize
+}
+
+struct MipsGenerator<'a> {
+    quadruplets: &'a [Quadruplet],
+    current: usize,                 // Index of the quadruplet being lowered
+    text: String,
+    floats: Vec<u32>,               // FLOAT constants by bit pattern, labelled mining_f{index}
+    strings: Vec<String>,           // WRITE strings, labelled mining_s{index}
+    labels: usize,                  // Local labels of the FLOAT comparisons, mining_c{index}
+}
+
+impl<'a> MipsGenerator<'a> {
+    fn new(quadruplets: &'a [Quadruplet]) -> Self {
+        MipsGenerator { quadruplets, current: 0, text: String::new(), floats: Vec::new(), strings: Vec::new(), labels: 0 }
+    }
+
+    fn line(&mut self, instruction: impl AsRef<str>) {
+        self.text.push_str("    ");
+        self.text.push_str(instruction.as_ref());
+        self.text.push('\n');
+    }
+
+    fn unsupported(&self, reason: &str) -> CodegenError {
+        CodegenError::Unsupported(self.current, self.quadruplets[self.current].to_string(), reason.to_string())
+    }
+
+    fn float_label(&mut self, value: f32) -> String {
+        let bits = value.to_bits();
+        let index = self.floats.iter().position(|b| *b == bits).unwrap_or_else(|| {
+            self.floats.push(bits);
+            self.floats.len() - 1
+        });
+        format!("mining_f{}", index)
+    }
+
+    fn system_call(&mut self, service: u32) {
+        self.line(format!("li $v0, {}", service));
+        self.line("syscall");
+    }
+
+    fn generate(&mut self) -> Result<(), CodegenError> {
+        let (initialized, _) = static_initializers(self.quadruplets);
+        let targets: HashSet<usize> = self.quadruplets.iter().filter_map(jump_target).collect();
+
+        self.text.push_str("    .text\n    .globl main\nmain:\n");
+        let quadruplets = self.quadruplets;
+        for (index, quadruplet) in quadruplets.iter().enumerate().skip(initialized) {
+            self.current = index;
+            if targets.contains(&index) {
+                self.text.push_str(&format!("q{}:\n", index));
+            }
+            self.text.push_str(&format!("    # {}: {}\n", index, quadruplet));
+            self.quadruplet(quadruplet)?;
+        }
+        self.text.push_str(&format!("q{}:\n", self.quadruplets.len()));
+        self.system_call(10);
+        Ok(())
+    }
+
+    // Memory operand of a variable, temporary or array element, its address is computed in $t2.
+    // The index of an array element is checked against the bounds of the array
+    fn address(&mut self, operand: &Operand) -> Result<String, CodegenError> {
+        match operand {
+            Operand::Variable(name, _) => self.line(format!("la $t2, {}", symbol(name))),
+            Operand::Temporary(_, _) => self.line(format!("la $t2, {}", symbol(&operand.to_string()))),
+            Operand::ArrayElement(name, Types::Array(element, size), index) => {
+                let shift = alignment(element);
+                if let Operand::Constant(Value::Integer(i)) = index.as_ref() {
+                    if (0..*size).contains(i) {
+                        self.line(format!("la $t2, {}", symbol(name)));
+                        return Ok(format!("{}($t2)", (*i as usize) << shift));
+                    }
+                }
+                match index.as_ref() {
+                    Operand::Constant(Value::Integer(i)) => self.line(format!("li $t2, {}", i)),
+                    Operand::Variable(_, Types::Integer) | Operand::Temporary(_, Types::Integer) => {
+                        let index = self.address(index)?;
+                        self.line(format!("lh $t2, {}", index));
+                    },
+                    _ => return Err(self.unsupported("array indices are INTEGER values")),
+                }
+                // Negative indices are above the size when compared unsigned
+                self.line(format!("li $t3, {}", size));
+                self.line("bgeu $t2, $t3, mining_index_error");
+                if shift > 0 {
+                    self.line(format!("sll $t2, $t2, {}", shift));
+                }
+                self.line(format!("la $t3, {}", symbol(name)));
+                self.line("addu $t2, $t2, $t3");
+            },
+            _ => return Err(self.unsupported("expected a variable, a temporary or an array element")),
+        }
+        Ok("0($t2)".to_string())
+    }
+
+    // Loads an operand in the first or second register of its type and returns the type
+    fn load(&mut self, operand: &Operand, slot: usize) -> Result<Types, CodegenError> {
+        let (integer, float) = (INTEGER_REGISTERS[slot], FLOAT_REGISTERS[slot]);
+        let value_type = match operand.value_type() {
+            Some(t @ (Types::Integer | Types::Float | Types::Char)) => t,
+            _ => return Err(self.unsupported("expected an INTEGER, FLOAT or CHAR value")),
+        };
+        match operand {
+            Operand::Constant(Value::Integer(i)) => self.line(format!("li {}, {}", integer, i)),
+            Operand::Constant(Value::Char(c)) => self.line(format!("li {}, {}", integer, char_code(*c))),
+            Operand::Constant(Value::Float(f)) => {
+                let label = self.float_label(*f);
+                self.line(format!("l.s {}, {}", float, label));
+            },
+            _ => {
+                let address = self.address(operand)?;
+                match value_type {
+                    Types::Integer => self.line(format!("lh {}, {}", integer, address)),
+                    Types::Char => self.line(format!("lbu {}, {}", integer, address)),
+                    _ => self.line(format!("l.s {}, {}", float, address)),
+                }
+            },
+        }
+        Ok(value_type)
+    }
+
+    // Stores $t0 or $f0 depending on the type of the value
+    fn store(&mut self, operand: Option<&Operand>, value_type: &Types) -> Result<(), CodegenError> {
+        let operand = operand.ok_or_else(|| self.unsupported("missing result"))?;
+        if operand.value_type().as_ref() != Some(value_type) {
+            return Err(self.unsupported("the result does not have the type of the value"));
+        }
+        let address = self.address(operand)?;
+        match value_type {
+            Types::Integer => self.line(format!("sh $t0, {}", address)),
+            Types::Char => self.line(format!("sb $t0, {}", address)),
+            _ => self.line(format!("s.s $f0, {}", address)),
+        }
+        Ok(())
+    }
+
+    // Copies the condition flag of coprocessor 1 to $t0, inverted when asked
+    fn float_condition(&mut self, inverted: bool) {
+        let label = format!("mining_c{}", self.labels);
+        self.labels += 1;
+        self.line(format!("li $t0, {}", if inverted { 0 } else { 1 }));
+        self.line(format!("bc1t {}", label));
+        self.line(format!("li $t0, {}", if inverted { 1 } else { 0 }));
+        self.text.push_str(&format!("{}:\n", label));
+    }
+
+    // Truth value of an operand in $t0: 1 unless it is zero
+    fn truth(&mut self, operand: &Operand) -> Result<(), CodegenError> {
+        if self.load(operand, 0)? == Types::Float {
+            // NaN is not equal to zero, so it is true
+            self.line("mtc1 $zero, $f2");
+            self.line("c.eq.s $f0, $f2");
+            self.float_condition(true);
+        } else {
+            self.line("sltu $t0, $zero, $t0");
+        }
+        Ok(())
+    }
+
+    fn operands(&self, quadruplet: &Quadruplet) -> Result<(Operand, Operand), CodegenError> {
+        match (&quadruplet.operand1, &quadruplet.operand2) {
+            (Some(a), Some(b)) => Ok((a.clone(), b.clone())),
+            _ => Err(self.unsupported("expected two operands")),
+        }
+    }
+
+    fn quadruplet(&mut self, quadruplet: &Quadruplet) -> Result<(), CodegenError> {
+        let result = quadruplet.result.as_ref();
+        match quadruplet.operator {
+            Operator::Assign => {
+                let value = quadruplet.operand1.as_ref().ok_or_else(|| self.unsupported("missing value"))?;
+                let value_type = self.load(value, 0)?;
+                self.store(result, &value_type)
+            },
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => {
+                let (a, b) = self.operands(quadruplet)?;
+                let value_type = self.arithmetic(&quadruplet.operator, &a, &b)?;
+                self.store(result, &value_type)
+            },
+            Operator::GreaterThan | Operator::LessThan | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual | Operator::Equal | Operator::NotEqual => {
+                let (a, b) = self.operands(quadruplet)?;
+                self.comparison(&quadruplet.operator, &a, &b)?;
+                self.store(result, &Types::Integer)
+            },
+            Operator::LogicalAnd | Operator::LogicalOr => {
+                let (a, b) = self.operands(quadruplet)?;
+                self.truth(&a)?;
+                self.line("move $t4, $t0");
+                self.truth(&b)?;
+                self.line(if quadruplet.operator == Operator::LogicalAnd { "and $t0, $t0, $t4" } else { "or $t0, $t0, $t4" });
+                self.store(result, &Types::Integer)
+            },
+            Operator::LogicalNot => {
+                let value = quadruplet.operand1.as_ref().ok_or_else(|| self.unsupported("missing value"))?;
+                self.truth(value)?;
+                self.line("xori $t0, $t0, 1");
+                self.store(result, &Types::Integer)
+            },
+            Operator::Read => {
+                let value_type = result.and_then(Operand::value_type).ok_or_else(|| self.unsupported("missing result"))?;
+                match value_type {
+                    Types::Integer => {
+                        // read_int returns 32 bits, the value must fit in an INTEGER
+                        self.system_call(5);
+                        self.line("move $t0, $v0");
+                        self.line("sll $t2, $t0, 16");
+                        self.line("sra $t2, $t2, 16");
+                        self.line("bne $t2, $t0, mining_integer_input_error");
+                    },
+                    Types::Float => self.system_call(6),
+                    Types::Char => {
+                        self.system_call(12);
+                        self.line("move $t0, $v0");
+                    },
+                    Types::Array(_, _) => return Err(self.unsupported("READ of a whole array")),
+                }
+                self.store(result, &value_type)
+            },
+            Operator::Write => match &quadruplet.operand1 {
+                Some(Operand::String(text)) => {
+                    self.strings.push(text.clone());
+                    self.line(format!("la $a0, mining_s{}", self.strings.len() - 1));
+                    self.system_call(4);
+                    Ok(())
+                },
+                Some(value) => {
+                    match self.load(value, 0)? {
+                        Types::Integer => {
+                            self.line("move $a0, $t0");
+                            self.system_call(1);
+                        },
+                        Types::Char => {
+                            self.line("move $a0, $t0");
+                            self.system_call(11);
+                        },
+                        _ => {
+                            self.line("mov.s $f12, $f0");
+                            self.system_call(2);
+                        },
+                    }
+                    Ok(())
+                },
+                None => Err(self.unsupported("missing value")),
+            },
+            Operator::Goto | Operator::IfTrue | Operator::IfFalse => {
+                let target = jump_target(quadruplet).ok_or_else(|| self.unsupported("missing target"))?;
+                match (&quadruplet.operator, &quadruplet.operand1) {
+                    (Operator::Goto, _) => self.line(format!("j q{}", target)),
+                    (operator, Some(condition)) => {
+                        self.truth(condition)?;
+                        let branch = if *operator == Operator::IfTrue { "bnez" } else { "beqz" };
+                        self.line(format!("{} $t0, q{}", branch, target));
+                    },
+                    _ => return Err(self.unsupported("missing condition")),
+                }
+                Ok(())
+            },
+            Operator::For => Err(self.unsupported("For must be lowered to jumps")),
+        }
+    }
+
+    // Computes a op b in $t0 or $f0 and returns the type of the result
+    fn arithmetic(&mut self, operator: &Operator, a: &Operand, b: &Operand) -> Result<Types, CodegenError> {
+        let value_type = self.load(a, 0)?;
+        if self.load(b, 1)? != value_type {
+            return Err(self.unsupported("operands of different types"));
+        }
+        match (value_type.clone(), operator) {
+            (Types::Integer, _) => {
+                match operator {
+                    Operator::Add => self.line("addu $t0, $t0, $t1"),
+                    Operator::Subtract => self.line("subu $t0, $t0, $t1"),
+                    Operator::Multiply => self.line("mul $t0, $t0, $t1"),
+                    _ => {
+                        // The two operands form of div does not check the divisor
+                        self.line("beqz $t1, mining_division_error");
+                        self.line("div $t0, $t1");
+                        self.line("mflo $t0");
+                    },
+                }
+                // The 32 bits result must fit in 16 bits, -32768 / -1 included
+                self.line("sll $t2, $t0, 16");
+                self.line("sra $t2, $t2, 16");
+                self.line("bne $t2, $t0, mining_overflow_error");
+            },
+            (Types::Float, Operator::Add) => self.line("add.s $f0, $f0, $f2"),
+            (Types::Float, Operator::Subtract) => self.line("sub.s $f0, $f0, $f2"),
+            (Types::Float, Operator::Multiply) => self.line("mul.s $f0, $f0, $f2"),
+            (Types::Float, _) => self.line("div.s $f0, $f0, $f2"),
+            (_, Operator::Add | Operator::Subtract) => {
+                // CHAR codes wrap around 0x7F, a negative remainder gets 127 added
+                self.line(if *operator == Operator::Add { "addu $t0, $t0, $t1" } else { "subu $t0, $t0, $t1" });
+                self.line("li $t2, 127");
+                self.line("div $t0, $t2");
+                self.line("mfhi $t0");
+                self.line("sra $t3, $t0, 31");
+                self.line("and $t3, $t3, $t2");
+                self.line("addu $t0, $t0, $t3");
+            },
+            _ => return Err(self.unsupported("CHAR values only support addition and subtraction")),
+        }
+        Ok(value_type)
+    }
+
+    // Sets $t0 to the result of comparing a with b
+    fn comparison(&mut self, operator: &Operator, a: &Operand, b: &Operand) -> Result<(), CodegenError> {
+        let value_type = self.load(a, 0)?;
+        if self.load(b, 1)? != value_type {
+            return Err(self.unsupported("operands of different types"));
+        }
+        if value_type == Types::Float {
+            // The comparisons are false when an operand is NaN, so only != is true then
+            match operator {
+                Operator::GreaterThan => self.line("c.lt.s $f2, $f0"),
+                Operator::LessThan => self.line("c.lt.s $f0, $f2"),
+                Operator::GreaterThanOrEqual => self.line("c.le.s $f2, $f0"),
+                Operator::LessThanOrEqual => self.line("c.le.s $f0, $f2"),
+                _ => self.line("c.eq.s $f0, $f2"),
+            }
+            self.float_condition(*operator == Operator::NotEqual);
+            return Ok(());
+        }
+
+        let less = if is_signed(&value_type) { "slt" } else { "sltu" };
+        match operator {
+            Operator::GreaterThan => self.line(format!("{} $t0, $t1, $t0", less)),
+            Operator::LessThan => self.line(format!("{} $t0, $t0, $t1", less)),
+            Operator::GreaterThanOrEqual => {
+                self.line(format!("{} $t0, $t0, $t1", less));
+                self.line("xori $t0, $t0, 1");
+            },
+            Operator::LessThanOrEqual => {
+                self.line(format!("{} $t0, $t1, $t0", less));
+                self.line("xori $t0, $t0, 1");
+            },
+            Operator::Equal => {
+                self.line("xor $t0, $t0, $t1");
+                self.line("sltiu $t0, $t0, 1");
+            },
+            _ => {
+                self.line("xor $t0, $t0, $t1");
+                self.line("sltu $t0, $zero, $t0");
+            },
+        }
+        Ok(())
+    }
+
+    // Appends the error handlers and the data: the constants, the strings and the storage of
+    // the symbols, larger elements first so that they stay aligned
+    fn finish(mut self) -> String {
+        let mut output = std::mem::take(&mut self.text);
+
+        let mut messages = String::new();
+        for (index, (label, message)) in RUNTIME_ERRORS.iter().enumerate() {
+            output.push_str(&format!("\n{}:\n", label));
+            output.push_str(&format!("    la $a0, mining_e{}\n", index));
+            output.push_str("    li $v0, 4\n    syscall\n");
+            output.push_str("    li $a0, 1\n    li $v0, 17\n    syscall\n");
+            messages.push_str(&format!("mining_e{}: {}\n", index, string_data(&format!("Runtime error: {}\n", message))));
+        }
+
+        output.push_str("\n    .data\n    .align 2\n");
+        for (index, bits) in self.floats.iter().enumerate() {
+            output.push_str(&format!("mining_f{}: .word 0x{:08x}    # {:?}\n", index, bits, f32::from_bits(*bits)));
+        }
+        let (_, initial) = static_initializers(self.quadruplets);
+        let mut symbols: Vec<(String, Types)> = collect_symbols(self.quadruplets).into_iter().collect();
+        symbols.sort_by_key(|(_, symbol_type)| std::cmp::Reverse(alignment(symbol_type)));
+        for (name, symbol_type) in &symbols {
+            // Variables given a constant at the start of the program are initialized
+            let directive = match initial.iter().find(|(known, _)| known == name).map(|(_, value)| value) {
+                Some(Value::Integer(i)) => format!(".half {}", i),
+                Some(Value::Float(f)) => format!(".word 0x{:08x}    # {:?}", f.to_bits(), f),
+                Some(Value::Char(c)) => format!(".byte {}", char_code(*c)),
+                None => format!(".space {}", storage_size(symbol_type).max(1)),
+            };
+            if alignment(symbol_type) > 0 {
+                output.push_str(&format!("    .align {}\n", alignment(symbol_type)));
+            }
+            output.push_str(&format!("{}: {}\n", symbol(name), directive));
+        }
+        for (index, text) in self.strings.iter().enumerate() {
+            output.push_str(&format!("mining_s{}: {}\n", index, string_data(text)));
+        }
+        output.push_str(&messages);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+    use crate::Test::tests::QUADRUPLETS;
+
+    // Lines of the code of a quadruplet, from its comment to the next one
+    fn lowering(assembly: &str, index: usize) -> Vec<String> {
+        let header = format!("    # {}: ", index);
+        assembly.lines()
+            .skip_while(|line| !line.starts_with(&header))
+            .skip(1)
+            .take_while(|line| !line.trim_start().starts_with('#') && !line.is_empty())
+            .filter(|line| !line.ends_with(':'))
+            .map(|line| line.trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_generated_mips() {
+        // The comparison is made with a FLOAT constant, loaded from the data section
+        let quads = parse_quadruplets(&QUADRUPLETS.replace("(GreaterThanOrEqual, F, G, t3)", "(NotEqual, F, 2.5, t3)")).unwrap();
+        let assembly = generate_mips(&quads).unwrap();
+        assert!(assembly.starts_with("    .text\n    .globl main\nmain:\n"));
+        // Leading constant assignments become initialized data
+        assert!(assembly.contains("m_S: .half 0\n"));
+        assert!(assembly.contains("m_G: .word 0x3fc00000"));
+        assert!(!assembly.contains("# 1: (Assign"));
+        assert!(assembly.contains("m_A: .space 6\n"));
+        assert!(assembly.contains("mining_s0: .asciiz \"S = \"\n"));
+        assert!(assembly.contains("mining_s1: .asciiz \"\\n\"\n"));
+        assert!(assembly.contains("mining_e0: .asciiz \"Runtime error: Division by zero\\n\"\n"));
+
+        // READ and WRITE are system calls
+        assert_eq!(lowering(&assembly, 2), ["li $v0, 5", "syscall", "move $t0, $v0", "sll $t2, $t0, 16", "sra $t2, $t2, 16",
+            "bne $t2, $t0, mining_integer_input_error", "la $t2, m_N", "sh $t0, 0($t2)"]);
+        assert_eq!(lowering(&assembly, 3), ["li $v0, 6", "syscall", "la $t2, m_F", "s.s $f0, 0($t2)"]);
+        assert_eq!(lowering(&assembly, 4), ["li $v0, 12", "syscall", "move $t0, $v0", "la $t2, m_C", "sb $t0, 0($t2)"]);
+        assert_eq!(lowering(&assembly, 13), ["la $a0, mining_s0", "li $v0, 4", "syscall"]);
+        assert_eq!(lowering(&assembly, 14), ["la $t2, m_S", "lh $t0, 0($t2)", "move $a0, $t0", "li $v0, 1", "syscall"]);
+        assert_eq!(lowering(&assembly, 17), ["la $t2, m_t4", "l.s $f0, 0($t2)", "mov.s $f12, $f0", "li $v0, 2", "syscall"]);
+        assert_eq!(lowering(&assembly, 19), ["la $t2, m_t5", "lbu $t0, 0($t2)", "move $a0, $t0", "li $v0, 11", "syscall"]);
+
+        // Array indices are checked, the element address is computed in $t2
+        assert_eq!(lowering(&assembly, 9), ["la $t2, m_S", "lh $t0, 0($t2)", "la $t2, m_I", "lh $t2, 0($t2)", "li $t3, 3",
+            "bgeu $t2, $t3, mining_index_error", "sll $t2, $t2, 1", "la $t3, m_A", "addu $t2, $t2, $t3", "lh $t1, 0($t2)",
+            "addu $t0, $t0, $t1", "sll $t2, $t0, 16", "sra $t2, $t2, 16", "bne $t2, $t0, mining_overflow_error",
+            "la $t2, m_S", "sh $t0, 0($t2)"]);
+        assert_eq!(lowering(&assembly, 7), ["la $t2, m_t1", "lh $t0, 0($t2)", "sltu $t0, $zero, $t0", "beqz $t0, q13"]);
+        assert_eq!(lowering(&assembly, 20), ["la $t2, m_F", "l.s $f0, 0($t2)", "l.s $f2, mining_f0", "c.eq.s $f0, $f2",
+            "li $t0, 0", "bc1t mining_c0", "li $t0, 1", "la $t2, m_t3", "sh $t0, 0($t2)"]);
+        assert!(assembly.contains("mining_f0: .word 0x40200000    # 2.5\n"));
+        assert!(lowering(&assembly, 22).contains(&"beqz $t1, mining_division_error".to_string()));
+        assert!(assembly.contains("q24:\n    li $v0, 10\n    syscall\n"));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let quads = parse_quadruplets("CHAR C, D;\n0: (Multiply, C, C, D)\n").unwrap();
+        assert!(matches!(generate_mips(&quads), Err(CodegenError::Unsupported(0, _, _))));
+    }
+}
@@ -0,0 +1,202 @@
+//! Backends lowering the quadruplets to target code
+//!
+//! Every backend follows the same run-time conventions so that programs behave identically on
+//! all of them: WRITE prints FLOAT values with `format_float`, READ parses whitespace separated
+//! tokens (INTEGER like `str::parse::<i16>`, FLOAT with `parse_float`, CHAR is the next non-space
+//! character), and run-time errors print `Runtime error: <message>` on stderr and exit with 1
+
+pub mod bytecode;
+pub mod c;
+pub mod debugger;
+pub mod elf;
+pub mod error;
+pub mod executor;
+pub mod i8086;
+pub mod interpreter;
+pub mod llvm;
+pub mod mips;
+pub mod regalloc;
+pub mod riscv;
+pub mod runtime;
+pub mod sim8086;
+pub mod simrv32;
+pub mod simwat;
+pub mod trace;
+pub mod vm;
+pub mod wat;
+pub mod x86_64;
+
+use crate::Semantic::quadruplets::{jump_target, Operand, Operator, Quadruplet, Value};
+use crate::Semantic::ts::Types;
+
+// Powers of ten exactly representable as f64, used to scale the digits of a FLOAT input
+const POWERS_OF_TEN: [f64; 19] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18,
+];
+
+/// Text written by WRITE for a FLOAT: at most six decimals with the trailing zeros removed
+/// (`2.5`, `-0.333333`, `4.0`), values of 1e9 and above are divided by ten until they fit and
+/// carry the exponent (`999999995.904e3` for 1e12), `nan`, `inf` and `-inf` for the special values
+///
+/// The computation goes through f64 and rounds half to even so that it can be done the same way
+/// with the floating point instructions of every target
+pub fn format_float(value: f32) -> String {
+    if value.is_nan() {
+        return "nan".to_string();
+    }
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    if value.is_infinite() {
+        return format!("{}inf", sign);
+    }
+
+    let mut magnitude = (value as f64).abs();
+    let mut exponent = 0;
+    while magnitude >= 1e9 {
+        magnitude /= 10.0;
+        exponent += 1;
+    }
+    let scaled = (magnitude * 1e6).round_ties_even() as u64;
+    let (integer, mut fraction, mut digits) = (scaled / 1_000_000, scaled % 1_000_000, 6);
+    while digits > 1 && fraction % 10 == 0 {
+        fraction /= 10;
+        digits -= 1;
+    }
+
+    let mut text = format!("{}{}.{:0width$}", sign, integer, fraction, width = digits);
+    if exponent > 0 {
+        text.push_str(&format!("e{}", exponent));
+    }
+    text
+}
+
+/// FLOAT read by READ: an optional sign, digits and an optional decimal point, at most 18 digits
+/// in total. The digits are read as an integer then divided by a power of ten in f64
+pub fn parse_float(token: &str) -> Option<f32> {
+    let (negative, unsigned) = match token.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, token.strip_prefix('+').unwrap_or(token)),
+    };
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let count = whole.len() + fraction.len();
+    if count == 0 || count > 18 || !whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let mantissa: u64 = format!("{}{}", whole, fraction).parse().ok()?;
+    let value = mantissa as f64 / POWERS_OF_TEN[fraction.len()];
+    Some(if negative { -value } else { value } as f32)
+}
+
+/// Constant assignments to variables at the start of the program, before anything can jump
+/// back to them, which backends can lay out as initialized data instead of instructions.
+/// Returns how many quadruplets they cover and the initial value of each variable
+pub fn static_initializers(quadruplets: &[Quadruplet]) -> (usize, Vec<(String, Value)>) {
+    let targets: Vec<usize> = quadruplets.iter().filter_map(jump_target).collect();
+    let mut values: Vec<(String, Value)> = Vec::new();
+    let mut count = 0;
+    for (index, quadruplet) in quadruplets.iter().enumerate() {
+        if targets.contains(&index) || quadruplet.operator != Operator::Assign {
+            break;
+        }
+        let (Some(Operand::Constant(value)), Some(Operand::Variable(name, t))) = (&quadruplet.operand1, &quadruplet.result) else {
+            break;
+        };
+        if value.get_type() != *t {
+            break;
+        }
+        match values.iter_mut().find(|(known, _)| known == name) {
+            Some((_, known)) => *known = *value,
+            None => values.push((name.clone(), *value)),
+        }
+        count += 1;
+    }
+    (count, values)
+}
+
+/// Outcome of a program run to its exit by one of the simulators
+#[derive(Debug, Clone, PartialEq)]
+pub struct Simulation {
+    pub stdout_bytes: Vec<u8>,
+    pub stderr_bytes: Vec<u8>,
+    pub exit_code: i32,
+    pub steps: u64,
+}
+
+impl Simulation {
+    pub fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.stdout_bytes).to_string()
+    }
+
+    pub fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.stderr_bytes).to_string()
+    }
+}
+
+/// Name of the storage of a variable, array or temporary in the generated code
+pub fn symbol(name: &str) -> String {
+    format!("m_{}", name)
+}
+
+/// Whether values of the type compare as signed numbers: INTEGER values are signed, CHAR codes unsigned
+pub fn is_signed(value_type: &Types) -> bool {
+    *value_type == Types::Integer
+}
+
+/// Bytes taken by a value of the given type: INTEGER is 16 bits, FLOAT 32 bits, CHAR one byte
+/// and arrays the size of their elements
+pub fn storage_size(symbol_type: &Types) -> usize {
+    match symbol_type {
+        Types::Integer => 2,
+        Types::Float => 4,
+        Types::Char => 1,
+        Types::Array(element, size) => storage_size(element) * (*size).max(0) as usize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+
+    #[test]
+    fn test_float_format() {
+        assert_eq!(format_float(2.5), "2.5");
+        assert_eq!(format_float(4.0), "4.0");
+        assert_eq!(format_float(-2.375), "-2.375");
+        assert_eq!(format_float(1.0 / 3.0), "0.333333");
+        assert_eq!(format_float(0.0000004), "0.0");
+        assert_eq!(format_float(123456789.0), "123456792.0");
+        assert_eq!(format_float(1e12), "999999995.904e3");
+        assert_eq!(format_float(f32::NAN), "nan");
+        assert_eq!(format_float(f32::NEG_INFINITY), "-inf");
+    }
+
+    #[test]
+    fn test_float_input() {
+        assert_eq!(parse_float("6.02"), Some(6.02));
+        assert_eq!(parse_float("-2"), Some(-2.0));
+        assert_eq!(parse_float("+.5"), Some(0.5));
+        assert_eq!(parse_float("7."), Some(7.0));
+        for invalid in ["", ".", "1e3", "1.2.3", "inf", "--1", "1234567890123456789"] {
+            assert_eq!(parse_float(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_static_initializers() {
+        let quads = parse_quadruplets(r#"
+            INTEGER X, Y, t1;
+            FLOAT F;
+            0: (Assign, 5, _, X)
+            1: (Assign, 2.5, _, F)
+            2: (Assign, 7, _, X)
+            3: (Assign, 1, _, Y)
+            4: (Add, X, Y, t1)
+            5: (Goto, _, _, 3)
+        "#).unwrap();
+        let (count, values) = static_initializers(&quads);
+        // Y = 1 is the target of the loop and must run every time
+        assert_eq!(count, 3);
+        assert_eq!(values, vec![("X".to_string(), Value::Integer(7)), ("F".to_string(), Value::Float(2.5))]);
+    }
+}
@@ -0,0 +1,488 @@
+//! Register allocation for the temporaries of the native backends
+//!
+//! Variables and arrays keep their static storage, the temporaries (`t1`, `t2`, ...) are given
+//! registers. Liveness comes from the optimizer's dataflow analysis: a temporary interferes with
+//! every temporary of its class that is live after one of its definitions. Two allocators share
+//! the same result:
+//!
+//! - graph coloring (Chaitin/Briggs): nodes of degree below the number of registers are removed
+//!   first, otherwise the node with the lowest spill cost per neighbour is removed optimistically,
+//!   colors are then given in the reverse order and nodes left without one are spilled
+//! - linear scan (Poletto/Sarkar): the live intervals over the program order are scanned once,
+//!   when no register is free the interval ending last is spilled. Faster, coarser
+//!
+//! Spilled temporaries get a stack slot each. INTEGER and CHAR values share the general purpose
+//! registers, FLOAT values use the floating point ones
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use crate::Optimizer::cfg::ControlFlowGraph;
+use crate::Optimizer::dataflow::{defined_name, quadruplet_facts, solve, used_names, LiveVariables};
+use crate::Optimizer::loops::find_loops;
+use crate::Semantic::quadruplets::{Operand, Quadruplet};
+use crate::Semantic::ts::Types;
+
+/// Register file a temporary is allocated from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RegisterClass {
+    Integer,        // INTEGER and CHAR values
+    Float,
+}
+
+impl RegisterClass {
+    pub fn of(value_type: &Types) -> RegisterClass {
+        match value_type {
+            Types::Float => RegisterClass::Float,
+            _ => RegisterClass::Integer,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            RegisterClass::Integer => 0,
+            RegisterClass::Float => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    GraphColoring,
+    LinearScan,
+}
+
+impl Strategy {
+    /// Strategy selected by `--regalloc=<name>`
+    pub fn from_name(name: &str) -> Option<Strategy> {
+        match name {
+            "graph" => Some(Strategy::GraphColoring),
+            "linear" => Some(Strategy::LinearScan),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::GraphColoring => write!(f, "graph coloring"),
+            Strategy::LinearScan => write!(f, "linear scan"),
+        }
+    }
+}
+
+/// Where a temporary lives: the index of a register in the allocatable registers of its class,
+/// or a stack slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Register(usize),
+    Stack(usize),
+}
+
+/// Figures reported by `--verbose`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AllocationStats {
+    pub strategy: Strategy,
+    pub temporaries: usize,
+    pub in_registers: usize,
+    pub spilled: usize,
+    pub registers_used: [usize; 2],     // Distinct integer and float registers
+    pub interferences: Option<usize>,   // Edges of the interference graph, not built by linear scan
+}
+
+impl fmt::Display for AllocationStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, {} temporaries, {} in registers ({} integer and {} float registers used), {} spilled",
+            self.strategy, self.temporaries, self.in_registers, self.registers_used[0], self.registers_used[1], self.spilled)?;
+        if let Some(edges) = self.interferences {
+            write!(f, ", {} interferences", edges)?;
+        }
+        Ok(())
+    }
+}
+
+/// Locations of the temporaries. Temporaries without one keep a static slot, which is what the
+/// default allocation does for all of them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Allocation {
+    pub locations: HashMap<String, Location>,
+    pub stack_slots: usize,
+    pub undefined: Vec<(String, RegisterClass)>,    // Temporaries read before any assignment, they start at zero
+    pub stats: Option<AllocationStats>,
+}
+
+impl Allocation {
+    pub fn location(&self, operand: &Operand) -> Option<Location> {
+        match operand {
+            Operand::Temporary(_, _) => self.locations.get(&operand.to_string()).copied(),
+            _ => None,
+        }
+    }
+}
+
+/// Temporaries of the quadruplets in order of appearance, with their register class
+pub fn temporaries(quadruplets: &[Quadruplet]) -> Vec<(String, RegisterClass)> {
+    fn visit(operand: &Operand, found: &mut Vec<(String, RegisterClass)>) {
+        match operand {
+            Operand::Temporary(_, t) => {
+                let name = operand.to_string();
+                if !found.iter().any(|(known, _)| *known == name) {
+                    found.push((name, RegisterClass::of(t)));
+                }
+            },
+            Operand::ArrayElement(_, _, index) => visit(index, found),
+            _ => {},
+        }
+    }
+
+    let mut found = Vec::new();
+    for quadruplet in quadruplets {
+        for operand in [&quadruplet.operand1, &quadruplet.operand2, &quadruplet.result].into_iter().flatten() {
+            visit(operand, &mut found);
+        }
+    }
+    found
+}
+
+// Liveness of the temporaries at each quadruplet
+struct Liveness {
+    temporaries: Vec<(String, RegisterClass)>,
+    classes: HashMap<String, RegisterClass>,
+    live_after: Vec<BTreeSet<String>>,
+    entry: BTreeSet<String>,                // Live before the first quadruplet
+    costs: HashMap<String, usize>,          // Reads and writes, weighted by ten per loop level
+}
+
+impl Liveness {
+    fn compute(quadruplets: &[Quadruplet]) -> Liveness {
+        let temporaries = temporaries(quadruplets);
+        let classes: HashMap<String, RegisterClass> = temporaries.iter().cloned().collect();
+        let cfg = ControlFlowGraph::build(quadruplets);
+        let result = solve(&LiveVariables, &cfg, quadruplets);
+        let live_after = quadruplet_facts(&LiveVariables, &cfg, quadruplets, &result).into_iter()
+            .map(|live| live.into_iter().filter(|name| classes.contains_key(name)).collect())
+            .collect();
+        let entry = cfg.entry()
+            .map(|block| result.block_in[block].iter().filter(|name| classes.contains_key(*name)).cloned().collect())
+            .unwrap_or_default();
+
+        let mut depth = vec![0u32; cfg.blocks.len()];
+        for natural_loop in find_loops(&cfg) {
+            for &block in &natural_loop.blocks {
+                depth[block] += 1;
+            }
+        }
+        let mut costs: HashMap<String, usize> = HashMap::new();
+        for (index, quadruplet) in quadruplets.iter().enumerate() {
+            let weight = 10usize.saturating_pow(depth[cfg.block_of(index)]);
+            for name in used_names(quadruplet).into_iter().chain(defined_name(quadruplet)) {
+                if classes.contains_key(&name) {
+                    *costs.entry(name).or_default() += weight;
+                }
+            }
+        }
+        Liveness { temporaries, classes, live_after, entry, costs }
+    }
+
+    // Temporaries defined by each quadruplet
+    fn definition(&self, quadruplet: &Quadruplet) -> Option<String> {
+        defined_name(quadruplet).filter(|name| self.classes.contains_key(name))
+    }
+
+    fn interference_graph(&self, quadruplets: &[Quadruplet]) -> BTreeMap<String, BTreeSet<String>> {
+        let mut graph: BTreeMap<String, BTreeSet<String>> = self.temporaries.iter().map(|(name, _)| (name.clone(), BTreeSet::new())).collect();
+        let mut connect = |a: &String, b: &String| {
+            if a != b && self.classes[a] == self.classes[b] {
+                graph.get_mut(a).unwrap().insert(b.clone());
+                graph.get_mut(b).unwrap().insert(a.clone());
+            }
+        };
+        for (index, quadruplet) in quadruplets.iter().enumerate() {
+            if let Some(defined) = self.definition(quadruplet) {
+                for live in &self.live_after[index] {
+                    connect(&defined, live);
+                }
+            }
+        }
+        // Temporaries read before any assignment all hold zero at the same time
+        for a in &self.entry {
+            for b in &self.entry {
+                connect(a, b);
+            }
+        }
+        graph
+    }
+
+    // First and last position where each temporary is read, written or live. Quadruplet i reads
+    // at 2i and writes at 2i + 1, so a value read for the last time can give its register to
+    // the result
+    fn intervals(&self, quadruplets: &[Quadruplet]) -> HashMap<String, (usize, usize)> {
+        let mut intervals: HashMap<String, (usize, usize)> = self.entry.iter().map(|name| (name.clone(), (0, 0))).collect();
+        for (index, quadruplet) in quadruplets.iter().enumerate() {
+            let reads = used_names(quadruplet).into_iter().map(|name| (name, 2 * index));
+            let writes = defined_name(quadruplet).into_iter()
+                .chain(self.live_after[index].iter().cloned())
+                .map(|name| (name, 2 * index + 1));
+            for (name, position) in reads.chain(writes).filter(|(name, _)| self.classes.contains_key(name)) {
+                let interval = intervals.entry(name).or_insert((position, position));
+                interval.0 = interval.0.min(position);
+                interval.1 = interval.1.max(position);
+            }
+        }
+        intervals
+    }
+}
+
+/// Allocates the temporaries to `registers[0]` integer and `registers[1]` float registers
+pub fn allocate(quadruplets: &[Quadruplet], registers: [usize; 2], strategy: Strategy) -> Allocation {
+    let liveness = Liveness::compute(quadruplets);
+    let mut colors: HashMap<String, usize> = HashMap::new();
+    let mut spilled: Vec<String> = Vec::new();
+    let mut interferences = None;
+
+    match strategy {
+        Strategy::GraphColoring => {
+            let graph = liveness.interference_graph(quadruplets);
+            interferences = Some(graph.values().map(BTreeSet::len).sum::<usize>() / 2);
+            for class in [RegisterClass::Integer, RegisterClass::Float] {
+                let nodes: Vec<String> = liveness.temporaries.iter().filter(|(_, c)| *c == class).map(|(name, _)| name.clone()).collect();
+                color_graph(&nodes, &graph, registers[class.index()], &liveness.costs, &mut colors, &mut spilled);
+            }
+        },
+        Strategy::LinearScan => {
+            let intervals = liveness.intervals(quadruplets);
+            for class in [RegisterClass::Integer, RegisterClass::Float] {
+                let nodes: Vec<String> = liveness.temporaries.iter()
+                    .filter(|(name, c)| *c == class && intervals.contains_key(name))
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                linear_scan(&nodes, &intervals, registers[class.index()], &mut colors, &mut spilled);
+            }
+            // Temporaries never read, written or live still need a place in the output
+            for (name, _) in &liveness.temporaries {
+                if !intervals.contains_key(name) {
+                    spilled.push(name.clone());
+                }
+            }
+        },
+    }
+
+    // Spill slots follow the order of appearance so that the output is stable
+    spilled.sort_by_key(|name| liveness.temporaries.iter().position(|(known, _)| known == name));
+    let mut locations: HashMap<String, Location> = colors.iter().map(|(name, color)| (name.clone(), Location::Register(*color))).collect();
+    for (slot, name) in spilled.iter().enumerate() {
+        locations.insert(name.clone(), Location::Stack(slot));
+    }
+
+    let mut registers_used = [BTreeSet::new(), BTreeSet::new()];
+    for (name, color) in &colors {
+        registers_used[liveness.classes[name].index()].insert(*color);
+    }
+    let stats = AllocationStats {
+        strategy,
+        temporaries: liveness.temporaries.len(),
+        in_registers: colors.len(),
+        spilled: spilled.len(),
+        registers_used: [registers_used[0].len(), registers_used[1].len()],
+        interferences,
+    };
+    Allocation {
+        locations,
+        stack_slots: spilled.len(),
+        undefined: liveness.temporaries.iter().filter(|(name, _)| liveness.entry.contains(name)).cloned().collect(),
+        stats: Some(stats),
+    }
+}
+
+// Simplify and select over the nodes of one class with k colors
+fn color_graph(
+    nodes: &[String],
+    graph: &BTreeMap<String, BTreeSet<String>>,
+    k: usize,
+    costs: &HashMap<String, usize>,
+    colors: &mut HashMap<String, usize>,
+    spilled: &mut Vec<String>,
+) {
+    let mut degree: HashMap<&String, usize> = nodes.iter().map(|node| (node, graph[node].len())).collect();
+    let mut removed: BTreeSet<&String> = BTreeSet::new();
+    let mut stack: Vec<&String> = Vec::new();
+    while stack.len() < nodes.len() {
+        let remaining = || nodes.iter().filter(|node| !removed.contains(node));
+        // A node of low degree gets a color whatever its neighbours get, otherwise the cheapest
+        // spill candidate is pushed anyway and may still find a color (Briggs)
+        let next = remaining().find(|node| degree[node] < k).or_else(|| {
+            remaining().min_by(|a, b| {
+                let cost = |node: &String| costs.get(node).copied().unwrap_or(0);
+                (cost(a) * degree[b].max(1)).cmp(&(cost(b) * degree[a].max(1)))
+            })
+        });
+        let node = next.expect("a node remains while the stack is incomplete");
+        removed.insert(node);
+        stack.push(node);
+        for neighbour in &graph[node] {
+            if let Some(d) = degree.get_mut(neighbour) {
+                *d = d.saturating_sub(1);
+            }
+        }
+    }
+
+    while let Some(node) = stack.pop() {
+        let taken: BTreeSet<usize> = graph[node].iter().filter_map(|neighbour| colors.get(neighbour).copied()).collect();
+        match (0..k).find(|color| !taken.contains(color)) {
+            Some(color) => {
+                colors.insert(node.clone(), color);
+            },
+            None => spilled.push(node.clone()),
+        }
+    }
+}
+
+// Scans the intervals of one class in order of their start with k registers
+fn linear_scan(
+    nodes: &[String],
+    intervals: &HashMap<String, (usize, usize)>,
+    k: usize,
+    colors: &mut HashMap<String, usize>,
+    spilled: &mut Vec<String>,
+) {
+    let mut order: Vec<&String> = nodes.iter().collect();
+    order.sort_by_key(|node| intervals[*node].0);
+    let mut free: BTreeSet<usize> = (0..k).collect();
+    let mut active: Vec<(usize, &String)> = Vec::new();      // End of the interval and temporary
+    for node in order {
+        let (start, end) = intervals[node];
+        active.retain(|(active_end, name)| {
+            let expired = *active_end < start;
+            if expired {
+                free.insert(colors[*name]);
+            }
+            !expired
+        });
+
+        if let Some(&register) = free.iter().next() {
+            free.remove(&register);
+            colors.insert(node.clone(), register);
+            active.push((end, node));
+            continue;
+        }
+        let last = active.iter().enumerate().max_by_key(|(_, (active_end, _))| *active_end).map(|(i, (e, _))| (i, *e));
+        match last {
+            Some((position, last_end)) if last_end > end => {
+                let (_, victim) = active.remove(position);
+                let register = colors.remove(victim).expect("active intervals have a register");
+                spilled.push(victim.clone());
+                colors.insert(node.clone(), register);
+                active.push((end, node));
+            },
+            _ => spilled.push(node.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+
+    const PROGRAM: &str = r#"
+        INTEGER I, S, N, t1, t2, t3, t4, t5;
+        FLOAT F, t6, t7;
+        0: (Read, _, _, N)
+        1: (Assign, 0, _, I)
+        2: (Multiply, N, 2, t1)
+        3: (Add, N, 1, t2)
+        4: (Add, t1, t2, t3)
+        5: (LessThan, I, t3, t4)
+        6: (IfFalse, t4, _, 11)
+        7: (Add, I, t1, t5)
+        8: (Assign, t5, _, I)
+        9: (Add, S, t1, S)
+        10: (Goto, _, _, 5)
+        11: (Divide, F, 2.0, t6)
+        12: (Add, t6, 1.0, t7)
+        13: (Write, t7, _, _)
+    "#;
+
+    // Two temporaries live at the same quadruplet never share a register
+    fn assert_valid(quadruplets: &[Quadruplet], allocation: &Allocation) {
+        let liveness = Liveness::compute(quadruplets);
+        for (name, _) in &liveness.temporaries {
+            assert!(allocation.locations.contains_key(name), "{} has no location", name);
+        }
+        for (index, quadruplet) in quadruplets.iter().enumerate() {
+            let mut live = liveness.live_after[index].clone();
+            live.extend(liveness.definition(quadruplet));
+            for a in &live {
+                for b in live.iter().filter(|b| *b != a && liveness.classes[*b] == liveness.classes[a]) {
+                    if let (Location::Register(x), Location::Register(y)) = (allocation.locations[a], allocation.locations[b]) {
+                        assert_ne!(x, y, "{} and {} share a register at {}", a, b, index);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_graph_coloring() {
+        let quads = parse_quadruplets(PROGRAM).unwrap();
+        let graph = Liveness::compute(&quads).interference_graph(&quads);
+        // t1 lives through the loop, t2 dies at 4 and t3 is defined there
+        assert!(graph["t1"].contains("t2") && graph["t1"].contains("t3") && graph["t1"].contains("t5"));
+        assert!(!graph["t2"].contains("t3"));
+        assert!(graph["t6"].is_empty() && !graph["t1"].contains("t6"));
+
+        let allocation = allocate(&quads, [6, 6], Strategy::GraphColoring);
+        assert_valid(&quads, &allocation);
+        let stats = allocation.stats.clone().unwrap();
+        assert_eq!((stats.temporaries, stats.in_registers, stats.spilled), (7, 7, 0));
+        assert_eq!(stats.registers_used, [3, 1]);
+        assert_eq!(allocation.stack_slots, 0);
+
+        // t1, t3 and t4 are live together in the loop, t3 is the cheapest per neighbour to spill
+        let allocation = allocate(&quads, [2, 1], Strategy::GraphColoring);
+        assert_valid(&quads, &allocation);
+        assert_eq!(allocation.locations["t3"], Location::Stack(0));
+        assert!(matches!(allocation.locations["t1"], Location::Register(_)));
+        assert_eq!(allocation.stats.clone().unwrap().spilled, 1);
+
+        let allocation = allocate(&quads, [1, 1], Strategy::GraphColoring);
+        assert_valid(&quads, &allocation);
+        assert_eq!(allocation.stack_slots, 2);
+    }
+
+    #[test]
+    fn test_linear_scan() {
+        let quads = parse_quadruplets(PROGRAM).unwrap();
+        let intervals = Liveness::compute(&quads).intervals(&quads);
+        assert_eq!(intervals["t1"], (5, 21));
+        assert_eq!(intervals["t2"], (7, 8));
+
+        let allocation = allocate(&quads, [6, 6], Strategy::LinearScan);
+        assert_valid(&quads, &allocation);
+        assert_eq!(allocation.stats.clone().unwrap().spilled, 0);
+
+        // t4 finds t1 and t3 in the registers, both end with the loop and the later one is spilled
+        let allocation = allocate(&quads, [2, 1], Strategy::LinearScan);
+        assert_valid(&quads, &allocation);
+        assert_eq!(allocation.locations["t3"], Location::Stack(0));
+        assert_eq!(allocation.locations["t5"], Location::Register(1));
+        let stats = allocation.stats.unwrap();
+        assert_eq!((stats.spilled, stats.interferences), (1, None));
+    }
+
+    #[test]
+    fn test_undefined_temporaries() {
+        let quads = parse_quadruplets(r#"
+            INTEGER t1, t2, t3;
+            0: (Add, t1, t2, t3)
+            1: (Write, t3, _, _)
+        "#).unwrap();
+        for strategy in [Strategy::GraphColoring, Strategy::LinearScan] {
+            let allocation = allocate(&quads, [4, 4], strategy);
+            assert_valid(&quads, &allocation);
+            let undefined: Vec<&str> = allocation.undefined.iter().map(|(name, _)| name.as_str()).collect();
+            assert_eq!(undefined, ["t1", "t2"]);
+            assert_ne!(allocation.locations["t1"], allocation.locations["t2"]);
+        }
+    }
+}
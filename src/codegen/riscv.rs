@@ -0,0 +1,1248 @@
+//! RISC-V backend: RV32IMF assembly for the RARS simulator
+//!
+//! The layout follows the x86-64 backend: every variable, array and temporary has a static slot in
+//! `.data` unless a register allocation puts the temporaries in s0-s11, fs0-fs11 or stack slots,
+//! each quadruplet loads its operands in t0/t1 or ft0/ft1, computes and stores its result.
+//! INTEGER values are sign extended from 16 bits, FLOAT values use the F extension. READ and WRITE
+//! call a runtime appended to the output, which only uses the RARS system calls 63 (read), 64
+//! (write) and 93 (exit), so the program runs in RARS and in `codegen::simrv32`:
+//!
+//! ```text
+//! java -jar rars.jar nc prog.riscv.asm
+//! ```
+//!
+//! RV32IMF has no double precision, the runtime reads and writes FLOAT values with 64 bits integer
+//! routines that round like the f64 computations of `format_float` and `parse_float`.
+//! Conditional branches only reach 4 KB, so the generated code jumps over a `j` to leave a
+//! quadruplet and keeps the runtime close to its error handlers.
+
+use std::collections::HashSet;
+use crate::codegen::error::CodegenError;
+use crate::codegen::regalloc::{Allocation, Location, RegisterClass};
+use crate::codegen::x86_64::RUNTIME_ERRORS;
+use crate::codegen::{is_signed, static_initializers, storage_size, symbol};
+use crate::Semantic::quadruplets::{collect_symbols, jump_target, Operand, Operator, Quadruplet, Value};
+use crate::Semantic::ts::Types;
+
+// Registers holding the first and second operand of a quadruplet
+const INTEGER_REGISTERS: [&str; 2] = ["t0", "t1"];
+const FLOAT_REGISTERS: [&str; 2] = ["ft0", "ft1"];
+
+// Registers given to temporaries, the runtime saves the ones it uses
+const ALLOCATABLE_INTEGER: [&str; 12] = ["s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11"];
+const ALLOCATABLE_FLOAT: [&str; 12] = ["fs0", "fs1", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9", "fs10", "fs11"];
+
+/// Number of integer and float registers `generate_riscv_with` can give to temporaries
+pub const ALLOCATABLE_REGISTERS: [usize; 2] = [ALLOCATABLE_INTEGER.len(), ALLOCATABLE_FLOAT.len()];
+
+/// READ and WRITE routines, they may clobber the temporary and argument registers
+const RUNTIME: &str = r#"
+# MinING runtime, RARS system calls only
+mining_write_string:                    # a0 = address, a1 = length
+    mv a2, a1
+    mv a1, a0
+    li a0, 1
+    li a7, 64
+    ecall
+    ret
+
+mining_write_char:                      # a0 = CHAR
+    la t0, mining_output
+    sb a0, 0(t0)
+    mv a0, t0
+    li a1, 1
+    j mining_write_string
+
+mining_write_buffer:                    # writes from a1 to the end of the output buffer
+    la t0, mining_output_end
+    sub t0, t0, a1
+    mv a0, a1
+    mv a1, t0
+    j mining_write_string
+
+mining_digits:                          # writes a0 in decimal before a1, a1 ends on the first digit
+    li t0, 10
+mining_digits_loop:
+    remu t1, a0, t0
+    divu a0, a0, t0
+    addi t1, t1, 48
+    addi a1, a1, -1
+    sb t1, 0(a1)
+    bnez a0, mining_digits_loop
+    ret
+
+mining_write_int:                       # a0 = INTEGER
+    addi sp, sp, -16
+    sw ra, 12(sp)
+    sw a0, 8(sp)
+    la a1, mining_output_end
+    bgez a0, mining_write_int_digits
+    neg a0, a0
+mining_write_int_digits:
+    call mining_digits
+    lw t0, 8(sp)
+    lw ra, 12(sp)
+    addi sp, sp, 16
+    bgez t0, mining_write_buffer
+    li t0, 45
+    addi a1, a1, -1
+    sb t0, 0(a1)
+    j mining_write_buffer
+
+mining_divu64:                          # a1:a0 / a2 in a1:a0, remainder in a2, a2 below 2^31
+    li t0, 0
+    li t1, 64
+mining_divu64_loop:
+    srli t2, a1, 31
+    slli t0, t0, 1
+    or t0, t0, t2
+    slli a1, a1, 1
+    srli t2, a0, 31
+    or a1, a1, t2
+    slli a0, a0, 1
+    bltu t0, a2, mining_divu64_next
+    sub t0, t0, a2
+    ori a0, a0, 1
+mining_divu64_next:
+    addi t1, t1, -1
+    bnez t1, mining_divu64_loop
+    mv a2, t0
+    ret
+
+mining_shift_round:                     # a2:a1:a0 >> a3 in a1:a0, rounded half to even
+    li t0, 0                            # last bit shifted out
+    li t1, 0                            # any bit shifted out before it
+mining_shift_round_loop:
+    beqz a3, mining_shift_round_done
+    or t1, t1, t0
+    andi t0, a0, 1
+    srli a0, a0, 1
+    slli t2, a1, 31
+    or a0, a0, t2
+    srli a1, a1, 1
+    slli t2, a2, 31
+    or a1, a1, t2
+    srli a2, a2, 1
+    addi a3, a3, -1
+    j mining_shift_round_loop
+mining_shift_round_done:
+    beqz t0, mining_shift_round_end
+    andi t2, a0, 1
+    or t1, t1, t2
+    beqz t1, mining_shift_round_end
+    addi a0, a0, 1
+    seqz t2, a0
+    add a1, a1, t2
+mining_shift_round_end:
+    ret
+
+mining_bit_length:                      # number of bits of a1:a0 in a0
+    li t0, 0
+    beqz a1, mining_bit_length_loop
+    mv a0, a1
+    li t0, 32
+mining_bit_length_loop:
+    beqz a0, mining_bit_length_done
+    srli a0, a0, 1
+    addi t0, t0, 1
+    j mining_bit_length_loop
+mining_bit_length_done:
+    mv a0, t0
+    ret
+
+mining_write_float:                     # fa0 = FLOAT
+    fmv.x.w t0, fa0
+    srli t3, t0, 31                     # sign
+    srli t1, t0, 23
+    andi t1, t1, 255                    # biased exponent
+    li t2, 0x7FFFFF
+    and t2, t0, t2                      # fraction
+    li t0, 255
+    bne t1, t0, mining_write_finite
+    la a0, mining_nan
+    li a1, 3
+    bnez t2, mining_write_string
+    la a0, mining_infinity
+    li a1, 4
+    bnez t3, mining_write_string
+    addi a0, a0, 1
+    li a1, 3
+    j mining_write_string
+mining_write_finite:
+    addi sp, sp, -32
+    sw ra, 28(sp)
+    sw s0, 24(sp)
+    sw s1, 20(sp)
+    sw s2, 16(sp)
+    sw s3, 12(sp)
+    sw s4, 8(sp)
+    mv s3, t3
+    li s4, 0                            # decimal exponent
+    li a0, 0
+    li a1, 0
+    or t0, t1, t2
+    beqz t0, mining_write_scaled
+    li t0, 0x800000                     # implicit bit, none for subnormal values
+    bnez t1, mining_write_normal
+    li t0, 0
+    li t1, 1
+mining_write_normal:
+    or t2, t2, t0
+    srli s1, t2, 3                      # the value is s1:s0 * 2^s2 with bit 52 set, like a f64
+    slli s0, t2, 29
+    addi s2, t1, -179
+mining_write_normalize:
+    li t0, 0x100000
+    and t0, s1, t0
+    bnez t0, mining_write_scale
+    slli s1, s1, 1
+    srli t0, s0, 31
+    or s1, s1, t0
+    slli s0, s0, 1
+    addi s2, s2, -1
+    j mining_write_normalize
+mining_write_scale:                     # divides by ten while the value is 1e9 or more
+    li t0, -23
+    blt t0, s2, mining_write_divide
+    bne s2, t0, mining_write_multiply
+    li t0, 0x1DCD65                     # high word of 1e9 * 2^23
+    bltu s1, t0, mining_write_multiply
+mining_write_divide:
+    slli a1, s1, 4                      # (s1:s0 << 4) / 10 has 53 or 54 bits
+    srli t0, s0, 28
+    or a1, a1, t0
+    slli a0, s0, 4
+    li a2, 10
+    call mining_divu64
+    addi s2, s2, -4
+    slli a2, a2, 1                      # twice the remainder, 10 is one half of the last bit
+    li t0, 0x200000
+    bltu a1, t0, mining_write_round
+    andi t1, a0, 1                      # 54 bits: the last one joins the remainder
+    srli a0, a0, 1
+    slli t2, a1, 31
+    or a0, a0, t2
+    srli a1, a1, 1
+    addi s2, s2, 1
+    srli a2, a2, 1
+    li t2, 10
+    mul t1, t1, t2
+    add a2, a2, t1
+mining_write_round:                     # rounds half to even
+    li t0, 10
+    blt a2, t0, mining_write_divided
+    bne a2, t0, mining_write_round_up
+    andi t1, a0, 1
+    beqz t1, mining_write_divided
+mining_write_round_up:
+    addi a0, a0, 1
+    seqz t1, a0
+    add a1, a1, t1
+    li t0, 0x200000
+    bltu a1, t0, mining_write_divided
+    srli a1, a1, 1                      # 2^53 becomes 2^52
+    addi s2, s2, 1
+mining_write_divided:
+    mv s0, a0
+    mv s1, a1
+    addi s4, s4, 1
+    j mining_write_scale
+mining_write_multiply:                  # rounds s1:s0 * 1e6 to 53 bits, then to an integer
+    li t0, 1000000
+    mul a0, s0, t0
+    mulhu t1, s0, t0
+    mul t2, s1, t0
+    mulhu a2, s1, t0
+    add a1, t1, t2
+    sltu t1, a1, t1
+    add a2, a2, t1
+    mv t0, a2
+    li a3, 11                           # the product has 64 + (bits of a2) bits, 53 are kept
+mining_write_length:
+    beqz t0, mining_write_product
+    srli t0, t0, 1
+    addi a3, a3, 1
+    j mining_write_length
+mining_write_product:
+    add s2, s2, a3
+    call mining_shift_round
+    li a2, 0
+    neg a3, s2
+    li t0, 64
+    blt a3, t0, mining_write_integer
+    li a0, 0
+    li a1, 0
+    j mining_write_scaled
+mining_write_integer:
+    call mining_shift_round
+mining_write_scaled:
+    li a2, 1000000
+    call mining_divu64
+    mv s0, a0                           # integer part
+    mv s1, a2                           # six digits of fraction
+    li s2, 6
+mining_write_strip:                     # drops the trailing zeros but one
+    li t0, 1
+    beq s2, t0, mining_write_print
+    li t0, 10
+    remu t1, s1, t0
+    bnez t1, mining_write_print
+    divu s1, s1, t0
+    addi s2, s2, -1
+    j mining_write_strip
+mining_write_print:
+    la a1, mining_output_end
+    beqz s4, mining_write_fraction
+    mv a0, s4
+    call mining_digits
+    li t0, 101
+    addi a1, a1, -1
+    sb t0, 0(a1)
+mining_write_fraction:
+    li t0, 10
+mining_write_fraction_loop:
+    remu t1, s1, t0
+    divu s1, s1, t0
+    addi t1, t1, 48
+    addi a1, a1, -1
+    sb t1, 0(a1)
+    addi s2, s2, -1
+    bnez s2, mining_write_fraction_loop
+    li t0, 46
+    addi a1, a1, -1
+    sb t0, 0(a1)
+    mv a0, s0
+    call mining_digits
+    beqz s3, mining_write_float_done
+    li t0, 45
+    addi a1, a1, -1
+    sb t0, 0(a1)
+mining_write_float_done:
+    lw ra, 28(sp)
+    lw s0, 24(sp)
+    lw s1, 20(sp)
+    lw s2, 16(sp)
+    lw s3, 12(sp)
+    lw s4, 8(sp)
+    addi sp, sp, 32
+    j mining_write_buffer
+
+mining_getc:                            # next input byte in a0, -1 at the end of the input
+    la t0, mining_input_position
+    lw t1, 0(t0)
+    lw t2, 4(t0)
+    bltu t1, t2, mining_getc_buffered
+    li a0, 0
+    la a1, mining_input
+    li a2, 4096
+    li a7, 63
+    ecall
+    la t0, mining_input_position
+    sw zero, 0(t0)
+    sw zero, 4(t0)
+    blez a0, mining_getc_end
+    sw a0, 4(t0)
+    li t1, 0
+mining_getc_buffered:
+    la t2, mining_input
+    add t2, t2, t1
+    lbu a0, 0(t2)
+    addi t1, t1, 1
+    sw t1, 0(t0)
+    ret
+mining_getc_end:
+    li a0, -1
+    ret
+
+mining_skip_spaces:                     # first input byte that is not a space in a0
+    addi sp, sp, -16
+    sw ra, 12(sp)
+mining_skip_spaces_loop:
+    call mining_getc
+    li t0, 32
+    beq a0, t0, mining_skip_spaces_loop
+    addi t0, a0, -9
+    li t1, 4
+    bleu t0, t1, mining_skip_spaces_loop
+    li t0, -1
+    beq a0, t0, mining_end_of_input_error
+    lw ra, 12(sp)
+    addi sp, sp, 16
+    ret
+
+mining_end_of_token:                    # a1 = 1 when a0 is a space or the end of the input
+    li a1, 1
+    li t0, -1
+    beq a0, t0, mining_end_of_token_done
+    li t0, 32
+    beq a0, t0, mining_end_of_token_done
+    addi t0, a0, -9
+    li t1, 4
+    bleu t0, t1, mining_end_of_token_done
+    li a1, 0
+mining_end_of_token_done:
+    ret
+
+mining_read_int:                        # INTEGER in a0
+    addi sp, sp, -16
+    sw ra, 12(sp)
+    sw s0, 8(sp)
+    sw s1, 4(sp)
+    sw s2, 0(sp)
+    call mining_skip_spaces
+    li s0, 0                            # 1 for a negative value
+    li t0, 43
+    beq a0, t0, mining_read_int_sign
+    li t0, 45
+    bne a0, t0, mining_read_int_start
+    li s0, 1
+mining_read_int_sign:
+    call mining_getc
+mining_read_int_start:
+    li s1, 0                            # value
+    li s2, 0                            # number of digits
+mining_read_int_loop:
+    addi t0, a0, -48
+    li t1, 9
+    bgtu t0, t1, mining_read_int_end
+    li t1, 10
+    mul s1, s1, t1
+    add s1, s1, t0
+    li t1, 32768
+    bgtu s1, t1, mining_integer_input_error
+    addi s2, s2, 1
+    call mining_getc
+    j mining_read_int_loop
+mining_read_int_end:
+    call mining_end_of_token
+    beqz a1, mining_integer_input_error
+    beqz s2, mining_integer_input_error
+    mv a0, s1
+    beqz s0, mining_read_int_positive
+    neg a0, a0
+    j mining_read_int_done
+mining_read_int_positive:
+    li t1, 32767
+    bgtu a0, t1, mining_integer_input_error
+mining_read_int_done:
+    lw ra, 12(sp)
+    lw s0, 8(sp)
+    lw s1, 4(sp)
+    lw s2, 0(sp)
+    addi sp, sp, 16
+    ret
+
+mining_read_float:                      # FLOAT in fa0
+    addi sp, sp, -32
+    sw ra, 28(sp)
+    sw s0, 24(sp)
+    sw s1, 20(sp)
+    sw s2, 16(sp)
+    sw s3, 12(sp)
+    sw s4, 8(sp)
+    sw s5, 4(sp)
+    call mining_skip_spaces
+    li s0, 0                            # 1 for a negative value
+    li t0, 43
+    beq a0, t0, mining_read_float_sign
+    li t0, 45
+    bne a0, t0, mining_read_float_start
+    li s0, 1
+mining_read_float_sign:
+    call mining_getc
+mining_read_float_start:
+    li s1, 0                            # digits read as an integer in s2:s1
+    li s2, 0
+    li s3, 0                            # number of digits
+    li s4, 0                            # number of digits after the point
+    li s5, 0                            # 1 after the point
+mining_read_float_loop:
+    li t0, 46
+    bne a0, t0, mining_read_float_digit
+    bnez s5, mining_float_input_error
+    li s5, 1
+    call mining_getc
+    j mining_read_float_loop
+mining_read_float_digit:
+    addi t0, a0, -48
+    li t1, 9
+    bgtu t0, t1, mining_read_float_end
+    li t1, 10
+    mulhu t2, s1, t1
+    mul s2, s2, t1
+    add s2, s2, t2
+    mul s1, s1, t1
+    add s1, s1, t0
+    sltu t2, s1, t0
+    add s2, s2, t2
+    addi s3, s3, 1
+    add s4, s4, s5
+    li t1, 18
+    bgtu s3, t1, mining_float_input_error
+    call mining_getc
+    j mining_read_float_loop
+mining_read_float_end:
+    call mining_end_of_token
+    beqz a1, mining_float_input_error
+    beqz s3, mining_float_input_error
+    mv a0, s1
+    mv a1, s2
+    mv a2, s4
+    call mining_decimal_to_float
+    beqz s0, mining_read_float_done
+    fneg.s fa0, fa0
+mining_read_float_done:
+    lw ra, 28(sp)
+    lw s0, 24(sp)
+    lw s1, 20(sp)
+    lw s2, 16(sp)
+    lw s3, 12(sp)
+    lw s4, 8(sp)
+    lw s5, 4(sp)
+    addi sp, sp, 32
+    ret
+
+mining_decimal_to_float:                # a1:a0 / 10^a2 in fa0, rounded to f64 then to f32
+    addi sp, sp, -32
+    sw ra, 28(sp)
+    sw s0, 24(sp)
+    sw s1, 20(sp)
+    sw s2, 16(sp)
+    sw s3, 12(sp)
+    sw s4, 8(sp)
+    sw s5, 4(sp)
+    mv s0, a0                           # the value is s1:s0 * 2^s2 / 10^s3
+    mv s1, a1
+    li s2, 0
+    mv s3, a2
+    fmv.w.x fa0, zero
+    or t0, a0, a1
+    beqz t0, mining_decimal_done
+    call mining_bit_length              # digits above 2^53 are rounded like a f64
+    addi a3, a0, -53
+    blez a3, mining_decimal_exact
+    add s2, s2, a3
+    mv a0, s0
+    mv a1, s1
+    li a2, 0
+    call mining_shift_round
+    mv s0, a0
+    mv s1, a1
+mining_decimal_exact:
+    beqz s3, mining_decimal_single
+    la t0, mining_powers
+    slli t1, s3, 3
+    add t0, t0, t1
+    lw s4, 0(t0)                        # divisor in s5:s4
+    lw s5, 4(t0)
+mining_decimal_below:                   # shifts the dividend until it is at least the divisor
+    bltu s1, s5, mining_decimal_shift
+    bne s1, s5, mining_decimal_above
+    bgeu s0, s4, mining_decimal_above
+mining_decimal_shift:
+    slli s1, s1, 1
+    srli t0, s0, 31
+    or s1, s1, t0
+    slli s0, s0, 1
+    addi s2, s2, -1
+    j mining_decimal_below
+mining_decimal_above:                   # and the divisor until the dividend is below twice it
+    slli t1, s5, 1
+    srli t0, s4, 31
+    or t1, t1, t0
+    slli t0, s4, 1
+    bltu s1, t1, mining_decimal_divide
+    bne s1, t1, mining_decimal_double
+    bltu s0, t0, mining_decimal_divide
+mining_decimal_double:
+    mv s5, t1
+    mv s4, t0
+    addi s2, s2, 1
+    j mining_decimal_above
+mining_decimal_divide:                  # 55 bits of quotient in a1:a0
+    li a0, 0
+    li a1, 0
+    li t2, 55
+mining_decimal_loop:
+    slli a1, a1, 1
+    srli t0, a0, 31
+    or a1, a1, t0
+    slli a0, a0, 1
+    bltu s1, s5, mining_decimal_next
+    bne s1, s5, mining_decimal_subtract
+    bltu s0, s4, mining_decimal_next
+mining_decimal_subtract:
+    sltu t0, s0, s4
+    sub s0, s0, s4
+    sub s1, s1, s5
+    sub s1, s1, t0
+    ori a0, a0, 1
+mining_decimal_next:
+    slli s1, s1, 1
+    srli t0, s0, 31
+    or s1, s1, t0
+    slli s0, s0, 1
+    addi t2, t2, -1
+    bnez t2, mining_decimal_loop
+    or t0, s0, s1                       # a remainder only matters for the rounding
+    snez t0, t0
+    or a0, a0, t0
+    li a2, 0
+    li a3, 2
+    call mining_shift_round
+    mv s0, a0
+    mv s1, a1
+    addi s2, s2, -52
+mining_decimal_single:                  # rounds to the 24 bits of a FLOAT
+    mv a0, s0
+    mv a1, s1
+    call mining_bit_length
+    addi a3, a0, -24
+    mv a0, s0
+    blez a3, mining_decimal_small
+    add s2, s2, a3
+    mv a1, s1
+    li a2, 0
+    call mining_shift_round
+    li t0, 0x1000000
+    bne a0, t0, mining_decimal_pack
+    srli a0, a0, 1
+    addi s2, s2, 1
+    j mining_decimal_pack
+mining_decimal_small:
+    li t0, 0x800000
+    and t1, a0, t0
+    bnez t1, mining_decimal_pack
+    slli a0, a0, 1
+    addi s2, s2, -1
+    j mining_decimal_small
+mining_decimal_pack:
+    addi t0, s2, 150
+    slli t0, t0, 23
+    li t1, 0x7FFFFF
+    and a0, a0, t1
+    or a0, a0, t0
+    fmv.w.x fa0, a0
+mining_decimal_done:
+    lw ra, 28(sp)
+    lw s0, 24(sp)
+    lw s1, 20(sp)
+    lw s2, 16(sp)
+    lw s3, 12(sp)
+    lw s4, 8(sp)
+    lw s5, 4(sp)
+    addi sp, sp, 32
+    ret
+
+mining_read_char:                       # CHAR in a0
+    j mining_skip_spaces
+
+mining_fail:                            # a1 = message, a2 = length
+    li a0, 2
+    li a7, 64
+    ecall
+    li a0, 1
+    li a7, 93
+    ecall
+"#;
+
+/// Data of the runtime, the powers of ten are 64 bits words (low word first)
+const RUNTIME_DATA: &str = r#"
+    .align 2
+mining_input_position: .word 0
+mining_input_length: .word 0
+mining_powers:
+    .word 0x00000001, 0x00000000, 0x0000000a, 0x00000000, 0x00000064, 0x00000000
+    .word 0x000003e8, 0x00000000, 0x00002710, 0x00000000, 0x000186a0, 0x00000000
+    .word 0x000f4240, 0x00000000, 0x00989680, 0x00000000, 0x05f5e100, 0x00000000
+    .word 0x3b9aca00, 0x00000000, 0x540be400, 0x00000002, 0x4876e800, 0x00000017
+    .word 0xd4a51000, 0x000000e8, 0x4e72a000, 0x00000918, 0x107a4000, 0x00005af3
+    .word 0xa4c68000, 0x00038d7e, 0x6fc10000, 0x002386f2, 0x5d8a0000, 0x01634578
+    .word 0xa7640000, 0x0de0b6b3
+mining_output: .space 64
+mining_output_end:
+mining_input: .space 4096
+mining_nan: .ascii "nan"
+mining_infinity: .ascii "-inf"
+"#;
+
+/// Lowers the quadruplets to a complete RARS program, `main` being the first quadruplet
+pub fn generate_riscv(quadruplets: &[Quadruplet]) -> Result<String, CodegenError> {
+    generate_riscv_with(quadruplets, &Allocation::default())
+}
+
+/// Same as `generate_riscv` with the temporaries placed by a register allocation made for
+/// `ALLOCATABLE_REGISTERS`, stack slots are words above sp
+pub fn generate_riscv_with(quadruplets: &[Quadruplet], allocation: &Allocation) -> Result<String, CodegenError> {
+    let mut generator = RiscvGenerator::new(quadruplets, allocation);
+    generator.generate()?;
+    Ok(generator.finish())
+}
+
+// Data directives for a string: .ascii when RARS can read it back, bytes otherwise
+fn string_data(text: &str) -> String {
+    if text.bytes().all(|byte| (0x20..=0x7E).contains(&byte) || byte == b'\n' || byte == b'\t') {
+        let escaped = text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+        return format!(".ascii \"{}\"", escaped);
+    }
+    let bytes: Vec<String> = text.bytes().map(|byte| byte.to_string()).collect();
+    format!(".byte {}", bytes.join(", "))
+}
+
+fn char_code(c: char) -> u8 {
+    c as u32 as u8
+}
+
+// log2 of the size of the elements of a symbol, for .align and the index scaling
+fn alignment(symbol_type: &Types) -> usize {
+    let size = match symbol_type {
+        Types::Array(element, _) => storage_size(element),
+        scalar => storage_size(scalar),
+    };
+    size.trailing_zeros() as usize
+}
+
+struct RiscvGenerator<'a> {
+    quadruplets: &'a [Quadruplet],
+    allocation: &'a Allocation,
+    current: usize,                 // Index of the quadruplet being lowered
+    text: String,
+    strings: Vec<String>,           // WRITE strings, labelled mining_s{index}
+    skips: usize,                   // Labels jumped to by inverted branches, mining_k{index}
+}
+
+impl<'a> RiscvGenerator<'a> {
+    fn new(quadruplets: &'a [Quadruplet], allocation: &'a Allocation) -> Self {
+        RiscvGenerator { quadruplets, allocation, current: 0, text: String::new(), strings: Vec::new(), skips: 0 }
+    }
+
+    fn line(&mut self, instruction: impl AsRef<str>) {
+        self.text.push_str("    ");
+        self.text.push_str(instruction.as_ref());
+        self.text.push('\n');
+    }
+
+    fn unsupported(&self, reason: &str) -> CodegenError {
+        CodegenError::Unsupported(self.current, self.quadruplets[self.current].to_string(), reason.to_string())
+    }
+
+    // Jumps to a far label when the branch is taken: the inverted branch skips a j
+    fn far_branch(&mut self, branch: &str, operands: &str, target: &str) {
+        let inverted = match branch {
+            "beqz" => "bnez",
+            "bnez" => "beqz",
+            "bgeu" => "bltu",
+            _ => "beq",
+        };
+        let label = format!("mining_k{}", self.skips);
+        self.skips += 1;
+        self.line(format!("{} {}, {}", inverted, operands, label));
+        self.line(format!("j {}", target));
+        self.text.push_str(&format!("{}:\n", label));
+    }
+
+    fn generate(&mut self) -> Result<(), CodegenError> {
+        let (initialized, _) = static_initializers(self.quadruplets);
+        let targets: HashSet<usize> = self.quadruplets.iter().filter_map(jump_target).collect();
+
+        self.text.push_str("    .text\n    .globl main\nmain:\n");
+        if self.allocation.stack_slots > 0 {
+            self.line(format!("addi sp, sp, -{}", self.allocation.stack_slots * 4));
+        }
+        // Temporaries read before being assigned start at zero like the static slots
+        let allocation = self.allocation;
+        for (name, class) in &allocation.undefined {
+            match (allocation.locations.get(name), class) {
+                (Some(Location::Register(register)), RegisterClass::Integer) => self.line(format!("li {}, 0", ALLOCATABLE_INTEGER[*register])),
+                (Some(Location::Register(register)), RegisterClass::Float) => self.line(format!("fmv.w.x {}, zero", ALLOCATABLE_FLOAT[*register])),
+                (Some(Location::Stack(slot)), _) => self.line(format!("sw zero, {}(sp)", slot * 4)),
+                (None, _) => {},
+            }
+        }
+        let quadruplets = self.quadruplets;
+        for (index, quadruplet) in quadruplets.iter().enumerate().skip(initialized) {
+            self.current = index;
+            if targets.contains(&index) {
+                self.text.push_str(&format!("q{}:\n", index));
+            }
+            self.text.push_str(&format!("    # {}: {}\n", index, quadruplet));
+            self.quadruplet(quadruplet)?;
+        }
+        self.text.push_str(&format!("q{}:\n", self.quadruplets.len()));
+        self.line("li a0, 0");
+        self.line("li a7, 93");
+        self.line("ecall");
+        Ok(())
+    }
+
+    // Memory operand of a variable, temporary or array element, its address is computed in t2.
+    // The index of an array element is checked against the bounds of the array
+    fn address(&mut self, operand: &Operand) -> Result<String, CodegenError> {
+        match operand {
+            Operand::Variable(name, _) => self.line(format!("la t2, {}", symbol(name))),
+            Operand::Temporary(_, _) => match self.allocation.location(operand) {
+                Some(Location::Stack(slot)) => return Ok(format!("{}(sp)", slot * 4)),
+                Some(Location::Register(_)) => return Err(self.unsupported("a temporary in a register has no address")),
+                None => self.line(format!("la t2, {}", symbol(&operand.to_string()))),
+            },
+            Operand::ArrayElement(name, Types::Array(element, size), index) => {
+                let shift = alignment(element);
+                if let Operand::Constant(Value::Integer(i)) = index.as_ref() {
+                    if (0..*size).contains(i) {
+                        self.line(format!("la t2, {}", symbol(name)));
+                        return Ok(format!("{}(t2)", (*i as usize) << shift));
+                    }
+                }
+                match index.as_ref() {
+                    Operand::Constant(Value::Integer(i)) => self.line(format!("li t2, {}", i)),
+                    index if self.register(index).is_some() => {
+                        let register = self.register(index).unwrap();
+                        self.line(format!("mv t2, {}", register));
+                    },
+                    Operand::Variable(_, Types::Integer) | Operand::Temporary(_, Types::Integer) => {
+                        let index = self.address(index)?;
+                        self.line(format!("lh t2, {}", index));
+                    },
+                    _ => return Err(self.unsupported("array indices are INTEGER values")),
+                }
+                // Negative indices are above the size when compared unsigned
+                self.line(format!("li t3, {}", size));
+                self.far_branch("bgeu", "t2, t3", "mining_index_error");
+                if shift > 0 {
+                    self.line(format!("slli t2, t2, {}", shift));
+                }
+                self.line(format!("la t3, {}", symbol(name)));
+                self.line("add t2, t2, t3");
+            },
+            _ => return Err(self.unsupported("expected a variable, a temporary or an array element")),
+        }
+        Ok("0(t2)".to_string())
+    }
+
+    // Register holding an allocated temporary
+    fn register(&self, operand: &Operand) -> Option<&'static str> {
+        match (self.allocation.location(operand), operand.value_type()) {
+            (Some(Location::Register(register)), Some(Types::Float)) => Some(ALLOCATABLE_FLOAT[register]),
+            (Some(Location::Register(register)), _) => Some(ALLOCATABLE_INTEGER[register]),
+            _ => None,
+        }
+    }
+
+    // Loads an operand in the first or second register of its type and returns the type
+    fn load(&mut self, operand: &Operand, slot: usize) -> Result<Types, CodegenError> {
+        let (integer, float) = (INTEGER_REGISTERS[slot], FLOAT_REGISTERS[slot]);
+        let value_type = match operand.value_type() {
+            Some(t @ (Types::Integer | Types::Float | Types::Char)) => t,
+            _ => return Err(self.unsupported("expected an INTEGER, FLOAT or CHAR value")),
+        };
+        match operand {
+            Operand::Constant(Value::Integer(i)) => self.line(format!("li {}, {}", integer, i)),
+            Operand::Constant(Value::Char(c)) => self.line(format!("li {}, {}", integer, char_code(*c))),
+            Operand::Constant(Value::Float(f)) => {
+                self.line(format!("li t2, 0x{:08x}                # {:?}", f.to_bits(), f));
+                self.line(format!("fmv.w.x {}, t2", float));
+            },
+            _ if self.register(operand).is_some() => {
+                let register = self.register(operand).unwrap();
+                match value_type {
+                    Types::Float => self.line(format!("fmv.s {}, {}", float, register)),
+                    _ => self.line(format!("mv {}, {}", integer, register)),
+                }
+            },
+            _ => {
+                let address = self.address(operand)?;
+                match value_type {
+                    Types::Integer => self.line(format!("lh {}, {}", integer, address)),
+                    Types::Char => self.line(format!("lbu {}, {}", integer, address)),
+                    _ => self.line(format!("flw {}, {}", float, address)),
+                }
+            },
+        }
+        Ok(value_type)
+    }
+
+    // Stores t0 or ft0 depending on the type of the value
+    fn store(&mut self, operand: Option<&Operand>, value_type: &Types) -> Result<(), CodegenError> {
+        let operand = operand.ok_or_else(|| self.unsupported("missing result"))?;
+        if operand.value_type().as_ref() != Some(value_type) {
+            return Err(self.unsupported("the result does not have the type of the value"));
+        }
+        // Values in t0 are already sign extended from 16 bits or below 128
+        if let Some(register) = self.register(operand) {
+            match value_type {
+                Types::Float => self.line(format!("fmv.s {}, ft0", register)),
+                _ => self.line(format!("mv {}, t0", register)),
+            }
+            return Ok(());
+        }
+        let address = self.address(operand)?;
+        match value_type {
+            Types::Integer => self.line(format!("sh t0, {}", address)),
+            Types::Char => self.line(format!("sb t0, {}", address)),
+            _ => self.line(format!("fsw ft0, {}", address)),
+        }
+        Ok(())
+    }
+
+    // Truth value of an operand in t0: 1 unless it is zero
+    fn truth(&mut self, operand: &Operand) -> Result<(), CodegenError> {
+        if self.load(operand, 0)? == Types::Float {
+            // NaN is not equal to zero, so it is true
+            self.line("fmv.w.x ft1, zero");
+            self.line("feq.s t0, ft0, ft1");
+            self.line("xori t0, t0, 1");
+        } else {
+            self.line("snez t0, t0");
+        }
+        Ok(())
+    }
+
+    fn operands(&self, quadruplet: &Quadruplet) -> Result<(Operand, Operand), CodegenError> {
+        match (&quadruplet.operand1, &quadruplet.operand2) {
+            (Some(a), Some(b)) => Ok((a.clone(), b.clone())),
+            _ => Err(self.unsupported("expected two operands")),
+        }
+    }
+
+    fn quadruplet(&mut self, quadruplet: &Quadruplet) -> Result<(), CodegenError> {
+        let result = quadruplet.result.as_ref();
+        match quadruplet.operator {
+            Operator::Assign => {
+                let value = quadruplet.operand1.as_ref().ok_or_else(|| self.unsupported("missing value"))?;
+                let value_type = self.load(value, 0)?;
+                self.store(result, &value_type)
+            },
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => {
+                let (a, b) = self.operands(quadruplet)?;
+                let value_type = self.arithmetic(&quadruplet.operator, &a, &b)?;
+                self.store(result, &value_type)
+            },
+            Operator::GreaterThan | Operator::LessThan | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual | Operator::Equal | Operator::NotEqual => {
+                let (a, b) = self.operands(quadruplet)?;
+                self.comparison(&quadruplet.operator, &a, &b)?;
+                self.store(result, &Types::Integer)
+            },
+            Operator::LogicalAnd | Operator::LogicalOr => {
+                let (a, b) = self.operands(quadruplet)?;
+                self.truth(&a)?;
+                self.line("mv t4, t0");
+                self.truth(&b)?;
+                self.line(if quadruplet.operator == Operator::LogicalAnd { "and t0, t0, t4" } else { "or t0, t0, t4" });
+                self.store(result, &Types::Integer)
+            },
+            Operator::LogicalNot => {
+                let value = quadruplet.operand1.as_ref().ok_or_else(|| self.unsupported("missing value"))?;
+                self.truth(value)?;
+                self.line("xori t0, t0, 1");
+                self.store(result, &Types::Integer)
+            },
+            Operator::Read => {
+                let value_type = result.and_then(Operand::value_type).ok_or_else(|| self.unsupported("missing result"))?;
+                match value_type {
+                    Types::Integer => self.line("call mining_read_int"),
+                    Types::Float => self.line("call mining_read_float"),
+                    Types::Char => self.line("call mining_read_char"),
+                    Types::Array(_, _) => return Err(self.unsupported("READ of a whole array")),
+                }
+                self.line(if value_type == Types::Float { "fmv.s ft0, fa0" } else { "mv t0, a0" });
+                self.store(result, &value_type)
+            },
+            Operator::Write => match &quadruplet.operand1 {
+                Some(Operand::String(text)) => {
+                    self.strings.push(text.clone());
+                    self.line(format!("la a0, mining_s{}", self.strings.len() - 1));
+                    self.line(format!("li a1, {}", text.len()));
+                    self.line("call mining_write_string");
+                    Ok(())
+                },
+                Some(value) => {
+                    match self.load(value, 0)? {
+                        Types::Integer => {
+                            self.line("mv a0, t0");
+                            self.line("call mining_write_int");
+                        },
+                        Types::Char => {
+                            self.line("mv a0, t0");
+                            self.line("call mining_write_char");
+                        },
+                        _ => {
+                            self.line("fmv.s fa0, ft0");
+                            self.line("call mining_write_float");
+                        },
+                    }
+                    Ok(())
+                },
+                None => Err(self.unsupported("missing value")),
+            },
+            Operator::Goto | Operator::IfTrue | Operator::IfFalse => {
+                let target = jump_target(quadruplet).ok_or_else(|| self.unsupported("missing target"))?;
+                let label = format!("q{}", target);
+                match (&quadruplet.operator, &quadruplet.operand1) {
+                    (Operator::Goto, _) => self.line(format!("j {}", label)),
+                    (operator, Some(condition)) => {
+                        self.truth(condition)?;
+                        let branch = if *operator == Operator::IfTrue { "bnez" } else { "beqz" };
+                        self.far_branch(branch, "t0", &label);
+                    },
+                    _ => return Err(self.unsupported("missing condition")),
+                }
+                Ok(())
+            },
+            Operator::For => Err(self.unsupported("For must be lowered to jumps")),
+        }
+    }
+
+    // Computes a op b in t0 or ft0 and returns the type of the result
+    fn arithmetic(&mut self, operator: &Operator, a: &Operand, b: &Operand) -> Result<Types, CodegenError> {
+        let value_type = self.load(a, 0)?;
+        if self.load(b, 1)? != value_type {
+            return Err(self.unsupported("operands of different types"));
+        }
+        match (value_type.clone(), operator) {
+            (Types::Integer, _) => {
+                match operator {
+                    Operator::Add => self.line("add t0, t0, t1"),
+                    Operator::Subtract => self.line("sub t0, t0, t1"),
+                    Operator::Multiply => self.line("mul t0, t0, t1"),
+                    _ => {
+                        self.far_branch("beqz", "t1", "mining_division_error");
+                        self.line("div t0, t0, t1");
+                    },
+                }
+                // The 32 bits result must fit in 16 bits, -32768 / -1 included
+                self.line("slli t2, t0, 16");
+                self.line("srai t2, t2, 16");
+                self.far_branch("bne", "t2, t0", "mining_overflow_error");
+            },
+            (Types::Float, Operator::Add) => self.line("fadd.s ft0, ft0, ft1"),
+            (Types::Float, Operator::Subtract) => self.line("fsub.s ft0, ft0, ft1"),
+            (Types::Float, Operator::Multiply) => self.line("fmul.s ft0, ft0, ft1"),
+            (Types::Float, _) => self.line("fdiv.s ft0, ft0, ft1"),
+            (_, Operator::Add | Operator::Subtract) => {
+                // CHAR codes wrap around 0x7F, a negative remainder gets 127 added
+                self.line(if *operator == Operator::Add { "add t0, t0, t1" } else { "sub t0, t0, t1" });
+                self.line("li t2, 127");
+                self.line("rem t0, t0, t2");
+                self.line("srai t3, t0, 31");
+                self.line("and t3, t3, t2");
+                self.line("add t0, t0, t3");
+            },
+            _ => return Err(self.unsupported("CHAR values only support addition and subtraction")),
+        }
+        Ok(value_type)
+    }
+
+    // Sets t0 to the result of comparing a with b
+    fn comparison(&mut self, operator: &Operator, a: &Operand, b: &Operand) -> Result<(), CodegenError> {
+        let value_type = self.load(a, 0)?;
+        if self.load(b, 1)? != value_type {
+            return Err(self.unsupported("operands of different types"));
+        }
+        if value_type == Types::Float {
+            // The comparisons are false when an operand is NaN, so only != is true then
+            match operator {
+                Operator::GreaterThan => self.line("flt.s t0, ft1, ft0"),
+                Operator::LessThan => self.line("flt.s t0, ft0, ft1"),
+                Operator::GreaterThanOrEqual => self.line("fle.s t0, ft1, ft0"),
+                Operator::LessThanOrEqual => self.line("fle.s t0, ft0, ft1"),
+                Operator::Equal => self.line("feq.s t0, ft0, ft1"),
+                _ => {
+                    self.line("feq.s t0, ft0, ft1");
+                    self.line("xori t0, t0, 1");
+                },
+            }
+            return Ok(());
+        }
+
+        let less = if is_signed(&value_type) { "slt" } else { "sltu" };
+        match operator {
+            Operator::GreaterThan => self.line(format!("{} t0, t1, t0", less)),
+            Operator::LessThan => self.line(format!("{} t0, t0, t1", less)),
+            Operator::GreaterThanOrEqual => {
+                self.line(format!("{} t0, t0, t1", less));
+                self.line("xori t0, t0, 1");
+            },
+            Operator::LessThanOrEqual => {
+                self.line(format!("{} t0, t1, t0", less));
+                self.line("xori t0, t0, 1");
+            },
+            Operator::Equal => {
+                self.line("sub t0, t0, t1");
+                self.line("seqz t0, t0");
+            },
+            _ => {
+                self.line("sub t0, t0, t1");
+                self.line("snez t0, t0");
+            },
+        }
+        Ok(())
+    }
+
+    // Appends the runtime, the error handlers and the data: the runtime constants, the strings
+    // and the storage of the symbols, larger elements first so that they stay aligned
+    fn finish(mut self) -> String {
+        let mut output = std::mem::take(&mut self.text);
+        output.push_str(RUNTIME);
+
+        let mut messages = String::new();
+        for (index, (label, message)) in RUNTIME_ERRORS.iter().enumerate() {
+            let message = format!("Runtime error: {}\n", message);
+            output.push_str(&format!("\n{}:\n", label));
+            output.push_str(&format!("    la a1, mining_e{}\n", index));
+            output.push_str(&format!("    li a2, {}\n", message.len()));
+            output.push_str("    j mining_fail\n");
+            messages.push_str(&format!("mining_e{}: {}\n", index, string_data(&message)));
+        }
+
+        output.push_str("\n    .data\n");
+        let (_, initial) = static_initializers(self.quadruplets);
+        let mut symbols: Vec<(String, Types)> = collect_symbols(self.quadruplets).into_iter()
+            .filter(|(name, _)| !self.allocation.locations.contains_key(name))
+            .collect();
+        symbols.sort_by_key(|(_, symbol_type)| std::cmp::Reverse(alignment(symbol_type)));
+        for (name, symbol_type) in &symbols {
+            // Variables given a constant at the start of the program are initialized
+            let directive = match initial.iter().find(|(known, _)| known == name).map(|(_, value)| value) {
+                Some(Value::Integer(i)) => format!(".half {}", i),
+                Some(Value::Float(f)) => format!(".word 0x{:08x}    # {:?}", f.to_bits(), f),
+                Some(Value::Char(c)) => format!(".byte {}", char_code(*c)),
+                None => format!(".space {}", storage_size(symbol_type).max(1)),
+            };
+            if alignment(symbol_type) > 0 {
+                output.push_str(&format!("    .align {}\n", alignment(symbol_type)));
+            }
+            output.push_str(&format!("{}: {}\n", symbol(name), directive));
+        }
+        output.push_str(RUNTIME_DATA);
+        for (index, text) in self.strings.iter().enumerate() {
+            output.push_str(&format!("mining_s{}: {}\n", index, string_data(text)));
+        }
+        output.push_str(&messages);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::simrv32::run_rv32;
+    use crate::codegen::{format_float, parse_float};
+    use crate::codegen::regalloc::{allocate, Strategy};
+    use crate::Semantic::quadruplets::parse_quadruplets;
+    use crate::Test::tests::QUADRUPLETS;
+
+    #[test]
+    fn test_generated_riscv() {
+        let quads = parse_quadruplets(QUADRUPLETS).unwrap();
+        let assembly = generate_riscv(&quads).unwrap();
+        // Leading constant assignments become initialized data
+        assert!(assembly.contains("m_S: .half 0"));
+        assert!(assembly.contains("m_G: .word 0x3fc00000"));
+        assert!(!assembly.contains("# 1: (Assign"));
+        assert!(assembly.contains("m_A: .space 6"));
+        assert!(assembly.contains("fdiv.s ft0, ft0, ft1"));
+
+        let execution = run_rv32(&assembly, b"-7 2.25\n a", 1_000_000).unwrap();
+        assert_eq!((execution.stdout().as_str(), execution.exit_code), ("S = -21\n1.5b13", 0));
+
+        let execution = run_rv32(&assembly, b"0 1.0 x", 1_000_000).unwrap();
+        assert_eq!(execution.stdout(), "S = 0\n0.666667y0");
+        assert_eq!((execution.stderr().as_str(), execution.exit_code), ("Runtime error: Division by zero\n", 1));
+
+        let execution = run_rv32(&assembly, b"40000", 1_000_000).unwrap();
+        assert_eq!((execution.stderr().as_str(), execution.exit_code), ("Runtime error: Invalid INTEGER input\n", 1));
+    }
+
+    #[test]
+    fn test_runtime_formats() {
+        let quads = parse_quadruplets(r#"
+            FLOAT F;
+            0: (Read, _, _, F)
+            1: (Write, F, _, _)
+            2: (Write, " ", _, _)
+            3: (Goto, _, _, 0)
+        "#).unwrap();
+        let assembly = generate_riscv(&quads).unwrap();
+        // The integer routines round like the f64 computations of parse_float and format_float
+        let tokens = [
+            "3.14", "-0.5", "100", "123456789012", "0.0000001", "0", "-0", "999999999", "1000000000",
+            "0.0000005", "340282346638528859811", "123456789012345678", "0.123456789012345678",
+            "16777217", "9007199254740993", "1.000000000000000001", "65504.0009765625", "0.1", "7", "2.5e",
+        ];
+        let mut expected = String::new();
+        for token in tokens {
+            match parse_float(token) {
+                Some(value) => expected.push_str(&format!("{} ", format_float(value))),
+                None => break,
+            }
+        }
+        let input = tokens.join(" ");
+        let execution = run_rv32(&assembly, input.as_bytes(), 10_000_000).unwrap();
+        assert_eq!(execution.stdout(), expected);
+        assert_eq!((execution.stderr().as_str(), execution.exit_code), ("Runtime error: Invalid FLOAT input\n", 1));
+
+        // Extreme values only reached by arithmetic, read from their bits
+        let values = [f32::MAX, f32::MIN_POSITIVE, 1e-45, -3.0e38, 1.0e10, 4.2949673e9, f32::INFINITY, f32::NAN];
+        for value in values {
+            let quads = parse_quadruplets(&format!("FLOAT F;\n0: (Assign, {:?}, _, F)\n1: (Write, F, _, _)\n", value)).unwrap();
+            let execution = run_rv32(&generate_riscv(&quads).unwrap(), b"", 1_000_000).unwrap();
+            assert_eq!(execution.stdout(), format_float(value), "{:?}", value);
+        }
+    }
+
+    const ALLOCATED: &str = r#"
+        INTEGER I, S, t1, t2, t3, t4, t5, t6;
+        FLOAT F, t7, t8;
+        CHAR t9;
+        INTEGER A[4];
+        0: (Read, _, _, t1)
+        1: (Assign, 0, _, I)
+        2: (LessThan, I, 4, t2)
+        3: (IfFalse, t2, _, 11)
+        4: (Multiply, I, t1, t3)
+        5: (Subtract, 3, I, t4)
+        6: (Assign, t3, _, A[t4])
+        7: (Add, S, A[t4], S)
+        8: (Add, I, 1, t5)
+        9: (Assign, t5, _, I)
+        10: (Goto, _, _, 2)
+        11: (Write, A[0], _, _)
+        12: (Write, " ", _, _)
+        13: (Write, S, _, _)
+        14: (Read, _, _, F)
+        15: (Multiply, F, F, t7)
+        16: (Add, t7, F, t8)
+        17: (Write, " ", _, _)
+        18: (Write, t8, _, _)
+        19: (Add, 'a', '\u{1}', t9)
+        20: (Write, t9, _, _)
+        21: (Subtract, 0, t1, t6)
+        22: (Write, t6, _, _)
+    "#;
+
+    #[test]
+    fn test_register_allocation() {
+        let quads = parse_quadruplets(ALLOCATED).unwrap();
+        let allocation = allocate(&quads, ALLOCATABLE_REGISTERS, Strategy::GraphColoring);
+        let assembly = generate_riscv_with(&quads, &allocation).unwrap();
+        assert!(!assembly.contains("m_t"));
+        assert!(assembly.contains("mv t2, s"));
+
+        // A single register of each class spills the temporaries living through the loop
+        let allocations = [
+            Allocation::default(),
+            allocation,
+            allocate(&quads, [1, 1], Strategy::GraphColoring),
+            allocate(&quads, [1, 1], Strategy::LinearScan),
+        ];
+        assert!(allocations[2].stack_slots > 0 && allocations[3].stack_slots > 0);
+        for allocation in &allocations {
+            let assembly = generate_riscv_with(&quads, allocation).unwrap();
+            let execution = run_rv32(&assembly, b"-5 1.5", 1_000_000).unwrap();
+            assert_eq!((execution.stdout().as_str(), execution.exit_code), ("-15 -30 3.75b5", 0));
+            let execution = run_rv32(&assembly, b"20000", 1_000_000).unwrap();
+            assert_eq!((execution.stderr().as_str(), execution.exit_code), ("Runtime error: INTEGER overflow\n", 1));
+        }
+    }
+}
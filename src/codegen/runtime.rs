@@ -0,0 +1,138 @@
+//! Run-time support of the execution engines running inside the compiler,
+//! READ and WRITE following the conventions of the generated programs
+
+use std::io::{BufRead, Write};
+use crate::codegen::error::RuntimeError;
+use crate::codegen::{format_float, parse_float};
+use crate::Semantic::quadruplets::Value;
+use crate::Semantic::ts::Types;
+
+/// Whitespace separated tokens read by READ
+pub struct Input<R> {
+    reader: R,
+}
+
+fn is_space(byte: u8) -> bool {
+    byte == b' ' || (b'\t'..=b'\r').contains(&byte)
+}
+
+impl<R: BufRead> Input<R> {
+    pub fn new(reader: R) -> Self {
+        Input { reader }
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>, RuntimeError> {
+        let byte = self.reader.fill_buf()?.first().copied();
+        if byte.is_some() {
+            self.reader.consume(1);
+        }
+        Ok(byte)
+    }
+
+    // First byte of the next token
+    fn token_start(&mut self) -> Result<u8, RuntimeError> {
+        loop {
+            match self.next_byte()? {
+                None => return Err(RuntimeError::EndOfInput),
+                Some(byte) if is_space(byte) => continue,
+                Some(byte) => return Ok(byte),
+            }
+        }
+    }
+
+    // The next token, the space that ends it is consumed
+    fn token(&mut self) -> Result<String, RuntimeError> {
+        let mut token = vec![self.token_start()?];
+        while let Some(byte) = self.next_byte()? {
+            if is_space(byte) {
+                break;
+            }
+            token.push(byte);
+        }
+        Ok(String::from_utf8_lossy(&token).to_string())
+    }
+
+    pub fn read_integer(&mut self) -> Result<i16, RuntimeError> {
+        self.token()?.parse().map_err(|_| RuntimeError::InvalidInteger)
+    }
+
+    pub fn read_float(&mut self) -> Result<f32, RuntimeError> {
+        parse_float(&self.token()?).ok_or(RuntimeError::InvalidFloat)
+    }
+
+    /// The next character that is not a space
+    pub fn read_char(&mut self) -> Result<char, RuntimeError> {
+        Ok(self.token_start()? as char)
+    }
+
+    /// The rest of the current line without its line break, None at the end of the input
+    pub fn read_line(&mut self) -> Result<Option<String>, RuntimeError> {
+        let mut line = Vec::new();
+        loop {
+            match self.next_byte()? {
+                None if line.is_empty() => return Ok(None),
+                None | Some(b'\n') => break,
+                Some(byte) => line.push(byte),
+            }
+        }
+        Ok(Some(String::from_utf8_lossy(&line).trim_end_matches('\r').to_string()))
+    }
+
+    pub fn read(&mut self, value_type: &Types) -> Result<Value, RuntimeError> {
+        match value_type {
+            Types::Integer => Ok(Value::Integer(self.read_integer()?)),
+            Types::Float => Ok(Value::Float(self.read_float()?)),
+            Types::Char => Ok(Value::Char(self.read_char()?)),
+            Types::Array(_, _) => Err(RuntimeError::InvalidProgram("READ of a whole array".to_string())),
+        }
+    }
+}
+
+/// Writes a value like WRITE, CHAR values are written as a single byte
+pub fn write_value(output: &mut impl Write, value: &Value) -> Result<(), RuntimeError> {
+    match value {
+        Value::Integer(i) => write!(output, "{}", i)?,
+        Value::Float(f) => output.write_all(format_float(*f).as_bytes())?,
+        Value::Char(c) => output.write_all(&[*c as u32 as u8])?,
+    }
+    Ok(())
+}
+
+/// Zero of a scalar type, the value of the variables that were never assigned
+pub fn zero(value_type: &Types) -> Value {
+    match value_type {
+        Types::Float => Value::Float(0.0),
+        Types::Char => Value::Char('\0'),
+        _ => Value::Integer(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_tokens() {
+        let mut input = Input::new("  -12\t+7 2.5\n\n  x  40000 1e3".as_bytes());
+        assert_eq!(input.read_integer(), Ok(-12));
+        assert_eq!(input.read_integer(), Ok(7));
+        assert_eq!(input.read_float(), Ok(2.5));
+        assert_eq!(input.read_char(), Ok('x'));
+        assert_eq!(input.read_integer(), Err(RuntimeError::InvalidInteger));
+        assert_eq!(input.read_float(), Err(RuntimeError::InvalidFloat));
+        assert_eq!(input.read_char(), Err(RuntimeError::EndOfInput));
+
+        let mut input = Input::new("5 print X\r\n\nlast".as_bytes());
+        assert_eq!(input.read_integer(), Ok(5));
+        assert_eq!(input.read_line(), Ok(Some("print X".to_string())));
+        assert_eq!(input.read_line(), Ok(Some(String::new())));
+        assert_eq!(input.read_line(), Ok(Some("last".to_string())));
+        assert_eq!(input.read_line(), Ok(None));
+
+        let mut output = Vec::new();
+        for value in [Value::Integer(-3), Value::Float(0.5), Value::Char('z')] {
+            write_value(&mut output, &value).unwrap();
+        }
+        assert_eq!(output, b"-30.5z");
+    }
+}
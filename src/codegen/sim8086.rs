@@ -0,0 +1,794 @@
+//! 8086 simulator for the assembly written by `codegen::i8086`
+//!
+//! It reads the MASM subset used by the backend (DB/DW data, PROC labels, 8 and 16 bits registers,
+//! `sym[bx]`, `[di]`, `OFFSET`, `BYTE PTR`/`WORD PTR`) and executes it on a 64 KB memory where the
+//! data segment starts at 0 and the stack grows down from the top. INT 21h implements the DOS
+//! services used by the runtime: 02h, 09h, 3Fh, 40h and 4Ch
+
+use std::collections::HashMap;
+use crate::codegen::error::SimulationError;
+use crate::codegen::Simulation;
+
+const MEMORY_SIZE: usize = 0x10000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Register {
+    Word(usize),    // AX, CX, DX, BX, SP, BP, SI, DI
+    Low(usize),     // AL, CL, DL, BL
+    High(usize),    // AH, CH, DH, BH
+    Segment,        // DS, ES, SS, CS are ignored, everything lives in one segment
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Argument {
+    Register(Register),
+    Immediate(i32),
+    Memory { base: Option<usize>, displacement: i32, size: Option<usize> },
+    Label(String),
+}
+
+#[derive(Debug, Clone)]
+struct Instruction {
+    line: usize,
+    mnemonic: String,
+    arguments: Vec<Argument>,
+}
+
+/// Assembles and runs a program with the given standard input, at most `max_steps` instructions
+pub fn run_8086(source: &str, input: &[u8], max_steps: u64) -> Result<Simulation, SimulationError> {
+    let program = Program::parse(source)?;
+    let mut machine = Machine::new(&program, input);
+    machine.run(max_steps)
+}
+
+fn register(name: &str) -> Option<Register> {
+    const WORDS: [&str; 8] = ["ax", "cx", "dx", "bx", "sp", "bp", "si", "di"];
+    const LOWS: [&str; 4] = ["al", "cl", "dl", "bl"];
+    const HIGHS: [&str; 4] = ["ah", "ch", "dh", "bh"];
+    let name = name.to_ascii_lowercase();
+    if let Some(index) = WORDS.iter().position(|r| *r == name) {
+        return Some(Register::Word(index));
+    }
+    if let Some(index) = LOWS.iter().position(|r| *r == name) {
+        return Some(Register::Low(index));
+    }
+    if let Some(index) = HIGHS.iter().position(|r| *r == name) {
+        return Some(Register::High(index));
+    }
+    matches!(name.as_str(), "ds" | "es" | "ss" | "cs").then_some(Register::Segment)
+}
+
+// Decimal, hexadecimal with an h suffix or a character in quotes
+fn number(text: &str) -> Option<i32> {
+    let text = text.trim();
+    if let Some(inner) = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        let mut chars = inner.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c as i32),
+            _ => None,
+        };
+    }
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest.trim()),
+        None => (false, text),
+    };
+    let value = match digits.strip_suffix(['h', 'H']) {
+        Some(hex) if hex.starts_with(|c: char| c.is_ascii_digit()) => i32::from_str_radix(hex, 16).ok()?,
+        _ if digits.starts_with(|c: char| c.is_ascii_digit()) => digits.parse().ok()?,
+        _ => return None,
+    };
+    Some(if negative { -value } else { value })
+}
+
+// Splits on the commas outside of quotes and brackets
+fn split_arguments(text: &str) -> Vec<String> {
+    let (mut parts, mut current, mut quoted, mut depth) = (Vec::new(), String::new(), None, 0);
+    for c in text.chars() {
+        match (c, quoted) {
+            ('\'' | '"', None) => quoted = Some(c),
+            (q, Some(open)) if q == open => quoted = None,
+            ('[' | '(', None) => depth += 1,
+            (']' | ')', None) => depth -= 1,
+            (',', None) if depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            },
+            _ => {},
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+// Text before a comment
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = None;
+    for (index, c) in line.char_indices() {
+        match (c, quoted) {
+            ('\'' | '"', None) => quoted = Some(c),
+            (q, Some(open)) if q == open => quoted = None,
+            (';', None) => return &line[..index],
+            _ => {},
+        }
+    }
+    line
+}
+
+struct Program {
+    memory: Vec<u8>,
+    instructions: Vec<Instruction>,
+    labels: HashMap<String, usize>,     // Instruction index of each code label
+    entry: usize,
+}
+
+impl Program {
+    fn parse(source: &str) -> Result<Program, SimulationError> {
+        // Data symbols first, the code may use them before they are defined
+        let mut memory = vec![0u8; MEMORY_SIZE];
+        let mut symbols: HashMap<String, (i32, usize)> = HashMap::new();    // Address and element size
+        let mut address = 0usize;
+        let mut in_data = false;
+        for (number, raw) in source.lines().enumerate() {
+            let line = strip_comment(raw).trim();
+            match line.to_ascii_uppercase().as_str() {
+                ".DATA" => in_data = true,
+                ".CODE" => in_data = false,
+                _ if in_data && !line.is_empty() && !line.starts_with('.') => {
+                    let error = |message: &str| SimulationError::Syntax(number + 1, message.to_string());
+                    let mut words = line.splitn(3, char::is_whitespace);
+                    let name = words.next().ok_or_else(|| error("expected a name"))?;
+                    let size = match words.next().map(str::to_ascii_uppercase).as_deref() {
+                        Some("DB") => 1,
+                        Some("DW") => 2,
+                        _ => return Err(error("expected DB or DW")),
+                    };
+                    symbols.insert(name.to_ascii_lowercase(), (address as i32, size));
+                    for item in split_arguments(words.next().unwrap_or("0")) {
+                        let upper = item.to_ascii_uppercase();
+                        if let Some(position) = upper.find("DUP(") {
+                            let count = number_of(&item[..position]).ok_or_else(|| error("invalid DUP count"))?;
+                            let value = number_of(item[position + 4..].trim_end_matches(')')).ok_or_else(|| error("invalid DUP value"))?;
+                            for _ in 0..count {
+                                address = store_data(&mut memory, address, value, size).ok_or_else(|| error("data segment full"))?;
+                            }
+                        } else if size == 1 && item.len() > 3 && item.starts_with('\'') && item.ends_with('\'') {
+                            for byte in item[1..item.len() - 1].bytes() {
+                                address = store_data(&mut memory, address, byte as i32, 1).ok_or_else(|| error("data segment full"))?;
+                            }
+                        } else {
+                            let value = number_of(&item).ok_or_else(|| error("invalid value"))?;
+                            address = store_data(&mut memory, address, value, size).ok_or_else(|| error("data segment full"))?;
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        let mut instructions = Vec::new();
+        let mut labels = HashMap::new();
+        let mut entry = None;
+        let mut in_code = false;
+        for (number, raw) in source.lines().enumerate() {
+            let error = |message: String| SimulationError::Syntax(number + 1, message);
+            let mut line = strip_comment(raw).trim();
+            let upper = line.to_ascii_uppercase();
+            if upper == ".CODE" || upper == ".DATA" {
+                in_code = upper == ".CODE";
+                continue;
+            }
+            if !in_code || line.is_empty() || line.starts_with('.') || upper.ends_with(" ENDP") {
+                continue;
+            }
+            if let Some(name) = upper.strip_prefix("END") {
+                if name.is_empty() || name.starts_with(' ') {
+                    entry = Some(name.trim().to_ascii_lowercase());
+                    break;
+                }
+            }
+            if let Some(name) = upper.strip_suffix(" PROC") {
+                labels.insert(name.trim().to_ascii_lowercase(), instructions.len());
+                continue;
+            }
+            if let Some((label, rest)) = line.split_once(':') {
+                if !label.contains(char::is_whitespace) && !label.contains('\'') {
+                    labels.insert(label.to_ascii_lowercase(), instructions.len());
+                    line = rest.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                }
+            }
+
+            let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let mnemonic = mnemonic.to_ascii_lowercase();
+            let mut arguments = Vec::new();
+            for text in split_arguments(rest) {
+                let argument = if mnemonic.starts_with('j') || mnemonic == "call" {
+                    Argument::Label(text.to_ascii_lowercase())
+                } else {
+                    parse_argument(&text, &symbols).ok_or_else(|| error(format!("invalid operand '{}'", text)))?
+                };
+                arguments.push(argument);
+            }
+            instructions.push(Instruction { line: number + 1, mnemonic, arguments });
+        }
+
+        for instruction in &instructions {
+            for argument in &instruction.arguments {
+                if let Argument::Label(label) = argument {
+                    if !labels.contains_key(label) {
+                        return Err(SimulationError::UnknownLabel(instruction.line, label.clone()));
+                    }
+                }
+            }
+        }
+        let entry = match entry.filter(|name| !name.is_empty()) {
+            Some(name) => *labels.get(&name).ok_or(SimulationError::UnknownLabel(0, name))?,
+            None => 0,
+        };
+        Ok(Program { memory, instructions, labels, entry })
+    }
+}
+
+fn number_of(text: &str) -> Option<i32> {
+    number(text.trim())
+}
+
+fn store_data(memory: &mut [u8], address: usize, value: i32, size: usize) -> Option<usize> {
+    if address + size > MEMORY_SIZE / 2 {
+        return None;
+    }
+    memory[address] = value as u8;
+    if size == 2 {
+        memory[address + 1] = (value >> 8) as u8;
+    }
+    Some(address + size)
+}
+
+// Register, immediate (numbers, characters, OFFSET symbol) or memory operand
+fn parse_argument(text: &str, symbols: &HashMap<String, (i32, usize)>) -> Option<Argument> {
+    if let Some(r) = register(text) {
+        return Some(Argument::Register(r));
+    }
+    let mut text = text.trim().to_string();
+    let mut size = None;
+    for (prefix, bytes) in [("BYTE PTR", 1), ("WORD PTR", 2)] {
+        if text.to_ascii_uppercase().starts_with(prefix) {
+            text = text[prefix.len()..].trim().to_string();
+            size = Some(bytes);
+        }
+    }
+    let offset = text.to_ascii_uppercase().starts_with("OFFSET ");
+    if offset {
+        text = text[7..].to_string();
+    }
+    if text.eq_ignore_ascii_case("@data") {
+        return Some(Argument::Immediate(0));
+    }
+
+    // Terms separated by + and -, brackets hold registers or numbers added to the address
+    let mut memory = false;
+    let (mut base, mut displacement) = (None, 0);
+    let mut terms = String::new();
+    for c in text.chars() {
+        match c {
+            '[' => {
+                memory = true;
+                terms.push('+');
+            },
+            ']' => {},
+            _ => terms.push(c),
+        }
+    }
+    let mut sign = 1;
+    let mut term = String::new();
+    let flush = |term: &mut String, sign: i32, base: &mut Option<usize>, displacement: &mut i32, memory: &mut bool, size: &mut Option<usize>| -> Option<()> {
+        let word = term.trim().to_string();
+        term.clear();
+        if word.is_empty() {
+            return Some(());
+        }
+        if let Some(Register::Word(index)) = register(&word) {
+            *base = Some(index);
+            *memory = true;
+        } else if let Some(value) = number(&word) {
+            *displacement += sign * value;
+        } else {
+            let (address, element) = symbols.get(&word.to_ascii_lowercase())?;
+            *displacement += sign * address;
+            *memory = true;
+            size.get_or_insert(*element);
+        }
+        Some(())
+    };
+    let mut quoted = false;
+    for c in terms.chars() {
+        match c {
+            '\'' => {
+                quoted = !quoted;
+                term.push(c);
+            },
+            '+' | '-' if !quoted && !term.trim().is_empty() => {
+                flush(&mut term, sign, &mut base, &mut displacement, &mut memory, &mut size)?;
+                sign = if c == '-' { -1 } else { 1 };
+            },
+            '+' if !quoted => sign = 1,
+            '-' if !quoted => sign = -sign,
+            _ => term.push(c),
+        }
+    }
+    flush(&mut term, sign, &mut base, &mut displacement, &mut memory, &mut size)?;
+
+    if offset || !memory {
+        return Some(Argument::Immediate(displacement));
+    }
+    Some(Argument::Memory { base, displacement, size })
+}
+
+struct Machine<'a> {
+    program: &'a Program,
+    memory: Vec<u8>,
+    registers: [u16; 8],
+    carry: bool,
+    zero: bool,
+    sign: bool,
+    overflow: bool,
+    input: &'a [u8],
+    input_position: usize,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+const AX: usize = 0;
+const CX: usize = 1;
+const DX: usize = 2;
+const BX: usize = 3;
+const SP: usize = 4;
+
+impl<'a> Machine<'a> {
+    fn new(program: &'a Program, input: &'a [u8]) -> Self {
+        let mut registers = [0u16; 8];
+        registers[SP] = 0xFFFE;
+        Machine {
+            program,
+            memory: program.memory.clone(),
+            registers,
+            carry: false,
+            zero: false,
+            sign: false,
+            overflow: false,
+            input,
+            input_position: 0,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        }
+    }
+
+    fn get_register(&self, r: Register) -> u16 {
+        match r {
+            Register::Word(i) => self.registers[i],
+            Register::Low(i) => self.registers[i] & 0xFF,
+            Register::High(i) => self.registers[i] >> 8,
+            Register::Segment => 0,
+        }
+    }
+
+    fn set_register(&mut self, r: Register, value: u16) {
+        match r {
+            Register::Word(i) => self.registers[i] = value,
+            Register::Low(i) => self.registers[i] = (self.registers[i] & 0xFF00) | (value & 0xFF),
+            Register::High(i) => self.registers[i] = (self.registers[i] & 0x00FF) | ((value & 0xFF) << 8),
+            Register::Segment => {},
+        }
+    }
+
+    fn read_memory(&self, address: usize, size: usize) -> u16 {
+        let low = self.memory[address & 0xFFFF] as u16;
+        if size == 1 {
+            low
+        } else {
+            low | (self.memory[(address + 1) & 0xFFFF] as u16) << 8
+        }
+    }
+
+    fn write_memory(&mut self, address: usize, size: usize, value: u16) {
+        self.memory[address & 0xFFFF] = value as u8;
+        if size == 2 {
+            self.memory[(address + 1) & 0xFFFF] = (value >> 8) as u8;
+        }
+    }
+
+    fn effective_address(&self, base: Option<usize>, displacement: i32) -> usize {
+        let base = base.map_or(0, |i| self.registers[i] as i32);
+        ((base + displacement) as u32 & 0xFFFF) as usize
+    }
+
+    // Operand size: the register, else the PTR or the symbol of the memory operand
+    fn width(&self, instruction: &Instruction) -> Result<usize, SimulationError> {
+        for argument in &instruction.arguments {
+            match argument {
+                Argument::Register(Register::Word(_)) => return Ok(2),
+                Argument::Register(Register::Low(_) | Register::High(_)) => return Ok(1),
+                _ => {},
+            }
+        }
+        for argument in &instruction.arguments {
+            if let Argument::Memory { size: Some(size), .. } = argument {
+                return Ok(*size);
+            }
+        }
+        Err(self.fault(instruction, "operand size unknown"))
+    }
+
+    fn read(&self, argument: &Argument, size: usize) -> u16 {
+        match argument {
+            Argument::Register(r) => self.get_register(*r),
+            Argument::Immediate(value) => *value as u16 & if size == 1 { 0xFF } else { 0xFFFF },
+            Argument::Memory { base, displacement, .. } => self.read_memory(self.effective_address(*base, *displacement), size),
+            Argument::Label(_) => 0,
+        }
+    }
+
+    fn write(&mut self, argument: &Argument, size: usize, value: u16) {
+        match argument {
+            Argument::Register(r) => self.set_register(*r, value),
+            Argument::Memory { base, displacement, .. } => {
+                let address = self.effective_address(*base, *displacement);
+                self.write_memory(address, size, value);
+            },
+            _ => {},
+        }
+    }
+
+    fn fault(&self, instruction: &Instruction, message: &str) -> SimulationError {
+        SimulationError::Fault(instruction.line, message.to_string())
+    }
+
+    fn set_result_flags(&mut self, value: u32, size: usize) {
+        let mask = if size == 1 { 0xFF } else { 0xFFFF };
+        let sign_bit = if size == 1 { 0x80 } else { 0x8000 };
+        self.zero = value & mask == 0;
+        self.sign = value & sign_bit != 0;
+    }
+
+    // Adds or subtracts with the carry in, setting every flag
+    fn add(&mut self, a: u16, b: u16, carry_in: bool, subtract: bool, size: usize) -> u16 {
+        let (mask, sign_bit) = if size == 1 { (0xFFu32, 0x80u32) } else { (0xFFFFu32, 0x8000u32) };
+        let (a, b, c) = (a as u32 & mask, b as u32 & mask, carry_in as u32);
+        let result = if subtract { a.wrapping_sub(b).wrapping_sub(c) } else { a + b + c } & mask;
+        self.carry = if subtract { a < b + c } else { a + b + c > mask };
+        let same_sign = (a & sign_bit) == (b & sign_bit);
+        self.overflow = if subtract {
+            !same_sign && (result & sign_bit) != (a & sign_bit)
+        } else {
+            same_sign && (result & sign_bit) != (a & sign_bit)
+        };
+        self.set_result_flags(result, size);
+        result as u16
+    }
+
+    fn condition(&self, mnemonic: &str) -> Option<bool> {
+        let less = self.sign != self.overflow;
+        Some(match mnemonic {
+            "je" | "jz" => self.zero,
+            "jne" | "jnz" => !self.zero,
+            "jl" | "jnge" => less,
+            "jge" | "jnl" => !less,
+            "jg" | "jnle" => !self.zero && !less,
+            "jle" | "jng" => self.zero || less,
+            "jb" | "jc" | "jnae" => self.carry,
+            "jae" | "jnc" | "jnb" => !self.carry,
+            "ja" | "jnbe" => !self.carry && !self.zero,
+            "jbe" | "jna" => self.carry || self.zero,
+            "jo" => self.overflow,
+            "jno" => !self.overflow,
+            "js" => self.sign,
+            "jns" => !self.sign,
+            _ => return None,
+        })
+    }
+
+    fn push(&mut self, value: u16) {
+        self.registers[SP] = self.registers[SP].wrapping_sub(2);
+        self.write_memory(self.registers[SP] as usize, 2, value);
+    }
+
+    fn pop(&mut self) -> u16 {
+        let value = self.read_memory(self.registers[SP] as usize, 2);
+        self.registers[SP] = self.registers[SP].wrapping_add(2);
+        value
+    }
+
+    fn run(&mut self, max_steps: u64) -> Result<Simulation, SimulationError> {
+        let program = self.program;
+        let mut pc = program.entry;
+        let mut steps = 0;
+        loop {
+            let instruction = program.instructions.get(pc).ok_or(SimulationError::Fault(0, "execution ran past the end of the code".to_string()))?;
+            steps += 1;
+            if steps > max_steps {
+                return Err(SimulationError::StepLimit(max_steps));
+            }
+            pc += 1;
+            if let Some(code) = self.execute(instruction, &mut pc)? {
+                return Ok(Simulation {
+                    stdout_bytes: std::mem::take(&mut self.stdout),
+                    stderr_bytes: std::mem::take(&mut self.stderr),
+                    exit_code: code.into(),
+                    steps,
+                });
+            }
+        }
+    }
+
+    fn target(&self, instruction: &Instruction) -> Result<usize, SimulationError> {
+        match instruction.arguments.first() {
+            Some(Argument::Label(label)) => Ok(self.program.labels[label]),
+            _ => Err(self.fault(instruction, "expected a label")),
+        }
+    }
+
+    // Executes one instruction, returns the exit code when the program ends
+    fn execute(&mut self, instruction: &Instruction, pc: &mut usize) -> Result<Option<u8>, SimulationError> {
+        let arguments = &instruction.arguments;
+        let count = |n: usize| if arguments.len() == n { Ok(()) } else { Err(self.fault(instruction, &format!("expected {} operand(s)", n))) };
+        let mnemonic = instruction.mnemonic.as_str();
+
+        if let Some(taken) = self.condition(mnemonic) {
+            if taken {
+                *pc = self.target(instruction)?;
+            }
+            return Ok(None);
+        }
+
+        match mnemonic {
+            "jmp" => *pc = self.target(instruction)?,
+            "call" => {
+                let target = self.target(instruction)?;
+                self.push(*pc as u16);
+                *pc = target;
+            },
+            "ret" => *pc = self.pop() as usize,
+            "push" => {
+                count(1)?;
+                let value = self.read(&arguments[0], 2);
+                self.push(value);
+            },
+            "pop" => {
+                count(1)?;
+                let value = self.pop();
+                self.write(&arguments[0], 2, value);
+            },
+            "mov" => {
+                count(2)?;
+                let size = self.width(instruction)?;
+                let value = self.read(&arguments[1], size);
+                self.write(&arguments[0], size, value);
+            },
+            "add" | "sub" | "cmp" | "sbb" | "adc" => {
+                count(2)?;
+                let size = self.width(instruction)?;
+                let (a, b) = (self.read(&arguments[0], size), self.read(&arguments[1], size));
+                let carry_in = matches!(mnemonic, "sbb" | "adc") && self.carry;
+                let result = self.add(a, b, carry_in, mnemonic != "add" && mnemonic != "adc", size);
+                if mnemonic != "cmp" {
+                    self.write(&arguments[0], size, result);
+                }
+            },
+            "and" | "or" | "xor" | "test" => {
+                count(2)?;
+                let size = self.width(instruction)?;
+                let (a, b) = (self.read(&arguments[0], size), self.read(&arguments[1], size));
+                let result = match mnemonic {
+                    "or" => a | b,
+                    "xor" => a ^ b,
+                    _ => a & b,
+                };
+                self.carry = false;
+                self.overflow = false;
+                self.set_result_flags(result as u32, size);
+                if mnemonic != "test" {
+                    self.write(&arguments[0], size, result);
+                }
+            },
+            "inc" | "dec" | "neg" => {
+                count(1)?;
+                let size = self.width(instruction)?;
+                let value = self.read(&arguments[0], size);
+                let carry = self.carry;
+                let result = match mnemonic {
+                    "inc" => self.add(value, 1, false, false, size),
+                    "dec" => self.add(value, 1, false, true, size),
+                    _ => self.add(0, value, false, true, size),
+                };
+                if mnemonic != "neg" {
+                    self.carry = carry;
+                }
+                self.write(&arguments[0], size, result);
+            },
+            "shl" | "sal" => {
+                count(2)?;
+                let size = self.width(instruction)?;
+                let (value, shift) = (self.read(&arguments[0], size) as u32, self.read(&arguments[1], 1) as u32 & 0x1F);
+                if shift > 0 {
+                    let bits = size as u32 * 8;
+                    let result = (value << shift) & if size == 1 { 0xFF } else { 0xFFFF };
+                    self.carry = shift <= bits && (value >> (bits - shift)) & 1 == 1;
+                    self.overflow = ((result >> (bits - 1)) & 1 == 1) != self.carry;
+                    self.set_result_flags(result, size);
+                    self.write(&arguments[0], size, result as u16);
+                }
+            },
+            "cwd" => self.registers[DX] = if self.registers[AX] & 0x8000 != 0 { 0xFFFF } else { 0 },
+            "cbw" => self.registers[AX] = self.registers[AX] as u8 as i8 as i16 as u16,
+            "mul" | "imul" => {
+                count(1)?;
+                let size = self.width(instruction)?;
+                let source = self.read(&arguments[0], size);
+                let signed = mnemonic == "imul";
+                if size == 1 {
+                    let al = self.registers[AX] & 0xFF;
+                    let product = if signed {
+                        (al as u8 as i8 as i16 * source as u8 as i8 as i16) as u16
+                    } else {
+                        al * source
+                    };
+                    self.registers[AX] = product;
+                    self.carry = if signed { product as i16 != product as u8 as i8 as i16 } else { product > 0xFF };
+                } else {
+                    let product = if signed {
+                        (self.registers[AX] as i16 as i32 * source as i16 as i32) as u32
+                    } else {
+                        self.registers[AX] as u32 * source as u32
+                    };
+                    self.registers[AX] = product as u16;
+                    self.registers[DX] = (product >> 16) as u16;
+                    self.carry = if signed { product as i32 != product as u16 as i16 as i32 } else { product > 0xFFFF };
+                }
+                self.overflow = self.carry;
+            },
+            "div" | "idiv" => {
+                count(1)?;
+                if self.width(instruction)? != 2 {
+                    return Err(self.fault(instruction, "only 16 bits division is supported"));
+                }
+                let source = self.read(&arguments[0], 2);
+                let dividend = (self.registers[DX] as u32) << 16 | self.registers[AX] as u32;
+                let divide_error = || SimulationError::Fault(instruction.line, "divide error".to_string());
+                if source == 0 {
+                    return Err(divide_error());
+                }
+                let (quotient, remainder) = if mnemonic == "idiv" {
+                    let (n, d) = (dividend as i32, source as i16 as i32);
+                    let quotient = n.checked_div(d).ok_or_else(divide_error)?;
+                    if !(-32768..=32767).contains(&quotient) {
+                        return Err(divide_error());
+                    }
+                    (quotient as u16, (n % d) as u16)
+                } else {
+                    let quotient = dividend / source as u32;
+                    if quotient > 0xFFFF {
+                        return Err(divide_error());
+                    }
+                    (quotient as u16, (dividend % source as u32) as u16)
+                };
+                self.registers[AX] = quotient;
+                self.registers[DX] = remainder;
+            },
+            "int" => {
+                count(1)?;
+                if self.read(&arguments[0], 1) != 0x21 {
+                    return Err(self.fault(instruction, "only INT 21h is supported"));
+                }
+                return self.dos(instruction);
+            },
+            "nop" => {},
+            _ => return Err(self.fault(instruction, &format!("unsupported instruction '{}'", mnemonic))),
+        }
+        Ok(None)
+    }
+
+    // DOS services selected by AH
+    fn dos(&mut self, instruction: &Instruction) -> Result<Option<u8>, SimulationError> {
+        let function = self.registers[AX] >> 8;
+        let (bx, cx, dx) = (self.registers[BX], self.registers[CX] as usize, self.registers[DX] as usize);
+        match function {
+            0x02 => self.stdout.push(dx as u8),
+            0x09 => {
+                let mut address = dx;
+                while self.memory[address & 0xFFFF] != b'$' {
+                    self.stdout.push(self.memory[address & 0xFFFF]);
+                    address += 1;
+                }
+            },
+            0x3F => {
+                let available = self.input.len() - self.input_position;
+                let count = cx.min(available);
+                for i in 0..count {
+                    self.memory[(dx + i) & 0xFFFF] = self.input[self.input_position + i];
+                }
+                self.input_position += count;
+                self.registers[AX] = count as u16;
+                self.carry = false;
+            },
+            0x40 => {
+                let bytes: Vec<u8> = (0..cx).map(|i| self.memory[(dx + i) & 0xFFFF]).collect();
+                match bx {
+                    1 => self.stdout.extend(bytes),
+                    2 => self.stderr.extend(bytes),
+                    _ => return Err(self.fault(instruction, "only the handles 1 and 2 can be written")),
+                }
+                self.registers[AX] = cx as u16;
+                self.carry = false;
+            },
+            0x4C => return Ok(Some(self.registers[AX] as u8)),
+            _ => return Err(self.fault(instruction, &format!("unsupported DOS function {:02X}h", function))),
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instructions_and_flags() {
+        let source = r#"
+    .MODEL SMALL
+    .DATA
+value DW -5
+bytes DB 'ab', 10
+table DW 3 DUP(7)
+    .CODE
+main PROC
+    mov ax, @data
+    mov ds, ax
+    mov ax, value
+    mov cx, 3
+    imul cx                 ; -15
+    mov bx, 2
+    shl bx, 1
+    mov table[bx], ax       ; table[2] = -15
+    mov al, bytes[1]
+    add al, 1               ; 'c'
+    mov dl, al
+    mov ah, 02h
+    int 21h
+    mov ax, 7FFFh
+    add ax, 1
+    jo overflow
+    mov ah, 4Ch
+    int 21h
+overflow:
+    mov ax, WORD PTR table[4]
+    cmp ax, -15
+    jne wrong
+    mov ax, 4C03h
+    int 21h
+wrong:
+    mov ax, 4C09h
+    int 21h
+main ENDP
+    END main
+"#;
+        let execution = run_8086(source, b"", 1000).unwrap();
+        assert_eq!(execution.stdout(), "c");
+        assert_eq!(execution.exit_code, 3);
+    }
+
+    #[test]
+    fn test_faults() {
+        let source = ".CODE\nstart:\n    mov ax, 1\n    xor dx, dx\n    mov cx, 0\n    div cx\n    END start\n";
+        assert_eq!(run_8086(source, b"", 100), Err(SimulationError::Fault(6, "divide error".to_string())));
+        let source = ".CODE\nstart:\n    jmp start\n    END start\n";
+        assert_eq!(run_8086(source, b"", 100), Err(SimulationError::StepLimit(100)));
+        let source = ".CODE\nstart:\n    jmp finish\n    END start\n";
+        assert_eq!(run_8086(source, b"", 100), Err(SimulationError::UnknownLabel(3, "finish".to_string())));
+    }
+}
@@ -0,0 +1,705 @@
+//! RV32IMF simulator for the assembly written by `codegen::riscv`
+//!
+//! It reads the RARS subset used by the backend: `.text`/`.data` sections, `.align`, `.word`,
+//! `.half`, `.byte`, `.ascii`, `.asciz` and `.space` data, ABI or numbered register names, the
+//! base, M and single precision F instructions and the usual pseudo-instructions (`li`, `la`,
+//! `call`, `ret`, `beqz`...). The memory map is the RARS one: code at 0x00400000, data at
+//! 0x10010000 and a 1 MB stack below 0x7FFFF000. `ecall` implements the system calls used by the
+//! runtime: 10 and 93 (exit), 63 (read from fd 0) and 64 (write to fd 1 or 2)
+
+use std::collections::HashMap;
+use crate::codegen::error::SimulationError;
+use crate::codegen::Simulation;
+
+const TEXT_BASE: u32 = 0x0040_0000;
+const DATA_BASE: u32 = 0x1001_0000;
+const DATA_SIZE: u32 = 0x10_0000;
+const STACK_TOP: u32 = 0x7FFF_F000;
+const STACK_SIZE: u32 = 0x10_0000;
+
+const INTEGER_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+const FLOAT_NAMES: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1", "fa0", "fa1", "fa2", "fa3", "fa4", "fa5",
+    "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9", "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+// Operands of each instruction: r integer register, f float register, i immediate, m offset(register),
+// l label. Instructions with several forms are listed once per form
+const INSTRUCTIONS: [(&str, &str); 92] = [
+    ("add", "rrr"), ("sub", "rrr"), ("sll", "rrr"), ("slt", "rrr"), ("sltu", "rrr"), ("xor", "rrr"),
+    ("srl", "rrr"), ("sra", "rrr"), ("or", "rrr"), ("and", "rrr"),
+    ("mul", "rrr"), ("mulh", "rrr"), ("mulhsu", "rrr"), ("mulhu", "rrr"),
+    ("div", "rrr"), ("divu", "rrr"), ("rem", "rrr"), ("remu", "rrr"),
+    ("addi", "rri"), ("slti", "rri"), ("sltiu", "rri"), ("xori", "rri"), ("ori", "rri"), ("andi", "rri"),
+    ("slli", "rri"), ("srli", "rri"), ("srai", "rri"), ("lui", "ri"),
+    ("lb", "rm"), ("lh", "rm"), ("lw", "rm"), ("lbu", "rm"), ("lhu", "rm"), ("sb", "rm"), ("sh", "rm"), ("sw", "rm"),
+    ("beq", "rrl"), ("bne", "rrl"), ("blt", "rrl"), ("bge", "rrl"), ("bltu", "rrl"), ("bgeu", "rrl"),
+    ("bgt", "rrl"), ("ble", "rrl"), ("bgtu", "rrl"), ("bleu", "rrl"),
+    ("beqz", "rl"), ("bnez", "rl"), ("bltz", "rl"), ("bgez", "rl"), ("bgtz", "rl"), ("blez", "rl"),
+    ("jal", "rl"), ("jal", "l"), ("jalr", "r"), ("jalr", "rri"), ("jalr", "rm"),
+    ("j", "l"), ("jr", "r"), ("call", "l"), ("tail", "l"), ("ret", ""), ("nop", ""), ("ecall", ""),
+    ("li", "ri"), ("la", "rl"), ("mv", "rr"), ("not", "rr"), ("neg", "rr"),
+    ("seqz", "rr"), ("snez", "rr"), ("sltz", "rr"), ("sgtz", "rr"),
+    ("flw", "fm"), ("fsw", "fm"),
+    ("fadd.s", "fff"), ("fsub.s", "fff"), ("fmul.s", "fff"), ("fdiv.s", "fff"),
+    ("fsgnj.s", "fff"), ("fsgnjn.s", "fff"), ("fsgnjx.s", "fff"),
+    ("feq.s", "rff"), ("flt.s", "rff"), ("fle.s", "rff"),
+    ("fcvt.s.w", "fr"), ("fcvt.w.s", "rf"), ("fmv.x.w", "rf"), ("fmv.w.x", "fr"),
+    ("fmv.s", "ff"), ("fneg.s", "ff"), ("fabs.s", "ff"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Argument {
+    Integer(usize),
+    Float(usize),
+    Immediate(i64),
+    Memory { offset: i32, base: usize },
+    Label(String),
+}
+
+#[derive(Debug, Clone)]
+struct Instruction {
+    line: usize,
+    mnemonic: String,
+    arguments: Vec<Argument>,
+}
+
+/// Assembles and runs a program with the given standard input, at most `max_steps` instructions
+pub fn run_rv32(source: &str, input: &[u8], max_steps: u64) -> Result<Simulation, SimulationError> {
+    let program = Program::parse(source)?;
+    let mut machine = Machine::new(&program, input);
+    machine.run(max_steps)
+}
+
+fn register(name: &str, names: &[&str; 32], prefix: char) -> Option<usize> {
+    if let Some(index) = names.iter().position(|r| *r == name) {
+        return Some(index);
+    }
+    if prefix == 'x' && name == "fp" {
+        return Some(8);
+    }
+    name.strip_prefix(prefix).and_then(|n| n.parse().ok()).filter(|n| *n < 32)
+}
+
+// Decimal, hexadecimal with 0x or a character in quotes
+fn number(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if let Some(inner) = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        let mut chars = unescape(inner).into_iter();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c as i64),
+            _ => None,
+        };
+    }
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None if digits.starts_with(|c: char| c.is_ascii_digit()) => digits.parse().ok()?,
+        None => return None,
+    };
+    Some(if negative { -value } else { value })
+}
+
+// Bytes of a string or character literal without its quotes
+fn unescape(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut escaped = false;
+    for byte in text.bytes() {
+        if escaped {
+            bytes.push(match byte {
+                b'n' => b'\n',
+                b't' => b'\t',
+                b'r' => b'\r',
+                b'0' => 0,
+                other => other,
+            });
+            escaped = false;
+        } else if byte == b'\\' {
+            escaped = true;
+        } else {
+            bytes.push(byte);
+        }
+    }
+    bytes
+}
+
+// Splits on the commas outside of quotes
+fn split_arguments(text: &str) -> Vec<String> {
+    let (mut parts, mut current, mut quoted, mut escaped) = (Vec::new(), String::new(), None, false);
+    for c in text.chars() {
+        match (c, quoted) {
+            (_, Some(_)) if escaped => escaped = false,
+            ('\\', Some(_)) => escaped = true,
+            ('\'' | '"', None) => quoted = Some(c),
+            (q, Some(open)) if q == open => quoted = None,
+            (',', None) => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            },
+            _ => {},
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+// Text before a comment
+fn strip_comment(line: &str) -> &str {
+    let (mut quoted, mut escaped) = (None, false);
+    for (index, c) in line.char_indices() {
+        match (c, quoted) {
+            (_, Some(_)) if escaped => escaped = false,
+            ('\\', Some(_)) => escaped = true,
+            ('\'' | '"', None) => quoted = Some(c),
+            (q, Some(open)) if q == open => quoted = None,
+            ('#', None) => return &line[..index],
+            _ => {},
+        }
+    }
+    line
+}
+
+fn parse_argument(text: &str, kind: char) -> Option<Argument> {
+    match kind {
+        'r' => register(text, &INTEGER_NAMES, 'x').map(Argument::Integer),
+        'f' => register(text, &FLOAT_NAMES, 'f').map(Argument::Float),
+        'i' => number(text).map(Argument::Immediate),
+        'm' => {
+            let (offset, base) = text.strip_suffix(')')?.split_once('(')?;
+            let offset = if offset.trim().is_empty() { 0 } else { number(offset)? };
+            let base = register(base.trim(), &INTEGER_NAMES, 'x')?;
+            Some(Argument::Memory { offset: i32::try_from(offset).ok()?, base })
+        },
+        _ => {
+            let valid = text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+                && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$');
+            valid.then(|| Argument::Label(text.to_string()))
+        },
+    }
+}
+
+struct Program {
+    data: Vec<u8>,
+    instructions: Vec<Instruction>,
+    labels: HashMap<String, u32>,       // Address of each code and data label
+    entry: usize,
+}
+
+impl Program {
+    fn parse(source: &str) -> Result<Program, SimulationError> {
+        let mut data = Vec::new();
+        let mut instructions = Vec::new();
+        let mut labels = HashMap::new();
+        let mut in_data = false;
+        for (index, raw) in source.lines().enumerate() {
+            let error = |message: String| SimulationError::Syntax(index + 1, message);
+            let mut line = strip_comment(raw).trim();
+
+            // Labels, possibly followed by a directive or an instruction
+            while let Some((label, rest)) = line.split_once(':') {
+                let label = label.trim();
+                if label.is_empty() || label.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+                    break;
+                }
+                let address = if in_data {
+                    DATA_BASE + data.len() as u32
+                } else {
+                    TEXT_BASE + 4 * instructions.len() as u32
+                };
+                if labels.insert(label.to_string(), address).is_some() {
+                    return Err(error(format!("label '{}' defined twice", label)));
+                }
+                line = rest.trim();
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let word = word.to_ascii_lowercase();
+            if word.starts_with('.') {
+                match word.as_str() {
+                    ".data" => in_data = true,
+                    ".text" => in_data = false,
+                    ".globl" | ".global" | ".extern" => {},
+                    _ if !in_data => return Err(error(format!("directive {} outside of .data", word))),
+                    ".align" => {
+                        let power = number(rest).filter(|p| (0..=12).contains(p)).ok_or_else(|| error("invalid alignment".to_string()))?;
+                        data.resize(data.len().next_multiple_of(1 << power), 0);
+                    },
+                    ".space" => {
+                        let size = number(rest).filter(|s| *s >= 0).ok_or_else(|| error("invalid size".to_string()))?;
+                        data.resize(data.len() + size as usize, 0);
+                    },
+                    ".ascii" | ".asciz" | ".string" => {
+                        let literal = rest.trim().strip_prefix('"').and_then(|t| t.strip_suffix('"')).ok_or_else(|| error("expected a string".to_string()))?;
+                        data.extend(unescape(literal));
+                        if word != ".ascii" {
+                            data.push(0);
+                        }
+                    },
+                    ".byte" | ".half" | ".word" => {
+                        let size = match word.as_str() {
+                            ".byte" => 1,
+                            ".half" => 2,
+                            _ => 4,
+                        };
+                        // Like RARS, halves and words are aligned on their size
+                        data.resize(data.len().next_multiple_of(size), 0);
+                        for item in split_arguments(rest) {
+                            let value = number(&item).ok_or_else(|| error(format!("invalid value '{}'", item)))?;
+                            data.extend(&(value as u32).to_le_bytes()[..size]);
+                        }
+                    },
+                    _ => return Err(error(format!("unsupported directive {}", word))),
+                }
+                if data.len() > DATA_SIZE as usize {
+                    return Err(error("data segment full".to_string()));
+                }
+                continue;
+            }
+            if in_data {
+                return Err(error(format!("instruction '{}' in .data", word)));
+            }
+
+            let texts = split_arguments(rest);
+            let forms = INSTRUCTIONS.iter().filter(|(name, _)| *name == word).map(|(_, form)| *form);
+            let mut known = false;
+            let mut arguments = None;
+            for form in forms {
+                known = true;
+                if form.len() == texts.len() {
+                    arguments = texts.iter().zip(form.chars()).map(|(text, kind)| parse_argument(text, kind)).collect::<Option<Vec<_>>>();
+                    if arguments.is_some() {
+                        break;
+                    }
+                }
+            }
+            if !known {
+                return Err(error(format!("unsupported instruction '{}'", word)));
+            }
+            let arguments = arguments.ok_or_else(|| error(format!("invalid operands for '{}'", word)))?;
+            instructions.push(Instruction { line: index + 1, mnemonic: word, arguments });
+        }
+
+        for instruction in &instructions {
+            for argument in &instruction.arguments {
+                if let Argument::Label(label) = argument {
+                    if !labels.contains_key(label) {
+                        return Err(SimulationError::UnknownLabel(instruction.line, label.clone()));
+                    }
+                }
+            }
+        }
+        // RARS starts at main when it is global, at the first instruction otherwise
+        let entry = match labels.get("main") {
+            Some(address) if *address >= TEXT_BASE && *address < DATA_BASE => ((address - TEXT_BASE) / 4) as usize,
+            _ => 0,
+        };
+        Ok(Program { data, instructions, labels, entry })
+    }
+}
+
+struct Machine<'a> {
+    program: &'a Program,
+    data: Vec<u8>,
+    stack: Vec<u8>,
+    registers: [u32; 32],
+    floats: [f32; 32],
+    input: &'a [u8],
+    input_position: usize,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+const RA: usize = 1;
+const SP: usize = 2;
+const GP: usize = 3;
+const A0: usize = 10;
+const A1: usize = 11;
+const A2: usize = 12;
+const A7: usize = 17;
+
+impl<'a> Machine<'a> {
+    fn new(program: &'a Program, input: &'a [u8]) -> Self {
+        let mut data = program.data.clone();
+        data.resize(DATA_SIZE as usize, 0);
+        let mut registers = [0u32; 32];
+        registers[SP] = STACK_TOP - 4;
+        registers[GP] = 0x1000_8000;
+        Machine {
+            program,
+            data,
+            stack: vec![0; STACK_SIZE as usize],
+            registers,
+            floats: [0.0; 32],
+            input,
+            input_position: 0,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        }
+    }
+
+    fn fault(&self, instruction: &Instruction, message: &str) -> SimulationError {
+        SimulationError::Fault(instruction.line, message.to_string())
+    }
+
+    // Bytes of memory at an address, which must be aligned on the size
+    fn memory(&mut self, instruction: &Instruction, address: u32, size: u32) -> Result<&mut [u8], SimulationError> {
+        if !address.is_multiple_of(size) {
+            return Err(self.fault(instruction, &format!("misaligned access at 0x{:08x}", address)));
+        }
+        if (DATA_BASE..DATA_BASE + DATA_SIZE).contains(&address) {
+            let offset = (address - DATA_BASE) as usize;
+            return Ok(&mut self.data[offset..offset + size as usize]);
+        }
+        if (STACK_TOP - STACK_SIZE..STACK_TOP).contains(&address) {
+            let offset = (address - (STACK_TOP - STACK_SIZE)) as usize;
+            return Ok(&mut self.stack[offset..offset + size as usize]);
+        }
+        Err(self.fault(instruction, &format!("access to unmapped address 0x{:08x}", address)))
+    }
+
+    fn load(&mut self, instruction: &Instruction, address: u32, size: u32) -> Result<u32, SimulationError> {
+        let bytes = self.memory(instruction, address, size)?;
+        Ok(bytes.iter().rev().fold(0, |value, byte| value << 8 | *byte as u32))
+    }
+
+    fn store(&mut self, instruction: &Instruction, address: u32, size: u32, value: u32) -> Result<(), SimulationError> {
+        let bytes = self.memory(instruction, address, size)?;
+        bytes.copy_from_slice(&value.to_le_bytes()[..size as usize]);
+        Ok(())
+    }
+
+    fn run(&mut self, max_steps: u64) -> Result<Simulation, SimulationError> {
+        let program = self.program;
+        let mut pc = program.entry;
+        let mut steps = 0;
+        loop {
+            let instruction = program.instructions.get(pc).ok_or(SimulationError::Fault(0, "execution ran past the end of the code".to_string()))?;
+            steps += 1;
+            if steps > max_steps {
+                return Err(SimulationError::StepLimit(max_steps));
+            }
+            pc += 1;
+            if let Some(code) = self.execute(instruction, &mut pc)? {
+                return Ok(Simulation {
+                    stdout_bytes: std::mem::take(&mut self.stdout),
+                    stderr_bytes: std::mem::take(&mut self.stderr),
+                    exit_code: code,
+                    steps,
+                });
+            }
+        }
+    }
+
+    // Instruction index of a code address
+    fn code_index(&self, instruction: &Instruction, address: u32) -> Result<usize, SimulationError> {
+        let index = address.wrapping_sub(TEXT_BASE) / 4;
+        if !address.is_multiple_of(4) || address < TEXT_BASE || index as usize > self.program.instructions.len() {
+            return Err(self.fault(instruction, &format!("jump to 0x{:08x} outside of the code", address)));
+        }
+        Ok(index as usize)
+    }
+
+    // Executes one instruction, returns the exit code when the program ends
+    fn execute(&mut self, instruction: &Instruction, pc: &mut usize) -> Result<Option<i32>, SimulationError> {
+        let arguments = &instruction.arguments;
+        let (registers, floats, program) = (self.registers, self.floats, self.program);
+        let x = |i: usize| match arguments.get(i) {
+            Some(Argument::Integer(r)) => registers[*r],
+            Some(Argument::Immediate(value)) => *value as u32,
+            _ => 0,
+        };
+        let f = |i: usize| match arguments.get(i) {
+            Some(Argument::Float(r)) => floats[*r],
+            _ => 0.0,
+        };
+        let destination = match arguments.first() {
+            Some(Argument::Integer(r) | Argument::Float(r)) => *r,
+            _ => 0,
+        };
+        let label = |i: usize| match arguments.get(i) {
+            Some(Argument::Label(label)) => program.labels[label],
+            _ => 0,
+        };
+        let address = |i: usize| match arguments.get(i) {
+            Some(Argument::Memory { offset, base }) => registers[*base].wrapping_add(*offset as u32),
+            _ => 0,
+        };
+        let return_address = TEXT_BASE + 4 * *pc as u32;
+        let mnemonic = instruction.mnemonic.as_str();
+
+        // Integer results
+        let value = match mnemonic {
+            "add" | "addi" => Some(x(1).wrapping_add(x(2))),
+            "sub" => Some(x(1).wrapping_sub(x(2))),
+            "sll" | "slli" => Some(x(1) << (x(2) & 31)),
+            "srl" | "srli" => Some(x(1) >> (x(2) & 31)),
+            "sra" | "srai" => Some(((x(1) as i32) >> (x(2) & 31)) as u32),
+            "slt" | "slti" => Some(((x(1) as i32) < (x(2) as i32)) as u32),
+            "sltu" | "sltiu" => Some((x(1) < x(2)) as u32),
+            "xor" | "xori" => Some(x(1) ^ x(2)),
+            "or" | "ori" => Some(x(1) | x(2)),
+            "and" | "andi" => Some(x(1) & x(2)),
+            "lui" => Some(x(1) << 12),
+            "mul" => Some(x(1).wrapping_mul(x(2))),
+            "mulh" => Some(((x(1) as i32 as i64 * x(2) as i32 as i64) >> 32) as u32),
+            "mulhsu" => Some(((x(1) as i32 as i64 * x(2) as i64) >> 32) as u32),
+            "mulhu" => Some(((x(1) as u64 * x(2) as u64) >> 32) as u32),
+            // Division by zero and overflow do not trap, the results are the ones of the specification
+            "div" => Some(match (x(1) as i32, x(2) as i32) {
+                (_, 0) => u32::MAX,
+                (a, b) => a.wrapping_div(b) as u32,
+            }),
+            "divu" => Some(x(1).checked_div(x(2)).unwrap_or(u32::MAX)),
+            "rem" => Some(match (x(1) as i32, x(2) as i32) {
+                (a, 0) => a as u32,
+                (a, b) => a.wrapping_rem(b) as u32,
+            }),
+            "remu" => Some(x(1).checked_rem(x(2)).unwrap_or(x(1))),
+            "li" | "mv" => Some(x(1)),
+            "la" => Some(label(1)),
+            "not" => Some(!x(1)),
+            "neg" => Some(x(1).wrapping_neg()),
+            "seqz" => Some((x(1) == 0) as u32),
+            "snez" => Some((x(1) != 0) as u32),
+            "sltz" => Some(((x(1) as i32) < 0) as u32),
+            "sgtz" => Some(((x(1) as i32) > 0) as u32),
+            "feq.s" => Some((f(1) == f(2)) as u32),
+            "flt.s" => Some((f(1) < f(2)) as u32),
+            "fle.s" => Some((f(1) <= f(2)) as u32),
+            "fmv.x.w" => Some(f(1).to_bits()),
+            // Rounds to nearest even like the default rounding mode, NaN gives the largest value
+            "fcvt.w.s" => Some(if f(1).is_nan() { i32::MAX } else { f(1).round_ties_even() as i32 } as u32),
+            "lb" | "lh" | "lw" | "lbu" | "lhu" => {
+                let size = match mnemonic {
+                    "lb" | "lbu" => 1,
+                    "lh" | "lhu" => 2,
+                    _ => 4,
+                };
+                let loaded = self.load(instruction, address(1), size)?;
+                Some(match mnemonic {
+                    "lb" => loaded as u8 as i8 as u32,
+                    "lh" => loaded as u16 as i16 as u32,
+                    _ => loaded,
+                })
+            },
+            _ => None,
+        };
+        if let Some(value) = value {
+            if destination != 0 {
+                self.registers[destination] = value;
+            }
+            return Ok(None);
+        }
+
+        // Float results
+        let value = match mnemonic {
+            "fadd.s" => Some(f(1) + f(2)),
+            "fsub.s" => Some(f(1) - f(2)),
+            "fmul.s" => Some(f(1) * f(2)),
+            "fdiv.s" => Some(f(1) / f(2)),
+            "fsgnj.s" | "fmv.s" => Some(f(1).copysign(f(arguments.len() - 1))),
+            "fsgnjn.s" | "fneg.s" => Some(f(1).copysign(-f(arguments.len() - 1))),
+            "fsgnjx.s" => Some(f32::from_bits(f(1).to_bits() ^ (f(2).to_bits() & 0x8000_0000))),
+            "fabs.s" => Some(f(1).abs()),
+            "fcvt.s.w" => Some(x(1) as i32 as f32),
+            "fmv.w.x" => Some(f32::from_bits(x(1))),
+            "flw" => Some(f32::from_bits(self.load(instruction, address(1), 4)?)),
+            _ => None,
+        };
+        if let Some(value) = value {
+            self.floats[destination] = value;
+            return Ok(None);
+        }
+
+        let branch = match mnemonic {
+            "beq" => Some(x(0) == x(1)),
+            "bne" => Some(x(0) != x(1)),
+            "blt" => Some((x(0) as i32) < x(1) as i32),
+            "bge" => Some(x(0) as i32 >= x(1) as i32),
+            "bltu" => Some(x(0) < x(1)),
+            "bgeu" => Some(x(0) >= x(1)),
+            "bgt" => Some(x(0) as i32 > x(1) as i32),
+            "ble" => Some(x(0) as i32 <= x(1) as i32),
+            "bgtu" => Some(x(0) > x(1)),
+            "bleu" => Some(x(0) <= x(1)),
+            "beqz" => Some(x(0) == 0),
+            "bnez" => Some(x(0) != 0),
+            "bltz" => Some((x(0) as i32) < 0),
+            "bgez" => Some(x(0) as i32 >= 0),
+            "bgtz" => Some(x(0) as i32 > 0),
+            "blez" => Some(x(0) as i32 <= 0),
+            _ => None,
+        };
+        if let Some(taken) = branch {
+            if taken {
+                *pc = self.code_index(instruction, label(arguments.len() - 1))?;
+            }
+            return Ok(None);
+        }
+
+        match mnemonic {
+            "sb" | "sh" | "sw" => {
+                let size = match mnemonic {
+                    "sb" => 1,
+                    "sh" => 2,
+                    _ => 4,
+                };
+                self.store(instruction, address(1), size, x(0))?;
+            },
+            "fsw" => self.store(instruction, address(1), 4, f(0).to_bits())?,
+            "j" | "tail" => *pc = self.code_index(instruction, label(0))?,
+            "call" => {
+                self.registers[RA] = return_address;
+                *pc = self.code_index(instruction, label(0))?;
+            },
+            "jal" => {
+                let link = if arguments.len() == 2 { destination } else { RA };
+                *pc = self.code_index(instruction, label(arguments.len() - 1))?;
+                if link != 0 {
+                    self.registers[link] = return_address;
+                }
+            },
+            "jalr" | "jr" | "ret" => {
+                let (link, target) = match (mnemonic, arguments.len()) {
+                    ("ret", _) => (0, self.registers[RA]),
+                    ("jr", _) => (0, x(0)),
+                    (_, 1) => (RA, x(0)),
+                    (_, 2) => (destination, address(1)),
+                    _ => (destination, x(1).wrapping_add(x(2))),
+                };
+                *pc = self.code_index(instruction, target)?;
+                if link != 0 {
+                    self.registers[link] = return_address;
+                }
+            },
+            "nop" => {},
+            "ecall" => return self.system_call(instruction),
+            _ => return Err(self.fault(instruction, &format!("unsupported instruction '{}'", mnemonic))),
+        }
+        Ok(None)
+    }
+
+    // RARS system calls selected by a7
+    fn system_call(&mut self, instruction: &Instruction) -> Result<Option<i32>, SimulationError> {
+        let (a0, a1, a2) = (self.registers[A0], self.registers[A1], self.registers[A2]);
+        match self.registers[A7] {
+            10 => return Ok(Some(0)),
+            93 => return Ok(Some(a0 as i32)),
+            63 => {
+                if a0 != 0 {
+                    return Err(self.fault(instruction, "only the descriptor 0 can be read"));
+                }
+                let count = (a2 as usize).min(self.input.len() - self.input_position);
+                for i in 0..count {
+                    let byte = self.input[self.input_position + i];
+                    self.store(instruction, a1.wrapping_add(i as u32), 1, byte as u32)?;
+                }
+                self.input_position += count;
+                self.registers[A0] = count as u32;
+            },
+            64 => {
+                let mut bytes = Vec::with_capacity(a2 as usize);
+                for i in 0..a2 {
+                    bytes.push(self.load(instruction, a1.wrapping_add(i), 1)? as u8);
+                }
+                match a0 {
+                    1 => self.stdout.extend(bytes),
+                    2 => self.stderr.extend(bytes),
+                    _ => return Err(self.fault(instruction, "only the descriptors 1 and 2 can be written")),
+                }
+                self.registers[A0] = a2;
+            },
+            service => return Err(self.fault(instruction, &format!("unsupported system call {}", service))),
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instructions() {
+        let source = r#"
+    .data
+value: .half -5
+bytes: .ascii "ab\n"
+    .align 2
+table: .word 7, 7, 7
+ratio: .word 0x3fc00000    # 1.5
+    .text
+    .globl main
+main:
+    la t0, value
+    lh t1, 0(t0)
+    li t2, 3
+    mul t1, t1, t2          # -15
+    la t0, table
+    sw t1, 8(t0)
+    la t0, bytes
+    lbu a0, 1(t0)
+    addi a0, a0, 1          # 'c'
+    call put
+    la t0, ratio
+    flw ft0, 0(t0)
+    fadd.s ft0, ft0, ft0    # 3.0
+    fcvt.w.s t3, ft0
+    li t4, 3
+    bne t3, t4, wrong
+    li t0, 0x7fffffff
+    addi t0, t0, 1
+    bgez t0, wrong
+    la t0, table
+    lw a0, 8(t0)
+    li t1, -15
+    bne a0, t1, wrong
+    li a0, 3
+    li a7, 93
+    ecall
+wrong:
+    li a0, 9
+    li a7, 93
+    ecall
+
+put:                        # writes the character in a0 from the stack
+    addi sp, sp, -16
+    sb a0, 0(sp)
+    mv a1, sp
+    li a0, 1
+    li a2, 1
+    li a7, 64
+    ecall
+    addi sp, sp, 16
+    ret
+"#;
+        let execution = run_rv32(source, b"", 1000).unwrap();
+        assert_eq!(execution.stdout(), "c");
+        assert_eq!(execution.exit_code, 3);
+    }
+
+    #[test]
+    fn test_faults() {
+        let source = "    .text\nmain:\n    li t0, 2\n    lw t1, 0(t0)\n";
+        assert_eq!(run_rv32(source, b"", 100), Err(SimulationError::Fault(4, "misaligned access at 0x00000002".to_string())));
+        let source = "    .text\nmain:\n    j main\n";
+        assert_eq!(run_rv32(source, b"", 100), Err(SimulationError::StepLimit(100)));
+        let source = "    .text\nmain:\n    j finish\n";
+        assert_eq!(run_rv32(source, b"", 100), Err(SimulationError::UnknownLabel(3, "finish".to_string())));
+        let source = "    .text\nmain:\n    fadd.s ft0, t0, ft1\n";
+        assert_eq!(run_rv32(source, b"", 100), Err(SimulationError::Syntax(3, "invalid operands for 'fadd.s'".to_string())));
+    }
+}
@@ -0,0 +1,987 @@
+//! Executor for the WebAssembly text written by `codegen::wat`
+//!
+//! It reads the subset of the text format used by the backend: a module of imports, one memory,
+//! data strings, globals and functions whose bodies are plain (not folded) instructions. Loading
+//! the module validates its structure: every name, local and label must be defined, blocks must
+//! be closed and immutable globals are never set. The values are checked when the instructions
+//! use them. The imports of the module `mining` are implemented with the run-time conventions of
+//! the other backends and `main` is run like a process with a standard input and output
+
+use std::collections::HashMap;
+use crate::codegen::error::{RuntimeError, SimulationError};
+use crate::codegen::runtime::{write_value, Input};
+use crate::codegen::Simulation;
+use crate::Semantic::quadruplets::Value as MiningValue;
+
+/// Validates a module and runs its `main` export with the given standard input,
+/// at most `max_steps` instructions
+pub fn run_wat(source: &str, input: &[u8], max_steps: u64) -> Result<Simulation, SimulationError> {
+    let module = Module::parse(source)?;
+    let mut machine = Machine {
+        module: &module,
+        memory: vec![0; module.pages * PAGE_SIZE],
+        globals: module.globals.iter().map(|global| global.value).collect(),
+        input: Input::new(input),
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+        steps: 0,
+        max_steps,
+    };
+    for (offset, bytes) in &module.data {
+        let end = offset + bytes.len();
+        if end > machine.memory.len() {
+            return Err(SimulationError::Fault(0, "data outside of the memory".to_string()));
+        }
+        machine.memory[*offset..end].copy_from_slice(bytes);
+    }
+    let main = *module.exports.get("main").ok_or_else(|| SimulationError::Syntax(0, "no main export".to_string()))?;
+    let exit_code = match machine.call(main, Vec::new()) {
+        Ok(_) => 0,
+        Err(Stop::Exit(code)) => code,
+        Err(Stop::Error(error)) => return Err(error),
+    };
+    Ok(Simulation { stdout_bytes: machine.stdout, stderr_bytes: machine.stderr, exit_code: exit_code.into(), steps: machine.steps })
+}
+
+const PAGE_SIZE: usize = 65536;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ValueType {
+    I32,
+    F32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    I32(i32),
+    F32(f32),
+}
+
+impl Value {
+    fn zero(value_type: ValueType) -> Value {
+        match value_type {
+            ValueType::I32 => Value::I32(0),
+            ValueType::F32 => Value::F32(0.0),
+        }
+    }
+}
+
+// Operators of two values of the same type
+type I32Binary = fn(i32, i32) -> Option<i32>;
+type F32Binary = fn(f32, f32) -> Value;
+
+// Division and remainder trap on zero like in WebAssembly
+const I32_BINARY: [(&str, I32Binary); 19] = [
+    ("i32.add", |a, b| Some(a.wrapping_add(b))),
+    ("i32.sub", |a, b| Some(a.wrapping_sub(b))),
+    ("i32.mul", |a, b| Some(a.wrapping_mul(b))),
+    ("i32.div_s", |a, b| a.checked_div(b)),
+    ("i32.rem_s", |a, b| if b == 0 { None } else { Some(a.wrapping_rem(b)) }),
+    ("i32.rem_u", |a, b| (a as u32).checked_rem(b as u32).map(|r| r as i32)),
+    ("i32.and", |a, b| Some(a & b)),
+    ("i32.or", |a, b| Some(a | b)),
+    ("i32.eq", |a, b| Some((a == b) as i32)),
+    ("i32.ne", |a, b| Some((a != b) as i32)),
+    ("i32.lt_s", |a, b| Some((a < b) as i32)),
+    ("i32.le_s", |a, b| Some((a <= b) as i32)),
+    ("i32.gt_s", |a, b| Some((a > b) as i32)),
+    ("i32.ge_s", |a, b| Some((a >= b) as i32)),
+    ("i32.lt_u", |a, b| Some(((a as u32) < b as u32) as i32)),
+    ("i32.le_u", |a, b| Some(((a as u32) <= b as u32) as i32)),
+    ("i32.gt_u", |a, b| Some((a as u32 > b as u32) as i32)),
+    ("i32.ge_u", |a, b| Some((a as u32 >= b as u32) as i32)),
+    ("i32.xor", |a, b| Some(a ^ b)),
+];
+
+const F32_BINARY: [(&str, F32Binary); 10] = [
+    ("f32.add", |a, b| Value::F32(a + b)),
+    ("f32.sub", |a, b| Value::F32(a - b)),
+    ("f32.mul", |a, b| Value::F32(a * b)),
+    ("f32.div", |a, b| Value::F32(a / b)),
+    ("f32.eq", |a, b| Value::I32((a == b) as i32)),
+    ("f32.ne", |a, b| Value::I32((a != b) as i32)),
+    ("f32.lt", |a, b| Value::I32((a < b) as i32)),
+    ("f32.le", |a, b| Value::I32((a <= b) as i32)),
+    ("f32.gt", |a, b| Value::I32((a > b) as i32)),
+    ("f32.ge", |a, b| Value::I32((a >= b) as i32)),
+];
+
+// Memory accesses: bytes, whether the value is sign extended and its type
+const LOADS: [(&str, usize, bool, ValueType); 4] = [
+    ("i32.load", 4, true, ValueType::I32),
+    ("i32.load16_s", 2, true, ValueType::I32),
+    ("i32.load8_u", 1, false, ValueType::I32),
+    ("f32.load", 4, false, ValueType::F32),
+];
+
+const STORES: [(&str, usize, ValueType); 4] = [
+    ("i32.store", 4, ValueType::I32),
+    ("i32.store16", 2, ValueType::I32),
+    ("i32.store8", 1, ValueType::I32),
+    ("f32.store", 4, ValueType::F32),
+];
+
+// Functions of the host module, with their parameters and result
+const HOST_FUNCTIONS: [(&str, &[ValueType], Option<ValueType>); 8] = [
+    ("read_int", &[], Some(ValueType::I32)),
+    ("read_float", &[], Some(ValueType::F32)),
+    ("read_char", &[], Some(ValueType::I32)),
+    ("write_int", &[ValueType::I32], None),
+    ("write_float", &[ValueType::F32], None),
+    ("write_char", &[ValueType::I32], None),
+    ("write_string", &[ValueType::I32, ValueType::I32], None),
+    ("fail", &[ValueType::I32, ValueType::I32], None),
+];
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    I32Const(i32),
+    F32Const(f32),
+    LocalGet(usize),
+    LocalSet(usize),
+    LocalTee(usize),
+    GlobalGet(usize),
+    GlobalSet(usize),
+    Load(usize),
+    Store(usize),
+    I32Binary(usize),
+    F32Binary(usize),
+    I32Eqz,
+    Select,
+    Drop,
+    Call(usize),
+    Block { end: usize, arity: usize },
+    Loop,
+    If { otherwise: Option<usize>, end: usize, arity: usize },
+    Else { end: usize },
+    End,
+    Br(usize),
+    BrIf(usize),
+    Return,
+    Unreachable,
+}
+
+// S-expressions of the text format with their line
+#[derive(Debug, Clone)]
+enum Sexp {
+    List(Vec<Sexp>, usize),
+    Atom(String, usize),
+    Str(Vec<u8>, usize),
+}
+
+impl Sexp {
+    fn line(&self) -> usize {
+        match self {
+            Sexp::List(_, line) | Sexp::Atom(_, line) | Sexp::Str(_, line) => *line,
+        }
+    }
+
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexp::Atom(text, _) => Some(text),
+            _ => None,
+        }
+    }
+
+    // Items of a list starting with the given keyword
+    fn form(&self, keyword: &str) -> Option<&[Sexp]> {
+        match self {
+            Sexp::List(items, _) if items.first().and_then(Sexp::atom) == Some(keyword) => Some(&items[1..]),
+            _ => None,
+        }
+    }
+}
+
+fn syntax(line: usize, message: impl Into<String>) -> SimulationError {
+    SimulationError::Syntax(line, message.into())
+}
+
+// Bytes of a string literal, with the escapes \n, \t, \", \', \\ and \XX
+fn unescape(text: &str, line: usize) -> Result<Vec<u8>, SimulationError> {
+    let mut bytes = Vec::new();
+    let mut chars = text.bytes();
+    while let Some(byte) = chars.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        match chars.next() {
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(quote @ (b'"' | b'\'' | b'\\')) => bytes.push(quote),
+            Some(high) => {
+                let low = chars.next().ok_or_else(|| syntax(line, "unfinished escape"))?;
+                let hex = String::from_utf8(vec![high, low]).unwrap_or_default();
+                bytes.push(u8::from_str_radix(&hex, 16).map_err(|_| syntax(line, format!("invalid escape \\{}", hex)))?);
+            },
+            None => return Err(syntax(line, "unfinished escape")),
+        }
+    }
+    Ok(bytes)
+}
+
+fn parse_sexps(source: &str) -> Result<Vec<Sexp>, SimulationError> {
+    let mut stack: Vec<(Vec<Sexp>, usize)> = vec![(Vec::new(), 1)];
+    let mut line = 1;
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {},
+            ';' if chars.peek().map(|(_, c)| *c) == Some(';') => {
+                while chars.peek().is_some_and(|(_, c)| *c != '\n') {
+                    chars.next();
+                }
+            },
+            '(' => stack.push((Vec::new(), line)),
+            ')' => {
+                let (items, opened) = stack.pop().filter(|_| !stack.is_empty()).ok_or_else(|| syntax(line, "unbalanced ')'"))?;
+                stack.last_mut().unwrap().0.push(Sexp::List(items, opened));
+            },
+            '"' => {
+                let mut end = start + 1;
+                let mut escaped = false;
+                for (index, c) in chars.by_ref() {
+                    match c {
+                        '"' if !escaped => {
+                            end = index;
+                            break;
+                        },
+                        '\\' => escaped = !escaped,
+                        '\n' => return Err(syntax(line, "unterminated string")),
+                        _ => escaped = false,
+                    }
+                }
+                let bytes = unescape(&source[start + 1..end], line)?;
+                stack.last_mut().unwrap().0.push(Sexp::Str(bytes, line));
+            },
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((index, c)) = chars.peek().copied() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    end = index + c.len_utf8();
+                    chars.next();
+                }
+                stack.last_mut().unwrap().0.push(Sexp::Atom(source[start..end].to_string(), line));
+            },
+        }
+    }
+    match stack.pop() {
+        Some((items, _)) if stack.is_empty() => Ok(items),
+        _ => Err(syntax(line, "unbalanced '('")),
+    }
+}
+
+fn value_type(sexp: &Sexp) -> Result<ValueType, SimulationError> {
+    match sexp.atom() {
+        Some("i32") => Ok(ValueType::I32),
+        Some("f32") => Ok(ValueType::F32),
+        _ => Err(syntax(sexp.line(), "expected the type i32 or f32")),
+    }
+}
+
+// Constant expression initializing a global or giving the offset of a data string
+fn constant(sexp: &Sexp) -> Result<Value, SimulationError> {
+    let invalid = || syntax(sexp.line(), "expected (i32.const n) or (f32.const x)");
+    let (value_type, items) = match (sexp.form("i32.const"), sexp.form("f32.const")) {
+        (Some(items), _) => (ValueType::I32, items),
+        (_, Some(items)) => (ValueType::F32, items),
+        _ => return Err(invalid()),
+    };
+    let text = items.first().and_then(Sexp::atom).ok_or_else(invalid)?;
+    number(value_type, text).ok_or_else(invalid)
+}
+
+fn number(value_type: ValueType, text: &str) -> Option<Value> {
+    match value_type {
+        ValueType::I32 => text.parse::<i64>().ok().filter(|n| (i32::MIN as i64..=u32::MAX as i64).contains(n)).map(|n| Value::I32(n as i32)),
+        ValueType::F32 => match text.trim_start_matches(['-', '+']) {
+            "nan" => Some(Value::F32(f32::NAN)),
+            _ => text.parse::<f32>().ok().map(Value::F32),
+        },
+    }
+}
+
+struct Global {
+    name: String,
+    value: Value,
+    mutable: bool,
+}
+
+struct Function {
+    name: String,
+    params: Vec<ValueType>,
+    result: Option<ValueType>,
+    locals: Vec<ValueType>,        // Parameters first
+    code: Vec<Op>,
+    lines: Vec<usize>,
+    host: Option<usize>,           // Index in HOST_FUNCTIONS of an import
+}
+
+struct Module {
+    functions: Vec<Function>,
+    globals: Vec<Global>,
+    pages: usize,
+    data: Vec<(usize, Vec<u8>)>,
+    exports: HashMap<String, usize>,
+}
+
+// Signature and local names of a function header: (param ...), (result ...), (local ...)
+struct Header {
+    params: Vec<ValueType>,
+    result: Option<ValueType>,
+    locals: Vec<ValueType>,
+    names: HashMap<String, usize>,
+    export: Option<String>,
+    body: usize,                   // Index of the first instruction among the items
+}
+
+fn header(items: &[Sexp], line: usize) -> Result<Header, SimulationError> {
+    let mut header = Header { params: Vec::new(), result: None, locals: Vec::new(), names: HashMap::new(), export: None, body: items.len() };
+    for (index, item) in items.iter().enumerate() {
+        if let Some(export) = item.form("export") {
+            match export.first() {
+                Some(Sexp::Str(name, _)) => header.export = Some(String::from_utf8_lossy(name).to_string()),
+                _ => return Err(syntax(item.line(), "expected an export name")),
+            }
+        } else if let Some(declared) = item.form("param").or_else(|| item.form("local")) {
+            let is_param = item.form("param").is_some();
+            if is_param && !header.locals.is_empty() {
+                return Err(syntax(item.line(), "parameters come before the locals"));
+            }
+            let types = match declared.first().and_then(Sexp::atom) {
+                Some(name) if name.starts_with('$') => {
+                    header.names.insert(name.to_string(), header.params.len() + header.locals.len());
+                    &declared[1..]
+                },
+                _ => declared,
+            };
+            for declared_type in types {
+                let declared_type = value_type(declared_type)?;
+                if is_param { header.params.push(declared_type) } else { header.locals.push(declared_type) }
+            }
+        } else if let Some(result) = item.form("result") {
+            header.result = match result {
+                [] => None,
+                [result_type] => Some(value_type(result_type)?),
+                _ => return Err(syntax(line, "functions return at most one value")),
+            };
+        } else {
+            header.body = index;
+            break;
+        }
+    }
+    Ok(header)
+}
+
+// Context of the instructions of a function body being read
+struct BodyReader<'a> {
+    functions: &'a HashMap<String, usize>,
+    globals: &'a [Global],
+    global_names: &'a HashMap<String, usize>,
+    locals: &'a HashMap<String, usize>,
+    local_count: usize,
+    code: Vec<Op>,
+    lines: Vec<usize>,
+    controls: Vec<(usize, Option<String>)>, // Opening instruction and label of the enclosing blocks
+}
+
+impl BodyReader<'_> {
+    // Index of a name or a number among `count` entries
+    fn index(names: &HashMap<String, usize>, count: usize, text: Option<&str>, line: usize, kind: &str) -> Result<usize, SimulationError> {
+        let text = text.ok_or_else(|| syntax(line, format!("missing {}", kind)))?;
+        let index = match text.parse::<usize>() {
+            Ok(index) => Some(index),
+            Err(_) => names.get(text).copied(),
+        };
+        index.filter(|index| *index < count).ok_or_else(|| SimulationError::UnknownLabel(line, text.to_string()))
+    }
+
+    // Relative depth of a label name or number
+    fn depth(&self, text: Option<&str>, line: usize) -> Result<usize, SimulationError> {
+        let text = text.ok_or_else(|| syntax(line, "missing label"))?;
+        if let Ok(depth) = text.parse::<usize>() {
+            return if depth < self.controls.len() { Ok(depth) } else { Err(SimulationError::UnknownLabel(line, text.to_string())) };
+        }
+        self.controls.iter().rev().position(|(_, label)| label.as_deref() == Some(text))
+            .ok_or_else(|| SimulationError::UnknownLabel(line, text.to_string()))
+    }
+
+    fn push(&mut self, op: Op, line: usize) {
+        self.code.push(op);
+        self.lines.push(line);
+    }
+
+    fn read(&mut self, items: &[Sexp]) -> Result<(), SimulationError> {
+        let mut position = 0;
+        while position < items.len() {
+            let item = &items[position];
+            let line = item.line();
+            let mnemonic = item.atom().ok_or_else(|| syntax(line, "folded instructions are not supported"))?;
+            position += 1;
+            // Immediate operand on the same line
+            let mut operand = || -> Option<&str> {
+                let next = items.get(position).filter(|next| next.line() == line)?.atom()?;
+                position += 1;
+                Some(next)
+            };
+            match mnemonic {
+                "i32.const" | "f32.const" => {
+                    let value_type = if mnemonic == "i32.const" { ValueType::I32 } else { ValueType::F32 };
+                    let text = operand().ok_or_else(|| syntax(line, "missing constant"))?;
+                    match number(value_type, text) {
+                        Some(Value::I32(n)) => self.push(Op::I32Const(n), line),
+                        Some(Value::F32(x)) => self.push(Op::F32Const(x), line),
+                        None => return Err(syntax(line, format!("invalid constant {}", text))),
+                    }
+                },
+                "local.get" | "local.set" | "local.tee" => {
+                    let index = Self::index(self.locals, self.local_count, operand(), line, "local")?;
+                    let op = match mnemonic {
+                        "local.get" => Op::LocalGet(index),
+                        "local.set" => Op::LocalSet(index),
+                        _ => Op::LocalTee(index),
+                    };
+                    self.push(op, line);
+                },
+                "global.get" | "global.set" => {
+                    let index = Self::index(self.global_names, self.globals.len(), operand(), line, "global")?;
+                    if mnemonic == "global.set" {
+                        if !self.globals[index].mutable {
+                            return Err(syntax(line, format!("global {} is immutable", self.globals[index].name)));
+                        }
+                        self.push(Op::GlobalSet(index), line);
+                    } else {
+                        self.push(Op::GlobalGet(index), line);
+                    }
+                },
+                "call" => {
+                    let index = Self::index(self.functions, self.functions.len(), operand(), line, "function")?;
+                    self.push(Op::Call(index), line);
+                },
+                "block" | "loop" | "if" => {
+                    let label = match items.get(position).and_then(Sexp::atom) {
+                        Some(label) if label.starts_with('$') && items[position].line() == line => {
+                            position += 1;
+                            Some(label.to_string())
+                        },
+                        _ => None,
+                    };
+                    let mut arity = 0;
+                    if let Some(result) = items.get(position).and_then(|item| item.form("result")) {
+                        for result_type in result {
+                            value_type(result_type)?;
+                        }
+                        arity = result.len();
+                        position += 1;
+                    }
+                    self.controls.push((self.code.len(), label));
+                    let op = match mnemonic {
+                        "block" => Op::Block { end: 0, arity },
+                        "loop" => Op::Loop,
+                        _ => Op::If { otherwise: None, end: 0, arity },
+                    };
+                    self.push(op, line);
+                },
+                "else" => {
+                    let (opening, _) = self.controls.last().ok_or_else(|| syntax(line, "else outside of an if"))?;
+                    let opening = *opening;
+                    let index = self.code.len();
+                    match &mut self.code[opening] {
+                        Op::If { otherwise: otherwise @ None, .. } => *otherwise = Some(index),
+                        _ => return Err(syntax(line, "else outside of an if")),
+                    }
+                    self.push(Op::Else { end: 0 }, line);
+                },
+                "end" => {
+                    let (opening, _) = self.controls.pop().ok_or_else(|| syntax(line, "end without a block"))?;
+                    let end = self.code.len();
+                    if let Op::Block { end: target, .. } | Op::If { end: target, .. } = &mut self.code[opening] {
+                        *target = end;
+                    }
+                    if let Some(Op::If { otherwise: Some(otherwise), .. }) = self.code.get(opening).copied() {
+                        self.code[otherwise] = Op::Else { end };
+                    }
+                    self.push(Op::End, line);
+                },
+                "br" | "br_if" => {
+                    let depth = self.depth(operand(), line)?;
+                    self.push(if mnemonic == "br" { Op::Br(depth) } else { Op::BrIf(depth) }, line);
+                },
+                "i32.eqz" => self.push(Op::I32Eqz, line),
+                "select" => self.push(Op::Select, line),
+                "drop" => self.push(Op::Drop, line),
+                "return" => self.push(Op::Return, line),
+                "unreachable" => self.push(Op::Unreachable, line),
+                _ => {
+                    let op = if let Some(index) = I32_BINARY.iter().position(|(name, _)| *name == mnemonic) {
+                        Op::I32Binary(index)
+                    } else if let Some(index) = F32_BINARY.iter().position(|(name, _)| *name == mnemonic) {
+                        Op::F32Binary(index)
+                    } else if let Some(index) = LOADS.iter().position(|(name, _, _, _)| *name == mnemonic) {
+                        Op::Load(index)
+                    } else if let Some(index) = STORES.iter().position(|(name, _, _)| *name == mnemonic) {
+                        Op::Store(index)
+                    } else {
+                        return Err(syntax(line, format!("unsupported instruction {}", mnemonic)));
+                    };
+                    self.push(op, line);
+                },
+            }
+        }
+        if let Some((opening, _)) = self.controls.last() {
+            return Err(syntax(self.lines[*opening], "block without an end"));
+        }
+        Ok(())
+    }
+}
+
+impl Module {
+    fn parse(source: &str) -> Result<Module, SimulationError> {
+        let sexps = parse_sexps(source)?;
+        let fields = match sexps.as_slice() {
+            [module] => module.form("module").ok_or_else(|| syntax(module.line(), "expected (module ...)"))?,
+            _ => return Err(syntax(1, "expected a single module")),
+        };
+
+        // Names are defined before the bodies are read, a function may call the ones after it
+        let mut module = Module { functions: Vec::new(), globals: Vec::new(), pages: 0, data: Vec::new(), exports: HashMap::new() };
+        let mut function_names = HashMap::new();
+        let mut global_names = HashMap::new();
+        let mut bodies = Vec::new();
+        for field in fields {
+            let line = field.line();
+            if let Some(items) = field.form("import") {
+                let (Some(Sexp::Str(module_name, _)), Some(Sexp::Str(name, _)), Some(function)) = (items.first(), items.get(1), items.get(2)) else {
+                    return Err(syntax(line, "expected (import \"module\" \"name\" (func ...))"));
+                };
+                let name = String::from_utf8_lossy(name).to_string();
+                let function = function.form("func").ok_or_else(|| syntax(line, "only functions are imported"))?;
+                let host = HOST_FUNCTIONS.iter().position(|(host, _, _)| *host == name).filter(|_| module_name == b"mining")
+                    .ok_or_else(|| syntax(line, format!("unknown import {}.{}", String::from_utf8_lossy(module_name), name)))?;
+                let (identifier, signature) = match function.first().and_then(Sexp::atom) {
+                    Some(identifier) if identifier.starts_with('$') => (Some(identifier), &function[1..]),
+                    _ => (None, function),
+                };
+                let header = header(signature, line)?;
+                let (_, params, result) = HOST_FUNCTIONS[host];
+                if header.params != params || header.result != result {
+                    return Err(syntax(line, format!("wrong signature for the import {}", name)));
+                }
+                if let Some(identifier) = identifier {
+                    function_names.insert(identifier.to_string(), module.functions.len());
+                }
+                module.functions.push(Function { name, params: header.params, result: header.result, locals: Vec::new(), code: Vec::new(), lines: Vec::new(), host: Some(host) });
+            } else if let Some(items) = field.form("func") {
+                let (identifier, rest) = match items.first().and_then(Sexp::atom) {
+                    Some(identifier) if identifier.starts_with('$') => (identifier.to_string(), &items[1..]),
+                    _ => (format!("{}", module.functions.len()), items),
+                };
+                let header = header(rest, line)?;
+                if let Some(export) = &header.export {
+                    module.exports.insert(export.clone(), module.functions.len());
+                }
+                function_names.insert(identifier.clone(), module.functions.len());
+                let mut locals = header.params.clone();
+                locals.extend(&header.locals);
+                module.functions.push(Function { name: identifier, params: header.params, result: header.result, locals, code: Vec::new(), lines: Vec::new(), host: None });
+                bodies.push((module.functions.len() - 1, &rest[header.body..], header.names));
+            } else if let Some(items) = field.form("global") {
+                let (name, rest) = match items.first().and_then(Sexp::atom) {
+                    Some(name) if name.starts_with('$') => (name.to_string(), &items[1..]),
+                    _ => (format!("{}", module.globals.len()), items),
+                };
+                let [declared, init] = rest else {
+                    return Err(syntax(line, "expected (global $name type (init))"));
+                };
+                let (declared_type, mutable) = match declared.form("mut") {
+                    Some([inner]) => (value_type(inner)?, true),
+                    _ => (value_type(declared)?, false),
+                };
+                let value = constant(init)?;
+                if (value_type_of(value)) != declared_type {
+                    return Err(syntax(line, format!("initial value of {} has the wrong type", name)));
+                }
+                global_names.insert(name.clone(), module.globals.len());
+                module.globals.push(Global { name, value, mutable });
+            } else if let Some(items) = field.form("memory") {
+                let pages = items.iter().filter(|item| item.form("export").is_none()).find_map(Sexp::atom);
+                module.pages = pages.and_then(|pages| pages.parse().ok()).ok_or_else(|| syntax(line, "expected a number of pages"))?;
+            } else if let Some(items) = field.form("data") {
+                let offset = match items.first().map(constant) {
+                    Some(Ok(Value::I32(offset))) if offset >= 0 => offset as usize,
+                    _ => return Err(syntax(line, "expected (data (i32.const offset) \"...\")")),
+                };
+                let mut bytes = Vec::new();
+                for item in &items[1..] {
+                    match item {
+                        Sexp::Str(text, _) => bytes.extend_from_slice(text),
+                        _ => return Err(syntax(item.line(), "expected a string")),
+                    }
+                }
+                module.data.push((offset, bytes));
+            } else if let Some(items) = field.form("export") {
+                let (Some(Sexp::Str(name, _)), Some(function)) = (items.first(), items.get(1)) else {
+                    return Err(syntax(line, "expected (export \"name\" (func $f))"));
+                };
+                let target = function.form("func").and_then(|f| f.first()).and_then(Sexp::atom);
+                let index = BodyReader::index(&function_names, module.functions.len(), target, line, "function")?;
+                module.exports.insert(String::from_utf8_lossy(name).to_string(), index);
+            } else {
+                return Err(syntax(line, "unsupported module field"));
+            }
+        }
+
+        for (index, items, locals) in bodies {
+            let mut reader = BodyReader {
+                functions: &function_names,
+                globals: &module.globals,
+                global_names: &global_names,
+                locals: &locals,
+                local_count: module.functions[index].locals.len(),
+                code: Vec::new(),
+                lines: Vec::new(),
+                controls: Vec::new(),
+            };
+            reader.read(items)?;
+            let (code, lines) = (reader.code, reader.lines);
+            module.functions[index].code = code;
+            module.functions[index].lines = lines;
+        }
+        Ok(module)
+    }
+}
+
+fn value_type_of(value: Value) -> ValueType {
+    match value {
+        Value::I32(_) => ValueType::I32,
+        Value::F32(_) => ValueType::F32,
+    }
+}
+
+// Why the execution stopped before the end of main
+enum Stop {
+    Exit(u8),
+    Error(SimulationError),
+}
+
+impl From<SimulationError> for Stop {
+    fn from(error: SimulationError) -> Self {
+        Stop::Error(error)
+    }
+}
+
+// Block being executed: where a branch to it continues, with the stack height and values kept
+struct Label {
+    continuation: usize,
+    height: usize,
+    arity: usize,
+    is_loop: bool,
+}
+
+struct Machine<'a> {
+    module: &'a Module,
+    memory: Vec<u8>,
+    globals: Vec<Value>,
+    input: Input<&'a [u8]>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    steps: u64,
+    max_steps: u64,
+}
+
+impl Machine<'_> {
+    // The program stops like the other backends: the message on stderr and exit code 1
+    fn runtime_error(&mut self, error: RuntimeError) -> Stop {
+        self.stderr.extend_from_slice(format!("Runtime error: {}\n", error).as_bytes());
+        Stop::Exit(1)
+    }
+
+    fn bytes(&self, offset: Value, length: Value, line: usize) -> Result<Vec<u8>, Stop> {
+        match (offset, length) {
+            (Value::I32(offset), Value::I32(length)) if offset >= 0 && length >= 0 && (offset as usize + length as usize) <= self.memory.len() => {
+                Ok(self.memory[offset as usize..offset as usize + length as usize].to_vec())
+            },
+            _ => Err(SimulationError::Fault(line, "string outside of the memory".to_string()).into()),
+        }
+    }
+
+    fn host(&mut self, host: usize, args: &[Value], line: usize) -> Result<Option<Value>, Stop> {
+        let result = match (HOST_FUNCTIONS[host].0, args) {
+            ("read_int", _) => self.input.read_integer().map(|value| Some(Value::I32(value as i32))),
+            ("read_float", _) => self.input.read_float().map(|value| Some(Value::F32(value))),
+            ("read_char", _) => self.input.read_char().map(|value| Some(Value::I32(value as u32 as u8 as i32))),
+            ("write_int", [Value::I32(value)]) => write_value(&mut self.stdout, &MiningValue::Integer(*value as i16)).map(|_| None),
+            ("write_float", [Value::F32(value)]) => write_value(&mut self.stdout, &MiningValue::Float(*value)).map(|_| None),
+            ("write_char", [Value::I32(value)]) => {
+                self.stdout.push(*value as u8);
+                Ok(None)
+            },
+            ("write_string", [offset, length]) => {
+                let bytes = self.bytes(*offset, *length, line)?;
+                self.stdout.extend_from_slice(&bytes);
+                Ok(None)
+            },
+            ("fail", [offset, length]) => {
+                let message = self.bytes(*offset, *length, line)?;
+                self.stderr.extend_from_slice(b"Runtime error: ");
+                self.stderr.extend_from_slice(&message);
+                self.stderr.push(b'\n');
+                return Err(Stop::Exit(1));
+            },
+            (name, _) => return Err(SimulationError::Fault(line, format!("invalid arguments for {}", name)).into()),
+        };
+        result.map_err(|error| self.runtime_error(error))
+    }
+
+    fn call(&mut self, index: usize, args: Vec<Value>) -> Result<Option<Value>, Stop> {
+        let module = self.module;
+        let function = &module.functions[index];
+        if let Some(host) = function.host {
+            return self.host(host, &args, 0);
+        }
+        let mut locals = args;
+        locals.extend(function.locals[function.params.len()..].iter().map(|local| Value::zero(*local)));
+        let mut stack: Vec<Value> = Vec::new();
+        let mut labels: Vec<Label> = Vec::new();
+        let mut pc = 0;
+
+        while pc < function.code.len() {
+            self.steps += 1;
+            if self.steps > self.max_steps {
+                return Err(SimulationError::StepLimit(self.max_steps).into());
+            }
+            let line = function.lines[pc];
+            let fault = |message: &str| Stop::Error(SimulationError::Fault(line, message.to_string()));
+            let op = function.code[pc];
+            pc += 1;
+            match op {
+                Op::I32Const(n) => stack.push(Value::I32(n)),
+                Op::F32Const(x) => stack.push(Value::F32(x)),
+                Op::LocalGet(local) => stack.push(locals[local]),
+                Op::LocalSet(local) | Op::LocalTee(local) => {
+                    let value = stack.pop().ok_or_else(|| fault("empty stack"))?;
+                    if value_type_of(value) != function.locals[local] {
+                        return Err(fault("value of the wrong type stored in a local"));
+                    }
+                    locals[local] = value;
+                    if let Op::LocalTee(_) = op {
+                        stack.push(value);
+                    }
+                },
+                Op::GlobalGet(global) => stack.push(self.globals[global]),
+                Op::GlobalSet(global) => {
+                    let value = stack.pop().ok_or_else(|| fault("empty stack"))?;
+                    if value_type_of(value) != value_type_of(self.globals[global]) {
+                        return Err(fault("value of the wrong type stored in a global"));
+                    }
+                    self.globals[global] = value;
+                },
+                Op::Load(load) => {
+                    let (_, width, signed, value_type) = LOADS[load];
+                    let address = match stack.pop() {
+                        Some(Value::I32(address)) => address as u32 as usize,
+                        _ => return Err(fault("expected an i32 address")),
+                    };
+                    let bytes = self.memory.get(address..address + width).ok_or_else(|| fault("memory access out of bounds"))?;
+                    let mut word = [0u8; 4];
+                    word[..width].copy_from_slice(bytes);
+                    let mut bits = u32::from_le_bytes(word);
+                    if signed && width < 4 {
+                        let shift = 32 - 8 * width as u32;
+                        bits = (((bits << shift) as i32) >> shift) as u32;
+                    }
+                    stack.push(match value_type {
+                        ValueType::I32 => Value::I32(bits as i32),
+                        ValueType::F32 => Value::F32(f32::from_bits(bits)),
+                    });
+                },
+                Op::Store(store) => {
+                    let (_, width, value_type) = STORES[store];
+                    let bits = match (stack.pop(), value_type) {
+                        (Some(Value::I32(value)), ValueType::I32) => value as u32,
+                        (Some(Value::F32(value)), ValueType::F32) => value.to_bits(),
+                        _ => return Err(fault("value of the wrong type stored in the memory")),
+                    };
+                    let address = match stack.pop() {
+                        Some(Value::I32(address)) => address as u32 as usize,
+                        _ => return Err(fault("expected an i32 address")),
+                    };
+                    let cells = self.memory.get_mut(address..address + width).ok_or_else(|| fault("memory access out of bounds"))?;
+                    cells.copy_from_slice(&bits.to_le_bytes()[..width]);
+                },
+                Op::I32Binary(operator) => {
+                    let (name, function) = I32_BINARY[operator];
+                    match (stack.pop(), stack.pop()) {
+                        (Some(Value::I32(b)), Some(Value::I32(a))) => {
+                            stack.push(Value::I32(function(a, b).ok_or_else(|| fault("integer division by zero"))?));
+                        },
+                        _ => return Err(fault(&format!("{} expects two i32 values", name))),
+                    }
+                },
+                Op::F32Binary(operator) => {
+                    let (name, function) = F32_BINARY[operator];
+                    match (stack.pop(), stack.pop()) {
+                        (Some(Value::F32(b)), Some(Value::F32(a))) => stack.push(function(a, b)),
+                        _ => return Err(fault(&format!("{} expects two f32 values", name))),
+                    }
+                },
+                Op::I32Eqz => match stack.pop() {
+                    Some(Value::I32(value)) => stack.push(Value::I32((value == 0) as i32)),
+                    _ => return Err(fault("i32.eqz expects an i32 value")),
+                },
+                Op::Select => match (stack.pop(), stack.pop(), stack.pop()) {
+                    (Some(Value::I32(condition)), Some(second), Some(first)) if value_type_of(first) == value_type_of(second) => {
+                        stack.push(if condition != 0 { first } else { second });
+                    },
+                    _ => return Err(fault("select expects two values of the same type and an i32")),
+                },
+                Op::Drop => {
+                    stack.pop().ok_or_else(|| fault("empty stack"))?;
+                },
+                Op::Call(callee) => {
+                    let called = &module.functions[callee];
+                    if stack.len() < called.params.len() {
+                        return Err(fault(&format!("missing arguments for {}", called.name)));
+                    }
+                    let args = stack.split_off(stack.len() - called.params.len());
+                    if args.iter().map(|arg| value_type_of(*arg)).ne(called.params.iter().copied()) {
+                        return Err(fault(&format!("arguments of the wrong type for {}", called.name)));
+                    }
+                    let result = match called.host {
+                        Some(host) => self.host(host, &args, line)?,
+                        None => self.call(callee, args)?,
+                    };
+                    stack.extend(result);
+                },
+                Op::Block { end, arity } => labels.push(Label { continuation: end + 1, height: stack.len(), arity, is_loop: false }),
+                Op::Loop => labels.push(Label { continuation: pc, height: stack.len(), arity: 0, is_loop: true }),
+                Op::If { otherwise, end, arity } => {
+                    let condition = match stack.pop() {
+                        Some(Value::I32(condition)) => condition,
+                        _ => return Err(fault("if expects an i32 condition")),
+                    };
+                    labels.push(Label { continuation: end + 1, height: stack.len(), arity, is_loop: false });
+                    if condition == 0 {
+                        pc = otherwise.map_or(end, |otherwise| otherwise + 1);
+                    }
+                },
+                // The end of the then branch
+                Op::Else { end } => pc = end,
+                Op::End => {
+                    labels.pop();
+                },
+                Op::Br(depth) | Op::BrIf(depth) => {
+                    if let Op::BrIf(_) = op {
+                        match stack.pop() {
+                            Some(Value::I32(0)) => continue,
+                            Some(Value::I32(_)) => {},
+                            _ => return Err(fault("br_if expects an i32 condition")),
+                        }
+                    }
+                    let target = labels.len() - 1 - depth;
+                    let label = &labels[target];
+                    let kept = stack.split_off(stack.len().saturating_sub(label.arity));
+                    stack.truncate(label.height);
+                    stack.extend(kept);
+                    pc = label.continuation;
+                    labels.truncate(if label.is_loop { target + 1 } else { target });
+                },
+                Op::Return => break,
+                Op::Unreachable => return Err(fault("unreachable executed")),
+            }
+        }
+
+        match function.result {
+            Some(result_type) => match stack.pop() {
+                Some(value) if value_type_of(value) == result_type => Ok(Some(value)),
+                _ => Err(SimulationError::Fault(function.lines.last().copied().unwrap_or(0), format!("{} does not return a {:?}", function.name, result_type)).into()),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMPORTS: &str = r#"
+        (import "mining" "read_int" (func $read_int (result i32)))
+        (import "mining" "write_int" (func $write_int (param i32)))
+        (import "mining" "write_string" (func $write_string (param i32 i32)))
+    "#;
+
+    fn module(body: &str) -> String {
+        format!("(module {}\n(memory 1)\n(data (i32.const 8) \" \")\n(global $n (mut i32) (i32.const 0))\n{})", IMPORTS, body)
+    }
+
+    #[test]
+    fn test_control_flow() {
+        // Writes the numbers below the input, skipping 2, then their sum
+        let source = module(r#"
+            (func $main (export "main") (local $i i32)
+              call $read_int
+              global.set $n
+              block $done
+                loop $next
+                  local.get $i
+                  global.get $n
+                  i32.ge_s
+                  br_if $done
+                  local.get $i
+                  i32.const 2
+                  i32.ne
+                  if
+                    local.get $i
+                    call $write_int
+                    i32.const 8
+                    i32.const 1
+                    call $write_string
+                  end
+                  local.get $i
+                  i32.const 1
+                  i32.add
+                  local.set $i
+                  br $next
+                end
+              end
+              global.get $n
+              call $triangle
+              call $write_int)
+            ;; n * (n - 1) / 2, from a function defined after its caller
+            (func $triangle (param $n i32) (result i32)
+              local.get $n
+              local.get $n
+              i32.const 1
+              i32.sub
+              i32.mul
+              i32.const 2
+              i32.div_s)
+        "#);
+        let execution = run_wat(&source, b"5", 1000).unwrap();
+        assert_eq!((execution.stdout().as_str(), execution.exit_code), ("0 1 3 4 10", 0));
+
+        let execution = run_wat(&source, b"x", 1000).unwrap();
+        assert_eq!((execution.stderr().as_str(), execution.exit_code), ("Runtime error: Invalid INTEGER input\n", 1));
+        assert_eq!(run_wat(&source, b"30000", 1000), Err(SimulationError::StepLimit(1000)));
+    }
+
+    #[test]
+    fn test_validation() {
+        let invalid = |body: &str| run_wat(&module(&format!("(func $main (export \"main\")\n{})", body)), b"", 100).unwrap_err();
+        assert_eq!(invalid("br $nowhere"), SimulationError::UnknownLabel(10, "$nowhere".to_string()));
+        assert_eq!(invalid("call $missing"), SimulationError::UnknownLabel(10, "$missing".to_string()));
+        assert_eq!(invalid("block\ni32.const 1"), SimulationError::Syntax(10, "block without an end".to_string()));
+        assert_eq!(invalid("i32.popcnt"), SimulationError::Syntax(10, "unsupported instruction i32.popcnt".to_string()));
+        assert_eq!(invalid("(i32.const 1)"), SimulationError::Syntax(10, "folded instructions are not supported".to_string()));
+        // Values of the wrong type are found when they are used
+        assert_eq!(invalid("f32.const 1.5\nglobal.set $n"), SimulationError::Fault(11, "value of the wrong type stored in a global".to_string()));
+        assert!(matches!(
+            run_wat(&module("(global $k i32 (i32.const 1))\n(func $main (export \"main\") i32.const 2 global.set $k)"), b"", 100),
+            Err(SimulationError::Syntax(_, message)) if message == "global $k is immutable"
+        ));
+    }
+}
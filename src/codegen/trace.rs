@@ -0,0 +1,309 @@
+//! Observers of the interpreter behind `run --trace` and `run --profile`. The tracer logs every
+//! instruction with the values it reads and writes, the profiler counts the executions of each
+//! line and the iterations of each FOR loop and reports the hot spots once the program stops
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use crate::codegen::error::{LocatedError, Position};
+use crate::codegen::interpreter::{Event, Interpreter, Observer};
+use crate::Parser::ast::Instruction;
+
+// Lines listed by the profile report
+const HOT_LINES: usize = 10;
+
+fn source_lines(source: &str) -> Vec<String> {
+    source.lines().map(|line| line.trim().to_string()).collect()
+}
+
+// Text of a line counted from 1
+fn source_line(lines: &[String], line: usize) -> &str {
+    lines.get(line.wrapping_sub(1)).map_or("", String::as_str)
+}
+
+// A variable or an array cell (e.g., A[2])
+fn cell_name(name: &str, index: Option<usize>) -> String {
+    match index {
+        Some(index) => format!("{}[{}]", name, index),
+        None => name.to_string(),
+    }
+}
+
+fn plural(count: u64, word: &str) -> String {
+    match count {
+        1 => format!("1 {}", word),
+        _ => format!("{} {}s", count, word),
+    }
+}
+
+/// Writes a line per executed instruction, followed by the values it reads and writes and the
+/// results of its operations, each at its position in the source
+pub struct Tracer<T> {
+    source: Vec<String>,
+    sink: T,
+    error: Option<std::io::Error>,  // First write that failed, reported before the next instruction
+    started: bool,                  // Whether an instruction was executed, the events before belong to the declarations
+}
+
+impl<T: Write> Tracer<T> {
+    pub fn new(source: &str, sink: T) -> Self {
+        Tracer { source: source_lines(source), sink, error: None, started: false }
+    }
+
+    fn log(&mut self, text: String) {
+        if self.error.is_none() {
+            self.error = writeln!(self.sink, "{}", text).err();
+        }
+    }
+
+    fn check<R: BufRead, W: Write>(&mut self, interpreter: &Interpreter<R, W>) -> Result<(), LocatedError> {
+        match self.error.take() {
+            Some(e) => Err(interpreter.locate(e, None)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<R: BufRead, W: Write, T: Write> Observer<R, W> for Tracer<T> {
+    fn before(&mut self, interpreter: &mut Interpreter<R, W>, instruction: &Instruction) -> Result<(), LocatedError> {
+        self.check(interpreter)?;
+        self.started = true;
+        let line = Position::from(instruction.position()).line;
+        self.log(format!("line {}: {}", line, source_line(&self.source, line)));
+        self.check(interpreter)
+    }
+
+    fn event(&mut self, event: &Event) {
+        if !self.started {
+            self.log("declarations".to_string());
+            self.started = true;
+        }
+        let text = match event {
+            Event::Load((name, position), index, value) => {
+                format!("    read {} = {} at {}", cell_name(name, *index), value, Position::from(*position))
+            },
+            Event::Store((name, position), index, value) => {
+                format!("    write {} = {} at {}", cell_name(name, *index), value, Position::from(*position))
+            },
+            Event::Operation(op, value) => format!("    {} gives {} at {}", op, value, Position::from(op.position())),
+            Event::Comparison(op, holds) => format!("    {} gives {} at {}", op, holds, Position::from(op.position())),
+            Event::Iteration(for_stmt, iteration, value) => {
+                let line = Position::from(for_stmt.position).line;
+                format!("line {}: iteration {} of FOR with {} = {}", line, iteration, for_stmt.init.var.0, value)
+            },
+            Event::Step(for_stmt) => format!("line {}: step of FOR", Position::from(for_stmt.position).line),
+        };
+        self.log(text);
+    }
+
+    fn finish(&mut self, interpreter: &mut Interpreter<R, W>) -> Result<(), LocatedError> {
+        self.check(interpreter)?;
+        self.sink.flush().map_err(|e| interpreter.locate(e, None))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct LoopProfile {
+    runs: u64,          // Times the FOR statement was executed
+    iterations: u64,    // Iterations over all the runs
+}
+
+/// Counts the executions of the instructions of each line, the steps of a FOR loop counting
+/// for its line, and the runs and iterations of each FOR loop. The report is written once the
+/// program stops, a runtime error included
+pub struct Profiler<T> {
+    source: Vec<String>,
+    sink: T,
+    lines: HashMap<usize, u64>,
+    loops: HashMap<(usize, usize), LoopProfile>,    // By position of the FOR keyword
+}
+
+impl<T: Write> Profiler<T> {
+    pub fn new(source: &str, sink: T) -> Self {
+        Profiler { source: source_lines(source), sink, lines: HashMap::new(), loops: HashMap::new() }
+    }
+
+    /// The most executed lines, then the FOR loops by number of iterations
+    pub fn report(&self) -> String {
+        let total: u64 = self.lines.values().sum();
+        let mut lines: Vec<(usize, u64)> = self.lines.iter().map(|(line, count)| (*line, *count)).collect();
+        lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let mut report = format!("Profile: {}\n", plural(total, "execution"));
+
+        report.push_str("Hot lines:\n");
+        for (line, count) in lines.iter().take(HOT_LINES) {
+            let share = 100.0 * *count as f64 / total as f64;
+            report.push_str(&format!("  line {}: {} ({:.1}%)  {}\n", line, plural(*count, "execution"), share, source_line(&self.source, *line)));
+        }
+        if lines.len() > HOT_LINES {
+            report.push_str(&format!("  ... {} more\n", plural((lines.len() - HOT_LINES) as u64, "line")));
+        }
+
+        let mut loops: Vec<(Position, LoopProfile)> = self.loops.iter().map(|(position, profile)| (Position::from(*position), *profile)).collect();
+        loops.sort_by(|a, b| b.1.iterations.cmp(&a.1.iterations).then((a.0.line, a.0.column).cmp(&(b.0.line, b.0.column))));
+        if !loops.is_empty() {
+            report.push_str("FOR loops:\n");
+        }
+        for (position, profile) in loops {
+            report.push_str(&format!("  {}: {}, {}  {}\n", position, plural(profile.runs, "run"),
+                plural(profile.iterations, "iteration"), source_line(&self.source, position.line)));
+        }
+        report
+    }
+}
+
+impl<R: BufRead, W: Write, T: Write> Observer<R, W> for Profiler<T> {
+    fn before(&mut self, _interpreter: &mut Interpreter<R, W>, instruction: &Instruction) -> Result<(), LocatedError> {
+        *self.lines.entry(Position::from(instruction.position()).line).or_default() += 1;
+        if let Instruction::For(for_stmt) = instruction {
+            self.loops.entry(for_stmt.position).or_default().runs += 1;
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, event: &Event) {
+        match event {
+            Event::Iteration(for_stmt, _, _) => self.loops.entry(for_stmt.position).or_default().iterations += 1,
+            Event::Step(for_stmt) => *self.lines.entry(Position::from(for_stmt.position).line).or_default() += 1,
+            _ => {},
+        }
+    }
+
+    fn finish(&mut self, interpreter: &mut Interpreter<R, W>) -> Result<(), LocatedError> {
+        let report = self.report();
+        self.sink.write_all(report.as_bytes()).and_then(|()| self.sink.flush()).map_err(|e| interpreter.locate(e, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::codegen::error::RuntimeError;
+    use crate::Test::tests::analyze_source;
+
+    const PROGRAM: &str = r#"
+        VAR_GLOBAL {
+            INTEGER N, I, S = 0;
+            INTEGER A[3];
+        }
+        DECLARATION {
+        }
+        INSTRUCTION {
+            READ(N);
+            FOR (I = 0 : 1 : N) {
+                IF (I > 0) {
+                    A[I] = S * I;
+                }
+                S = S + I;
+            }
+            WRITE(S);
+        }
+    "#;
+
+    // Buffer the test keeps a handle on while the interpreter owns the observer writing to it
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Runs the program with the tracer or the profiler, returns what it wrote and the program output
+    fn observe(input: &str, profile: bool) -> (String, String, Result<(), RuntimeError>) {
+        let (program, _) = analyze_source(PROGRAM);
+        let sink = Shared::default();
+        let mut interpreter = Interpreter::new(input.as_bytes(), Vec::new());
+        match profile {
+            true => interpreter.add_observer(Box::new(Profiler::new(PROGRAM, sink.clone()))),
+            false => interpreter.add_observer(Box::new(Tracer::new(PROGRAM, sink.clone()))),
+        }
+        let result = interpreter.run(&program).map_err(|e| e.error);
+        let output = String::from_utf8(interpreter.output().clone()).unwrap();
+        let text = String::from_utf8(sink.0.borrow().clone()).unwrap();
+        (text, output, result)
+    }
+
+    #[test]
+    fn test_trace() {
+        let (trace, output, result) = observe("2", false);
+        assert_eq!(result, Ok(()));
+        assert_eq!(output, "1\n");
+        assert_eq!(trace, "\
+declarations
+    write S = 0 at line 3, column 27
+line 9: READ(N);
+    write N = 2 at line 9, column 18
+line 10: FOR (I = 0 : 1 : N) {
+    write I = 0 at line 10, column 18
+    read I = 0 at line 10, column 18
+    read N = 2 at line 10, column 30
+line 10: iteration 1 of FOR with I = 0
+line 11: IF (I > 0) {
+    read I = 0 at line 11, column 21
+    > gives false at line 11, column 23
+line 14: S = S + I;
+    read S = 0 at line 14, column 21
+    read I = 0 at line 14, column 25
+    + gives 0 at line 14, column 23
+    write S = 0 at line 14, column 17
+line 10: step of FOR
+    read I = 0 at line 10, column 18
+    write I = 1 at line 10, column 18
+    read I = 1 at line 10, column 18
+    read N = 2 at line 10, column 30
+line 10: iteration 2 of FOR with I = 1
+line 11: IF (I > 0) {
+    read I = 1 at line 11, column 21
+    > gives true at line 11, column 23
+line 12: A[I] = S * I;
+    read S = 0 at line 12, column 28
+    read I = 1 at line 12, column 32
+    * gives 0 at line 12, column 30
+    read I = 1 at line 12, column 23
+    write A[1] = 0 at line 12, column 21
+line 14: S = S + I;
+    read S = 0 at line 14, column 21
+    read I = 1 at line 14, column 25
+    + gives 1 at line 14, column 23
+    write S = 1 at line 14, column 17
+line 10: step of FOR
+    read I = 1 at line 10, column 18
+    write I = 2 at line 10, column 18
+    read I = 2 at line 10, column 18
+    read N = 2 at line 10, column 30
+line 16: WRITE(S);
+    read S = 1 at line 16, column 19
+");
+    }
+
+    #[test]
+    fn test_profile() {
+        let (report, output, result) = observe("3", true);
+        assert_eq!(result, Ok(()));
+        assert_eq!(output, "3\n");
+        assert_eq!(report, "\
+Profile: 14 executions
+Hot lines:
+  line 10: 4 executions (28.6%)  FOR (I = 0 : 1 : N) {
+  line 11: 3 executions (21.4%)  IF (I > 0) {
+  line 14: 3 executions (21.4%)  S = S + I;
+  line 12: 2 executions (14.3%)  A[I] = S * I;
+  line 9: 1 execution (7.1%)  READ(N);
+  line 16: 1 execution (7.1%)  WRITE(S);
+FOR loops:
+  line 10, column 13: 1 run, 3 iterations  FOR (I = 0 : 1 : N) {
+");
+
+        // The report is written when a runtime error stops the program too
+        let (report, _, result) = observe("x", true);
+        assert_eq!(result, Err(RuntimeError::InvalidInteger));
+        assert_eq!(report, "Profile: 1 execution\nHot lines:\n  line 9: 1 execution (100.0%)  READ(N);\n");
+    }
+}
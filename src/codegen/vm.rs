@@ -0,0 +1,201 @@
+//! Virtual machine running the MinING bytecode
+
+use std::io::{BufRead, Write};
+use crate::codegen::bytecode::{Constant, Instruction, Module};
+use crate::codegen::error::{LocatedError, RuntimeError};
+use crate::codegen::runtime::{write_value, zero, Input};
+use crate::Semantic::quadruplets::Value;
+use crate::Semantic::ts::Types;
+
+/// Runs a module until its Halt, READ takes its tokens from `input` and WRITE goes to `output`.
+/// Returns the number of instructions executed, errors are located with the origins of the module
+pub fn run_bytecode<R: BufRead, W: Write>(module: &Module, input: R, output: &mut W) -> Result<u64, LocatedError> {
+    let mut vm = Vm {
+        module,
+        memory: module.slots.iter().map(|slot| match &slot.slot_type {
+            Types::Array(element, size) => vec![zero(element); (*size).max(0) as usize],
+            scalar => vec![zero(scalar)],
+        }).collect(),
+        stack: Vec::new(),
+        input: Input::new(input),
+        pc: 0,
+    };
+    let result = vm.run(output).map_err(|e| match module.origin(vm.pc.saturating_sub(1)) {
+        Some(origin) => origin.locate(e),
+        None => LocatedError::from(e),
+    });
+    output.flush().map_err(|e| LocatedError::from(RuntimeError::from(e)))?;
+    result
+}
+
+struct Vm<'a, R> {
+    module: &'a Module,
+    memory: Vec<Vec<Value>>, // Cells of every slot, a single one for scalars
+    stack: Vec<Value>,
+    input: Input<R>,
+    pc: usize,                  // Index of the next instruction
+}
+
+impl<R: BufRead> Vm<'_, R> {
+    fn pop(&mut self) -> Result<Value, RuntimeError> {
+        self.stack.pop().ok_or_else(|| RuntimeError::InvalidProgram("pop from an empty stack".to_string()))
+    }
+
+    fn index(&mut self, slot: u16) -> Result<usize, RuntimeError> {
+        match self.pop()? {
+            Value::Integer(index) if index >= 0 && (index as usize) < self.memory[slot as usize].len() => Ok(index as usize),
+            Value::Integer(_) => Err(RuntimeError::IndexOutOfBounds),
+            _ => Err(RuntimeError::InvalidProgram("array indices are INTEGER values".to_string())),
+        }
+    }
+
+    // Values keep the type of their slot
+    fn store(&mut self, slot: u16, cell: usize, value: Value) -> Result<(), RuntimeError> {
+        let target = &mut self.memory[slot as usize][cell];
+        if target.get_type() != value.get_type() {
+            return Err(RuntimeError::InvalidProgram(format!("{:?} value stored in slot {}", value.get_type(), slot)));
+        }
+        *target = value;
+        Ok(())
+    }
+
+    fn run<W: Write>(&mut self, output: &mut W) -> Result<u64, RuntimeError> {
+        let mut steps = 0;
+        loop {
+            let instruction = self.module.code.get(self.pc).copied().unwrap_or(Instruction::Halt);
+            steps += 1;
+            self.pc += 1;
+            match instruction {
+                Instruction::Push(index) => match &self.module.constants[index as usize] {
+                    Constant::Value(value) => self.stack.push(*value),
+                    Constant::String(_) => return Err(RuntimeError::InvalidProgram(format!("constant {} is not a value", index))),
+                },
+                Instruction::Pop => {
+                    self.pop()?;
+                },
+                Instruction::Load(slot) => self.stack.push(self.memory[slot as usize][0]),
+                Instruction::Store(slot) => {
+                    let value = self.pop()?;
+                    self.store(slot, 0, value)?;
+                },
+                Instruction::LoadElement(slot) => {
+                    let cell = self.index(slot)?;
+                    self.stack.push(self.memory[slot as usize][cell]);
+                },
+                Instruction::StoreElement(slot) => {
+                    let value = self.pop()?;
+                    let cell = self.index(slot)?;
+                    self.store(slot, cell, value)?;
+                },
+                Instruction::Not => {
+                    let value = self.pop()?;
+                    self.stack.push(Value::Integer(!value.is_true() as i16));
+                },
+                Instruction::Jump(target) => self.pc = target as usize,
+                Instruction::JumpIfTrue(target) => {
+                    if self.pop()?.is_true() {
+                        self.pc = target as usize;
+                    }
+                },
+                Instruction::JumpIfFalse(target) => {
+                    if !self.pop()?.is_true() {
+                        self.pc = target as usize;
+                    }
+                },
+                Instruction::ReadInteger => {
+                    let value = self.input.read_integer()?;
+                    self.stack.push(Value::Integer(value));
+                },
+                Instruction::ReadFloat => {
+                    let value = self.input.read_float()?;
+                    self.stack.push(Value::Float(value));
+                },
+                Instruction::ReadChar => {
+                    let value = self.input.read_char()?;
+                    self.stack.push(Value::Char(value));
+                },
+                Instruction::Write => {
+                    let value = self.pop()?;
+                    write_value(output, &value)?;
+                },
+                Instruction::WriteString(index) => match &self.module.constants[index as usize] {
+                    Constant::String(text) => output.write_all(text.as_bytes())?,
+                    Constant::Value(_) => return Err(RuntimeError::InvalidProgram(format!("constant {} is not a string", index))),
+                },
+                Instruction::Halt => return Ok(steps),
+                binary => {
+                    let operator = binary.binary_operator().expect("every other instruction is a binary operator");
+                    let right = self.pop()?;
+                    let left = self.pop()?;
+                    let value = left.binary(&operator, &right)?;
+                    self.stack.push(value);
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::bytecode::compile_bytecode;
+    use crate::Semantic::quadruplets::parse_quadruplets;
+    use crate::Test::tests::{generate_source, FAILING_PROGRAM, QUADRUPLETS};
+
+    fn run(module: &Module, input: &str) -> (String, Result<u64, RuntimeError>) {
+        let mut output = Vec::new();
+        let result = run_bytecode(module, input.as_bytes(), &mut output).map_err(|e| e.error);
+        (String::from_utf8_lossy(&output).to_string(), result)
+    }
+
+    #[test]
+    fn test_run_bytecode() {
+        let module = compile_bytecode(&parse_quadruplets(QUADRUPLETS).unwrap()).unwrap();
+        // The program is run from its file contents
+        let module = Module::decode(&module.encode()).unwrap();
+
+        let (output, result) = run(&module, "-7 2.25\n a");
+        assert_eq!(output, "S = -21\n1.5b13");
+        assert!(result.is_ok());
+
+        let (output, result) = run(&module, "0 1.0 x");
+        assert_eq!(output, "S = 0\n0.666667y0");
+        assert_eq!(result, Err(RuntimeError::DivisionByZero));
+
+        assert_eq!(run(&module, "40000").1, Err(RuntimeError::InvalidInteger));
+        assert_eq!(run(&module, "1 2.0").1, Err(RuntimeError::EndOfInput));
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let quads = parse_quadruplets(r#"
+            INTEGER I, X;
+            INTEGER A[2];
+            0: (Read, _, _, I)
+            1: (Assign, 32767, _, X)
+            2: (Add, X, I, A[I])
+        "#).unwrap();
+        let module = compile_bytecode(&quads).unwrap();
+        assert!(run(&module, "0").1.is_ok());
+        assert_eq!(run(&module, "1").1, Err(RuntimeError::Overflow));
+        // The overflow is found before the index is checked
+        assert_eq!(run(&module, "5").1, Err(RuntimeError::Overflow));
+        assert_eq!(run(&module, "-1").1, Err(RuntimeError::IndexOutOfBounds));
+    }
+
+    #[test]
+    fn test_error_positions() {
+        // The positions are kept in the file, the iterations are not known
+        let module = compile_bytecode(&generate_source(FAILING_PROGRAM)).unwrap();
+        let module = Module::decode(&module.encode()).unwrap();
+        let error = |input: &str| run_bytecode(&module, input.as_bytes(), &mut Vec::new()).unwrap_err().to_string();
+        let stack = "\n    in FOR at line 10, column 13\
+                     \n    in IF at line 9, column 9\
+                     \n    in FOR at line 8, column 5";
+        assert_eq!(error("1"), format!("Division by zero at line 11, column 28{}", stack));
+        assert_eq!(error("32767"), format!("INTEGER overflow at line 11, column 33{}", stack));
+        assert_eq!(error("5"), "Array index out of bounds at line 15, column 5");
+        assert_eq!(error("2 x"), "Invalid INTEGER input at line 16, column 10");
+        assert_eq!(error("2"), "Unexpected end of input at line 16, column 10");
+    }
+}
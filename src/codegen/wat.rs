@@ -0,0 +1,755 @@
+//! WebAssembly backend, translating the abstract syntax tree to a module in the text format
+//!
+//! The storage comes from the symbol table: scalars are globals named `$m_<name>` (INTEGER and
+//! CHAR values in an `i32`, FLOAT in an `f32`) and arrays are laid out at the start of the linear
+//! memory, 2 bytes per INTEGER, 4 per FLOAT and 1 per CHAR, followed by the strings. IF becomes `if`/`else`
+//! and FOR a `loop` inside a `block` left with `br_if`. READ, WRITE and the run-time errors call
+//! functions imported from the host module `mining`:
+//!
+//! | import         | signature             |                                                   |
+//! |----------------|-----------------------|---------------------------------------------------|
+//! | `read_int`     | `() -> i32`           | next token as an INTEGER                          |
+//! | `read_float`   | `() -> f32`           | next token as a FLOAT                             |
+//! | `read_char`    | `() -> i32`           | next character that is not a space                |
+//! | `write_int`    | `(i32)`               |                                                   |
+//! | `write_float`  | `(f32)`               |                                                   |
+//! | `write_char`   | `(i32)`               |                                                   |
+//! | `write_string` | `(i32 offset, i32 length)` | bytes of the memory                          |
+//! | `fail`         | `(i32 offset, i32 length)` | stops the program with `Runtime error: <message>` |
+//!
+//! The program is the exported function `main`, `codegen::simwat` runs it without a browser
+
+use std::collections::HashMap;
+use crate::codegen::error::CodegenError;
+use crate::codegen::{is_signed, symbol};
+use crate::codegen::x86_64::RUNTIME_ERRORS;
+use crate::Parser::ast::*;
+use crate::Semantic::quadruplets::{constant_value, to_types, to_value, Value};
+use crate::Semantic::ts::{Symbol, Types};
+
+const IMPORTS: &str = r#"  (import "mining" "read_int" (func $mining_read_int (result i32)))
+  (import "mining" "read_float" (func $mining_read_float (result f32)))
+  (import "mining" "read_char" (func $mining_read_char (result i32)))
+  (import "mining" "write_int" (func $mining_write_int (param i32)))
+  (import "mining" "write_float" (func $mining_write_float (param f32)))
+  (import "mining" "write_char" (func $mining_write_char (param i32)))
+  (import "mining" "write_string" (func $mining_write_string (param i32 i32)))
+  (import "mining" "fail" (func $mining_fail (param i32 i32)))
+"#;
+
+// Helpers following the run-time conventions shared with the other backends,
+// INTEGER values are kept sign extended in their i32
+const RUNTIME: &str = r#"
+  (func $mining_check (param $value i32) (result i32)
+    local.get $value
+    i32.const -32768
+    i32.lt_s
+    local.get $value
+    i32.const 32767
+    i32.gt_s
+    i32.or
+    if
+      call $mining_overflow_error
+    end
+    local.get $value)
+
+  (func $mining_add (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    local.get $b
+    i32.add
+    call $mining_check)
+
+  (func $mining_sub (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    local.get $b
+    i32.sub
+    call $mining_check)
+
+  (func $mining_mul (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    local.get $b
+    i32.mul
+    call $mining_check)
+
+  (func $mining_div (param $a i32) (param $b i32) (result i32)
+    local.get $b
+    i32.eqz
+    if
+      call $mining_division_error
+    end
+    local.get $a
+    local.get $b
+    i32.div_s
+    call $mining_check)
+
+  ;; CHAR arithmetic wraps around 0x7F
+  (func $mining_char_add (param $a i32) (param $b i32) (result i32)
+    local.get $a
+    local.get $b
+    i32.add
+    i32.const 127
+    i32.rem_u)
+
+  (func $mining_char_sub (param $a i32) (param $b i32) (result i32) (local $code i32)
+    local.get $a
+    local.get $b
+    i32.sub
+    i32.const 127
+    i32.rem_s
+    local.tee $code
+    i32.const 127
+    i32.add
+    local.get $code
+    local.get $code
+    i32.const 0
+    i32.lt_s
+    select)
+
+  ;; Negative indices are above the size once compared as unsigned values
+  (func $mining_index (param $index i32) (param $size i32) (result i32)
+    local.get $index
+    local.get $size
+    i32.ge_u
+    if
+      call $mining_index_error
+    end
+    local.get $index)
+"#;
+
+/// Translates a program that passed the semantic analysis to a WebAssembly text module,
+/// with the types and array sizes of its symbol table
+pub fn generate_wat(program: &Program, symbols: &HashMap<String, Symbol>) -> Result<String, CodegenError> {
+    let mut generator = WatGenerator::default();
+    generator.layout(symbols);
+    for declarations in [&program.global, &program.decls].into_iter().flatten() {
+        generator.declarations(declarations)?;
+    }
+    if let Some(instructions) = &program.inst {
+        generator.instructions(instructions)?;
+    }
+    Ok(generator.finish())
+}
+
+// Where the value of a name lives
+#[derive(Clone)]
+enum Storage {
+    Global(Types),
+    Array(Types, i16, u32), // Element type, size and address of the first element
+}
+
+// Instructions leaving one value on the stack
+struct WatExpr {
+    code: Vec<String>,
+    value_type: Types,
+    can_fail: bool, // Whether evaluating it may stop the program with a run-time error
+}
+
+#[derive(Default)]
+struct WatGenerator {
+    storage: HashMap<String, Storage>,
+    constants: HashMap<String, Value>,     // Constant values known at generation time, used for array sizes
+    initial: HashMap<String, (Value, bool)>, // Initial value of a global and whether it is immutable
+    globals: Vec<String>,                  // Scalar names in the order of the global section
+    data: Vec<u8>,                         // Strings, stored after the arrays
+    strings: HashMap<String, u32>,
+    arrays_end: u32,
+    locals: Vec<Types>,                    // Local n + 1 holds a value of the n-th type
+    body: Vec<String>,
+    depth: usize,
+    loops: usize,
+}
+
+fn wat_name(name: &str) -> String {
+    format!("${}", symbol(name))
+}
+
+fn wat_type(value_type: &Types) -> &'static str {
+    match value_type {
+        Types::Float => "f32",
+        _ => "i32",
+    }
+}
+
+fn wat_value(value: &Value) -> String {
+    match value {
+        Value::Integer(i) => format!("i32.const {}", i),
+        Value::Float(f) => format!("f32.const {:?}", f),
+        Value::Char(c) => format!("i32.const {}", *c as u32 as u8),
+    }
+}
+
+// Bytes of a memory cell and the instructions reading and writing it
+fn cell(element: &Types) -> (u32, &'static str, &'static str) {
+    match element {
+        Types::Float => (4, "f32.load", "f32.store"),
+        Types::Char => (1, "i32.load8_u", "i32.store8"),
+        _ => (2, "i32.load16_s", "i32.store16"),
+    }
+}
+
+// String of the text format, bytes outside of printable ASCII are written in hexadecimal
+fn wat_string(bytes: &[u8]) -> String {
+    let mut escaped = String::new();
+    for byte in bytes {
+        match byte {
+            b'"' | b'\\' => escaped.push_str(&format!("\\{}", *byte as char)),
+            0x20..=0x7E => escaped.push(*byte as char),
+            _ => escaped.push_str(&format!("\\{:02x}", byte)),
+        }
+    }
+    escaped
+}
+
+impl WatGenerator {
+    fn line(&mut self, text: impl Into<String>) {
+        self.body.push(format!("{}{}", "  ".repeat(self.depth + 2), text.into()));
+    }
+
+    fn lines(&mut self, code: Vec<String>) {
+        for text in code {
+            self.line(text);
+        }
+    }
+
+    fn local(&mut self, value_type: Types) -> String {
+        self.locals.push(value_type);
+        format!("$t{}", self.locals.len())
+    }
+
+    // Offset of a string in the data section, each text is stored once
+    fn string(&mut self, text: &str) -> u32 {
+        if let Some(offset) = self.strings.get(text) {
+            return *offset;
+        }
+        let offset = self.arrays_end + self.data.len() as u32;
+        self.data.extend_from_slice(text.as_bytes());
+        self.strings.insert(text.to_string(), offset);
+        offset
+    }
+
+    // Globals for the scalars and cells of the linear memory for the arrays, in the order of the names
+    fn layout(&mut self, symbols: &HashMap<String, Symbol>) {
+        let mut names: Vec<&String> = symbols.keys().collect();
+        names.sort();
+        let mut address: u32 = 0;
+        for name in names {
+            let symbol = &symbols[name];
+            let Some(symbol_type) = &symbol.Type else { continue };
+            match symbol.size.or_else(|| (symbol.Value.len() > 1).then_some(symbol.Value.len() as i16)) {
+                Some(size) => {
+                    let (width, _, _) = cell(symbol_type);
+                    address = address.next_multiple_of(width);
+                    self.storage.insert(name.clone(), Storage::Array(symbol_type.clone(), size, address));
+                    address += width * size.max(0) as u32;
+                },
+                None => {
+                    self.storage.insert(name.clone(), Storage::Global(symbol_type.clone()));
+                    self.globals.push(name.clone());
+                },
+            }
+        }
+        self.arrays_end = address;
+    }
+
+    fn finish(mut self) -> String {
+        let errors: Vec<(&str, u32, usize)> = RUNTIME_ERRORS[..3].iter()
+            .map(|(function, message)| (*function, self.string(message), message.len()))
+            .collect();
+        let mut code = String::from("(module\n");
+        code.push_str(IMPORTS);
+        let pages = (self.arrays_end as usize + self.data.len()).div_ceil(65536).max(1);
+        code.push_str(&format!("  (memory (export \"memory\") {})\n", pages));
+        code.push_str(&format!("  (data (i32.const {}) \"{}\")\n", self.arrays_end, wat_string(&self.data)));
+
+        for name in &self.globals {
+            let Some(Storage::Global(value_type)) = self.storage.get(name) else { continue };
+            let (value, immutable) = self.initial.get(name).copied().unwrap_or((Value::Integer(0), false));
+            let value = match (value_type, value) {
+                (Types::Float, Value::Integer(_)) => Value::Float(0.0),
+                (_, value) => value,
+            };
+            let declared_type = if immutable { wat_type(value_type).to_string() } else { format!("(mut {})", wat_type(value_type)) };
+            code.push_str(&format!("  (global {} {} ({}))\n", wat_name(name), declared_type, wat_value(&value)));
+        }
+
+        code.push_str("\n  (func $main (export \"main\")");
+        for (index, local_type) in self.locals.iter().enumerate() {
+            code.push_str(&format!(" (local $t{} {})", index + 1, wat_type(local_type)));
+        }
+        code.push('\n');
+        for line in &self.body {
+            code.push_str(line);
+            code.push('\n');
+        }
+        code.push_str("  )\n");
+        code.push_str(RUNTIME);
+
+        for (function, offset, length) in errors {
+            code.push_str(&format!(
+                "\n  (func ${}\n    i32.const {}\n    i32.const {}\n    call $mining_fail\n    unreachable)\n",
+                function, offset, length
+            ));
+        }
+        code.push_str(")\n");
+        code
+    }
+
+    fn storage(&self, name: &str) -> Result<Storage, CodegenError> {
+        self.storage.get(name).cloned().ok_or_else(|| CodegenError::UndeclaredName(name.to_string()))
+    }
+
+    // Same declaration order and initial values as the quadruplet generator
+    fn declarations(&mut self, declarations: &[Declaration]) -> Result<(), CodegenError> {
+        for declaration in declarations {
+            match declaration {
+                Declaration::Variable(type_decl, variables) => {
+                    let variable_type = to_types(type_decl);
+                    for variable in variables {
+                        let Variable::Initialized((name, _), expr) = variable else { continue };
+                        match constant_value(expr, &self.constants) {
+                            Some(value) if value.get_type() == variable_type => {
+                                self.initial.insert(name.clone(), (value, false));
+                            },
+                            _ => self.assignment(name, None, expr)?,
+                        }
+                    }
+                },
+                Declaration::ADEC(_, arrays) => {
+                    for array in arrays {
+                        match array {
+                            ArrayDecl::Simple(_, _) => {},
+                            ArrayDecl::Initialized((name, _), _, values) => self.array(name, values)?,
+                            ArrayDecl::InitializedString((name, _), _, (value, _)) => {
+                                // The literal still holds its surrounding double quotes
+                                let content = &value[1..value.len() - 1];
+                                let values: Vec<Expr> = if content.is_empty() {
+                                    vec![char_literal('\0')]
+                                } else {
+                                    content.chars().map(char_literal).collect()
+                                };
+                                self.array(name, &values)?;
+                            },
+                        }
+                    }
+                },
+                Declaration::Constant(type_decl, constants) => {
+                    let constant_type = to_types(type_decl);
+                    for constant in constants {
+                        let name = &constant.var.0;
+                        match constant_value(&constant.expr, &self.constants) {
+                            Some(value) if value.get_type() == constant_type => {
+                                self.constants.insert(name.clone(), value);
+                                self.initial.insert(name.clone(), (value, true));
+                            },
+                            value => {
+                                if let Some(value) = value {
+                                    self.constants.insert(name.clone(), value);
+                                }
+                                self.assignment(name, None, &constant.expr)?;
+                            },
+                        }
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // Stores the values at the start of main, repeated until every cell is filled
+    fn array(&mut self, name: &str, values: &[Expr]) -> Result<(), CodegenError> {
+        let Storage::Array(element, size, address) = self.storage(name)? else {
+            return Err(CodegenError::InvalidExpression(format!("'{}' is not an array", name)));
+        };
+        let (width, _, store) = cell(&element);
+        let mut locals = Vec::new();
+        for value in values {
+            let value = self.expression(value)?;
+            let local = self.local(value.value_type.clone());
+            self.lines(value.code);
+            self.line(format!("local.set {}", local));
+            locals.push(local);
+        }
+        for index in 0..size.max(0) as usize {
+            if locals.is_empty() {
+                break;
+            }
+            self.line(format!("i32.const {}", address + width * index as u32));
+            self.line(format!("local.get {}", locals[index % locals.len()]));
+            self.line(store);
+        }
+        Ok(())
+    }
+
+    fn instructions(&mut self, instructions: &[Instruction]) -> Result<(), CodegenError> {
+        for instruction in instructions {
+            match instruction {
+                Instruction::Assign(assignment) => {
+                    self.assignment(&assignment.var.0, assignment.index.as_ref(), &assignment.expr)?;
+                },
+                Instruction::If(if_stmt) => {
+                    let condition = self.condition(&if_stmt.condition)?;
+                    self.lines(condition);
+                    self.line("if");
+                    self.block(&if_stmt.then_block)?;
+                    if let Some(else_block) = &if_stmt.else_block {
+                        self.line("else");
+                        self.block(else_block)?;
+                    }
+                    self.line("end");
+                },
+                Instruction::For(for_stmt) => self.for_loop(for_stmt)?,
+                Instruction::Read(read_stmt) => {
+                    let name = &read_stmt.variable.0;
+                    let (value_type, address) = self.target(name, read_stmt.index.as_ref())?;
+                    let function = match value_type {
+                        Types::Float => "$mining_read_float",
+                        Types::Char => "$mining_read_char",
+                        _ => "$mining_read_int",
+                    };
+                    // The address is computed before the call, so the index is checked before reading
+                    match address {
+                        Some(address) => {
+                            self.lines(address);
+                            self.line(format!("call {}", function));
+                            self.line(cell(&value_type).2);
+                        },
+                        None => {
+                            self.line(format!("call {}", function));
+                            self.line(format!("global.set {}", wat_name(name)));
+                        },
+                    }
+                },
+                Instruction::Write(write_stmt) => {
+                    for element in &write_stmt.elements {
+                        match element {
+                            // The literal still holds its surrounding double quotes
+                            WriteElement::String((literal, _)) => self.write_string(&literal[1..literal.len() - 1]),
+                            WriteElement::Variable((name, _), index) => {
+                                let value = self.location(name, index.as_ref())?;
+                                let function = match value.value_type {
+                                    Types::Float => "$mining_write_float",
+                                    Types::Char => "$mining_write_char",
+                                    _ => "$mining_write_int",
+                                };
+                                self.lines(value.code);
+                                self.line(format!("call {}", function));
+                            },
+                        }
+                    }
+                    self.write_string("\n");
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn write_string(&mut self, text: &str) {
+        let offset = self.string(text);
+        self.line(format!("i32.const {}", offset));
+        self.line(format!("i32.const {}", text.len()));
+        self.line("call $mining_write_string");
+    }
+
+    fn block(&mut self, instructions: &[Instruction]) -> Result<(), CodegenError> {
+        self.depth += 1;
+        let result = self.instructions(instructions);
+        self.depth -= 1;
+        result
+    }
+
+    // FOR(var = start : step : end) runs while var < end, the end and the step
+    // are evaluated again at every iteration like in the quadruplets
+    fn for_loop(&mut self, for_stmt: &ForStmt) -> Result<(), CodegenError> {
+        let init = &for_stmt.init;
+        let counter = match &init.index {
+            Some(index) => Expr::SUBS(init.var.clone(), Box::new(index.clone())),
+            None => Expr::Variable(init.var.clone()),
+        };
+        self.loops += 1;
+        let label = self.loops;
+        self.assignment(&init.var.0, init.index.as_ref(), &init.expr)?;
+        self.line(format!("block $for{}_end", label));
+        self.depth += 1;
+        self.line(format!("loop $for{}", label));
+        self.depth += 1;
+        let condition = self.binary(&counter, "<", &for_stmt.condition)?;
+        self.lines(condition.code);
+        self.line("i32.eqz");
+        self.line(format!("br_if $for{}_end", label));
+        self.depth -= 1;
+        self.block(&for_stmt.body)?;
+        self.depth += 1;
+        let next = Expr::BinaryOp(Box::new(counter), BinOp::Add(0, 0), Box::new(for_stmt.step.clone()));
+        self.assignment(&init.var.0, init.index.as_ref(), &next)?;
+        self.line(format!("br $for{}", label));
+        self.depth -= 1;
+        self.line("end");
+        self.depth -= 1;
+        self.line("end");
+        Ok(())
+    }
+
+    // The value is computed before the index of the target is checked,
+    // through a local when both of them can fail
+    fn assignment(&mut self, name: &str, index: Option<&Expr>, expr: &Expr) -> Result<(), CodegenError> {
+        let value = self.expression(expr)?;
+        let (value_type, address) = self.target(name, index)?;
+        if value.value_type != value_type {
+            return Err(CodegenError::InvalidExpression(format!(
+                "{:?} value assigned to '{}' of type {:?}", value.value_type, name, value_type
+            )));
+        }
+        match address {
+            None => {
+                self.lines(value.code);
+                self.line(format!("global.set {}", wat_name(name)));
+            },
+            Some(address) if value.can_fail && address.len() > 1 => {
+                let local = self.local(value_type.clone());
+                self.lines(value.code);
+                self.line(format!("local.set {}", local));
+                self.lines(address);
+                self.line(format!("local.get {}", local));
+                self.line(cell(&value_type).2);
+            },
+            Some(address) => {
+                self.lines(address);
+                self.lines(value.code);
+                self.line(cell(&value_type).2);
+            },
+        }
+        Ok(())
+    }
+
+    // Type of a variable or array cell and the address of the cell
+    fn target(&mut self, name: &str, index: Option<&Expr>) -> Result<(Types, Option<Vec<String>>), CodegenError> {
+        match (index, self.storage(name)?) {
+            (None, Storage::Global(value_type)) => Ok((value_type, None)),
+            (Some(index), Storage::Array(element, size, address)) => {
+                let (width, _, _) = cell(&element);
+                let code = match constant_value(index, &self.constants) {
+                    // Indexes are checked unless they are constants within the bounds
+                    Some(Value::Integer(i)) if (0..size).contains(&i) => vec![format!("i32.const {}", address + width * i as u32)],
+                    _ => {
+                        let index = self.expression(index)?;
+                        if index.value_type != Types::Integer {
+                            return Err(CodegenError::InvalidExpression(format!("index of '{}' is not an INTEGER", name)));
+                        }
+                        let mut code = index.code;
+                        code.push(format!("i32.const {}", size));
+                        code.push("call $mining_index".to_string());
+                        if width > 1 {
+                            code.push(format!("i32.const {}", width));
+                            code.push("i32.mul".to_string());
+                        }
+                        code.push(format!("i32.const {}", address));
+                        code.push("i32.add".to_string());
+                        code
+                    },
+                };
+                Ok((element, Some(code)))
+            },
+            _ => Err(CodegenError::InvalidExpression(format!("'{}' is used with the wrong shape", name))),
+        }
+    }
+
+    fn location(&mut self, name: &str, index: Option<&Expr>) -> Result<WatExpr, CodegenError> {
+        let (value_type, address) = self.target(name, index)?;
+        Ok(match address {
+            None => WatExpr { code: vec![format!("global.get {}", wat_name(name))], value_type, can_fail: false },
+            Some(mut code) => {
+                let can_fail = code.len() > 1;
+                code.push(cell(&value_type).1.to_string());
+                WatExpr { code, value_type, can_fail }
+            },
+        })
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<WatExpr, CodegenError> {
+        match expr {
+            Expr::Literal(literal) => match to_value(literal) {
+                Some(value) => Ok(WatExpr { code: vec![wat_value(&value)], value_type: value.get_type(), can_fail: false }),
+                None => Err(CodegenError::InvalidExpression("array literal used as a value".to_string())),
+            },
+            Expr::Variable((name, _)) => self.location(name, None),
+            Expr::SUBS((name, _), index) => self.location(name, Some(index)),
+            Expr::BinaryOp(left, op, right) => {
+                let operator = match op {
+                    BinOp::Add(_, _) => "+",
+                    BinOp::Sub(_, _) => "-",
+                    BinOp::Mul(_, _) => "*",
+                    BinOp::Div(_, _) => "/",
+                };
+                self.binary(left, operator, right)
+            },
+        }
+    }
+
+    // Arithmetic operators and comparisons, the operands are evaluated from left to right
+    fn binary(&mut self, left: &Expr, operator: &str, right: &Expr) -> Result<WatExpr, CodegenError> {
+        let left = self.expression(left)?;
+        let right = self.expression(right)?;
+        if left.value_type != right.value_type {
+            return Err(CodegenError::InvalidExpression(format!(
+                "operands of '{}' have different types {:?} and {:?}", operator, left.value_type, right.value_type
+            )));
+        }
+
+        let (instruction, value_type, can_fail) = match (operator, &left.value_type) {
+            ("<" | "<=" | ">" | ">=" | "==" | "!=", Types::Float) => {
+                let instruction = match operator {
+                    "<" => "f32.lt",
+                    "<=" => "f32.le",
+                    ">" => "f32.gt",
+                    ">=" => "f32.ge",
+                    "==" => "f32.eq",
+                    _ => "f32.ne",
+                };
+                (instruction, Types::Integer, false)
+            },
+            ("<" | "<=" | ">" | ">=" | "==" | "!=", value_type) => {
+                let unsigned = !is_signed(value_type);
+                let instruction = match (operator, unsigned) {
+                    ("<", false) => "i32.lt_s",
+                    ("<=", false) => "i32.le_s",
+                    (">", false) => "i32.gt_s",
+                    (">=", false) => "i32.ge_s",
+                    ("<", true) => "i32.lt_u",
+                    ("<=", true) => "i32.le_u",
+                    (">", true) => "i32.gt_u",
+                    (">=", true) => "i32.ge_u",
+                    ("==", _) => "i32.eq",
+                    _ => "i32.ne",
+                };
+                (instruction, Types::Integer, false)
+            },
+            (_, Types::Integer) => {
+                let function = match operator {
+                    "+" => "call $mining_add",
+                    "-" => "call $mining_sub",
+                    "*" => "call $mining_mul",
+                    _ => "call $mining_div",
+                };
+                (function, Types::Integer, true)
+            },
+            (_, Types::Float) => {
+                let instruction = match operator {
+                    "+" => "f32.add",
+                    "-" => "f32.sub",
+                    "*" => "f32.mul",
+                    _ => "f32.div",
+                };
+                (instruction, Types::Float, false)
+            },
+            ("+", Types::Char) => ("call $mining_char_add", Types::Char, false),
+            ("-", Types::Char) => ("call $mining_char_sub", Types::Char, false),
+            _ => {
+                return Err(CodegenError::InvalidExpression(format!(
+                    "'{}' is not defined for {:?} values", operator, left.value_type
+                )));
+            },
+        };
+        let mut code = left.code;
+        code.extend(right.code);
+        code.push(instruction.to_string());
+        Ok(WatExpr { code, value_type, can_fail: can_fail || left.can_fail || right.can_fail })
+    }
+
+    // Leaves 1 or 0 on the stack, the right side of && and || is evaluated only when needed
+    // like the jumps of the quadruplets
+    fn condition(&mut self, condition: &Condition) -> Result<Vec<String>, CodegenError> {
+        match condition {
+            Condition::Basic(basic) => {
+                let operator = match basic.operator {
+                    RelOp::Gt(_, _) => ">",
+                    RelOp::Lt(_, _) => "<",
+                    RelOp::Ge(_, _) => ">=",
+                    RelOp::Le(_, _) => "<=",
+                    RelOp::Eq(_, _) => "==",
+                    RelOp::Ne(_, _) => "!=",
+                };
+                Ok(self.binary(&basic.left, operator, &basic.right)?.code)
+            },
+            Condition::Logic(left, operator, right) => {
+                let mut code = self.condition(left)?;
+                let right = self.condition(right)?.into_iter().map(|line| format!("  {}", line));
+                code.push("if (result i32)".to_string());
+                match operator {
+                    LogOp::And(_, _) => {
+                        code.extend(right);
+                        code.push("else".to_string());
+                        code.push("  i32.const 0".to_string());
+                    },
+                    LogOp::Or(_, _) => {
+                        code.push("  i32.const 1".to_string());
+                        code.push("else".to_string());
+                        code.extend(right);
+                    },
+                }
+                code.push("end".to_string());
+                Ok(code)
+            },
+            Condition::Not(inner) => {
+                let mut code = self.condition(inner)?;
+                code.push("i32.eqz".to_string());
+                Ok(code)
+            },
+        }
+    }
+}
+
+fn char_literal(c: char) -> Expr {
+    Expr::Literal(TypeValue::Char((c, (0, 0))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::simwat::run_wat;
+    use crate::Test::tests::{analyze_source, PROGRAM};
+
+    fn run(module: &str, input: &str) -> (String, String, i32) {
+        let execution = run_wat(module, input.as_bytes(), 100_000).unwrap();
+        (execution.stdout(), execution.stderr(), execution.exit_code)
+    }
+
+    #[test]
+    fn test_generated_wat() {
+        let (program, symbols) = analyze_source(PROGRAM);
+        let module = generate_wat(&program, &symbols).unwrap();
+        // Arrays in the order of their names: A at 0, B at 6 and Word at 14
+        assert!(module.contains("(global $m_Size i32 (i32.const 3))"));
+        assert!(module.contains("(global $m_G (mut f32) (f32.const 1.5))"));
+        assert!(module.contains("(data (i32.const 19) \"S = "));
+        assert!(module.contains("block $for1_end\n      loop $for1\n"));
+        assert!(module.contains("br_if $for1_end"));
+
+        assert_eq!(run(&module, "-7 2.25\n a"), ("S = -21 100%\n2ab\n1.5D\nyes\n3\n".to_string(), String::new(), 0));
+        assert_eq!(run(&module, "-1 1.0 x"), ("S = -3 100%\n2ab\n0.666667[\nno\n".to_string(), "Runtime error: Division by zero\n".to_string(), 1));
+        assert_eq!(run(&module, "40000").1, "Runtime error: Invalid INTEGER input\n");
+        assert_eq!(run(&module, "5 1e3").1, "Runtime error: Invalid FLOAT input\n");
+        assert_eq!(run(&module, "5 1.0").1, "Runtime error: Unexpected end of input\n");
+    }
+
+    #[test]
+    fn test_evaluation_order() {
+        let (program, symbols) = analyze_source(r#"
+            VAR_GLOBAL {
+                INTEGER I, X;
+                INTEGER A[2] = [1];
+            }
+            DECLARATION { }
+            INSTRUCTION {
+                READ(I);
+                READ(X);
+                A[I] = X + A[0];
+            }
+        "#);
+        let module = generate_wat(&program, &symbols).unwrap();
+        // The value is computed before the index is checked
+        assert!(module.contains("call $mining_add\n    local.set $t2\n    global.get $m_I\n    i32.const 2\n    call $mining_index\n"));
+        assert_eq!(run(&module, "5 32767").1, "Runtime error: INTEGER overflow\n");
+        assert_eq!(run(&module, "5 0").1, "Runtime error: Array index out of bounds\n");
+        assert_eq!(run(&module, "1 2"), (String::new(), String::new(), 0));
+    }
+}
@@ -0,0 +1,960 @@
+//! x86-64 backend: GAS assembly in Intel syntax for Linux
+//!
+//! Every variable and array gets a static slot, in `.data` when the program starts by assigning
+//! it a constant and in `.bss` otherwise. Temporaries live in the registers the runtime preserves
+//! (rbx, rbp, r12-r15 and xmm2-xmm15) or in stack slots when given an allocation from
+//! `codegen::regalloc`, in static slots otherwise. Each quadruplet loads its operands, computes
+//! and stores its result, INTEGER values are sign extended from 16 bits and FLOAT values use the
+//! scalar SSE instructions. READ and WRITE call a small runtime appended to the output, it only
+//! uses Linux system calls so the program is linked without the C library:
+//!
+//! ```text
+//! as prog.s -o prog.o && ld prog.o -o prog
+//! ```
+
+use std::collections::HashSet;
+use crate::codegen::error::CodegenError;
+use crate::codegen::regalloc::{Allocation, Location, RegisterClass};
+use crate::codegen::{is_signed, static_initializers, storage_size, symbol};
+use crate::Semantic::quadruplets::{collect_symbols, jump_target, Operand, Operator, Quadruplet, Value};
+use crate::Semantic::ts::Types;
+
+// Registers holding the first and second operand of a quadruplet
+const INTEGER_REGISTERS: [&str; 2] = ["eax", "ecx"];
+const FLOAT_REGISTERS: [&str; 2] = ["xmm0", "xmm1"];
+
+// Registers given to temporaries, as 32 and 64 bits names for the integer ones
+const ALLOCATABLE_INTEGER: [(&str, &str); 6] = [("ebx", "rbx"), ("ebp", "rbp"), ("r12d", "r12"), ("r13d", "r13"), ("r14d", "r14"), ("r15d", "r15")];
+const ALLOCATABLE_FLOAT: [&str; 14] = [
+    "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10", "xmm11", "xmm12", "xmm13", "xmm14", "xmm15",
+];
+
+/// Number of integer and float registers `generate_x86_64_with` can give to temporaries
+pub const ALLOCATABLE_REGISTERS: [usize; 2] = [ALLOCATABLE_INTEGER.len(), ALLOCATABLE_FLOAT.len()];
+
+/// Run-time errors: label of the handler and message printed on stderr
+pub const RUNTIME_ERRORS: [(&str, &str); 6] = [
+    ("mining_division_error", "Division by zero"),
+    ("mining_overflow_error", "INTEGER overflow"),
+    ("mining_index_error", "Array index out of bounds"),
+    ("mining_integer_input_error", "Invalid INTEGER input"),
+    ("mining_float_input_error", "Invalid FLOAT input"),
+    ("mining_end_of_input_error", "Unexpected end of input"),
+];
+
+/// READ and WRITE routines, they may clobber every caller-saved register
+const RUNTIME: &str = r#"
+# MinING runtime, Linux system calls only
+mining_write_string:                    # rdi = address, rsi = length
+    mov rdx, rsi
+    mov rsi, rdi
+    mov edi, 1
+    mov eax, 1
+    syscall
+    ret
+
+mining_write_char:                      # edi = CHAR
+    mov byte ptr [rip + mining_output], dil
+    lea rdi, [rip + mining_output]
+    mov esi, 1
+    jmp mining_write_string
+
+mining_digits:                          # writes rax in decimal before rsi, rsi ends on the first digit
+    mov ecx, 10
+1:  xor edx, edx
+    div rcx
+    add dl, 48
+    dec rsi
+    mov byte ptr [rsi], dl
+    test rax, rax
+    jnz 1b
+    ret
+
+mining_write_buffer:                    # writes from rsi to the end of the output buffer
+    lea rdi, [rip + mining_output + 64]
+    sub rdi, rsi
+    xchg rdi, rsi
+    jmp mining_write_string
+
+mining_write_int:                       # edi = INTEGER sign extended to 32 bits
+    lea rsi, [rip + mining_output + 64]
+    mov r8d, edi
+    mov eax, edi
+    test eax, eax
+    jns 1f
+    neg eax
+1:  call mining_digits
+    test r8d, r8d
+    jns mining_write_buffer
+    dec rsi
+    mov byte ptr [rsi], 45
+    jmp mining_write_buffer
+
+mining_write_float:                     # xmm0 = FLOAT
+    lea rsi, [rip + mining_output + 64]
+    cvtss2sd xmm0, xmm0
+    movq rax, xmm0
+    mov r8, rax                         # the sign is bit 63
+    btr rax, 63
+    movq xmm0, rax
+    mov rcx, 0x7FF0000000000000
+    cmp rax, rcx
+    ja mining_write_nan
+    je mining_write_infinity
+    xor r9d, r9d                        # decimal exponent
+1:  ucomisd xmm0, qword ptr [rip + mining_1e9]
+    jb 2f
+    divsd xmm0, qword ptr [rip + mining_10]
+    inc r9d
+    jmp 1b
+2:  mulsd xmm0, qword ptr [rip + mining_1e6]
+    cvtsd2si r10, xmm0                  # rounds half to even
+    test r9d, r9d
+    jz 3f
+    mov eax, r9d
+    call mining_digits
+    dec rsi
+    mov byte ptr [rsi], 101
+3:  mov rax, r10
+    xor edx, edx
+    mov ecx, 1000000
+    div rcx
+    mov r10, rax                        # integer part
+    mov rax, rdx                        # six digits of fraction
+    mov r9d, 6
+    mov ecx, 10
+4:  cmp r9d, 1                          # drops the trailing zeros but one
+    je 5f
+    mov r11, rax
+    xor edx, edx
+    div rcx
+    test rdx, rdx
+    jz 6f
+    mov rax, r11
+    jmp 5f
+6:  dec r9d
+    jmp 4b
+5:  xor edx, edx
+    div rcx
+    add dl, 48
+    dec rsi
+    mov byte ptr [rsi], dl
+    dec r9d
+    jnz 5b
+    dec rsi
+    mov byte ptr [rsi], 46
+    mov rax, r10
+    call mining_digits
+    test r8, r8
+    jns mining_write_buffer
+    dec rsi
+    mov byte ptr [rsi], 45
+    jmp mining_write_buffer
+
+mining_write_nan:
+    lea rdi, [rip + mining_nan]
+    mov esi, 3
+    jmp mining_write_string
+
+mining_write_infinity:
+    lea rdi, [rip + mining_infinity]
+    mov esi, 4
+    test r8, r8
+    js mining_write_string
+    inc rdi
+    dec esi
+    jmp mining_write_string
+
+mining_getc:                            # next input byte in eax, -1 at the end of the input
+    mov rax, qword ptr [rip + mining_input_position]
+    cmp rax, qword ptr [rip + mining_input_length]
+    jb 1f
+    xor eax, eax
+    xor edi, edi
+    lea rsi, [rip + mining_input]
+    mov edx, 4096
+    syscall
+    mov qword ptr [rip + mining_input_position], 0
+    mov qword ptr [rip + mining_input_length], 0
+    test rax, rax
+    jle 2f
+    mov qword ptr [rip + mining_input_length], rax
+    xor eax, eax
+1:  lea rcx, [rip + mining_input]
+    movzx edx, byte ptr [rcx + rax]
+    inc rax
+    mov qword ptr [rip + mining_input_position], rax
+    mov eax, edx
+    ret
+2:  mov eax, -1
+    ret
+
+mining_skip_spaces:                     # first input byte that is not a space in eax
+1:  call mining_getc
+    cmp eax, 32
+    je 1b
+    lea ecx, [rax - 9]
+    cmp ecx, 4
+    jbe 1b
+    cmp eax, -1
+    je mining_end_of_input_error
+    ret
+
+mining_end_of_token:                    # ZF set when eax is a space or the end of the input
+    cmp eax, -1
+    je 1f
+    cmp eax, 32
+    je 1f
+    lea ecx, [rax - 9]
+    cmp ecx, 4
+    ja 1f
+    cmp eax, eax
+1:  ret
+
+mining_read_int:                        # INTEGER in ax
+    call mining_skip_spaces
+    xor r8d, r8d                        # 1 for a negative value
+    cmp eax, 43
+    je 1f
+    cmp eax, 45
+    jne 2f
+    inc r8d
+1:  call mining_getc
+2:  xor r9d, r9d                        # value
+    xor r10d, r10d                      # number of digits
+3:  lea ecx, [rax - 48]
+    cmp ecx, 9
+    ja 4f
+    imul r9d, r9d, 10
+    add r9d, ecx
+    cmp r9d, 32768
+    ja mining_integer_input_error
+    inc r10d
+    call mining_getc
+    jmp 3b
+4:  call mining_end_of_token
+    jne mining_integer_input_error
+    test r10d, r10d
+    jz mining_integer_input_error
+    mov eax, r9d
+    test r8d, r8d
+    jz 5f
+    neg eax
+    ret
+5:  cmp eax, 32767
+    ja mining_integer_input_error
+    ret
+
+mining_read_float:                      # FLOAT in xmm0
+    push r12
+    push r13
+    call mining_skip_spaces
+    xor r8d, r8d                        # 1 for a negative value
+    cmp eax, 43
+    je 1f
+    cmp eax, 45
+    jne 2f
+    inc r8d
+1:  call mining_getc
+2:  xor r9d, r9d                        # digits read as an integer
+    xor r10d, r10d                      # number of digits
+    xor r12d, r12d                      # number of digits after the point
+    xor r13d, r13d                      # 1 after the point
+3:  cmp eax, 46
+    jne 4f
+    test r13d, r13d
+    jnz mining_float_input_error
+    inc r13d
+    call mining_getc
+    jmp 3b
+4:  lea ecx, [rax - 48]
+    cmp ecx, 9
+    ja 5f
+    imul r9, r9, 10
+    add r9, rcx
+    inc r10d
+    add r12d, r13d
+    cmp r10d, 18
+    ja mining_float_input_error
+    call mining_getc
+    jmp 3b
+5:  call mining_end_of_token
+    jne mining_float_input_error
+    test r10d, r10d
+    jz mining_float_input_error
+    cvtsi2sd xmm0, r9
+    lea rcx, [rip + mining_powers]
+    divsd xmm0, qword ptr [rcx + r12*8]
+    test r8d, r8d
+    jz 6f
+    movq rax, xmm0
+    btc rax, 63
+    movq xmm0, rax
+6:  cvtsd2ss xmm0, xmm0
+    pop r13
+    pop r12
+    ret
+
+mining_read_char:                       # CHAR in al
+    jmp mining_skip_spaces
+
+mining_fail:                            # rsi = message, rdx = length, followed by the location
+    mov edi, 2
+    mov eax, 1
+    syscall
+    mov rsi, qword ptr [rip + mining_location]
+    test rsi, rsi
+    jnz 1f
+    lea rsi, [rip + mining_no_location]
+1:  mov edx, dword ptr [rsi]            # locations are a length and the text
+    add rsi, 4
+    mov edi, 2
+    mov eax, 1
+    syscall
+    mov edi, 1
+    mov eax, 60
+    syscall
+
+    .section .rodata
+    .balign 8
+mining_1e6: .double 1000000.0
+mining_1e9: .double 1000000000.0
+mining_10: .double 10.0
+mining_powers:
+    .double 1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9
+    .double 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18
+mining_nan: .ascii "nan"
+mining_infinity: .ascii "-inf"
+    .balign 4
+mining_no_location: .long 1
+    .ascii "\n"
+
+    .bss
+    .balign 8
+mining_location: .zero 8                # location of the failing instruction, 0 when unknown
+mining_input_position: .zero 8
+mining_input_length: .zero 8
+mining_output: .zero 64
+mining_input: .zero 4096
+"#;
+
+/// Lowers the quadruplets to a complete assembly program, `_start` being the first quadruplet
+pub fn generate_x86_64(quadruplets: &[Quadruplet]) -> Result<String, CodegenError> {
+    generate_x86_64_with(quadruplets, &Allocation::default())
+}
+
+/// Same as `generate_x86_64` with the temporaries placed by a register allocation made for
+/// `ALLOCATABLE_REGISTERS`, stack slots are 8 bytes apart from rsp
+pub fn generate_x86_64_with(quadruplets: &[Quadruplet], allocation: &Allocation) -> Result<String, CodegenError> {
+    let mut generator = X86Generator::new(quadruplets, allocation);
+    generator.generate()?;
+    Ok(generator.finish())
+}
+
+// String literal for the .ascii directive
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'"' | b'\\' => escaped.push_str(&format!("\\{}", byte as char)),
+            0x20..=0x7E => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
+
+fn char_code(c: char) -> u8 {
+    c as u32 as u8
+}
+
+struct X86Generator<'a> {
+    quadruplets: &'a [Quadruplet],
+    allocation: &'a Allocation,
+    current: usize,                 // Index of the quadruplet being lowered
+    text: String,
+    floats: Vec<u32>,               // FLOAT constants by bit pattern, labelled .Lf{index}
+    strings: Vec<String>,           // WRITE strings, labelled .Ls{index}
+    locations: Vec<String>,         // Locations printed after runtime errors, labelled .Lo{index}
+    stubs: Vec<(usize, &'static str)>,  // Checks of located quadruplets: location and handler, labelled .Lx{index}
+}
+
+impl<'a> X86Generator<'a> {
+    fn new(quadruplets: &'a [Quadruplet], allocation: &'a Allocation) -> Self {
+        X86Generator {
+            quadruplets, allocation, current: 0, text: String::new(), floats: Vec::new(), strings: Vec::new(),
+            locations: Vec::new(), stubs: Vec::new(),
+        }
+    }
+
+    fn line(&mut self, instruction: impl AsRef<str>) {
+        self.text.push_str("    ");
+        self.text.push_str(instruction.as_ref());
+        self.text.push('\n');
+    }
+
+    fn unsupported(&self, reason: &str) -> CodegenError {
+        CodegenError::Unsupported(self.current, self.quadruplets[self.current].to_string(), reason.to_string())
+    }
+
+    fn float_label(&mut self, value: f32) -> String {
+        let bits = value.to_bits();
+        let index = self.floats.iter().position(|b| *b == bits).unwrap_or_else(|| {
+            self.floats.push(bits);
+            self.floats.len() - 1
+        });
+        format!(".Lf{}", index)
+    }
+
+    // Label index of the location of the current quadruplet, None when it has no origin
+    fn location(&mut self) -> Option<usize> {
+        let location = self.quadruplets[self.current].origin.as_ref()?.location();
+        Some(self.locations.iter().position(|known| *known == location).unwrap_or_else(|| {
+            self.locations.push(location);
+            self.locations.len() - 1
+        }))
+    }
+
+    // Conditional jump to an error handler, through a stub recording the location when it is known
+    fn check(&mut self, jump: &str, handler: &'static str) {
+        match self.location() {
+            Some(location) => {
+                self.stubs.push((location, handler));
+                self.line(format!("{} .Lx{}", jump, self.stubs.len() - 1));
+            },
+            None => self.line(format!("{} {}", jump, handler)),
+        }
+    }
+
+    fn generate(&mut self) -> Result<(), CodegenError> {
+        let (initialized, _) = static_initializers(self.quadruplets);
+        let targets: HashSet<usize> = self.quadruplets.iter().filter_map(jump_target).collect();
+
+        self.text.push_str("    .intel_syntax noprefix\n    .text\n    .globl _start\n_start:\n");
+        if self.allocation.stack_slots > 0 {
+            self.line(format!("sub rsp, {}", self.allocation.stack_slots * 8));
+        }
+        // Temporaries read before being assigned start at zero like the static slots
+        let allocation = self.allocation;
+        for (name, class) in &allocation.undefined {
+            match (allocation.locations.get(name), class) {
+                (Some(Location::Register(register)), RegisterClass::Integer) => self.line(format!("xor {0}, {0}", ALLOCATABLE_INTEGER[*register].0)),
+                (Some(Location::Register(register)), RegisterClass::Float) => self.line(format!("xorps {0}, {0}", ALLOCATABLE_FLOAT[*register])),
+                (Some(Location::Stack(slot)), _) => self.line(format!("mov qword ptr [rsp + {}], 0", slot * 8)),
+                (None, _) => {},
+            }
+        }
+        let quadruplets = self.quadruplets;
+        for (index, quadruplet) in quadruplets.iter().enumerate().skip(initialized) {
+            self.current = index;
+            if targets.contains(&index) {
+                self.text.push_str(&format!(".Lq{}:\n", index));
+            }
+            self.text.push_str(&format!("    # {}: {}\n", index, quadruplet));
+            self.quadruplet(quadruplet)?;
+        }
+        self.text.push_str(&format!(".Lq{}:\n", self.quadruplets.len()));
+        self.line("xor edi, edi");
+        self.line("mov eax, 60");
+        self.line("syscall");
+        Ok(())
+    }
+
+    // Register holding an allocated temporary: the 32 and 64 bits names of an integer register,
+    // twice the name of a float one
+    fn register(&self, operand: &Operand) -> Option<(&'static str, &'static str)> {
+        match (self.allocation.location(operand), operand.value_type()) {
+            (Some(Location::Register(register)), Some(Types::Float)) => Some((ALLOCATABLE_FLOAT[register], ALLOCATABLE_FLOAT[register])),
+            (Some(Location::Register(register)), _) => Some(ALLOCATABLE_INTEGER[register]),
+            _ => None,
+        }
+    }
+
+    // Memory operand of a variable, temporary or array element. The index of an array element
+    // is checked against the bounds of the array, it is kept in r11 and the base in r10
+    fn address(&mut self, operand: &Operand) -> Result<String, CodegenError> {
+        match operand {
+            Operand::Variable(name, _) => Ok(format!("[rip + {}]", symbol(name))),
+            Operand::Temporary(_, _) => match self.allocation.location(operand) {
+                Some(Location::Stack(slot)) => Ok(format!("[rsp + {}]", slot * 8)),
+                Some(Location::Register(_)) => Err(self.unsupported("a temporary in a register has no address")),
+                None => Ok(format!("[rip + {}]", symbol(&operand.to_string()))),
+            },
+            Operand::ArrayElement(name, Types::Array(element, size), index) => {
+                let element_size = storage_size(element);
+                if let Operand::Constant(Value::Integer(i)) = index.as_ref() {
+                    if (0..*size).contains(i) {
+                        return Ok(format!("[rip + {} + {}]", symbol(name), *i as usize * element_size));
+                    }
+                }
+                match index.as_ref() {
+                    Operand::Constant(Value::Integer(i)) => self.line(format!("mov r11, {}", i)),
+                    // Allocated registers hold INTEGER values sign extended to 32 bits
+                    index if self.register(index).is_some() => {
+                        let (register, _) = self.register(index).unwrap();
+                        self.line(format!("movsxd r11, {}", register));
+                    },
+                    Operand::Variable(_, Types::Integer) | Operand::Temporary(_, Types::Integer) => {
+                        let index = self.address(index)?;
+                        self.line(format!("movsx r11, word ptr {}", index));
+                    },
+                    _ => return Err(self.unsupported("array indices are INTEGER values")),
+                }
+                self.line(format!("cmp r11, {}", size));
+                self.check("jae", "mining_index_error");
+                self.line(format!("lea r10, [rip + {}]", symbol(name)));
+                Ok(format!("[r10 + r11*{}]", element_size))
+            },
+            _ => Err(self.unsupported("expected a variable, a temporary or an array element")),
+        }
+    }
+
+    // Loads an operand in the first or second register of its type and returns the type
+    fn load(&mut self, operand: &Operand, slot: usize) -> Result<Types, CodegenError> {
+        let (integer, float) = (INTEGER_REGISTERS[slot], FLOAT_REGISTERS[slot]);
+        let value_type = match operand.value_type() {
+            Some(t @ (Types::Integer | Types::Float | Types::Char)) => t,
+            _ => return Err(self.unsupported("expected an INTEGER, FLOAT or CHAR value")),
+        };
+        match operand {
+            Operand::Constant(Value::Integer(i)) => self.line(format!("mov {}, {}", integer, i)),
+            Operand::Constant(Value::Char(c)) => self.line(format!("mov {}, {}", integer, char_code(*c))),
+            Operand::Constant(Value::Float(f)) => {
+                let label = self.float_label(*f);
+                self.line(format!("movss {}, dword ptr [rip + {}]", float, label));
+            },
+            _ if self.register(operand).is_some() => {
+                let (register, _) = self.register(operand).unwrap();
+                match value_type {
+                    Types::Float => self.line(format!("movaps {}, {}", float, register)),
+                    _ => self.line(format!("mov {}, {}", integer, register)),
+                }
+            },
+            _ => {
+                let address = self.address(operand)?;
+                match value_type {
+                    Types::Integer => self.line(format!("movsx {}, word ptr {}", integer, address)),
+                    Types::Char => self.line(format!("movzx {}, byte ptr {}", integer, address)),
+                    _ => self.line(format!("movss {}, dword ptr {}", float, address)),
+                }
+            },
+        }
+        Ok(value_type)
+    }
+
+    // Stores eax, al or xmm0 depending on the type of the value
+    fn store(&mut self, operand: Option<&Operand>, value_type: &Types) -> Result<(), CodegenError> {
+        let operand = operand.ok_or_else(|| self.unsupported("missing result"))?;
+        if operand.value_type().as_ref() != Some(value_type) {
+            return Err(self.unsupported("the result does not have the type of the value"));
+        }
+        if let Some((register, _)) = self.register(operand) {
+            match value_type {
+                Types::Integer => self.line(format!("movsx {}, ax", register)),
+                Types::Char => self.line(format!("movzx {}, al", register)),
+                _ => self.line(format!("movaps {}, xmm0", register)),
+            }
+            return Ok(());
+        }
+        let address = self.address(operand)?;
+        match value_type {
+            Types::Integer => self.line(format!("mov word ptr {}, ax", address)),
+            Types::Char => self.line(format!("mov byte ptr {}, al", address)),
+            _ => self.line(format!("movss dword ptr {}, xmm0", address)),
+        }
+        Ok(())
+    }
+
+    // Truth value of an operand in al: 1 unless it is zero
+    fn truth(&mut self, operand: &Operand) -> Result<(), CodegenError> {
+        if self.load(operand, 0)? == Types::Float {
+            // NaN is not zero, so an unordered comparison is true
+            self.line("xorps xmm1, xmm1");
+            self.line("ucomiss xmm0, xmm1");
+            self.line("setne al");
+            self.line("setp cl");
+            self.line("or al, cl");
+        } else {
+            self.line("test eax, eax");
+            self.line("setne al");
+        }
+        Ok(())
+    }
+
+    fn operands(&self, quadruplet: &Quadruplet) -> Result<(Operand, Operand), CodegenError> {
+        match (&quadruplet.operand1, &quadruplet.operand2) {
+            (Some(a), Some(b)) => Ok((a.clone(), b.clone())),
+            _ => Err(self.unsupported("expected two operands")),
+        }
+    }
+
+    fn quadruplet(&mut self, quadruplet: &Quadruplet) -> Result<(), CodegenError> {
+        let result = quadruplet.result.as_ref();
+        match quadruplet.operator {
+            Operator::Assign => {
+                let value = quadruplet.operand1.as_ref().ok_or_else(|| self.unsupported("missing value"))?;
+                let value_type = self.load(value, 0)?;
+                self.store(result, &value_type)
+            },
+            Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide => {
+                let (a, b) = self.operands(quadruplet)?;
+                let value_type = self.arithmetic(&quadruplet.operator, &a, &b)?;
+                self.store(result, &value_type)
+            },
+            Operator::GreaterThan | Operator::LessThan | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual | Operator::Equal | Operator::NotEqual => {
+                let (a, b) = self.operands(quadruplet)?;
+                self.comparison(&quadruplet.operator, &a, &b)?;
+                self.line("movzx eax, al");
+                self.store(result, &Types::Integer)
+            },
+            Operator::LogicalAnd | Operator::LogicalOr => {
+                let (a, b) = self.operands(quadruplet)?;
+                self.truth(&a)?;
+                self.line("mov dl, al");
+                self.truth(&b)?;
+                self.line(if quadruplet.operator == Operator::LogicalAnd { "and al, dl" } else { "or al, dl" });
+                self.line("movzx eax, al");
+                self.store(result, &Types::Integer)
+            },
+            Operator::LogicalNot => {
+                let value = quadruplet.operand1.as_ref().ok_or_else(|| self.unsupported("missing value"))?;
+                self.truth(value)?;
+                self.line("xor al, 1");
+                self.line("movzx eax, al");
+                self.store(result, &Types::Integer)
+            },
+            Operator::Read => {
+                let value_type = result.and_then(Operand::value_type).ok_or_else(|| self.unsupported("missing result"))?;
+                // Invalid input is detected by the runtime, which reads the location from memory
+                if let Some(location) = self.location() {
+                    self.line(format!("lea rax, [rip + .Lo{}]", location));
+                    self.line("mov qword ptr [rip + mining_location], rax");
+                }
+                match value_type {
+                    Types::Integer => self.line("call mining_read_int"),
+                    Types::Float => self.line("call mining_read_float"),
+                    Types::Char => self.line("call mining_read_char"),
+                    Types::Array(_, _) => return Err(self.unsupported("READ of a whole array")),
+                }
+                self.store(result, &value_type)
+            },
+            Operator::Write => match &quadruplet.operand1 {
+                Some(Operand::String(text)) => {
+                    self.strings.push(text.clone());
+                    self.line(format!("lea rdi, [rip + .Ls{}]", self.strings.len() - 1));
+                    self.line(format!("mov esi, {}", text.len()));
+                    self.line("call mining_write_string");
+                    Ok(())
+                },
+                Some(value) => {
+                    match self.load(value, 0)? {
+                        Types::Integer => {
+                            self.line("mov edi, eax");
+                            self.line("call mining_write_int");
+                        },
+                        Types::Char => {
+                            self.line("mov edi, eax");
+                            self.line("call mining_write_char");
+                        },
+                        _ => self.line("call mining_write_float"),
+                    }
+                    Ok(())
+                },
+                None => Err(self.unsupported("missing value")),
+            },
+            Operator::Goto | Operator::IfTrue | Operator::IfFalse => {
+                let target = jump_target(quadruplet).ok_or_else(|| self.unsupported("missing target"))?;
+                match (&quadruplet.operator, &quadruplet.operand1) {
+                    (Operator::Goto, _) => {},
+                    (_, Some(condition)) => {
+                        self.truth(condition)?;
+                        self.line("test al, al");
+                    },
+                    _ => return Err(self.unsupported("missing condition")),
+                }
+                let jump = match quadruplet.operator {
+                    Operator::Goto => "jmp",
+                    Operator::IfTrue => "jnz",
+                    _ => "jz",
+                };
+                self.line(format!("{} .Lq{}", jump, target));
+                Ok(())
+            },
+            Operator::For => Err(self.unsupported("For must be lowered to jumps")),
+        }
+    }
+
+    // Computes a op b in eax or xmm0 and returns the type of the result
+    fn arithmetic(&mut self, operator: &Operator, a: &Operand, b: &Operand) -> Result<Types, CodegenError> {
+        let value_type = self.load(a, 0)?;
+        if self.load(b, 1)? != value_type {
+            return Err(self.unsupported("operands of different types"));
+        }
+        match (value_type.clone(), operator) {
+            (Types::Integer, Operator::Add) => self.line("add ax, cx"),
+            (Types::Integer, Operator::Subtract) => self.line("sub ax, cx"),
+            (Types::Integer, Operator::Multiply) => self.line("imul ax, cx"),
+            (Types::Integer, _) => {
+                // The only quotient that does not fit in 16 bits is -32768 / -1
+                self.line("test cx, cx");
+                self.check("jz", "mining_division_error");
+                self.line("cmp cx, -1");
+                self.line("jne 1f");
+                self.line("cmp ax, -32768");
+                self.check("je", "mining_overflow_error");
+                self.text.push_str("1:\n");
+                self.line("cwd");
+                self.line("idiv cx");
+            },
+            (Types::Float, Operator::Add) => self.line("addss xmm0, xmm1"),
+            (Types::Float, Operator::Subtract) => self.line("subss xmm0, xmm1"),
+            (Types::Float, Operator::Multiply) => self.line("mulss xmm0, xmm1"),
+            (Types::Float, _) => self.line("divss xmm0, xmm1"),
+            (_, Operator::Add | Operator::Subtract) => {
+                // CHAR codes wrap around 0x7F
+                self.line(if *operator == Operator::Add { "add eax, ecx" } else { "sub eax, ecx" });
+                self.line("cdq");
+                self.line("mov ecx, 127");
+                self.line("idiv ecx");
+                self.line("test edx, edx");
+                self.line("jns 1f");
+                self.line("add edx, 127");
+                self.text.push_str("1:\n");
+                self.line("mov eax, edx");
+            },
+            _ => return Err(self.unsupported("CHAR values only support addition and subtraction")),
+        }
+        if matches!(value_type, Types::Integer) && *operator != Operator::Divide {
+            self.check("jo", "mining_overflow_error");
+        }
+        Ok(value_type)
+    }
+
+    // Sets al to the result of comparing a with b
+    fn comparison(&mut self, operator: &Operator, a: &Operand, b: &Operand) -> Result<(), CodegenError> {
+        let value_type = self.load(a, 0)?;
+        if self.load(b, 1)? != value_type {
+            return Err(self.unsupported("operands of different types"));
+        }
+        if value_type == Types::Float {
+            // ucomiss sets ZF, PF and CF when the comparison is unordered (NaN), only != is true then
+            match operator {
+                Operator::LessThan | Operator::LessThanOrEqual => self.line("ucomiss xmm1, xmm0"),
+                _ => self.line("ucomiss xmm0, xmm1"),
+            }
+            match operator {
+                Operator::GreaterThan | Operator::LessThan => self.line("seta al"),
+                Operator::GreaterThanOrEqual | Operator::LessThanOrEqual => self.line("setae al"),
+                Operator::Equal => {
+                    self.line("sete al");
+                    self.line("setnp cl");
+                    self.line("and al, cl");
+                },
+                _ => {
+                    self.line("setne al");
+                    self.line("setp cl");
+                    self.line("or al, cl");
+                },
+            }
+            return Ok(());
+        }
+
+        let signed = is_signed(&value_type);
+        self.line("cmp eax, ecx");
+        let set = match operator {
+            Operator::GreaterThan => if signed { "setg" } else { "seta" },
+            Operator::LessThan => if signed { "setl" } else { "setb" },
+            Operator::GreaterThanOrEqual => if signed { "setge" } else { "setae" },
+            Operator::LessThanOrEqual => if signed { "setle" } else { "setbe" },
+            Operator::Equal => "sete",
+            _ => "setne",
+        };
+        self.line(format!("{} al", set));
+        Ok(())
+    }
+
+    // Appends the runtime, the error handlers, the constants and the storage of the symbols
+    fn finish(mut self) -> String {
+        let mut output = std::mem::take(&mut self.text);
+        output.push_str(RUNTIME.strip_prefix('\n').unwrap_or(RUNTIME));
+
+        output.push_str("\n    .text\n");
+        let mut messages = String::new();
+        for (index, (location, handler)) in self.stubs.iter().enumerate() {
+            output.push_str(&format!(".Lx{}:\n", index));
+            output.push_str(&format!("    lea rax, [rip + .Lo{}]\n", location));
+            output.push_str("    mov qword ptr [rip + mining_location], rax\n");
+            output.push_str(&format!("    jmp {}\n", handler));
+        }
+        for (index, (label, message)) in RUNTIME_ERRORS.iter().enumerate() {
+            let message = format!("Runtime error: {}", message);
+            output.push_str(&format!("{}:\n", label));
+            output.push_str(&format!("    lea rsi, [rip + .Le{}]\n", index));
+            output.push_str(&format!("    mov edx, {}\n", message.len()));
+            output.push_str("    jmp mining_fail\n");
+            messages.push_str(&format!(".Le{}: .ascii \"{}\"\n", index, escape(&message)));
+        }
+
+        output.push_str("\n    .section .rodata\n    .balign 4\n");
+        for (index, bits) in self.floats.iter().enumerate() {
+            output.push_str(&format!(".Lf{}: .long 0x{:08x}    # {:?}\n", index, bits, f32::from_bits(*bits)));
+        }
+        for (index, text) in self.strings.iter().enumerate() {
+            output.push_str(&format!(".Ls{}: .ascii \"{}\"\n", index, escape(text)));
+        }
+        output.push_str(&messages);
+        for (index, location) in self.locations.iter().enumerate() {
+            let location = format!("{}\n", location);
+            output.push_str(&format!("    .balign 4\n.Lo{}: .long {}\n    .ascii \"{}\"\n", index, location.len(), escape(&location)));
+        }
+
+        // Variables given a constant at the start of the program are initialized data
+        let (_, initial) = static_initializers(self.quadruplets);
+        let symbols = collect_symbols(self.quadruplets);
+        output.push_str("\n    .data\n    .balign 4\n");
+        for (name, value) in &initial {
+            let directive = match value {
+                Value::Integer(i) => format!(".word {}", i),
+                Value::Float(f) => format!(".long 0x{:08x}", f.to_bits()),
+                Value::Char(c) => format!(".byte {}", char_code(*c)),
+            };
+            output.push_str(&format!("    .balign {}\n{}: {}\n", storage_size(&value.get_type()), symbol(name), directive));
+        }
+        output.push_str("\n    .bss\n    .balign 4\n");
+        let allocated = |name: &String| self.allocation.locations.contains_key(name);
+        for (name, symbol_type) in symbols.iter().filter(|(name, _)| !initial.iter().any(|(known, _)| known == name) && !allocated(name)) {
+            let alignment = match symbol_type {
+                Types::Array(element, _) => storage_size(element),
+                scalar => storage_size(scalar),
+            };
+            output.push_str(&format!("    .balign {}\n{}: .zero {}\n", alignment, symbol(name), storage_size(symbol_type).max(1)));
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use crate::codegen::{format_float, parse_float};
+    use crate::codegen::regalloc::{allocate, Strategy};
+    use crate::Semantic::quadruplets::parse_quadruplets;
+    use crate::Test::tests::{run_program, run_tool, TemporaryDirectory, QUADRUPLETS};
+
+    // Assembles and links with binutils, which the test needs
+    fn run(assembly: &str, input: &str) -> (String, String, i32) {
+        let directory = TemporaryDirectory::new(&format!("mining_x86_64_{}", assembly.len()));
+        let (source, object, program) = (directory.path().join("p.s"), directory.path().join("p.o"), directory.path().join("p"));
+        std::fs::write(&source, assembly).unwrap();
+        run_tool(Command::new("as").arg(&source).arg("-o").arg(&object));
+        run_tool(Command::new("ld").arg(&object).arg("-o").arg(&program));
+        run_program(&mut Command::new(&program), input)
+    }
+
+    #[test]
+    fn test_generated_assembly() {
+        let quads = parse_quadruplets(QUADRUPLETS).unwrap();
+        let assembly = generate_x86_64(&quads).unwrap();
+        // Leading constant assignments become initialized data
+        assert!(assembly.contains("m_S: .word 0"));
+        assert!(assembly.contains("m_G: .long 0x3fc00000"));
+        assert!(!assembly.contains("# 1: (Assign"));
+        assert!(assembly.contains("m_A: .zero 6"));
+        assert!(assembly.contains("movsx eax, word ptr [rip + m_S]"));
+        assert!(assembly.contains("divss xmm0, xmm1"));
+        assert!(assembly.contains("jae mining_index_error"));
+
+        let (output, _, code) = run(&assembly, "-7 2.25\n a");
+        assert_eq!(code, 0);
+        assert_eq!(output, "S = -21\n1.5b13");
+
+        let (output, errors, code) = run(&assembly, "0 1.0 x");
+        assert_eq!(output, "S = 0\n0.666667y0");
+        assert_eq!((errors.as_str(), code), ("Runtime error: Division by zero\n", 1));
+
+        let (_, errors, code) = run(&assembly, "40000");
+        assert_eq!((errors.as_str(), code), ("Runtime error: Invalid INTEGER input\n", 1));
+    }
+
+    #[test]
+    fn test_runtime_formats() {
+        let quads = parse_quadruplets(r#"
+            INTEGER I, t1;
+            FLOAT F;
+            0: (Read, _, _, F)
+            1: (Write, F, _, _)
+            2: (Write, " ", _, _)
+            3: (Read, _, _, I)
+            4: (Multiply, I, 2, t1)
+            5: (Write, t1, _, _)
+            6: (Goto, _, _, 0)
+        "#).unwrap();
+        let assembly = generate_x86_64(&quads).unwrap();
+        let (output, errors, code) = run(&assembly, "3.14 1 -0.5 -3 100 16383 123456789012 0 0.0000001 -16384 x");
+        // The runtime prints FLOAT values exactly like format_float
+        let floats: Vec<String> = ["3.14", "-0.5", "100", "123456789012", "0.0000001"].iter()
+            .map(|token| format_float(parse_float(token).unwrap()))
+            .collect();
+        assert_eq!(floats[3], "123456790.528e3");
+        assert_eq!(output, format!("{} 2{} -6{} 32766{} 0{} -32768", floats[0], floats[1], floats[2], floats[3], floats[4]));
+        assert_eq!((errors.as_str(), code), ("Runtime error: Invalid FLOAT input\n", 1));
+    }
+
+    const ALLOCATED: &str = r#"
+        INTEGER I, S, t1, t2, t3, t4, t5, t6;
+        FLOAT F, t7, t8;
+        CHAR t9;
+        INTEGER A[4];
+        0: (Read, _, _, t1)
+        1: (Assign, 0, _, I)
+        2: (LessThan, I, 4, t2)
+        3: (IfFalse, t2, _, 11)
+        4: (Multiply, I, t1, t3)
+        5: (Subtract, 3, I, t4)
+        6: (Assign, t3, _, A[t4])
+        7: (Add, S, A[t4], S)
+        8: (Add, I, 1, t5)
+        9: (Assign, t5, _, I)
+        10: (Goto, _, _, 2)
+        11: (Write, A[0], _, _)
+        12: (Write, " ", _, _)
+        13: (Write, S, _, _)
+        14: (Read, _, _, F)
+        15: (Multiply, F, F, t7)
+        16: (Add, t7, F, t8)
+        17: (Write, " ", _, _)
+        18: (Write, t8, _, _)
+        19: (Add, 'a', '\u{1}', t9)
+        20: (Write, t9, _, _)
+        21: (Subtract, 0, t1, t6)
+        22: (Write, t6, _, _)
+    "#;
+
+    #[test]
+    fn test_register_allocation() {
+        let quads = parse_quadruplets(ALLOCATED).unwrap();
+        let allocation = allocate(&quads, ALLOCATABLE_REGISTERS, Strategy::GraphColoring);
+        let assembly = generate_x86_64_with(&quads, &allocation).unwrap();
+        assert!(!assembly.contains("m_t"));
+        assert!(assembly.contains("movsxd r11, "));
+        assert!(!assembly.contains("sub rsp"));
+
+        // A single register of each class spills the temporaries living through the loop
+        let allocations = [
+            Allocation::default(),
+            allocation,
+            allocate(&quads, [1, 1], Strategy::GraphColoring),
+            allocate(&quads, [1, 1], Strategy::LinearScan),
+        ];
+        assert!(allocations[2].stack_slots > 0 && allocations[3].stack_slots > 0);
+        for allocation in &allocations {
+            let assembly = generate_x86_64_with(&quads, allocation).unwrap();
+            let (output, _, code) = run(&assembly, "-5 1.5");
+            assert_eq!((output.as_str(), code), ("-15 -30 3.75b5", 0));
+            let (_, errors, code) = run(&assembly, "20000");
+            assert_eq!((errors.as_str(), code), ("Runtime error: INTEGER overflow\n", 1));
+        }
+    }
+}
@@ -4,11 +4,13 @@
 #![allow(nonstandard_style)]
 mod codegen;
 mod Lexer;
+mod Optimizer;
 mod Parser;
 mod Semantic;
 mod Test;
 use std::{env, fs};
 use std::collections::HashMap;
+use std::path::Path;
 use std::process::exit;
 use std::sync::Mutex;
 use lalrpop_util;
@@ -16,7 +18,26 @@ use lalrpop_util::lalrpop_mod;
 use logos::Logos;
 use once_cell::sync::Lazy;
 use crate::Parser::ast::BinOp;
-// use crate::Semantic::quadruplets::QuadrupletGenerator;
+use crate::codegen::bytecode::{compile_bytecode, Module};
+use crate::codegen::c::generate_c;
+use crate::codegen::debugger::debug_program;
+use crate::codegen::elf::build_executable;
+use crate::codegen::error::RuntimeError;
+use crate::codegen::executor::run_quadruplets;
+use crate::codegen::i8086::generate_8086;
+use crate::codegen::interpreter::{run_program, Observer};
+use crate::codegen::llvm::generate_llvm;
+use crate::codegen::mips::generate_mips;
+use crate::codegen::regalloc::{allocate, Allocation, Strategy};
+use crate::codegen::riscv::{self, generate_riscv_with};
+use crate::codegen::trace::{Profiler, Tracer};
+use crate::codegen::vm::run_bytecode;
+use crate::codegen::wat::generate_wat;
+use crate::codegen::x86_64::{self, generate_x86_64_with};
+use crate::Optimizer::cfg::ControlFlowGraph;
+use crate::Optimizer::pass_manager::{pipeline_for_level, print_reports, PassManager, PASS_NAMES};
+use crate::Optimizer::ssa::SsaForm;
+use crate::Semantic::quadruplets::{format_quadruplets, parse_quadruplets, print_quadruplets, Quadruplet, QuadrupletGenerator};
 use crate::Semantic::semantic_analyzer::SemanticAnalyzer;
 use crate::Semantic::ts::*;
 use colored::*;
@@ -139,7 +160,382 @@ INSTRUCTION {
 }
     "#;
 
-fn process_program(input: &str, is_default: bool) {
+// What the compiler does with the program, given as the first argument
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Command {
+    #[default]
+    Compile,        // Analyses the program and writes the --emit outputs
+    Build,          // Also writes a static x86-64 executable
+    Run,            // Runs the program in the tree-walking interpreter
+    Debug,          // Runs the program in the interpreter under the interactive debugger
+}
+
+// Command line options
+#[derive(Debug, Default)]
+struct Options {
+    command: Command,
+    input: Option<String>,  // Source file, a .quads file to load the intermediate code from or a .mbc file to run
+    emit: Vec<String>,      // Outputs written next to the input (e.g., quads)
+    opt_level: u8,          // -O0, -O1 or -O2
+    passes: Option<Vec<String>>,    // Pipeline given with --passes, replaces the one of the -O level
+    print_after: Vec<String>,       // Passes followed by a dump of the quadruplets
+    verify: bool,           // Verify the quadruplets between passes
+    check_input: Option<String>,    // --check-passes, input the program runs on between passes to catch miscompilations
+    regalloc: Option<Strategy>,     // Register allocator of the native backends, None keeps the temporaries in static slots
+    verbose: bool,          // Print the register allocation statistics
+    trace: bool,            // run logs every executed instruction on stderr
+    profile: bool,          // run prints the most executed lines and FOR loops on stderr
+    output: Option<String>, // -o, path of the executable written by build
+}
+
+const EMIT_KINDS: [&str; 11] = ["quads", "dot", "ssa", "asm", "asm8086", "riscv", "mips", "c", "mbc", "ll", "wat"];
+
+fn parse_arguments(args: &[String]) -> Result<Options, String> {
+    let mut options = Options { regalloc: Some(Strategy::GraphColoring), ..Options::default() };
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        if i == 0 && arg == "build" {
+            options.command = Command::Build;
+        } else if i == 0 && arg == "run" {
+            options.command = Command::Run;
+        } else if i == 0 && arg == "debug" {
+            options.command = Command::Debug;
+        } else if arg == "-o" {
+            i += 1;
+            options.output = Some(args.get(i).cloned().ok_or("Missing path after -o")?);
+        } else if arg == "--emit" || arg.starts_with("--emit=") {
+            let kind = match arg.strip_prefix("--emit=") {
+                Some(kind) => kind.to_string(),
+                None => {
+                    i += 1;
+                    args.get(i).cloned().ok_or("Missing value after --emit")?
+                }
+            };
+            if !EMIT_KINDS.contains(&kind.as_str()) {
+                return Err(format!("Unknown --emit kind '{}', expected one of: {}", kind, EMIT_KINDS.join(", ")));
+            }
+            options.emit.push(kind);
+        } else if let Some(level) = arg.strip_prefix("-O") {
+            options.opt_level = match level {
+                "" | "1" => 1,
+                "0" => 0,
+                "2" => 2,
+                _ => return Err(format!("Unknown optimization level '{}', expected -O0, -O1 or -O2", arg)),
+            };
+        } else if let Some(names) = arg.strip_prefix("--passes=") {
+            let names: Vec<String> = names.split(',').filter(|n| !n.is_empty()).map(str::to_string).collect();
+            if let Some(name) = names.iter().find(|n| !PASS_NAMES.contains(&n.as_str())) {
+                return Err(format!("Unknown pass '{}', expected one of: {}", name, PASS_NAMES.join(", ")));
+            }
+            options.passes = Some(names);
+        } else if let Some(name) = arg.strip_prefix("--print-after=") {
+            if name != "all" && !PASS_NAMES.contains(&name) {
+                return Err(format!("Unknown pass '{}', expected all or one of: {}", name, PASS_NAMES.join(", ")));
+            }
+            options.print_after.push(name.to_string());
+        } else if arg == "--verify" {
+            options.verify = true;
+        } else if let Some(path) = arg.strip_prefix("--check-passes=") {
+            options.check_input = Some(path.to_string());
+        } else if let Some(name) = arg.strip_prefix("--regalloc=") {
+            options.regalloc = match name {
+                "none" => None,
+                _ => Some(Strategy::from_name(name).ok_or_else(|| format!("Unknown register allocator '{}', expected graph, linear or none", name))?),
+            };
+        } else if arg == "--trace" {
+            options.trace = true;
+        } else if arg == "--profile" {
+            options.profile = true;
+        } else if arg == "-v" || arg == "--verbose" {
+            options.verbose = true;
+        } else if arg.starts_with('-') {
+            return Err(format!("Unknown option '{}'", arg));
+        } else if options.input.is_none() {
+            options.input = Some(arg.clone());
+        } else {
+            return Err(format!("Unexpected argument '{}'", arg));
+        }
+        i += 1;
+    }
+    Ok(options)
+}
+
+// Path of an emitted file: the input path with a new extension, or default.<extension> for the default example
+fn output_path(options: &Options, extension: &str) -> String {
+    match &options.input {
+        Some(input) => Path::new(input).with_extension(extension).to_string_lossy().to_string(),
+        None => format!("default.{}", extension),
+    }
+}
+
+// Path of the executable written by build: -o, or the input path without its extension
+fn executable_path(options: &Options) -> String {
+    match (&options.output, &options.input) {
+        (Some(output), _) => output.clone(),
+        (None, Some(input)) => Path::new(input).with_extension("").to_string_lossy().to_string(),
+        (None, None) => "default".to_string(),
+    }
+}
+
+fn write_output(path: &str, content: impl AsRef<[u8]>) {
+    match fs::write(path, content) {
+        Ok(_) => println!("{} {}", "Wrote".green(), path),
+        Err(e) => {
+            eprintln!("{} {}: {}", "Error writing file".red(), path, e);
+            exit(1);
+        }
+    }
+}
+
+// Writes a program that can be run directly
+fn write_executable(path: &str, image: Vec<u8>) {
+    write_output(path, image);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o755)) {
+            eprintln!("{} {}: {}", "Error writing file".red(), path, e);
+            exit(1);
+        }
+    }
+}
+
+// Register allocation of the temporaries for a native backend, reported in verbose mode
+fn allocate_registers(quadruplets: &[Quadruplet], registers: [usize; 2], target: &str, options: &Options) -> Allocation {
+    let Some(strategy) = options.regalloc else {
+        return Allocation::default();
+    };
+    let allocation = allocate(quadruplets, registers, strategy);
+    if let (true, Some(stats)) = (options.verbose, &allocation.stats) {
+        println!("{} {}", format!("Register allocation ({}):", target).blue(), stats);
+    }
+    allocation
+}
+
+// Everything that runs on the intermediate code, whether it was generated or loaded from a .quads file
+fn process_quadruplets(mut quadruplets: Vec<Quadruplet>, options: &Options) {
+    let pipeline: Vec<&str> = match &options.passes {
+        Some(names) => names.iter().map(String::as_str).collect(),
+        None => pipeline_for_level(options.opt_level),
+    };
+    if !pipeline.is_empty() {
+        let check_input = options.check_input.as_ref().map(|path| match fs::read(path) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("{} {}: {}", "Error reading file".red(), path, e);
+                exit(1);
+            }
+        });
+        let result = PassManager::with_passes(&pipeline).and_then(|mut manager| {
+            manager.set_verify(options.verify);
+            manager.set_check_input(check_input);
+            for name in &options.print_after {
+                manager.print_after(name);
+            }
+            manager.run(&mut quadruplets)
+        });
+        match result {
+            Ok(reports) => {
+                print_reports(&reports);
+                print_quadruplets("Optimized quadruplets", &quadruplets);
+            },
+            Err(e) => {
+                eprintln!("{} {}", "Optimization Error:".red(), e);
+                exit(1);
+            }
+        }
+    }
+
+    if options.emit.iter().any(|kind| kind == "quads") {
+        write_output(&output_path(options, "quads"), format_quadruplets(&quadruplets));
+    }
+    if options.emit.iter().any(|kind| kind == "dot") {
+        let title = options.input.as_deref().unwrap_or("default");
+        let cfg = ControlFlowGraph::build(&quadruplets);
+        write_output(&output_path(options, "dot"), cfg.to_dot(&quadruplets, title));
+    }
+    if options.emit.iter().any(|kind| kind == "ssa") {
+        write_output(&output_path(options, "ssa"), SsaForm::build(&quadruplets).to_string());
+    }
+    if options.emit.iter().any(|kind| kind == "asm") {
+        let allocation = allocate_registers(&quadruplets, x86_64::ALLOCATABLE_REGISTERS, "x86-64", options);
+        match generate_x86_64_with(&quadruplets, &allocation) {
+            Ok(assembly) => write_output(&output_path(options, "s"), assembly),
+            Err(e) => {
+                eprintln!("{} {}", "Code Generation Error:".red(), e);
+                exit(1);
+            }
+        }
+    }
+    if options.emit.iter().any(|kind| kind == "asm8086") {
+        match generate_8086(&quadruplets) {
+            Ok(assembly) => write_output(&output_path(options, "asm"), assembly),
+            Err(e) => {
+                eprintln!("{} {}", "Code Generation Error:".red(), e);
+                exit(1);
+            }
+        }
+    }
+    if options.emit.iter().any(|kind| kind == "riscv") {
+        let allocation = allocate_registers(&quadruplets, riscv::ALLOCATABLE_REGISTERS, "RISC-V", options);
+        match generate_riscv_with(&quadruplets, &allocation) {
+            Ok(assembly) => write_output(&output_path(options, "riscv.asm"), assembly),
+            Err(e) => {
+                eprintln!("{} {}", "Code Generation Error:".red(), e);
+                exit(1);
+            }
+        }
+    }
+    if options.emit.iter().any(|kind| kind == "mips") {
+        match generate_mips(&quadruplets) {
+            Ok(assembly) => write_output(&output_path(options, "mips.s"), assembly),
+            Err(e) => {
+                eprintln!("{} {}", "Code Generation Error:".red(), e);
+                exit(1);
+            }
+        }
+    }
+    if options.emit.iter().any(|kind| kind == "ll") {
+        match generate_llvm(&quadruplets) {
+            Ok(module) => write_output(&output_path(options, "ll"), module),
+            Err(e) => {
+                eprintln!("{} {}", "Code Generation Error:".red(), e);
+                exit(1);
+            }
+        }
+    }
+    if options.emit.iter().any(|kind| kind == "mbc") {
+        match compile_bytecode(&quadruplets) {
+            Ok(module) => write_output(&output_path(options, "mbc"), module.encode()),
+            Err(e) => {
+                eprintln!("{} {}", "Code Generation Error:".red(), e);
+                exit(1);
+            }
+        }
+    }
+    if options.command == Command::Build {
+        let allocation = allocate_registers(&quadruplets, x86_64::ALLOCATABLE_REGISTERS, "x86-64", options);
+        match build_executable(&quadruplets, &allocation) {
+            Ok(image) => write_executable(&executable_path(options), image),
+            Err(e) => {
+                eprintln!("{} {}", "Code Generation Error:".red(), e);
+                exit(1);
+            }
+        }
+    }
+}
+
+// Runs a bytecode file in the virtual machine with the standard input and output
+fn run_bytecode_file(path: &str) {
+    let module = match fs::read(path) {
+        Ok(bytes) => Module::decode(&bytes),
+        Err(e) => {
+            eprintln!("{} {}: {}", "Error reading file".red(), path, e);
+            exit(1);
+        }
+    };
+    let module = match module {
+        Ok(module) => module,
+        Err(e) => {
+            eprintln!("{} {}", "Bytecode Error:".red(), e);
+            exit(1);
+        }
+    };
+    let mut output = std::io::BufWriter::new(std::io::stdout().lock());
+    if let Err(e) = run_bytecode(&module, std::io::stdin().lock(), &mut output) {
+        eprintln!("Runtime error: {}", e);
+        exit(1);
+    }
+}
+
+// Analyses a program without printing the compilation steps, then runs it in the interpreter
+// so that the standard output only holds what the program writes, or what the debugger prints.
+// The trace and the profile go to the standard error
+fn run_source(input: &str, options: &Options) {
+    let lexer = Lexer::lexer::Token::lexer(input);
+    let parser = grammar::ProgramParser::new();
+    let program = match parser.parse(input, lexer.enumerate().map(|(i, t)| t.map(|token| (i, token, i+1)))) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("{} {:?}", "Syntactic Error:".red(), e);
+            exit(1);
+        },
+    };
+    if let Err(msg) = SemanticAnalyzer::new().analyze(&program) {
+        eprintln!("{} {}", "Semantic Error:".red(), msg);
+        exit(1);
+    }
+    if options.command == Command::Debug {
+        // The debugger prints why the program stopped itself
+        let symbols = SymbolTable.lock().unwrap().clone();
+        if debug_program(&program, input, symbols, std::io::stdin().lock(), &mut std::io::stdout().lock()).is_err_and(|e| e.error != RuntimeError::Stopped) {
+            exit(1);
+        }
+        return;
+    }
+    let mut output = std::io::BufWriter::new(std::io::stdout().lock());
+    let mut observers: Vec<Box<dyn Observer<_, _>>> = Vec::new();
+    if options.trace {
+        observers.push(Box::new(Tracer::new(input, std::io::BufWriter::new(std::io::stderr()))));
+    }
+    if options.profile {
+        observers.push(Box::new(Profiler::new(input, std::io::stderr())));
+    }
+    if let Err(e) = run_program(&program, std::io::stdin().lock(), &mut output, observers) {
+        eprintln!("Runtime error: {}", e);
+        exit(1);
+    }
+}
+
+// Runs a .quads file in the executor with the standard input and output
+fn run_quadruplets_file(path: &str) {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{} {}: {}", "Error reading file".red(), path, e);
+            exit(1);
+        }
+    };
+    let quadruplets = match parse_quadruplets(&text) {
+        Ok(quadruplets) => quadruplets,
+        Err(e) => {
+            eprintln!("{} {}", "Quadruplet Error:".red(), e);
+            exit(1);
+        }
+    };
+    let mut output = std::io::BufWriter::new(std::io::stdout().lock());
+    if let Err(e) = run_quadruplets(&quadruplets, std::io::stdin().lock(), &mut output, u64::MAX) {
+        eprintln!("Runtime error: {}", e);
+        exit(1);
+    }
+}
+
+fn load_quadruplets(path: &str, options: &Options) {
+    if let Some(kind) = options.emit.iter().find(|kind| *kind == "c" || *kind == "wat") {
+        eprintln!("{} --emit {} translates the syntax tree and needs a MinING source file", "Error:".red(), kind);
+        exit(1);
+    }
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{} {}: {}", "Error reading file".red(), path, e);
+            exit(1);
+        }
+    };
+    match parse_quadruplets(&text) {
+        Ok(quadruplets) => {
+            print_quadruplets(&format!("Quadruplets loaded from {}", path), &quadruplets);
+            process_quadruplets(quadruplets, options);
+        },
+        Err(e) => {
+            eprintln!("{} {}", "Quadruplet Error:".red(), e);
+            exit(1);
+        }
+    }
+}
+
+fn process_program(input: &str, is_default: bool, options: &Options) {
     if is_default {
         println!("{}", "No input file provided or file reading failed. Running default example:".yellow());
         println!("{}", input);
@@ -219,18 +615,83 @@ fn process_program(input: &str, is_default: bool) {
     println!("-------------------------------------------------------------------------------------------------");
     println!("{}", "The contents of the symbols table".green());
     print_table(&SymbolTable);
+
+    println!("-------------------------------------------------------------------------------------------------");
+    let mut generator = QuadrupletGenerator::new();
+    match generator.generate_program(&program) {
+        Ok(_) => {
+            println!("{}", "Intermediate Code Generation Successful.".green());
+            generator.print_quadruplets();
+        },
+        Err(e) => {
+            eprintln!("{} {}", "Intermediate Code Generation Error:".red(), e);
+            exit(1);
+        },
+    }
+
+    process_quadruplets(generator.into_quadruplets(), options);
+
+    if options.emit.iter().any(|kind| kind == "c") {
+        match generate_c(&program) {
+            Ok(code) => write_output(&output_path(options, "c"), code),
+            Err(e) => {
+                eprintln!("{} {}", "Code Generation Error:".red(), e);
+                exit(1);
+            }
+        }
+    }
+    if options.emit.iter().any(|kind| kind == "wat") {
+        let symbols = SymbolTable.lock().unwrap().clone();
+        match generate_wat(&program, &symbols) {
+            Ok(module) => write_output(&output_path(options, "wat"), module),
+            Err(e) => {
+                eprintln!("{} {}", "Code Generation Error:".red(), e);
+                exit(1);
+            }
+        }
+    }
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let program = if args.len() > 1 {
-        match fs::read_to_string(&args[1]) {
+    let options = match parse_arguments(&args[1..]) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{} {}", "Error:".red(), e);
+            exit(1);
+        }
+    };
+
+    if let Some(path) = options.input.as_deref().filter(|path| path.ends_with(".mbc")) {
+        if options.command != Command::Run {
+            eprintln!("{} a .mbc file can only be run, use: run {}", "Error:".red(), path);
+            exit(1);
+        }
+        run_bytecode_file(path);
+        return;
+    }
+    if let Some(path) = options.input.as_deref().filter(|path| path.ends_with(".quads")) {
+        match options.command {
+            Command::Run => run_quadruplets_file(path),
+            Command::Debug => {
+                eprintln!("{} debug needs a MinING source file", "Error:".red());
+                exit(1);
+            },
+            _ => load_quadruplets(path, &options),
+        }
+        return;
+    }
+
+    let program = if let Some(path) = &options.input {
+        match fs::read_to_string(path) {
             Ok(content) => {
-                println!("{} {}", "Reading from file:".blue(), args[1]);
+                if !matches!(options.command, Command::Run | Command::Debug) {
+                    println!("{} {}", "Reading from file:".blue(), path);
+                }
                 (content, false)
             },
             Err(e) => {
-                eprintln!("{} {}: {}", "Error reading file".red(), args[1], e);
+                eprintln!("{} {}: {}", "Error reading file".red(), path, e);
                 (DEFAULT_PROGRAM.to_string(), true)
             }
         }
@@ -238,5 +699,9 @@ fn main() {
         (DEFAULT_PROGRAM.to_string(), true)
     };
 
-    process_program(&program.0, program.1);
+    if matches!(options.command, Command::Run | Command::Debug) {
+        run_source(&program.0, &options);
+        return;
+    }
+    process_program(&program.0, program.1, &options);
 }